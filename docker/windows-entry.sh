@@ -6,10 +6,29 @@ export HOME=/tmp/home
 mkdir -p "${HOME}"
 
 # Initialize the wine prefix (virtual windows installation)
-export WINEPREFIX=/tmp/wine
+# cross provides a persistent prefix in a volume, so we only
+# need to bootstrap it once.
+export WINEPREFIX="${WINEPREFIX:-/tmp/wine}"
 mkdir -p "${WINEPREFIX}"
-# FIXME: Make the wine prefix initialization faster
-wineboot &> /dev/null
+# Fail early if the image doesn't have the pinned wine version.
+if [[ -n "${CROSS_WINE_VERSION:-}" ]]; then
+    version="$(wine --version)"
+    if [[ "${version}" != "wine-${CROSS_WINE_VERSION}" ]] &&
+        [[ "${version}" != "wine-${CROSS_WINE_VERSION}."* ]] &&
+        [[ "${version}" != "wine-${CROSS_WINE_VERSION} "* ]]; then
+        echo "cross: wine ${CROSS_WINE_VERSION} is required, but the image has ${version}." 1>&2
+        exit 1
+    fi
+fi
+# Silence wine diagnostics and, unless they're enabled, disable the
+# mono and gecko installer dialogs, which block in a headless environment.
+export WINEDEBUG="${WINEDEBUG:--all}"
+if [[ "${CROSS_WINE_DIALOGS:-}" != "1" ]]; then
+    export WINEDLLOVERRIDES="${WINEDLLOVERRIDES:-mscoree,mshtml=}"
+fi
+if [[ ! -f "${WINEPREFIX}/system.reg" ]]; then
+    wineboot &> /dev/null
+fi
 
 # Put libstdc++ and some other mingw dlls in WINEPATH
 # This must work for x86_64 and i686
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use cross::manifest::{Manifest, MANIFEST_FILENAME};
+use cross::shell::MessageInfo;
+use cross::ToUtf8;
+
+#[derive(Args, Debug)]
+pub struct Artifacts {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Path to the manifest. Defaults to the one in the target directory.
+    #[clap(long)]
+    pub manifest: Option<PathBuf>,
+    /// Only list the artifacts for these targets.
+    #[clap(long)]
+    pub target: Vec<String>,
+    /// Print the manifest entries as JSON rather than the artifact paths.
+    #[clap(long)]
+    pub json: bool,
+}
+
+impl Artifacts {
+    pub fn run(self, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        let path = match self.manifest {
+            Some(path) => path,
+            None => cross::cargo_metadata_with_args(None, None, msg_info)?
+                .ok_or_else(|| eyre::eyre!("unable to find the target directory"))?
+                .target_directory
+                .join(MANIFEST_FILENAME),
+        };
+        let mut manifest = Manifest::read(&path)?;
+        if !self.target.is_empty() {
+            manifest
+                .targets
+                .retain(|record| self.target.contains(&record.target));
+        }
+
+        if self.json {
+            msg_info.print(serde_json::to_string_pretty(&manifest)?)?;
+            return Ok(());
+        }
+        for record in &manifest.targets {
+            if !record.success {
+                msg_info.warn(format_args!(
+                    "the build for {} failed, it has no artifacts.",
+                    record.target
+                ))?;
+            }
+            for artifact in &record.artifacts {
+                msg_info.print(artifact.to_utf8()?)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        self.color.as_deref()
+    }
+}
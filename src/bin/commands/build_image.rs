@@ -0,0 +1,213 @@
+use std::env;
+use std::path::PathBuf;
+
+use clap::Args;
+use cross::docker::{self, BuildCommandExt, BuildResultExt, Progress};
+use cross::shell::MessageInfo;
+use cross::{CommandExt, ToUtf8};
+
+#[derive(Args, Debug)]
+pub struct BuildImage {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+    /// The target to build the image for, with an optional sub after a colon,
+    /// such as `aarch64-unknown-linux-gnu:centos`.
+    pub target: String,
+    /// Tag of the image.
+    #[clap(long, default_value = "local")]
+    pub tag: String,
+    /// Repository name for the image.
+    #[clap(long, default_value = docker::CROSS_IMAGE)]
+    pub repository: String,
+    /// Push the image to the registry after building it.
+    #[clap(long)]
+    pub push: bool,
+    /// The `docker` directory of a checkout of cross. Defaults to the first
+    /// one found in the current directory or its parents.
+    #[clap(long)]
+    pub docker_dir: Option<PathBuf>,
+    /// The platform the image runs on.
+    #[clap(long, default_value = "linux/amd64")]
+    pub platform: String,
+    /// Docker build progress output type.
+    #[clap(long, value_parser = ["plain", "auto", "tty"])]
+    pub progress: Option<String>,
+    /// Do not use the build cache.
+    #[clap(long)]
+    pub no_cache: bool,
+    /// Pass a build argument to the build.
+    #[clap(long)]
+    pub build_arg: Vec<String>,
+}
+
+/// The Dockerfile for an image, relative to the `docker` directory.
+/// `target` is `name` or `name:sub`, like the images in `PROVIDED_IMAGES`.
+fn dockerfile(target: &str, platform: &str) -> String {
+    let (name, sub) = match target.split_once(':') {
+        Some((name, sub)) => (name, Some(sub)),
+        None => (target, None),
+    };
+    // the images for the native target of the platform use the toolchain of
+    // the image, rather than a cross-compiler.
+    let native = matches!(
+        (platform, name, sub),
+        (
+            "linux/amd64",
+            "x86_64-unknown-linux-gnu",
+            None | Some("centos")
+        ) | (
+            "linux/arm64" | "linux/arm64/v8",
+            "aarch64-unknown-linux-gnu",
+            None | Some("centos")
+        )
+    );
+    match (native, sub) {
+        (true, Some(sub)) => format!("Dockerfile.native.{sub}"),
+        (true, None) => "Dockerfile.native".to_owned(),
+        (false, Some(sub)) => format!("Dockerfile.{name}.{sub}"),
+        (false, None) => format!("Dockerfile.{name}"),
+    }
+}
+
+fn find_docker_dir() -> cross::Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    cwd.ancestors()
+        .map(|dir| dir.join("docker"))
+        .find(|dir| dir.join("Dockerfile.native").is_file())
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "unable to find the `docker` directory of cross, specify it with `--docker-dir`"
+            )
+        })
+}
+
+impl BuildImage {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        let docker_dir = match self.docker_dir {
+            Some(dir) => dir,
+            None => find_docker_dir()?,
+        };
+        let cross_toolchains_dir = docker_dir.join("cross-toolchains").join("docker");
+        let dockerfile = dockerfile(&self.target, &self.platform);
+        let dockerfile = [&cross_toolchains_dir, &docker_dir]
+            .iter()
+            .map(|dir| dir.join(&dockerfile))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                eyre::eyre!("unable to find `{dockerfile}` for target `{}`", self.target)
+            })?;
+        let (name, sub) = match self.target.split_once(':') {
+            Some((name, sub)) => (name, Some(sub)),
+            None => (self.target.as_str(), None),
+        };
+        let image = docker::image_name(name, sub, &self.repository, &self.tag);
+        let progress = self
+            .progress
+            .as_deref()
+            .map(str::parse::<Progress>)
+            .transpose()?;
+        let has_buildkit = docker::Engine::has_buildkit();
+
+        msg_info.note(format_args!(
+            "building `{image}` from `{}`",
+            dockerfile.to_utf8()?
+        ))?;
+        let mut build = engine.command();
+        build.invoke_build_command();
+        build.current_dir(&docker_dir);
+        build.args(["--platform", &self.platform]);
+        build.args([
+            "--build-arg",
+            &format!(
+                "CROSS_TARGET_TRIPLE={}",
+                name.to_ascii_uppercase().replace('-', "_")
+            ),
+        ]);
+        for arg in &self.build_arg {
+            build.args(["--build-arg", arg]);
+        }
+        if engine.kind.supports_pull_flag() {
+            build.arg("--pull");
+        }
+        if self.no_cache {
+            build.arg("--no-cache");
+        }
+        if self.push && has_buildkit {
+            build.arg("--push");
+        } else if has_buildkit {
+            build.arg("--load");
+        }
+        build.args(["--tag", &image]);
+        build.cross_labels(name, &self.platform);
+        build.args(["--file", dockerfile.to_utf8()?]);
+        build.progress(progress)?;
+        build.verbose(msg_info.verbosity);
+        build.arg(".");
+        build
+            .run(msg_info, false)
+            .engine_warning(&engine)
+            .buildkit_warning()?;
+
+        if self.push && !has_buildkit {
+            engine.subcommand("push").arg(&image).run(msg_info, false)?;
+        }
+        msg_info.status(format_args!("built `{image}`"))?;
+
+        Ok(())
+    }
+
+    pub fn engine(&self) -> Option<&str> {
+        self.engine.as_deref()
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        self.color.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dockerfiles() {
+        assert_eq!(
+            dockerfile("aarch64-unknown-linux-gnu", "linux/amd64"),
+            "Dockerfile.aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            dockerfile("aarch64-unknown-linux-gnu:centos", "linux/amd64"),
+            "Dockerfile.aarch64-unknown-linux-gnu.centos"
+        );
+        assert_eq!(
+            dockerfile("x86_64-unknown-linux-gnu", "linux/amd64"),
+            "Dockerfile.native"
+        );
+        assert_eq!(
+            dockerfile("x86_64-unknown-linux-gnu:centos", "linux/amd64"),
+            "Dockerfile.native.centos"
+        );
+        assert_eq!(
+            dockerfile("aarch64-unknown-linux-gnu", "linux/arm64"),
+            "Dockerfile.native"
+        );
+    }
+}
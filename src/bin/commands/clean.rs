@@ -2,6 +2,7 @@ use std::fs;
 
 use super::containers::*;
 use super::images::*;
+use super::networks::*;
 use clap::Args;
 use cross::shell::MessageInfo;
 
@@ -49,7 +50,7 @@ impl Clean {
             ))?,
         }
 
-        // containers -> images -> volumes -> prune to ensure no conflicts.
+        // containers -> networks -> images -> volumes -> prune to ensure no conflicts.
         let remove_containers = RemoveAllContainers {
             verbose: self.verbose,
             quiet: self.quiet,
@@ -60,6 +61,16 @@ impl Clean {
         };
         remove_containers.run(engine.clone(), msg_info)?;
 
+        let remove_networks = RemoveNetworks {
+            verbose: self.verbose,
+            quiet: self.quiet,
+            color: self.color.clone(),
+            project: false,
+            execute: self.execute,
+            engine: None,
+        };
+        remove_networks.run(engine.clone(), msg_info)?;
+
         let remove_images = RemoveImages {
             targets: vec![],
             verbose: self.verbose,
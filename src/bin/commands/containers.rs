@@ -2,6 +2,7 @@ use std::io;
 
 use clap::{Args, Subcommand};
 use cross::docker::ImagePlatform;
+use cross::file::format_size;
 use cross::rustc::{QualifiedToolchain, Toolchain};
 use cross::shell::{MessageInfo, Stream};
 use cross::{docker, CommandExt, TargetTriple};
@@ -28,6 +29,31 @@ impl ListVolumes {
     }
 }
 
+#[derive(Args, Debug)]
+pub struct SizeVolumes {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Only show the largest volumes.
+    #[clap(long)]
+    pub top: Option<usize>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+}
+
+impl SizeVolumes {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        size_volumes(self, &engine, msg_info)
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct RemoveAllVolumes {
     /// Provide verbose diagnostic output.
@@ -157,6 +183,8 @@ impl RemoveVolume {
 pub enum Volumes {
     /// List cross data volumes in local storage.
     List(ListVolumes),
+    /// Show the disk usage of cross data volumes, largest first.
+    Size(SizeVolumes),
     /// Remove cross data volumes in local storage.
     RemoveAll(RemoveAllVolumes),
     /// Prune volumes not used by any container.
@@ -171,6 +199,7 @@ macro_rules! volumes_get_field {
     ($self:ident, $field:ident $(.$cb:ident)?) => {{
         match $self {
             Volumes::List(l) => l.$field$(.$cb())?,
+            Volumes::Size(l) => l.$field$(.$cb())?,
             Volumes::RemoveAll(l) => l.$field$(.$cb())?,
             Volumes::Prune(l) => l.$field$(.$cb())?,
             Volumes::Create(l) => l.$field$(.$cb())?,
@@ -188,6 +217,7 @@ impl Volumes {
     ) -> cross::Result<()> {
         match self {
             Volumes::List(args) => args.run(engine, msg_info),
+            Volumes::Size(args) => args.run(engine, msg_info),
             Volumes::RemoveAll(args) => args.run(engine, msg_info),
             Volumes::Prune(args) => args.run(engine, msg_info),
             Volumes::Create(args) => args.run(engine, channel, msg_info),
@@ -203,6 +233,7 @@ impl Volumes {
     pub fn docker_in_docker(&self) -> bool {
         match self {
             Volumes::List(_) => false,
+            Volumes::Size(_) => false,
             Volumes::RemoveAll(_) => false,
             Volumes::Prune(_) => false,
             Volumes::Create(l) => l.docker_in_docker,
@@ -315,23 +346,15 @@ impl Containers {
     }
 }
 
-fn get_cross_volumes(
+pub(crate) fn get_cross_volumes(
     engine: &docker::Engine,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<Vec<String>> {
-    use cross::docker::VOLUME_PREFIX;
-    let stdout = engine
-        .subcommand("volume")
-        .arg("list")
-        .args(["--format", "{{.Name}}"])
-        // handles simple regex: ^ for start of line.
-        .args(["--filter", &format!("name=^{VOLUME_PREFIX}")])
-        .run_and_get_stdout(msg_info)?;
-
-    let mut volumes: Vec<String> = stdout.lines().map(|s| s.to_string()).collect();
-    volumes.sort();
-
-    Ok(volumes)
+    docker::CrossLabels::list(
+        engine,
+        &["volume", "list", "--format", "{{.Name}}"],
+        msg_info,
+    )
 }
 
 pub fn list_volumes(engine: &docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
@@ -342,6 +365,69 @@ pub fn list_volumes(engine: &docker::Engine, msg_info: &mut MessageInfo) -> cros
     Ok(())
 }
 
+/// Get the size in bytes of each cross volume.
+pub(crate) fn get_volume_sizes(
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<Vec<(String, u64)>> {
+    let names = get_cross_volumes(engine, msg_info)?;
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // measure all volumes at once from a single container.
+    let mut du = engine.subcommand("run");
+    du.arg("--rm");
+    for name in &names {
+        du.args(
+            docker::MountSpec::new(name, format!("/volumes/{name}"))?
+                .read_only(true)
+                .args(),
+        );
+    }
+    du.args([docker::UBUNTU_BASE, "du", "-sk"]);
+    du.args(names.iter().map(|name| format!("/volumes/{name}")));
+    let stdout = du.run_and_get_stdout(msg_info)?;
+
+    let mut volumes = vec![];
+    for line in stdout.lines() {
+        // formatted as `${size}\t/volumes/${name}`
+        let (size, path) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| eyre::eyre!("invalid output from `du`: {line}"))?;
+        let name = path.trim().trim_start_matches("/volumes/").to_owned();
+        volumes.push((name, size.parse::<u64>()? * 1024));
+    }
+
+    Ok(volumes)
+}
+
+pub fn size_volumes(
+    SizeVolumes { top, .. }: SizeVolumes,
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let mut volumes = get_volume_sizes(engine, msg_info)?;
+    volumes.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let total: u64 = volumes.iter().map(|(_, size)| size).sum();
+    let count = volumes.len();
+    let width = volumes
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for (name, size) in volumes.iter().take(top.unwrap_or(count)) {
+        msg_info.print(format_args!("{name:width$}  {:>10}", format_size(*size)))?;
+    }
+    msg_info.print(format_args!(
+        "{:width$}  {:>10}",
+        format!("total ({count} volumes)"),
+        format_size(total)
+    ))?;
+
+    Ok(())
+}
+
 pub fn remove_all_volumes(
     RemoveAllVolumes { force, execute, .. }: RemoveAllVolumes,
     engine: &docker::Engine,
@@ -371,9 +457,27 @@ pub fn prune_volumes(
     engine: &docker::Engine,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<()> {
+    // `volume prune` only filters on labels, so the unused volumes are
+    // removed by name, which includes the volumes without labels.
+    let volumes = docker::CrossLabels::list(
+        engine,
+        &[
+            "volume",
+            "list",
+            "--format",
+            "{{.Name}}",
+            "--filter",
+            "dangling=true",
+        ],
+        msg_info,
+    )?;
+
     let mut command = engine.subcommand("volume");
-    command.args(["prune", "--force"]);
-    if execute {
+    command.arg("rm");
+    command.args(&volumes);
+    if volumes.is_empty() {
+        Ok(())
+    } else if execute {
         command.run(msg_info, false).map_err(Into::into)
     } else {
         msg_info.note("this is a dry run. to prune the volumes, pass the `--execute` flag.")?;
@@ -392,12 +496,7 @@ pub fn create_persistent_volume(
     channel: Option<&Toolchain>,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<()> {
-    let mut toolchain = toolchain_or_target(&toolchain, msg_info)?;
-    if let Some(channel) = channel {
-        toolchain.channel = channel.channel.clone();
-    };
-    let mount_finder = docker::MountFinder::create(engine, msg_info)?;
-    let dirs = docker::ToolchainDirectories::assemble(&mount_finder, toolchain.clone())?;
+    let (toolchain, dirs) = toolchain_directories(&toolchain, engine, channel, msg_info)?;
     let container_id = dirs.unique_container_identifier(&toolchain.host().target)?;
     let volume_id = dirs.unique_toolchain_identifier()?;
     let volume = docker::DockerVolume::new(engine, &volume_id);
@@ -406,7 +505,11 @@ pub fn create_persistent_volume(
         eyre::bail!("Error: volume {volume_id} already exists.");
     }
 
-    volume.create(msg_info)?;
+    let labels = docker::CrossLabels {
+        toolchain: Some(toolchain.to_string()),
+        ..docker::CrossLabels::default()
+    };
+    volume.create(&labels, msg_info)?;
 
     // stop the container if it's already running
     let container = docker::DockerContainer::new(engine, &container_id);
@@ -425,7 +528,8 @@ pub fn create_persistent_volume(
     let mut docker = engine.subcommand("run");
     docker.args(["--name", &container_id]);
     docker.arg("--rm");
-    docker.args(["-v", &format!("{}:{}", volume_id, mount_prefix)]);
+    docker.args(labels.args());
+    docker.args(docker::MountSpec::new(&volume_id, mount_prefix)?.args());
     docker.arg("-d");
     let is_tty = io::Stdin::is_atty() && io::Stdout::is_atty() && io::Stderr::is_atty();
     if is_tty {
@@ -460,13 +564,15 @@ pub fn remove_persistent_volume(
     channel: Option<&Toolchain>,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<()> {
-    let mut toolchain = toolchain_or_target(&toolchain, msg_info)?;
-    if let Some(channel) = channel {
-        toolchain.channel = channel.channel.clone();
-    };
-    let mount_finder = docker::MountFinder::create(engine, msg_info)?;
-    let dirs = docker::ToolchainDirectories::assemble(&mount_finder, toolchain)?;
-    let volume_id = dirs.unique_toolchain_identifier()?;
+    let (_, dirs) = toolchain_directories(&toolchain, engine, channel, msg_info)?;
+    let mut volume_id = dirs.unique_toolchain_identifier()?;
+    if !docker::DockerVolume::new(engine, &volume_id).exists(msg_info)? {
+        // fallback to a volume created by an older version of cross
+        let legacy_id = dirs.legacy_toolchain_identifier()?;
+        if docker::DockerVolume::new(engine, &legacy_id).exists(msg_info)? {
+            volume_id = legacy_id;
+        }
+    }
     let volume = docker::DockerVolume::new(engine, &volume_id);
 
     if !volume.exists(msg_info)? {
@@ -478,23 +584,15 @@ pub fn remove_persistent_volume(
     Ok(())
 }
 
-fn get_cross_containers(
+pub(crate) fn get_cross_containers(
     engine: &docker::Engine,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<Vec<String>> {
-    use cross::docker::VOLUME_PREFIX;
-    let stdout = engine
-        .subcommand("ps")
-        .arg("-a")
-        .args(["--format", "{{.Names}}: {{.State}}"])
-        // handles simple regex: ^ for start of line.
-        .args(["--filter", &format!("name=^{VOLUME_PREFIX}")])
-        .run_and_get_stdout(msg_info)?;
-
-    let mut containers: Vec<String> = stdout.lines().map(|s| s.to_string()).collect();
-    containers.sort();
-
-    Ok(containers)
+    docker::CrossLabels::list(
+        engine,
+        &["ps", "-a", "--format", "{{.Names}}: {{.State}}"],
+        msg_info,
+    )
 }
 
 pub fn list_containers(engine: &docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
@@ -555,6 +653,23 @@ pub fn remove_all_containers(
     Ok(())
 }
 
+/// The directories of the toolchain, or the default toolchain for a target,
+/// that are copied to its persistent data volume.
+pub(crate) fn toolchain_directories(
+    toolchain: &str,
+    engine: &docker::Engine,
+    channel: Option<&Toolchain>,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<(QualifiedToolchain, docker::ToolchainDirectories)> {
+    let mut toolchain = toolchain_or_target(toolchain, msg_info)?;
+    if let Some(channel) = channel {
+        toolchain.channel = channel.channel.clone();
+    };
+    let mount_finder = docker::MountFinder::create(engine, msg_info)?;
+    let dirs = docker::ToolchainDirectories::assemble(&mount_finder, toolchain.clone())?;
+    Ok((toolchain, dirs))
+}
+
 fn toolchain_or_target(
     s: &str,
     msg_info: &mut MessageInfo,
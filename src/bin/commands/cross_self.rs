@@ -0,0 +1,68 @@
+use clap::{Args, Subcommand};
+use cross::shell::MessageInfo;
+use cross::version_check;
+
+#[derive(Subcommand, Debug)]
+pub enum CrossSelf {
+    /// Check for a newer release of cross.
+    Check(CheckVersion),
+}
+
+#[derive(Args, Debug)]
+pub struct CheckVersion {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Fetch the latest release even if it was checked today.
+    #[clap(long)]
+    pub refresh: bool,
+}
+
+impl CheckVersion {
+    pub fn run(self, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        if version_check::is_offline(&[]) {
+            eyre::bail!("unable to check for a newer release, network access is disabled by `CARGO_NET_OFFLINE`");
+        }
+        match version_check::newer_release(self.refresh, msg_info)? {
+            Some(latest) => msg_info.note(version_check::upgrade_hint(&latest))?,
+            None => msg_info.print(format_args!(
+                "cross {} is the latest release.",
+                env!("CARGO_PKG_VERSION")
+            ))?,
+        }
+
+        Ok(())
+    }
+}
+
+impl CrossSelf {
+    pub fn run(self, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        match self {
+            CrossSelf::Check(args) => args.run(msg_info),
+        }
+    }
+
+    pub fn verbose(&self) -> bool {
+        match self {
+            CrossSelf::Check(args) => args.verbose,
+        }
+    }
+
+    pub fn quiet(&self) -> bool {
+        match self {
+            CrossSelf::Check(args) => args.quiet,
+        }
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        match self {
+            CrossSelf::Check(args) => args.color.as_deref(),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, thread};
+
+use clap::Args;
+use cross::file::format_size;
+use cross::shell::MessageInfo;
+use cross::{docker, CommandExt};
+use serde::Deserialize;
+
+use super::containers::*;
+use super::images::*;
+
+#[derive(Args, Debug)]
+pub struct Gc {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Path to the TOML file with the retention policy.
+    #[clap(long)]
+    pub policy: PathBuf,
+    /// Keep running, applying the policy periodically.
+    #[clap(short, long)]
+    pub watch: bool,
+    /// Seconds to wait between runs with `--watch`.
+    #[clap(long, default_value_t = 3600)]
+    pub interval: u64,
+    /// Remove data. Default is a dry run.
+    #[clap(short, long)]
+    pub execute: bool,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+}
+
+/// The retention policy for cross data in local storage.
+///
+/// ```toml
+/// max-volume-size = "50GB"
+/// max-image-age = "30d"
+/// remove-orphaned-containers = true
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Policy {
+    /// Remove the oldest volumes while their total size is above this size.
+    max_volume_size: Option<String>,
+    /// Remove images created longer ago than this duration.
+    max_image_age: Option<String>,
+    /// Remove cross containers that are no longer running.
+    #[serde(default)]
+    remove_orphaned_containers: bool,
+}
+
+impl Gc {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        let policy = fs::read_to_string(&self.policy)?;
+        let policy: Policy = toml::from_str(&policy)?;
+        let max_volume_size = policy
+            .max_volume_size
+            .as_deref()
+            .map(parse_size)
+            .transpose()?;
+        let max_image_age = policy
+            .max_image_age
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?;
+
+        loop {
+            let result = collect(
+                &engine,
+                max_volume_size,
+                max_image_age,
+                policy.remove_orphaned_containers,
+                self.execute,
+                msg_info,
+            );
+            if !self.watch {
+                return result;
+            }
+            // a failure in one run shouldn't stop the watcher.
+            if let Err(err) = result {
+                msg_info.warn(format_args!("garbage collection failed: {err}"))?;
+            }
+            thread::sleep(Duration::from_secs(self.interval));
+        }
+    }
+
+    pub fn engine(&self) -> Option<&str> {
+        self.engine.as_deref()
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        self.color.as_deref()
+    }
+}
+
+fn collect(
+    engine: &docker::Engine,
+    max_volume_size: Option<u64>,
+    max_image_age: Option<Duration>,
+    remove_orphaned_containers: bool,
+    execute: bool,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    // containers first, since they may hold on to images and volumes.
+    if remove_orphaned_containers {
+        let mut orphaned = vec![];
+        for container in get_cross_containers(engine, msg_info)? {
+            // cannot fail, formatted as {{.Names}}: {{.State}}
+            let (name, state) = container.split_once(':').unwrap();
+            if docker::ContainerState::new(state.trim())?.is_stopped() {
+                orphaned.push(name.trim().to_owned());
+            }
+        }
+        if !orphaned.is_empty() {
+            msg_info.info(format_args!(
+                "removing orphaned containers: {}",
+                orphaned.join(", ")
+            ))?;
+            let mut rm = engine.subcommand("rm");
+            rm.args(&orphaned);
+            run_or_print(rm, execute, msg_info)?;
+        }
+    }
+
+    if let Some(max_age) = max_image_age {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut expired = vec![];
+        for image in get_cross_images(engine, msg_info, true)? {
+            let created = engine
+                .subcommand("image")
+                .args(["inspect", "--format", "{{.Created}}", &image.id])
+                .run_and_get_stdout(msg_info)?;
+            let created = created.trim();
+            if now.saturating_sub(parse_timestamp(created)?) > max_age.as_secs() {
+                msg_info.info(format_args!("removing image {image}, created {created}"))?;
+                expired.push(image);
+            }
+        }
+        remove_images(engine, &expired, msg_info, false, execute)?;
+    }
+
+    if let Some(max_size) = max_volume_size {
+        let mut volumes = volume_usage(engine, msg_info)?;
+        // remove the oldest volumes first.
+        volumes.sort_by_key(|(_, created, _)| *created);
+        let mut total: u64 = volumes.iter().map(|(_, _, size)| size).sum();
+        for (name, _, size) in volumes {
+            if total <= max_size {
+                break;
+            }
+            msg_info.info(format_args!(
+                "removing volume {name} ({}), total size is {}",
+                format_size(size),
+                format_size(total)
+            ))?;
+            let mut rm = engine.subcommand("volume");
+            rm.args(["rm", &name]);
+            run_or_print(rm, execute, msg_info)?;
+            total -= size;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_or_print(
+    mut command: std::process::Command,
+    execute: bool,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    if execute {
+        command.run(msg_info, false)
+    } else {
+        msg_info.note("this is a dry run. to remove the data, pass the `--execute` flag.")?;
+        command.print(msg_info)
+    }
+}
+
+/// Get the name, creation time and size in bytes of each cross volume.
+fn volume_usage(
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<Vec<(String, u64, u64)>> {
+    let mut volumes = vec![];
+    for (name, size) in get_volume_sizes(engine, msg_info)? {
+        let created = engine
+            .subcommand("volume")
+            .args(["inspect", "--format", "{{.CreatedAt}}", &name])
+            .run_and_get_stdout(msg_info)?;
+        volumes.push((name, parse_timestamp(&created)?, size));
+    }
+
+    Ok(volumes)
+}
+
+/// Parse a size such as `512MB` or `2GiB` into bytes.
+fn parse_size(size: &str) -> cross::Result<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (value, unit) = size.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000_u64.pow(2),
+        "g" | "gb" => 1000_u64.pow(3),
+        "t" | "tb" => 1000_u64.pow(4),
+        "kib" => 1024,
+        "mib" => 1024_u64.pow(2),
+        "gib" => 1024_u64.pow(3),
+        "tib" => 1024_u64.pow(4),
+        _ => eyre::bail!("invalid size `{size}`"),
+    };
+
+    value
+        .parse::<u64>()?
+        .checked_mul(multiplier)
+        .ok_or_else(|| eyre::eyre!("size `{size}` is too large"))
+}
+
+/// Parse a duration such as `30d` or `12h`.
+pub(crate) fn parse_duration(duration: &str) -> cross::Result<Duration> {
+    let duration = duration.trim();
+    let invalid = || eyre::eyre!("invalid duration `{duration}`");
+    // the last character may be multibyte in invalid input.
+    let (split, unit) = duration.char_indices().last().ok_or_else(invalid)?;
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    duration[..split]
+        .parse::<u64>()?
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| eyre::eyre!("duration `{duration}` is too long"))
+}
+
+/// Parse the seconds since the epoch from a timestamp given by the engine.
+///
+/// Both `2022-06-01T10:00:00.5Z` and `2022-06-01 10:00:00 +0000 UTC` are
+/// accepted. Any timezone offset is ignored, which is fine for retention.
+pub(crate) fn parse_timestamp(timestamp: &str) -> cross::Result<u64> {
+    let timestamp = timestamp.trim();
+    let invalid = || eyre::eyre!("invalid timestamp `{timestamp}`");
+    let field = |range: std::ops::Range<usize>| -> cross::Result<u64> {
+        timestamp
+            .get(range)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    if year < 1970 || !(1..=12).contains(&month) {
+        return Err(invalid());
+    }
+
+    // days since the epoch for a date in the proleptic gregorian calendar.
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Ok(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_policy() -> cross::Result<()> {
+        let policy: Policy = toml::from_str(
+            r#"
+            max-volume-size = "50GB"
+            max-image-age = "30d"
+            remove-orphaned-containers = true
+            "#,
+        )?;
+        assert_eq!(
+            parse_size(policy.max_volume_size.as_deref().unwrap())?,
+            50_000_000_000
+        );
+        assert_eq!(
+            parse_duration(policy.max_image_age.as_deref().unwrap())?,
+            Duration::from_secs(30 * 24 * 60 * 60)
+        );
+        assert!(policy.remove_orphaned_containers);
+        assert_eq!(parse_size("2GiB")?, 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("100")?, 100);
+        assert!(parse_size("1PB").is_err());
+        assert!(parse_duration("10y").is_err());
+        assert!(parse_duration("10µ").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration(&format!("{}w", u64::MAX)).is_err());
+        assert!(parse_size(&format!("{}TB", u64::MAX)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_timestamps() -> cross::Result<()> {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z")?, 0);
+        assert_eq!(
+            parse_timestamp("2022-06-01T10:00:00.123456789Z")?,
+            1654077600
+        );
+        assert_eq!(
+            parse_timestamp("2022-06-01 10:00:00 +0000 UTC\n")?,
+            1654077600
+        );
+        assert_eq!(parse_timestamp("2024-02-29T00:00:00Z")?, 1709164800);
+        assert!(parse_timestamp("yesterday").is_err());
+
+        Ok(())
+    }
+}
@@ -1,17 +1,20 @@
 use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use clap::builder::PossibleValue;
 use clap::{Args, Subcommand};
 use cross::docker::{self, CROSS_CUSTOM_DOCKERFILE_IMAGE_PREFIX};
+use cross::errors::{Context, Section};
 use cross::shell::MessageInfo;
 use cross::{CommandExt, TargetList};
 
+use super::gc::{parse_duration, parse_timestamp};
+
 // known image prefixes, with their registry
 // the docker.io registry can also be implicit
-const GHCR_IO: &str = docker::CROSS_IMAGE;
 const RUST_EMBEDDED: &str = "rustembedded/cross";
 const DOCKER_IO: &str = "docker.io/rustembedded/cross";
-const IMAGE_PREFIXES: &[&str] = &[GHCR_IO, DOCKER_IO, RUST_EMBEDDED];
+const IMAGE_PREFIXES: &[&str] = &[DOCKER_IO, RUST_EMBEDDED];
 
 #[derive(Args, Debug)]
 pub struct ListImages {
@@ -96,12 +99,145 @@ impl RemoveImages {
     }
 }
 
+#[derive(Args, Debug)]
+pub struct PruneImages {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Remove images cross hasn't used for longer than this duration, such as `30d`.
+    /// Images never used since they were recorded are aged from their creation.
+    #[clap(long)]
+    pub older_than: Option<String>,
+    /// Remove images superseded by a newer image for the same target.
+    #[clap(long)]
+    pub superseded: bool,
+    /// Also prune local (development) images.
+    #[clap(short, long)]
+    pub local: bool,
+    /// Remove images. Default is a dry run.
+    #[clap(short, long)]
+    pub execute: bool,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+}
+
+impl PruneImages {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        prune_images(self, &engine, msg_info)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InspectImages {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+    /// Output format
+    #[clap(long, default_value = "human")]
+    pub format: OutputFormat,
+    /// Also inspect local (development) images.
+    #[clap(short, long)]
+    pub local: bool,
+    /// Only inspect images for specific target(s). By default, inspect all targets.
+    pub targets: Vec<String>,
+}
+
+impl InspectImages {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        inspect_images(self, &engine, msg_info)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ScanImages {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+    /// Output format
+    #[clap(long, default_value = "human")]
+    pub format: OutputFormat,
+    /// The scanner to use. By default, `trivy` or `grype`, whichever is found first.
+    #[clap(long)]
+    pub scanner: Option<Scanner>,
+    /// Also scan local (development) images.
+    #[clap(short, long)]
+    pub local: bool,
+    /// Only scan images for specific target(s). By default, scan all targets.
+    pub targets: Vec<String>,
+}
+
+impl ScanImages {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        scan_images(self, &engine, msg_info)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyImages {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+    /// Pull the images that don't match the registry again.
+    #[clap(long)]
+    pub fix: bool,
+    /// Only verify images for specific target(s). By default, verify all targets.
+    pub targets: Vec<String>,
+}
+
+impl VerifyImages {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        verify_images(self, &engine, msg_info)
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Images {
     /// List cross images in local storage.
     List(ListImages),
     /// Remove cross images in local storage.
     Remove(RemoveImages),
+    /// Remove old or superseded cross images that no container uses.
+    Prune(PruneImages),
+    /// Show the target, version, creation date and size of cross images.
+    Inspect(InspectImages),
+    /// Scan cross images for vulnerabilities, with trivy or grype.
+    Scan(ScanImages),
+    /// Check that cross images match the digests in the registry.
+    Verify(VerifyImages),
 }
 
 impl Images {
@@ -109,6 +245,10 @@ impl Images {
         match self {
             Images::List(args) => args.run(engine, msg_info),
             Images::Remove(args) => args.run(engine, msg_info),
+            Images::Prune(args) => args.run(engine, msg_info),
+            Images::Inspect(args) => args.run(engine, msg_info),
+            Images::Scan(args) => args.run(engine, msg_info),
+            Images::Verify(args) => args.run(engine, msg_info),
         }
     }
 
@@ -116,6 +256,10 @@ impl Images {
         match self {
             Images::List(l) => l.engine.as_deref(),
             Images::Remove(l) => l.engine.as_deref(),
+            Images::Prune(l) => l.engine.as_deref(),
+            Images::Inspect(l) => l.engine.as_deref(),
+            Images::Scan(l) => l.engine.as_deref(),
+            Images::Verify(l) => l.engine.as_deref(),
         }
     }
 
@@ -123,6 +267,10 @@ impl Images {
         match self {
             Images::List(l) => l.verbose,
             Images::Remove(l) => l.verbose,
+            Images::Prune(l) => l.verbose,
+            Images::Inspect(l) => l.verbose,
+            Images::Scan(l) => l.verbose,
+            Images::Verify(l) => l.verbose,
         }
     }
 
@@ -130,6 +278,10 @@ impl Images {
         match self {
             Images::List(l) => l.quiet,
             Images::Remove(l) => l.quiet,
+            Images::Prune(l) => l.quiet,
+            Images::Inspect(l) => l.quiet,
+            Images::Scan(l) => l.quiet,
+            Images::Verify(l) => l.quiet,
         }
     }
 
@@ -137,16 +289,20 @@ impl Images {
         match self {
             Images::List(l) => l.color.as_deref(),
             Images::Remove(l) => l.color.as_deref(),
+            Images::Prune(l) => l.color.as_deref(),
+            Images::Inspect(l) => l.color.as_deref(),
+            Images::Scan(l) => l.color.as_deref(),
+            Images::Verify(l) => l.color.as_deref(),
         }
     }
 }
 
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, serde::Serialize)]
-struct Image {
+pub(crate) struct Image {
     repository: String,
     tag: String,
     // need to remove images by ID, not just tag
-    id: String,
+    pub(crate) id: String,
 }
 
 impl std::fmt::Display for Image {
@@ -176,15 +332,22 @@ fn parse_image(image: &str) -> Image {
     }
 }
 
+// the target of an image provided by cross, from the default registry
+// or the mirror set with `CROSS_IMAGE_REGISTRY`.
+fn provided_image_target(repository: &str) -> Option<&str> {
+    docker::provided_image_target(repository, &cross::config::Config::new(None))
+}
+
 fn is_cross_image(repository: &str) -> bool {
-    IMAGE_PREFIXES.iter().any(|i| repository.starts_with(i))
+    provided_image_target(repository).is_some()
+        || IMAGE_PREFIXES.iter().any(|i| repository.starts_with(i))
 }
 
 fn is_local_image(tag: &str) -> bool {
     tag.starts_with("local")
 }
 
-fn get_cross_images(
+pub(crate) fn get_cross_images(
     engine: &docker::Engine,
     msg_info: &mut MessageInfo,
     local: bool,
@@ -244,7 +407,7 @@ fn get_image_target(
     target_list: &TargetList,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<String> {
-    if let Some(stripped) = image.repository.strip_prefix(&format!("{GHCR_IO}/")) {
+    if let Some(stripped) = provided_image_target(&image.repository) {
         return Ok(stripped.to_string());
     } else if let Some(tag) = image.tag.strip_prefix(RUST_EMBEDDED) {
         return Ok(rustembedded_target(tag));
@@ -362,18 +525,30 @@ pub fn list_images(
     Ok(())
 }
 
-fn remove_images(
+pub(crate) fn remove_images(
     engine: &docker::Engine,
     images: &[Image],
     msg_info: &mut MessageInfo,
     force: bool,
     execute: bool,
+) -> cross::Result<()> {
+    let ids: Vec<String> = images.iter().map(|i| i.id.clone()).collect();
+    rmi(engine, &ids, msg_info, force, execute)
+}
+
+/// Remove images by their IDs or names.
+fn rmi(
+    engine: &docker::Engine,
+    images: &[String],
+    msg_info: &mut MessageInfo,
+    force: bool,
+    execute: bool,
 ) -> cross::Result<()> {
     let mut command = engine.subcommand("rmi");
     if force {
         command.arg("--force");
     }
-    command.args(images.iter().map(|i| &i.id));
+    command.args(images);
     if images.is_empty() {
         Ok(())
     } else if execute {
@@ -422,10 +597,706 @@ pub fn remove_target_images(
     remove_images(engine, &images, msg_info, force, execute)
 }
 
+/// The label with the version of a released image.
+const VERSION_LABEL: &str = "org.opencontainers.image.version";
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct ImageDetails {
+    target: String,
+    image: String,
+    id: String,
+    version: Option<String>,
+    created: String,
+    size: u64,
+    labels: BTreeMap<String, String>,
+}
+
+/// Parse the output of `image inspect`, which has an entry for each image.
+fn parse_image_details(
+    json: &str,
+    images: Vec<(String, Image)>,
+) -> cross::Result<Vec<ImageDetails>> {
+    let inspected: Vec<serde_json::Value> =
+        serde_json::from_str(json).map_err(|e| eyre::eyre!("invalid image details: {e}"))?;
+    if inspected.len() != images.len() {
+        eyre::bail!(
+            "expected details for {} images, got {}",
+            images.len(),
+            inspected.len()
+        );
+    }
+    Ok(images
+        .into_iter()
+        .zip(inspected)
+        .map(|((target, image), value)| {
+            let labels: BTreeMap<String, String> = value["Config"]["Labels"]
+                .as_object()
+                .map(|labels| {
+                    labels
+                        .iter()
+                        .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_owned())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            ImageDetails {
+                version: labels.get(VERSION_LABEL).cloned(),
+                created: value["Created"].as_str().unwrap_or_default().to_owned(),
+                size: value["Size"].as_u64().unwrap_or_default(),
+                image: image.to_string(),
+                id: image.id,
+                target,
+                labels,
+            }
+        })
+        .collect())
+}
+
+pub fn inspect_images(
+    InspectImages {
+        targets,
+        format,
+        local,
+        ..
+    }: InspectImages,
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let cross_images = get_cross_images(engine, msg_info, local)?;
+    let target_list = msg_info.as_quiet(cross::rustc::target_list)?;
+    let mut images = vec![];
+    for image in cross_images {
+        let target = get_image_target(engine, &image, &target_list, msg_info)?;
+        if targets.is_empty() || targets.contains(&target) {
+            images.push((target, image));
+        }
+    }
+    images.sort();
+
+    let details = if images.is_empty() {
+        vec![]
+    } else {
+        let json = engine
+            .subcommand("image")
+            .arg("inspect")
+            .args(images.iter().map(|(_, image)| &image.id))
+            .run_and_get_stdout(msg_info)?;
+        parse_image_details(&json, images)?
+    };
+
+    match format {
+        OutputFormat::Json => {
+            msg_info.info(format_args!("{}", serde_json::to_string(&details)?))?;
+        }
+        OutputFormat::Human => {
+            let mut rows = vec![["Target", "Image", "Version", "Created", "Size"]
+                .map(ToOwned::to_owned)
+                .to_vec()];
+            for image in &details {
+                rows.push(vec![
+                    image.target.clone(),
+                    image.image.clone(),
+                    image.version.clone().unwrap_or_else(|| "-".to_owned()),
+                    // only show the date and time, without the fractional seconds
+                    image
+                        .created
+                        .chars()
+                        .take(19)
+                        .collect::<String>()
+                        .replace('T', " "),
+                    cross::file::format_size(image.size),
+                ]);
+            }
+            print_table(&rows, msg_info)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a table, with the first row as the header.
+fn print_table(rows: &[Vec<String>], msg_info: &mut MessageInfo) -> cross::Result<()> {
+    let mut widths = vec![0; rows.first().map(Vec::len).unwrap_or_default()];
+    for row in rows {
+        for (width, col) in widths.iter_mut().zip(row) {
+            *width = col.len().max(*width);
+        }
+    }
+    for (index, row) in rows.iter().enumerate() {
+        let mut line = String::from("|");
+        for (width, col) in widths.iter().zip(row) {
+            line.push_str(&format!(" {col:width$} |"));
+        }
+        msg_info.print(line)?;
+        if index == 0 {
+            let mut line = String::from("|");
+            for width in &widths {
+                line.push_str(&"-".repeat(width + 2));
+                line.push('|');
+            }
+            msg_info.print(line)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A vulnerability scanner for images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scanner {
+    Trivy,
+    Grype,
+}
+
+impl clap::ValueEnum for Scanner {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Trivy, Self::Grype]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.as_str()))
+    }
+}
+
+impl Scanner {
+    fn as_str(self) -> &'static str {
+        match self {
+            Scanner::Trivy => "trivy",
+            Scanner::Grype => "grype",
+        }
+    }
+
+    /// The first scanner found on the `PATH`.
+    fn find() -> Option<Scanner> {
+        [Scanner::Trivy, Scanner::Grype]
+            .into_iter()
+            .find(|scanner| which::which(scanner.as_str()).is_ok())
+    }
+
+    fn command(self, engine: &docker::Engine, image: &str) -> std::process::Command {
+        let mut command = std::process::Command::new(self.as_str());
+        match self {
+            Scanner::Trivy => {
+                command.args(["image", "--quiet", "--format", "json"]);
+                if engine.kind.is_podman() {
+                    command.args(["--image-src", "podman"]);
+                }
+                command.arg(image);
+            }
+            Scanner::Grype => {
+                command.args(["--quiet", "--output", "json"]);
+                if engine.kind.is_podman() {
+                    command.arg(format!("podman:{image}"));
+                } else {
+                    command.arg(format!("docker:{image}"));
+                }
+            }
+        }
+        command
+    }
+
+    /// Count the vulnerabilities in the JSON report, by severity.
+    fn parse_report(self, json: &str) -> cross::Result<Severities> {
+        let report: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| eyre::eyre!("invalid {} report: {e}", self.as_str()))?;
+        let mut severities = Severities::default();
+        match self {
+            Scanner::Trivy => {
+                let results = report["Results"].as_array().into_iter().flatten();
+                for vulnerability in results
+                    .flat_map(|result| result["Vulnerabilities"].as_array().into_iter().flatten())
+                {
+                    severities.add(vulnerability["Severity"].as_str().unwrap_or_default());
+                }
+            }
+            Scanner::Grype => {
+                for found in report["matches"].as_array().into_iter().flatten() {
+                    severities.add(
+                        found["vulnerability"]["severity"]
+                            .as_str()
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+        }
+
+        Ok(severities)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct Severities {
+    critical: u64,
+    high: u64,
+    medium: u64,
+    low: u64,
+    unknown: u64,
+}
+
+impl Severities {
+    fn add(&mut self, severity: &str) {
+        match severity.to_ascii_lowercase().as_str() {
+            "critical" => self.critical += 1,
+            "high" => self.high += 1,
+            "medium" => self.medium += 1,
+            "low" | "negligible" => self.low += 1,
+            _ => self.unknown += 1,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct ScanResult {
+    target: String,
+    image: String,
+    vulnerabilities: Severities,
+}
+
+pub fn scan_images(
+    ScanImages {
+        targets,
+        format,
+        local,
+        scanner,
+        ..
+    }: ScanImages,
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let scanner = scanner
+        .or_else(Scanner::find)
+        .ok_or_else(|| eyre::eyre!("no vulnerability scanner found, install `trivy` or `grype`"))?;
+    let cross_images = get_cross_images(engine, msg_info, local)?;
+    let target_list = msg_info.as_quiet(cross::rustc::target_list)?;
+    let mut images = vec![];
+    for image in cross_images {
+        let target = get_image_target(engine, &image, &target_list, msg_info)?;
+        if targets.is_empty() || targets.contains(&target) {
+            images.push((target, image));
+        }
+    }
+    images.sort();
+
+    let mut results = vec![];
+    for (target, image) in images {
+        // untagged images can only be scanned by ID. the progress goes to
+        // stderr, so only the results are written to stdout.
+        let name = image.to_string();
+        msg_info.status(format_args!(
+            "scanning image {name} with {}",
+            scanner.as_str()
+        ))?;
+        let report = scanner
+            .command(engine, &name)
+            .run_and_get_stdout(msg_info)?;
+        results.push(ScanResult {
+            target,
+            image: name,
+            vulnerabilities: scanner.parse_report(&report)?,
+        });
+    }
+
+    match format {
+        OutputFormat::Json => {
+            msg_info.print(serde_json::to_string(&results)?)?;
+        }
+        OutputFormat::Human => {
+            let mut rows = vec![[
+                "Target", "Image", "Critical", "High", "Medium", "Low", "Unknown",
+            ]
+            .map(ToOwned::to_owned)
+            .to_vec()];
+            for result in &results {
+                let counts = &result.vulnerabilities;
+                let mut row = vec![result.target.clone(), result.image.clone()];
+                row.extend(
+                    [
+                        counts.critical,
+                        counts.high,
+                        counts.medium,
+                        counts.low,
+                        counts.unknown,
+                    ]
+                    .iter()
+                    .map(ToString::to_string),
+                );
+                rows.push(row);
+            }
+            print_table(&rows, msg_info)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum DigestStatus {
+    /// The image was pulled from the registry's current digest for its tag.
+    Current,
+    /// The tag now points to another digest in the registry.
+    Drifted {
+        local: Vec<String>,
+        registry: String,
+    },
+    /// The image wasn't pulled from the registry, so it can't be verified.
+    Unverifiable,
+}
+
+/// Compare the repository digests of a local image, `repo@sha256:...`, with
+/// the digest of its tag in the registry.
+fn digest_status(repo_digests: &[String], registry: &str) -> DigestStatus {
+    let local: Vec<String> = repo_digests
+        .iter()
+        .filter_map(|d| d.split_once('@').map(|(_, digest)| digest.to_owned()))
+        .collect();
+    if local.is_empty() {
+        DigestStatus::Unverifiable
+    } else if local.iter().any(|digest| digest == registry) {
+        DigestStatus::Current
+    } else {
+        DigestStatus::Drifted {
+            local,
+            registry: registry.to_owned(),
+        }
+    }
+}
+
+/// Get the digest of an image's tag in the registry.
+fn registry_digest(
+    engine: &docker::Engine,
+    name: &str,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<String> {
+    let digest = if engine.kind.is_podman() {
+        // podman has no command to inspect a remote manifest.
+        which::which("skopeo")
+            .map_err(|_| eyre::eyre!("`skopeo` is needed to verify images with podman"))
+            .with_suggestion(|| "install `skopeo`, or verify the images with docker.")?;
+        std::process::Command::new("skopeo")
+            .args(["inspect", "--format", "{{.Digest}}"])
+            .arg(format!("docker://{name}"))
+            .run_and_get_stdout(msg_info)?
+    } else {
+        engine
+            .subcommand("buildx")
+            .args(["imagetools", "inspect", "--format", "{{.Manifest.Digest}}"])
+            .arg(name)
+            .run_and_get_stdout(msg_info)?
+    };
+    Ok(digest.trim().to_owned())
+}
+
+pub fn verify_images(
+    VerifyImages { targets, fix, .. }: VerifyImages,
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let cross_images = get_cross_images(engine, msg_info, false)?;
+    let target_list = msg_info.as_quiet(cross::rustc::target_list)?;
+    let mut drifted = vec![];
+    for image in cross_images {
+        // only the official images, or their mirror, have a known upstream
+        if provided_image_target(&image.repository).is_none() || image.tag == "<none>" {
+            continue;
+        }
+        let target = get_image_target(engine, &image, &target_list, msg_info)?;
+        if !targets.is_empty() && !targets.contains(&target) {
+            continue;
+        }
+
+        let name = image.name();
+        let repo_digests = engine
+            .subcommand("image")
+            .args(["inspect", "--format", "{{json .RepoDigests}}", &image.id])
+            .run_and_get_stdout(msg_info)?;
+        let repo_digests: Vec<String> = serde_json::from_str(repo_digests.trim())
+            .wrap_err_with(|| format!("invalid repository digests for image {name}"))?;
+        let registry = registry_digest(engine, &name, msg_info)?;
+        match digest_status(&repo_digests, &registry) {
+            DigestStatus::Current => msg_info.print(format_args!("{name}: ok"))?,
+            DigestStatus::Drifted { local, registry } => {
+                msg_info.warn(format_args!(
+                    "{name}: local digest {} doesn't match {registry} in the registry",
+                    local.join(", ")
+                ))?;
+                drifted.push(name);
+            }
+            DigestStatus::Unverifiable => msg_info.warn(format_args!(
+                "{name}: not pulled from the registry, so it can't be verified"
+            ))?,
+        }
+    }
+
+    if drifted.is_empty() {
+        return Ok(());
+    } else if !fix {
+        return Err(eyre::eyre!(
+            "{} image(s) don't match the registry: {}",
+            drifted.len(),
+            drifted.join(", ")
+        ))
+        .with_suggestion(|| "pass `--fix` to pull the images that don't match again.");
+    }
+    for name in drifted {
+        engine.subcommand("pull").arg(&name).run(msg_info, false)?;
+    }
+
+    Ok(())
+}
+
+/// Get when an image was created, in seconds since the epoch.
+fn image_created(
+    engine: &docker::Engine,
+    image: &Image,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<u64> {
+    let created = engine
+        .subcommand("image")
+        .args(["inspect", "--format", "{{.Created}}", &image.id])
+        .run_and_get_stdout(msg_info)?;
+    parse_timestamp(&created)
+}
+
+/// Returns if any container, running or not, was created from the image.
+fn image_in_use(
+    engine: &docker::Engine,
+    image: &Image,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<bool> {
+    let containers = engine
+        .subcommand("ps")
+        .args(["-a", "--format", "{{.ID}}"])
+        .args(["--filter", &format!("ancestor={}", image.id)])
+        .run_and_get_stdout(msg_info)?;
+    Ok(!containers.trim().is_empty())
+}
+
+/// Pick the images to prune, given their target, creation time and the last
+/// time cross used them.
+///
+/// The newest image for each target is never superseded, including any
+/// other tag of the same image.
+fn images_to_prune(
+    mut images: Vec<(String, u64, u64, Image)>,
+    now: u64,
+    max_age: Option<u64>,
+    superseded: bool,
+) -> Vec<(Image, String)> {
+    // newest first for each target
+    images.sort_by(|(t1, c1, ..), (t2, c2, ..)| t1.cmp(t2).then(c2.cmp(c1)));
+    let mut newest: BTreeMap<String, String> = BTreeMap::new();
+    let mut prune = vec![];
+    for (target, _, last_used, image) in images {
+        let newest_id = newest
+            .entry(target.clone())
+            .or_insert_with(|| image.id.clone());
+        let age = now.saturating_sub(last_used);
+        let reason = if superseded && *newest_id != image.id {
+            Some(format!("superseded by a newer image for {target}"))
+        } else if max_age.map(|max| age > max).unwrap_or_default() {
+            Some(format!("unused for {} days", age / 86400))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            prune.push((image, reason));
+        }
+    }
+
+    prune
+}
+
+pub fn prune_images(
+    PruneImages {
+        older_than,
+        superseded,
+        local,
+        execute,
+        ..
+    }: PruneImages,
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let max_age = older_than
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?
+        .map(|age| age.as_secs());
+    if max_age.is_none() && !superseded {
+        eyre::bail!("nothing to prune, pass `--older-than` or `--superseded`");
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let target_list = msg_info.as_quiet(cross::rustc::target_list)?;
+    let uses = docker::image_uses()?;
+    let mut images = vec![];
+    for image in get_cross_images(engine, msg_info, local)? {
+        let target = get_image_target(engine, &image, &target_list, msg_info)?;
+        let created = image_created(engine, &image, msg_info)?;
+        let last_used = uses
+            .get(&image.name())
+            .map_or(created, |&used| used.max(created));
+        images.push((target, created, last_used, image));
+    }
+
+    // images are removed by their tags, so other tags of the same image
+    // are kept, and the image is only removed with its last tag.
+    let mut prune = vec![];
+    for (image, reason) in images_to_prune(images, now, max_age, superseded) {
+        if image_in_use(engine, &image, msg_info)? {
+            msg_info.info(format_args!("keeping image {image}, which is in use"))?;
+        } else {
+            msg_info.info(format_args!("removing image {image}, {reason}"))?;
+            prune.push(image.to_string());
+        }
+    }
+    rmi(engine, &prune, msg_info, false, execute)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const GHCR_IO: &str = docker::CROSS_IMAGE;
+
+    #[test]
+    fn prune_images() {
+        const DAY: u64 = 86400;
+        let image = |tag: &str, id: &str| Image {
+            repository: "ghcr.io/cross-rs/aarch64-unknown-linux-gnu".to_owned(),
+            tag: tag.to_owned(),
+            id: id.to_owned(),
+        };
+        let target = "aarch64-unknown-linux-gnu".to_owned();
+        let images = || {
+            vec![
+                (target.clone(), 10 * DAY, 10 * DAY, image("0.2.4", "a")),
+                (target.clone(), 50 * DAY, 50 * DAY, image("0.2.5", "b")),
+                (target.clone(), 50 * DAY, 50 * DAY, image("latest", "b")),
+                (target.clone(), 40 * DAY, 40 * DAY, image("edge", "c")),
+                (target.clone(), 5 * DAY, 55 * DAY, image("0.2.3", "d")),
+            ]
+        };
+        let tags = |prune: Vec<(Image, String)>| {
+            prune
+                .into_iter()
+                .map(|(image, _)| image.tag)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            tags(images_to_prune(images(), 60 * DAY, None, true)),
+            ["edge", "0.2.4", "0.2.3"]
+        );
+        assert_eq!(
+            tags(images_to_prune(images(), 60 * DAY, Some(30 * DAY), false)),
+            ["0.2.4"]
+        );
+        assert!(images_to_prune(images(), 60 * DAY, Some(60 * DAY), false).is_empty());
+    }
+
+    #[test]
+    fn image_details() -> cross::Result<()> {
+        let image = |tag: &str, id: &str| Image {
+            repository: format!("{GHCR_IO}/aarch64-unknown-linux-gnu"),
+            tag: tag.to_owned(),
+            id: id.to_owned(),
+        };
+        let json = r#"[
+            {
+                "Id": "sha256:abc",
+                "Created": "2022-06-01T10:00:00.123456789Z",
+                "Size": 1234567,
+                "Config": {"Labels": {"org.opencontainers.image.version": "0.2.4", "org.cross-rs.for-cross-target": "aarch64-unknown-linux-gnu"}}
+            },
+            {"Id": "sha256:def", "Created": "2022-07-01T10:00:00Z", "Size": 10, "Config": {"Labels": null}}
+        ]"#;
+        let target = "aarch64-unknown-linux-gnu".to_owned();
+        let details = parse_image_details(
+            json,
+            vec![
+                (target.clone(), image("0.2.4", "abc")),
+                (target.clone(), image("main", "def")),
+            ],
+        )?;
+        assert_eq!(details[0].version.as_deref(), Some("0.2.4"));
+        assert_eq!(details[0].size, 1234567);
+        assert_eq!(details[0].labels.len(), 2);
+        assert_eq!(details[1].version, None);
+        assert!(details[1].labels.is_empty());
+        assert_eq!(
+            details[1].image,
+            format!("{GHCR_IO}/aarch64-unknown-linux-gnu:main")
+        );
+        assert!(parse_image_details(json, vec![(target, image("0.2.4", "abc"))]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_reports() -> cross::Result<()> {
+        let trivy = r#"{
+            "Results": [
+                {"Target": "ubuntu", "Vulnerabilities": [{"Severity": "HIGH"}, {"Severity": "LOW"}, {"Severity": "HIGH"}]},
+                {"Target": "usr/local/bin", "Vulnerabilities": null},
+                {"Target": "usr/lib", "Vulnerabilities": [{"Severity": "CRITICAL"}, {"Severity": "UNKNOWN"}]}
+            ]
+        }"#;
+        assert_eq!(
+            Scanner::Trivy.parse_report(trivy)?,
+            Severities {
+                critical: 1,
+                high: 2,
+                medium: 0,
+                low: 1,
+                unknown: 1,
+            }
+        );
+
+        let grype = r#"{
+            "matches": [
+                {"vulnerability": {"id": "CVE-1", "severity": "Medium"}},
+                {"vulnerability": {"id": "CVE-2", "severity": "Negligible"}}
+            ]
+        }"#;
+        assert_eq!(
+            Scanner::Grype.parse_report(grype)?,
+            Severities {
+                medium: 1,
+                low: 1,
+                ..Severities::default()
+            }
+        );
+        assert_eq!(
+            Scanner::Trivy.parse_report(r#"{"SchemaVersion": 2}"#)?,
+            Severities::default()
+        );
+        assert!(Scanner::Grype.parse_report("not json").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn digest_statuses() {
+        let repo_digests = |digests: &[&str]| -> Vec<String> {
+            digests
+                .iter()
+                .map(|d| format!("{GHCR_IO}/aarch64-unknown-linux-gnu@{d}"))
+                .collect()
+        };
+        assert_eq!(
+            digest_status(&repo_digests(&["sha256:abc", "sha256:def"]), "sha256:def"),
+            DigestStatus::Current
+        );
+        assert_eq!(
+            digest_status(&repo_digests(&["sha256:abc"]), "sha256:def"),
+            DigestStatus::Drifted {
+                local: vec!["sha256:abc".to_owned()],
+                registry: "sha256:def".to_owned(),
+            }
+        );
+        assert_eq!(digest_status(&[], "sha256:def"), DigestStatus::Unverifiable);
+    }
+
     #[test]
     fn parse_rustembedded_target() {
         let targets = [
@@ -1,7 +1,23 @@
+mod artifacts;
+mod build_image;
 mod clean;
 mod containers;
+mod cross_self;
+mod gc;
 mod images;
+mod networks;
+mod nix;
+mod remote;
+mod stats;
 
+pub use self::artifacts::*;
+pub use self::build_image::*;
 pub use self::clean::*;
 pub use self::containers::*;
+pub use self::cross_self::*;
+pub use self::gc::*;
 pub use self::images::*;
+pub use self::networks::*;
+pub use self::nix::*;
+pub use self::remote::*;
+pub use self::stats::*;
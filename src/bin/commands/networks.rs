@@ -0,0 +1,137 @@
+use clap::{Args, Subcommand};
+use cross::docker::{self, DockerNetwork};
+use cross::shell::MessageInfo;
+use cross::CommandExt;
+
+#[derive(Args, Debug)]
+pub struct ListNetworks {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+}
+
+impl ListNetworks {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        list_networks(&engine, msg_info)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RemoveNetworks {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Only remove the network for the project in the current directory.
+    #[clap(long)]
+    pub project: bool,
+    /// Remove networks. Default is a dry run.
+    #[clap(short, long)]
+    pub execute: bool,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+}
+
+impl RemoveNetworks {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        remove_networks(self, &engine, msg_info)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Networks {
+    /// List cross networks.
+    List(ListNetworks),
+    /// Remove cross networks that are no longer in use.
+    Remove(RemoveNetworks),
+}
+
+macro_rules! networks_get_field {
+    ($self:ident, $field:ident $(.$cb:ident)?) => {{
+        match $self {
+            Networks::List(l) => l.$field$(.$cb())?,
+            Networks::Remove(l) => l.$field$(.$cb())?,
+        }
+    }};
+}
+
+impl Networks {
+    pub fn run(self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        match self {
+            Networks::List(args) => args.run(engine, msg_info),
+            Networks::Remove(args) => args.run(engine, msg_info),
+        }
+    }
+
+    pub fn engine(&self) -> Option<&str> {
+        networks_get_field!(self, engine.as_deref)
+    }
+
+    pub fn verbose(&self) -> bool {
+        networks_get_field!(self, verbose)
+    }
+
+    pub fn quiet(&self) -> bool {
+        networks_get_field!(self, quiet)
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        networks_get_field!(self, color.as_deref)
+    }
+}
+
+pub fn list_networks(engine: &docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+    for line in DockerNetwork::existing(engine, msg_info)?.iter() {
+        msg_info.print(line)?;
+    }
+
+    Ok(())
+}
+
+pub fn remove_networks(
+    RemoveNetworks {
+        project, execute, ..
+    }: RemoveNetworks,
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let networks = if project {
+        let metadata = cross::cargo_metadata_with_args(None, None, msg_info)?
+            .ok_or_else(|| eyre::eyre!("unable to find the workspace root"))?;
+        let name = DockerNetwork::project_name(&metadata.workspace_root)?;
+        match DockerNetwork::new(engine, &name).exists(msg_info)? {
+            true => vec![name],
+            false => vec![],
+        }
+    } else {
+        DockerNetwork::existing(engine, msg_info)?
+    };
+
+    let mut command = engine.subcommand("network");
+    command.arg("rm");
+    command.args(&networks);
+    if networks.is_empty() {
+        Ok(())
+    } else if execute {
+        command.run(msg_info, false)
+    } else {
+        msg_info.note("this is a dry run. to remove the networks, pass the `--execute` flag.")?;
+        command.print(msg_info)?;
+        Ok(())
+    }
+}
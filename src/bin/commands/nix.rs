@@ -0,0 +1,106 @@
+use std::env;
+
+use clap::{Args, Subcommand};
+use cross::docker::{self, ContainerEnvironment};
+use cross::rustc::{QualifiedToolchain, Toolchain};
+use cross::shell::MessageInfo;
+
+#[derive(Args, Debug)]
+pub struct PrintEnv {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+    /// The target to build for. Defaults to the host.
+    #[clap(long)]
+    pub target: Option<String>,
+    /// The format of the output.
+    #[clap(long, default_value = "json", value_parser = ["json", "nix"])]
+    pub format: String,
+}
+
+impl PrintEnv {
+    pub fn run(
+        self,
+        engine: docker::Engine,
+        channel: Option<&Toolchain>,
+        msg_info: &mut MessageInfo,
+    ) -> cross::Result<()> {
+        let metadata = cross::cargo_metadata_with_args(None, None, msg_info)?
+            .ok_or_else(|| eyre::eyre!("unable to find the workspace root"))?;
+        let config = cross::config::Config::new(None);
+        let mut toolchain = QualifiedToolchain::default(&config, msg_info)?;
+        if let Some(channel) = channel {
+            toolchain.channel = channel.channel.clone();
+        }
+        let target = match self.target {
+            Some(target) => target,
+            None => toolchain.host().target.triple().to_owned(),
+        };
+        let paths = docker::DockerPaths::create(
+            &engine,
+            metadata,
+            env::current_dir()?,
+            toolchain,
+            msg_info,
+        )?;
+        let environment = ContainerEnvironment::new(&paths, &target)?;
+        match self.format.as_str() {
+            "nix" => msg_info.print(environment.to_nix().trim_end())?,
+            _ => msg_info.print(serde_json::to_string_pretty(&environment)?)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Nix {
+    /// Print the environment and mounts of the build container.
+    PrintEnv(PrintEnv),
+}
+
+macro_rules! nix_get_field {
+    ($self:ident, $field:ident $(.$cb:ident)?) => {{
+        match $self {
+            Nix::PrintEnv(l) => l.$field$(.$cb())?,
+        }
+    }};
+}
+
+impl Nix {
+    pub fn run(
+        self,
+        engine: docker::Engine,
+        channel: Option<&Toolchain>,
+        msg_info: &mut MessageInfo,
+    ) -> cross::Result<()> {
+        match self {
+            Nix::PrintEnv(args) => args.run(engine, channel, msg_info),
+        }
+    }
+
+    pub fn engine(&self) -> Option<&str> {
+        nix_get_field!(self, engine.as_deref)
+    }
+
+    pub fn verbose(&self) -> bool {
+        nix_get_field!(self, verbose)
+    }
+
+    pub fn quiet(&self) -> bool {
+        nix_get_field!(self, quiet)
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        nix_get_field!(self, color.as_deref)
+    }
+}
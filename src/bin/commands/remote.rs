@@ -0,0 +1,245 @@
+use std::process::Command;
+
+use clap::{Args, Subcommand};
+use cross::docker::{self, RemoteProfile, RemoteProfiles};
+use cross::errors::{Context, Section};
+use cross::rustc::Toolchain;
+use cross::shell::MessageInfo;
+use cross::{CommandExt, Target, TargetTriple};
+
+use super::containers::{create_persistent_volume, toolchain_directories, CreateVolume};
+
+#[derive(Args, Debug)]
+pub struct SetupRemote {
+    /// The name to select the builder with, as in `cross --remote <name>`.
+    pub name: String,
+    /// The daemon host, such as `ssh://user@host`.
+    #[clap(long)]
+    pub host: Option<String>,
+    /// The docker context or podman connection of the daemon.
+    #[clap(long)]
+    pub context: Option<String>,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+    /// Toolchain to create a volume for
+    #[clap(long, default_value = TargetTriple::DEFAULT.triple(), )]
+    pub toolchain: String,
+    /// If we should copy the cargo registry to the volume.
+    #[clap(short, long)]
+    pub copy_registry: bool,
+    /// Don't create the persistent data volume for the toolchain.
+    #[clap(long)]
+    pub no_volume: bool,
+    /// Register qemu interpreters on the host of the daemon, if they aren't.
+    #[clap(long)]
+    pub binfmt: bool,
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+}
+
+impl SetupRemote {
+    pub fn run(self, channel: Option<&Toolchain>, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        if self.host.is_none() && self.context.is_none() {
+            eyre::bail!("a remote builder needs a `--host` or a `--context`");
+        }
+        let profile = RemoteProfile {
+            host: self.host,
+            context: self.context,
+            engine: self.engine,
+        };
+
+        // 1. check the host is reachable, since the engine hides ssh errors
+        if let Some((destination, port)) = profile.ssh_destination() {
+            msg_info.note(format_args!("checking the connection to `{destination}`."))?;
+            let mut ssh = Command::new("ssh");
+            ssh.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+            if let Some(port) = port {
+                ssh.args(["-p", port]);
+            }
+            ssh.args([destination, "true"])
+                .run(msg_info, true)
+                .wrap_err_with(|| format!("could not connect to `{destination}` with ssh"))
+                .with_suggestion(|| {
+                    format!("check `ssh {destination}` works without a password prompt, such as with an ssh agent.")
+                })?;
+        }
+
+        // 2. probe the engine, which checks its version
+        let engine = docker::Engine::from_profile(&profile, msg_info)?;
+        match &engine.version {
+            Some(version) => msg_info.note(format_args!(
+                "connected to {} {version}.",
+                engine.kind.name()
+            ))?,
+            None => msg_info.warn(format_args!(
+                "could not get the version of {}.",
+                engine.kind.name()
+            ))?,
+        }
+
+        // 3. create the toolchain volume, so the toolchain is only copied once
+        if !self.no_volume {
+            let (_, dirs) = toolchain_directories(&self.toolchain, &engine, channel, msg_info)?;
+            let volume_id = dirs.unique_toolchain_identifier()?;
+            if docker::DockerVolume::new(&engine, &volume_id).exists(msg_info)? {
+                msg_info.note(format_args!("volume `{volume_id}` already exists."))?;
+            } else {
+                msg_info.note(format_args!("creating volume `{volume_id}`."))?;
+                let create = CreateVolume {
+                    docker_in_docker: false,
+                    copy_registry: self.copy_registry,
+                    verbose: self.verbose,
+                    quiet: self.quiet,
+                    color: None,
+                    engine: None,
+                    toolchain: self.toolchain,
+                };
+                create_persistent_volume(create, &engine, channel, msg_info)?;
+            }
+        }
+
+        // 4. register qemu for the targets that need an interpreter
+        if self.binfmt {
+            if binfmt_registered(&engine, msg_info)? {
+                msg_info.note("qemu interpreters are already registered.")?;
+            } else {
+                engine.register_binfmt(&Target::DEFAULT, msg_info)?;
+            }
+        }
+
+        // 5. store the profile
+        let path = RemoteProfiles::path()?;
+        let mut profiles = RemoteProfiles::read(&path)?;
+        profiles.profiles.insert(self.name.clone(), profile);
+        profiles.write(&path)?;
+        msg_info.note(format_args!(
+            "saved remote builder `{}`, select it with `cross --remote {}`.",
+            self.name, self.name
+        ))?;
+
+        Ok(())
+    }
+}
+
+/// Check if qemu is registered with binfmt_misc on the host of the daemon.
+fn binfmt_registered(engine: &docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<bool> {
+    let status = engine
+        .subcommand("run")
+        .args(["--rm", "--privileged", docker::UBUNTU_BASE])
+        .args([
+            "sh",
+            "-c",
+            "mount binfmt_misc -t binfmt_misc /proc/sys/fs/binfmt_misc 2>/dev/null; \
+                ls /proc/sys/fs/binfmt_misc | grep -q '^qemu-'",
+        ])
+        .run_and_get_status(msg_info, true)?;
+    Ok(status.success())
+}
+
+#[derive(Args, Debug)]
+pub struct ListRemotes {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+}
+
+impl ListRemotes {
+    pub fn run(self, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        let profiles = RemoteProfiles::read(&RemoteProfiles::path()?)?;
+        for (name, profile) in &profiles.profiles {
+            let address = profile
+                .host
+                .as_deref()
+                .or(profile.context.as_deref())
+                .unwrap_or_default();
+            msg_info.print(format_args!("{name}: {address}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RemoveRemote {
+    /// The name of the builder.
+    pub name: String,
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+}
+
+impl RemoveRemote {
+    pub fn run(self, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        let path = RemoteProfiles::path()?;
+        let mut profiles = RemoteProfiles::read(&path)?;
+        profiles.get(&self.name)?;
+        profiles.profiles.remove(&self.name);
+        profiles.write(&path)?;
+        msg_info.note(format_args!(
+            "removed remote builder `{}`, its volumes are kept.",
+            self.name
+        ))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Remote {
+    /// Check a remote daemon, prepare it for builds and save it as a named builder.
+    Setup(SetupRemote),
+    /// List the remote builders.
+    List(ListRemotes),
+    /// Remove a remote builder.
+    Remove(RemoveRemote),
+}
+
+macro_rules! remote_get_field {
+    ($self:ident, $field:ident $(.$cb:ident)?) => {{
+        match $self {
+            Remote::Setup(l) => l.$field$(.$cb())?,
+            Remote::List(l) => l.$field$(.$cb())?,
+            Remote::Remove(l) => l.$field$(.$cb())?,
+        }
+    }};
+}
+
+impl Remote {
+    pub fn run(self, channel: Option<&Toolchain>, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        match self {
+            Remote::Setup(args) => args.run(channel, msg_info),
+            Remote::List(args) => args.run(msg_info),
+            Remote::Remove(args) => args.run(msg_info),
+        }
+    }
+
+    pub fn verbose(&self) -> bool {
+        remote_get_field!(self, verbose)
+    }
+
+    pub fn quiet(&self) -> bool {
+        remote_get_field!(self, quiet)
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        remote_get_field!(self, color.as_deref)
+    }
+}
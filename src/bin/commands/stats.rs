@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use cross::shell::MessageInfo;
+use cross::stats::{self, Stats};
+
+/// The number of latest runs compared to the earlier ones for the trend.
+const RECENT_RUNS: usize = 5;
+
+#[derive(Args, Debug)]
+pub struct BuildStats {
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Coloring: auto, always, never
+    #[clap(long)]
+    pub color: Option<String>,
+    /// Path to the statistics. Defaults to the one in the workspace root.
+    #[clap(long)]
+    pub path: Option<PathBuf>,
+    /// Only show the statistics for these targets.
+    #[clap(long)]
+    pub target: Vec<String>,
+    /// Print the statistics as JSON rather than a summary.
+    #[clap(long)]
+    pub json: bool,
+}
+
+impl BuildStats {
+    pub fn run(self, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        let path = match self.path {
+            Some(path) => path,
+            None => stats::path(
+                &cross::cargo_metadata_with_args(None, None, msg_info)?
+                    .ok_or_else(|| eyre::eyre!("unable to find the workspace root"))?
+                    .workspace_root,
+            ),
+        };
+        let mut stats = Stats::read(&path)?;
+        if !self.target.is_empty() {
+            stats
+                .targets
+                .retain(|target, _| self.target.contains(target));
+        }
+
+        if self.json {
+            msg_info.print(serde_json::to_string_pretty(&stats)?)?;
+            return Ok(());
+        }
+        if stats.targets.is_empty() {
+            msg_info.info(format_args!(
+                "no build statistics found, set `{}=1` to record them.",
+                stats::STATS_ENV
+            ))?;
+            return Ok(());
+        }
+        for (target, target_stats) in &stats.targets {
+            let mut line = format!(
+                "{target}: {} builds, {} failed",
+                target_stats.builds, target_stats.failures
+            );
+            if let Some(mean) = target_stats.mean_duration() {
+                line.push_str(&format!(", {mean:.1}s on average"));
+            }
+            if let Some(last) = target_stats.runs.last() {
+                line.push_str(&format!(", last {:.1}s", last.duration_secs));
+            }
+            if let Some(trend) = target_stats.trend(RECENT_RUNS) {
+                line.push_str(&format!(
+                    ", {:+.0}% over the last {RECENT_RUNS} builds",
+                    trend * 100.0
+                ));
+            }
+            msg_info.print(line)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn color(&self) -> Option<&str> {
+        self.color.as_deref()
+    }
+}
@@ -15,6 +15,9 @@ struct Cli {
     /// Toolchain name/version to use (such as stable or 1.59.0).
     #[clap(value_parser = is_toolchain)]
     toolchain: Option<Toolchain>,
+    /// Disable color output, the same as `--color never`.
+    #[clap(long, global = true)]
+    no_color: bool,
     #[clap(subcommand)]
     command: Commands,
 }
@@ -37,8 +40,28 @@ enum Commands {
     /// Work with cross containers in local storage.
     #[clap(subcommand)]
     Containers(commands::Containers),
+    /// Work with the networks cross creates for projects.
+    #[clap(subcommand)]
+    Networks(commands::Networks),
     /// Clean all cross data in local storage.
     Clean(commands::Clean),
+    /// Apply a retention policy to cross data in local storage.
+    Gc(commands::Gc),
+    /// List the artifacts in the manifest of a multi-target build.
+    Artifacts(commands::Artifacts),
+    /// Build the image for a target from a checkout of cross.
+    BuildImage(commands::BuildImage),
+    /// Describe the build environment for Nix-based tools.
+    #[clap(subcommand)]
+    Nix(commands::Nix),
+    /// Set up and manage named remote builders.
+    #[clap(subcommand)]
+    Remote(commands::Remote),
+    /// Show the build statistics recorded with `CROSS_STATS`.
+    Stats(commands::BuildStats),
+    /// Work with the installation of cross.
+    #[clap(name = "self", subcommand)]
+    CrossSelf(commands::CrossSelf),
 }
 
 fn is_toolchain(toolchain: &str) -> cross::Result<Toolchain> {
@@ -64,7 +87,7 @@ fn get_container_engine(
         true => Some(true),
         false => None,
     };
-    docker::Engine::from_path(engine, in_docker, None, msg_info)
+    docker::Engine::from_path(engine, in_docker, None, None, msg_info)
 }
 
 macro_rules! get_engine {
@@ -74,8 +97,12 @@ macro_rules! get_engine {
 }
 
 macro_rules! get_msg_info {
-    ($args:ident) => {{
-        MessageInfo::create($args.verbose(), $args.quiet(), $args.color())
+    ($args:ident, $no_color:expr) => {{
+        let color = match $no_color {
+            true => Some("never"),
+            false => $args.color(),
+        };
+        MessageInfo::create($args.verbose(), $args.quiet(), color)
     }};
 }
 
@@ -84,25 +111,61 @@ pub fn main() -> cross::Result<()> {
     let cli = Cli::parse();
     match cli.command {
         Commands::Images(args) => {
-            let mut msg_info = get_msg_info!(args)?;
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
             let engine = get_engine!(args, false, msg_info)?;
             args.run(engine, &mut msg_info)?;
         }
         Commands::Volumes(args) => {
-            let mut msg_info = get_msg_info!(args)?;
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
             let engine = get_engine!(args, args.docker_in_docker(), msg_info)?;
             args.run(engine, cli.toolchain.as_ref(), &mut msg_info)?;
         }
         Commands::Containers(args) => {
-            let mut msg_info = get_msg_info!(args)?;
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            let engine = get_engine!(args, false, msg_info)?;
+            args.run(engine, &mut msg_info)?;
+        }
+        Commands::Networks(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
             let engine = get_engine!(args, false, msg_info)?;
             args.run(engine, &mut msg_info)?;
         }
         Commands::Clean(args) => {
-            let mut msg_info = get_msg_info!(args)?;
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
             let engine = get_engine!(args, false, msg_info)?;
             args.run(engine, &mut msg_info)?;
         }
+        Commands::Gc(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            let engine = get_engine!(args, false, msg_info)?;
+            args.run(engine, &mut msg_info)?;
+        }
+        Commands::Artifacts(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            args.run(&mut msg_info)?;
+        }
+        Commands::BuildImage(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            let engine = get_engine!(args, false, msg_info)?;
+            args.run(engine, &mut msg_info)?;
+        }
+        Commands::Nix(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            let engine = get_engine!(args, false, msg_info)?;
+            args.run(engine, cli.toolchain.as_ref(), &mut msg_info)?;
+        }
+        Commands::Remote(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            args.run(cli.toolchain.as_ref(), &mut msg_info)?;
+        }
+        Commands::Stats(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            args.run(&mut msg_info)?;
+        }
+        Commands::CrossSelf(args) => {
+            let mut msg_info = get_msg_info!(args, cli.no_color)?;
+            args.run(&mut msg_info)?;
+        }
     }
 
     Ok(())
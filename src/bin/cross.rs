@@ -1,12 +1,11 @@
 #![deny(missing_debug_implementations, rust_2018_idioms)]
 
-use std::{
-    env,
-    io::{self, Write},
-};
+use std::io::{self, Write};
 
 use cross::{
-    cargo, cli, rustc,
+    cargo, cli,
+    errors::ExitCode,
+    rustc,
     shell::{self, Verbosity},
     OutputExt, Subcommand,
 };
@@ -15,17 +14,28 @@ pub fn main() -> cross::Result<()> {
     cross::install_panic_hook()?;
     cross::install_termination_hook()?;
 
+    // failures in cross itself map to a stable exit code for their category,
+    // while the exit code of cargo is returned unchanged.
+    let code = match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::failure()
+        }
+    };
+    std::process::exit(code)
+}
+
+fn run() -> cross::Result<i32> {
     let target_list = rustc::target_list(&mut Verbosity::Quiet.into())?;
     let args = cli::parse(&target_list)?;
-    let subcommand = args.subcommand;
     let mut msg_info = shell::MessageInfo::create(args.verbose, args.quiet, args.color.as_deref())?;
-    let status = match cross::run(args, target_list, &mut msg_info)? {
+    let status = match cross::run(args.clone(), target_list, &mut msg_info)? {
         Some(status) => status,
         None => {
-            // if we fallback to the host cargo, use the same invocation that was made to cross
-            let argv: Vec<String> = env::args().skip(1).collect();
             msg_info.note("Falling back to `cargo` on the host.")?;
-            match subcommand {
+            let argv = cross::host_cargo_args(&args, &mut msg_info)?;
+            match args.subcommand {
                 Some(Subcommand::List) => {
                     // this won't print in order if we have both stdout and stderr.
                     let out = cargo::run_and_get_output(&argv, &mut msg_info)?;
@@ -43,8 +53,7 @@ pub fn main() -> cross::Result<()> {
             }
         }
     };
-    let code = status
+    status
         .code()
-        .ok_or_else(|| eyre::Report::msg("Cargo process terminated by signal"))?;
-    std::process::exit(code)
+        .ok_or_else(|| eyre::Report::msg("Cargo process terminated by signal"))
 }
@@ -21,6 +21,8 @@ pub enum Subcommand {
     Metadata,
     List,
     Clean,
+    Nextest,
+    RunBin,
 }
 
 impl Subcommand {
@@ -40,13 +42,58 @@ impl Subcommand {
 
     #[must_use]
     pub fn needs_interpreter(self) -> bool {
-        matches!(self, Subcommand::Run | Subcommand::Test | Subcommand::Bench)
+        matches!(
+            self,
+            Subcommand::Run
+                | Subcommand::Test
+                | Subcommand::Bench
+                | Subcommand::Nextest
+                | Subcommand::RunBin
+        )
+    }
+
+    /// If the subcommand accepts `--features`.
+    #[must_use]
+    pub fn accepts_features(self) -> bool {
+        matches!(
+            self,
+            Subcommand::Build
+                | Subcommand::Check
+                | Subcommand::Doc
+                | Subcommand::Run
+                | Subcommand::Rustc
+                | Subcommand::Test
+                | Subcommand::Bench
+                | Subcommand::Clippy
+                | Subcommand::Metadata
+                | Subcommand::Nextest
+        )
     }
 
     #[must_use]
     pub fn needs_target_in_command(self) -> bool {
         !matches!(self, Subcommand::Metadata)
     }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Subcommand::Build => "build",
+            Subcommand::Check => "check",
+            Subcommand::Doc => "doc",
+            Subcommand::Other => "other",
+            Subcommand::Run => "run",
+            Subcommand::Rustc => "rustc",
+            Subcommand::Test => "test",
+            Subcommand::Bench => "bench",
+            Subcommand::Clippy => "clippy",
+            Subcommand::Metadata => "metadata",
+            Subcommand::List => "--list",
+            Subcommand::Clean => "clean",
+            Subcommand::Nextest => "nextest",
+            Subcommand::RunBin => "run-bin",
+        }
+    }
 }
 
 impl<'a> From<&'a str> for Subcommand {
@@ -63,6 +110,8 @@ impl<'a> From<&'a str> for Subcommand {
             "clippy" => Subcommand::Clippy,
             "metadata" => Subcommand::Metadata,
             "--list" => Subcommand::List,
+            "nextest" => Subcommand::Nextest,
+            "run-bin" => Subcommand::RunBin,
             _ => Subcommand::Other,
         }
     }
@@ -88,6 +137,28 @@ impl CargoMetadata {
         self.non_workspace_members().filter_map(|p| p.crate_path())
     }
 
+    /// The `[package.metadata.docs.rs]` of the package in the current
+    /// directory, or of the only workspace member.
+    pub fn docs_rs(&self, cwd: &Path) -> Result<Option<DocsRsMetadata>> {
+        let members: Vec<_> = self
+            .packages
+            .iter()
+            .filter(|p| self.workspace_members.iter().any(|m| m == &p.id))
+            .collect();
+        let package = members
+            .iter()
+            .filter(|p| matches!(p.manifest_path.parent(), Some(dir) if cwd.starts_with(dir)))
+            .max_by_key(|p| p.manifest_path.components().count())
+            .or(match members.as_slice() {
+                [package] => Some(package),
+                _ => None,
+            });
+        match package {
+            Some(package) => package.docs_rs(),
+            None => Ok(None),
+        }
+    }
+
     #[cfg(feature = "dev")]
     #[must_use]
     pub fn get_package(&self, package: &str) -> Option<&Package> {
@@ -103,9 +174,72 @@ pub struct Package {
     pub source: Option<String>,
     pub version: String,
     pub license: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// The settings docs.rs builds a package with, from
+/// `[package.metadata.docs.rs]`.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct DocsRsMetadata {
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub all_features: bool,
+    #[serde(default)]
+    pub no_default_features: bool,
+    pub default_target: Option<String>,
+    #[serde(default)]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub rustdoc_args: Vec<String>,
+}
+
+impl DocsRsMetadata {
+    /// The target docs.rs builds the documentation for by default.
+    pub fn target(&self) -> Option<&str> {
+        self.default_target
+            .as_deref()
+            .or_else(|| self.targets.first().map(String::as_str))
+    }
+
+    /// The cargo flags to select the features docs.rs builds with.
+    pub fn cargo_flags(&self) -> Vec<String> {
+        let mut flags = vec![];
+        if self.all_features {
+            flags.push("--all-features".to_owned());
+        }
+        if self.no_default_features {
+            flags.push("--no-default-features".to_owned());
+        }
+        if !self.features.is_empty() {
+            flags.push("--features".to_owned());
+            flags.push(self.features.join(","));
+        }
+        flags
+    }
+
+    /// The flags docs.rs passes to rustdoc, including `--cfg docsrs`.
+    pub fn rustdoc_flags(&self) -> Vec<String> {
+        let mut flags = vec!["--cfg".to_owned(), "docsrs".to_owned()];
+        flags.extend(self.rustdoc_args.iter().cloned());
+        flags
+    }
 }
 
 impl Package {
+    fn docs_rs(&self) -> Result<Option<DocsRsMetadata>> {
+        match self.metadata.as_ref().map(|m| &m["docs"]["rs"]) {
+            Some(docs_rs) if !docs_rs.is_null() => serde_json::from_value(docs_rs.clone())
+                .map(Some)
+                .wrap_err_with(|| {
+                    format!("invalid `[package.metadata.docs.rs]` for `{}`", self.name)
+                }),
+            _ => Ok(None),
+        }
+    }
+
     /// Returns the absolute path to the packages manifest "folder"
     fn crate_path(&self) -> Option<&Path> {
         // when source is none, this package is a path dependency or a workspace member
@@ -183,3 +317,64 @@ pub fn run_and_get_output(
 ) -> Result<std::process::Output> {
     cargo_command().args(args).run_and_get_output(msg_info)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn docs_rs_metadata() -> Result<()> {
+        let metadata: CargoMetadata = serde_json::from_str(
+            r#"{
+                "workspace_root": "/project",
+                "target_directory": "/project/target",
+                "workspace_members": ["foo 0.1.0", "bar 0.1.0"],
+                "packages": [
+                    {
+                        "id": "foo 0.1.0",
+                        "name": "foo",
+                        "manifest_path": "/project/Cargo.toml",
+                        "source": null,
+                        "version": "0.1.0",
+                        "license": null,
+                        "metadata": null
+                    },
+                    {
+                        "id": "bar 0.1.0",
+                        "name": "bar",
+                        "manifest_path": "/project/bar/Cargo.toml",
+                        "source": null,
+                        "version": "0.1.0",
+                        "license": null,
+                        "metadata": {
+                            "docs": {
+                                "rs": {
+                                    "features": ["serde", "std"],
+                                    "no-default-features": true,
+                                    "targets": ["aarch64-unknown-linux-gnu"],
+                                    "rustdoc-args": ["--generate-link-to-definition"]
+                                }
+                            }
+                        }
+                    }
+                ]
+            }"#,
+        )?;
+        assert_eq!(metadata.docs_rs(Path::new("/project"))?, None);
+
+        let docs_rs = metadata
+            .docs_rs(Path::new("/project/bar/src"))?
+            .expect("bar has docs.rs metadata");
+        assert_eq!(docs_rs.target(), Some("aarch64-unknown-linux-gnu"));
+        assert_eq!(
+            docs_rs.cargo_flags(),
+            ["--no-default-features", "--features", "serde,std"]
+        );
+        assert_eq!(
+            docs_rs.rustdoc_flags(),
+            ["--cfg", "docsrs", "--generate-link-to-definition"]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! The subset of the cargo configuration used by `cross`.
+//!
+//! Cargo aliases, such as `alias.br = "build --release"`, are expanded before
+//! the subcommand is classified, otherwise the build would run on the host.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::cargo::Subcommand;
+use crate::errors::*;
+use crate::file;
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+enum CargoAlias {
+    /// The arguments as a single whitespace-separated string.
+    Str(String),
+    Vec(Vec<String>),
+}
+
+impl CargoAlias {
+    fn args(&self) -> Vec<String> {
+        match self {
+            CargoAlias::Str(s) => s.split_whitespace().map(ToOwned::to_owned).collect(),
+            CargoAlias::Vec(v) => v.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CargoConfigFile {
+    #[serde(default)]
+    alias: BTreeMap<String, CargoAlias>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CargoConfig {
+    aliases: BTreeMap<String, Vec<String>>,
+}
+
+impl CargoConfig {
+    /// Read the configuration files that apply to `cwd`.
+    ///
+    /// Like cargo, files in deeper directories have precedence, followed by
+    /// the one in `CARGO_HOME`.
+    pub fn new(cwd: &Path) -> Result<Self> {
+        let mut config = CargoConfig::default();
+        let mut dirs: Vec<PathBuf> = cwd.ancestors().map(|dir| dir.join(".cargo")).collect();
+        if let Ok(cargo_home) = home::cargo_home() {
+            if !dirs.contains(&cargo_home) {
+                dirs.push(cargo_home);
+            }
+        }
+        for dir in dirs.iter().rev() {
+            let path = match ["config.toml", "config"]
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.is_file())
+            {
+                Some(path) => path,
+                None => continue,
+            };
+            config
+                .merge_str(&file::read(&path)?)
+                .wrap_err_with(|| format!("could not parse cargo config `{path:?}`"))?;
+        }
+
+        Ok(config)
+    }
+
+    fn merge_str(&mut self, contents: &str) -> Result<()> {
+        let file: CargoConfigFile = toml::from_str(contents)?;
+        for (name, alias) in file.alias {
+            self.aliases.insert(name, alias.args());
+        }
+
+        Ok(())
+    }
+
+    /// Get the arguments for an alias, where `CARGO_ALIAS_<NAME>` has
+    /// precedence over the configuration files.
+    pub fn alias(&self, name: &str) -> Option<Vec<String>> {
+        let var = format!("CARGO_ALIAS_{}", name.to_uppercase().replace('-', "_"));
+        match env::var(var) {
+            Ok(value) => Some(value.split_whitespace().map(ToOwned::to_owned).collect()),
+            Err(_) => self.aliases.get(name).cloned(),
+        }
+    }
+
+    /// Expand an alias, including any aliases it refers to, until the first
+    /// argument is a subcommand `cross` recognizes or isn't an alias.
+    pub fn expand_alias(&self, name: &str) -> Result<Option<Vec<String>>> {
+        let mut args = match self.alias(name) {
+            Some(args) => args,
+            None => return Ok(None),
+        };
+        let mut seen = vec![name.to_owned()];
+        while let Some(first) = args.first().cloned() {
+            if Subcommand::from(first.as_str()) != Subcommand::Other {
+                break;
+            }
+            let nested = match self.alias(&first) {
+                Some(nested) => nested,
+                None => break,
+            };
+            if seen.contains(&first) {
+                seen.push(first);
+                eyre::bail!("alias `{name}` is recursive: {}", seen.join(" -> "));
+            }
+            seen.push(first);
+            args.splice(0..1, nested);
+        }
+
+        Ok(Some(args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(contents: &str) -> Result<CargoConfig> {
+        let mut config = CargoConfig::default();
+        config.merge_str(contents)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn expand_aliases() -> Result<()> {
+        let config = config(
+            r#"
+            [alias]
+            br = "build --release"
+            brt = ["br", "--target", "aarch64-unknown-linux-gnu"]
+            xt = "brt --locked"
+            fmtall = "fmt --all"
+            "#,
+        )?;
+        let args = |args: &[&str]| args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        assert_eq!(
+            config.expand_alias("br")?,
+            Some(args(&["build", "--release"]))
+        );
+        assert_eq!(
+            config.expand_alias("xt")?,
+            Some(args(&[
+                "build",
+                "--release",
+                "--target",
+                "aarch64-unknown-linux-gnu",
+                "--locked"
+            ]))
+        );
+        assert_eq!(
+            config.expand_alias("fmtall")?,
+            Some(args(&["fmt", "--all"]))
+        );
+        assert_eq!(config.expand_alias("missing")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_alias() -> Result<()> {
+        let config = config(
+            r#"
+            [alias]
+            first = "second --release"
+            second = "first"
+            "#,
+        )?;
+        assert!(config.expand_alias("first").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn later_files_override() -> Result<()> {
+        let mut config = config("alias.br = \"build --release\"")?;
+        config.merge_str("alias.br = [\"build\", \"--profile\", \"dist\"]")?;
+        assert_eq!(
+            config.alias("br"),
+            Some(vec![
+                "build".to_owned(),
+                "--profile".to_owned(),
+                "dist".to_owned()
+            ])
+        );
+
+        Ok(())
+    }
+}
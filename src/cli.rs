@@ -1,27 +1,80 @@
+use std::collections::VecDeque;
 use std::env;
 use std::path::{Path, PathBuf};
 
 use crate::cargo::Subcommand;
+use crate::cargo_config::CargoConfig;
+use crate::docker;
 use crate::errors::Result;
 use crate::file::{absolute_path, PathExt};
 use crate::rustc::TargetList;
 use crate::shell::{self, MessageInfo};
 use crate::Target;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Args {
     pub cargo_args: Vec<String>,
     pub rest_args: Vec<String>,
     pub subcommand: Option<Subcommand>,
     pub channel: Option<String>,
     pub target: Option<Target>,
+    // all targets provided, if more than one `--target` was given
+    pub targets: Vec<Target>,
     pub features: Vec<String>,
+    // the `features` sets from `Cross.toml` selected with `--feature-set`
+    pub feature_sets: Vec<String>,
     pub target_dir: Option<PathBuf>,
     pub manifest_path: Option<PathBuf>,
     pub version: bool,
+    // check for a newer release of cross, even in CI
+    pub version_check: bool,
+    // dump the environment of failing build scripts
+    pub emit_build_script_env: bool,
+    // fail if the image is for an incompatible version of cross
+    pub strict: bool,
+    // build the documentation like docs.rs, from `[package.metadata.docs.rs]`
+    pub docs_rs: bool,
+    // continue with the remaining targets after one fails
+    pub keep_going: bool,
+    // don't copy the target directory back from a remote engine
+    pub no_copy_back: bool,
     pub verbose: u8,
     pub quiet: bool,
     pub color: Option<String>,
+    pub context: Option<String>,
+    // the remote builder saved with `cross-util remote setup`
+    pub remote: Option<String>,
+    pub print: Option<PrintKind>,
+}
+
+/// A value resolved by `cross` that can be queried with `--print`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintKind {
+    /// The image the container is run with.
+    Image,
+    /// The sysroot of the toolchain mounted in the container.
+    Sysroot,
+    /// The name of the persistent data volume for the toolchain.
+    Volume,
+    /// The prefix of the container names for the target and project.
+    Container,
+    /// The recognized environment variables and their values.
+    Env,
+}
+
+impl std::str::FromStr for PrintKind {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "image" => Ok(PrintKind::Image),
+            "sysroot" => Ok(PrintKind::Sysroot),
+            "volume" => Ok(PrintKind::Volume),
+            "container" => Ok(PrintKind::Container),
+            "env" => Ok(PrintKind::Env),
+            _ => eyre::bail!("unknown print kind `{s}`"),
+        }
+    }
 }
 
 pub fn is_subcommand_list(stdout: &str) -> bool {
@@ -101,6 +154,20 @@ fn is_value_arg(arg: &str, field: &str) -> Option<ArgKind> {
     }
 }
 
+// the arguments, with the expansion of an alias inserted before the rest.
+struct Arguments<I> {
+    expanded: VecDeque<String>,
+    rest: I,
+}
+
+impl<I: Iterator<Item = String>> Iterator for Arguments<I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.expanded.pop_front().or_else(|| self.rest.next())
+    }
+}
+
 fn parse_next_arg<T>(
     arg: String,
     out: &mut Vec<String>,
@@ -157,22 +224,44 @@ fn store_target_dir(_: String) -> Result<String> {
     Ok("/target".to_owned())
 }
 
+fn change_dir(path: &str) -> Result<()> {
+    let path = absolute_path(PathBuf::from(path))?;
+    env::set_current_dir(&path)
+        .map_err(|e| eyre::eyre!("could not change to directory {path:?}: {e}"))
+}
+
 pub fn parse(target_list: &TargetList) -> Result<Args> {
     let mut channel = None;
     let mut target = None;
+    let mut targets = Vec::new();
     let mut features = Vec::new();
+    let mut feature_sets = Vec::new();
     let mut manifest_path: Option<PathBuf> = None;
     let mut target_dir = None;
     let mut sc = None;
     let mut cargo_args: Vec<String> = Vec::new();
     let mut rest_args: Vec<String> = Vec::new();
     let mut version = false;
+    let mut version_check = false;
+    let mut emit_build_script_env = false;
+    let mut strict = false;
+    let mut docs_rs = false;
+    let mut keep_going = false;
+    let mut no_copy_back = false;
     let mut quiet = false;
     let mut verbose = 0;
     let mut color = None;
+    let mut context = None;
+    let mut remote = None;
+    let mut print = None;
+    let mut print_args = vec![];
+    let mut expanded_alias = false;
 
     {
-        let mut args = env::args().skip(1);
+        let mut args = Arguments {
+            expanded: VecDeque::new(),
+            rest: env::args().skip(1),
+        };
         while let Some(arg) = args.next() {
             if arg.is_empty() {
                 continue;
@@ -185,9 +274,27 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
                 cargo_args.push(arg);
             } else if matches!(arg.as_str(), "--version" | "-V") {
                 version = true;
+            } else if arg == "--version-check" {
+                version_check = true;
+            } else if arg == docker::EMIT_BUILD_SCRIPT_ENV_FLAG {
+                emit_build_script_env = true;
+            } else if arg == "--strict" {
+                strict = true;
+            } else if arg == "--docs-rs" {
+                docs_rs = true;
+            } else if arg == "--keep-going" {
+                // cargo also supports it, which is used for a single target
+                keep_going = true;
+                cargo_args.push(arg);
+            } else if arg == "--no-copy-back" {
+                no_copy_back = true;
             } else if matches!(arg.as_str(), "--quiet" | "-q") {
                 quiet = true;
                 cargo_args.push(arg);
+            } else if arg == "--no-color" {
+                // cargo has no `--no-color`, so pass the equivalent
+                color = Some("never".to_owned());
+                cargo_args.extend(["--color".to_owned(), "never".to_owned()]);
             } else if let Some(kind) = is_value_arg(&arg, "--color") {
                 color = match kind {
                     ArgKind::Next => {
@@ -209,6 +316,61 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
                         identity,
                     )?),
                 };
+            } else if let Some(kind) = is_value_arg(&arg, "--remote") {
+                // the remote builder is only used by cross, and never passed to cargo
+                let mut ignored = vec![];
+                remote = match kind {
+                    ArgKind::Next => {
+                        parse_next_arg(arg, &mut ignored, str_to_owned, identity, &mut args)?
+                    }
+                    ArgKind::Equal => {
+                        Some(parse_equal_arg(arg, &mut ignored, str_to_owned, identity)?)
+                    }
+                };
+            } else if let Some(kind) = is_value_arg(&arg, "--context") {
+                // the context is only used by cross, and never passed to cargo
+                let mut ignored = vec![];
+                context = match kind {
+                    ArgKind::Next => {
+                        parse_next_arg(arg, &mut ignored, str_to_owned, identity, &mut args)?
+                    }
+                    ArgKind::Equal => {
+                        Some(parse_equal_arg(arg, &mut ignored, str_to_owned, identity)?)
+                    }
+                };
+            } else if let Some(kind) = is_value_arg(&arg, "--print") {
+                // `cargo rustc --print` is also valid, so store the
+                // arguments in case they need to be passed to cargo.
+                let parse_print = |p: &str| Ok(p.parse::<PrintKind>().ok());
+                print = match kind {
+                    ArgKind::Next => {
+                        parse_next_arg(arg, &mut print_args, parse_print, identity, &mut args)?
+                            .flatten()
+                    }
+                    ArgKind::Equal => parse_equal_arg(arg, &mut print_args, parse_print, identity)?,
+                };
+                if print.is_none() {
+                    cargo_args.append(&mut print_args);
+                }
+            } else if let (Some(kind), None) = (
+                is_value_arg(&arg, "-C").or_else(|| is_value_arg(&arg, "--chdir")),
+                sc,
+            ) {
+                // like cargo, this is only valid before the subcommand. the
+                // directory is changed right away, so any relative paths and
+                // the manifest and mount resolution all use the new directory.
+                // cargo is run in it too, so it's never passed to cargo.
+                let mut ignored = vec![];
+                let flag = arg.clone();
+                match kind {
+                    ArgKind::Next => {
+                        parse_next_arg(arg, &mut ignored, change_dir, identity, &mut args)?
+                            .ok_or_else(|| eyre::eyre!("`{flag}` requires a directory"))?;
+                    }
+                    ArgKind::Equal => {
+                        parse_equal_arg(arg, &mut ignored, change_dir, identity)?;
+                    }
+                }
             } else if let Some(kind) = is_value_arg(&arg, "--manifest-path") {
                 manifest_path = match kind {
                     ArgKind::Next => parse_next_arg(
@@ -241,6 +403,7 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
                         identity,
                     )?),
                 };
+                targets.extend(target.clone());
             } else if let Some(kind) = is_value_arg(&arg, "--features") {
                 match kind {
                     ArgKind::Next => {
@@ -264,6 +427,17 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
                         )?);
                     }
                 }
+            } else if let Some(kind) = is_value_arg(&arg, "--feature-set") {
+                // expanded into `--features` once `Cross.toml` is read
+                match kind {
+                    ArgKind::Next => match args.next() {
+                        Some(name) => feature_sets.push(name),
+                        None => eyre::bail!("`--feature-set` requires the name of a feature set"),
+                    },
+                    ArgKind::Equal => {
+                        feature_sets.push(arg["--feature-set=".len()..].to_owned());
+                    }
+                }
             } else if let Some(kind) = is_value_arg(&arg, "--target-dir") {
                 match kind {
                     ArgKind::Next => {
@@ -286,7 +460,17 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
                 }
             } else {
                 if (!arg.starts_with('-') || arg == "--list") && sc.is_none() {
-                    sc = Some(Subcommand::from(arg.as_ref()));
+                    let subcommand = Subcommand::from(arg.as_ref());
+                    // like cargo, aliases can't shadow builtin subcommands.
+                    if subcommand == Subcommand::Other && !expanded_alias {
+                        expanded_alias = true;
+                        let config = CargoConfig::new(&env::current_dir()?)?;
+                        if let Some(expansion) = config.expand_alias(&arg)? {
+                            args.expanded.extend(expansion);
+                            continue;
+                        }
+                    }
+                    sc = Some(subcommand);
                 }
 
                 cargo_args.push(arg.clone());
@@ -294,19 +478,35 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
         }
     }
 
+    if sc == Some(Subcommand::Rustc) && print.is_some() {
+        print = None;
+        cargo_args.append(&mut print_args);
+    }
+
     Ok(Args {
         cargo_args,
         rest_args,
         subcommand: sc,
         channel,
         target,
+        targets,
         features,
+        feature_sets,
         target_dir,
         manifest_path,
         version,
+        version_check,
+        emit_build_script_env,
+        strict,
+        docs_rs,
+        keep_going,
+        no_copy_back,
         verbose,
         quiet,
         color,
+        context,
+        remote,
+        print,
     })
 }
 
@@ -325,4 +525,11 @@ mod tests {
         assert!(is_verbose("-vvvv") == 4);
         assert!(is_verbose("-version") == 0);
     }
+
+    #[test]
+    fn print_kind_from_str() {
+        assert_eq!("image".parse::<PrintKind>().ok(), Some(PrintKind::Image));
+        assert_eq!("volume".parse::<PrintKind>().ok(), Some(PrintKind::Volume));
+        assert!("cfg".parse::<PrintKind>().is_err());
+    }
 }
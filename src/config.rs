@@ -1,3 +1,4 @@
+use crate::cross_toml::CrossPodConfig;
 use crate::docker::custom::PreBuild;
 use crate::docker::{ImagePlatform, PossibleImage};
 use crate::shell::MessageInfo;
@@ -7,14 +8,172 @@ use std::collections::HashMap;
 use std::env;
 use std::str::FromStr;
 
+/// Environment variables recognized by `cross` outside of the build and target
+/// configuration, such as `CROSS_BUILD_XARGO`, with a short description.
+pub const RECOGNIZED_VARS: &[(&str, &str)] = &[
+    (
+        "CROSS_CONFIG",
+        "path to the `Cross.toml` configuration file",
+    ),
+    ("CROSS_CONTAINER_ENGINE", "the container engine to use"),
+    (
+        "CROSS_CONTAINER_ENGINE_TYPE",
+        "the type of engine, such as docker or podman",
+    ),
+    (
+        "CROSS_CONTAINER_CONTEXT",
+        "the docker context or podman connection to use",
+    ),
+    (
+        "CROSS_PODMAN_CONNECTION",
+        "the podman system connection to use",
+    ),
+    (
+        "CROSS_PODMAN_MACHINE_START",
+        "start a stopped podman machine",
+    ),
+    (
+        "CROSS_CONTAINER_ENGINE_API",
+        "use the engine API over a local socket",
+    ),
+    (
+        "CROSS_CONTAINER_ENGINE_NO_BUILDKIT",
+        "build images without buildkit",
+    ),
+    (
+        "CROSS_CONTAINER_IN_CONTAINER",
+        "cross is run inside a container",
+    ),
+    (
+        "CROSS_CONTAINER_PLATFORM",
+        "the platform of the images to run, such as `linux/arm64`",
+    ),
+    ("CROSS_IMAGE_TAG", "the tag of the images provided by cross"),
+    (
+        "CROSS_IMAGE_REGISTRY",
+        "the registry of the images provided by cross",
+    ),
+    (
+        "CROSS_NIX_STORE",
+        "the path of the nix store mounted in the container",
+    ),
+    ("CROSS_NIX_STORE_READ_ONLY", "mount the nix store read-only"),
+    (
+        "CROSS_USE_LOCAL_IMAGES",
+        "prefer locally built images over the provided ones",
+    ),
+    (
+        "CROSS_LOCAL_IMAGE_TAG",
+        "the tag of the locally built images, `local` by default",
+    ),
+    (
+        "CROSS_COSIGN_KEY",
+        "the public key to verify the images provided by cross with",
+    ),
+    (
+        "CROSS_CONTAINER_OPTS",
+        "additional flags for the container engine",
+    ),
+    (
+        "CROSS_CONTAINER_ENGINE_RETRIES",
+        "the number of retries of failed container engine commands",
+    ),
+    (
+        "CROSS_CONTAINER_ENGINE_RETRY_DELAY",
+        "the delay before retrying an engine command, in milliseconds",
+    ),
+    (
+        "CROSS_CONTAINER_USER_NAMESPACE",
+        "the user namespace for the container",
+    ),
+    ("CROSS_CONTAINER_UID", "the user ID in the container"),
+    ("CROSS_CONTAINER_GID", "the group ID in the container"),
+    (
+        "CROSS_ROOTLESS_CONTAINER_ENGINE",
+        "the container engine is rootless",
+    ),
+    ("CROSS_REMOTE", "copy data into volumes for a remote daemon"),
+    (
+        "CROSS_REMOTE_COPY_CACHE",
+        "copy the target directory into the volume",
+    ),
+    (
+        "CROSS_REMOTE_CLEAN_CONFLICTS",
+        "remove files in the volume that conflict with the image",
+    ),
+    (
+        "CROSS_REMOTE_COMPRESS",
+        "compress the data copied into volumes with zstd",
+    ),
+    (
+        "CROSS_REMOTE_COPY_REGISTRY",
+        "copy the cargo registry into the volume",
+    ),
+    (
+        "CROSS_REMOTE_MOUNT_PREFIX",
+        "the directory the data volume is mounted at",
+    ),
+    (
+        "CROSS_REMOTE_SKIP_BUILD_ARTIFACTS",
+        "do not copy build artifacts back",
+    ),
+    (
+        "CROSS_REMOTE_XARGO_CACHE",
+        "cache xargo sysroots in a volume",
+    ),
+    (
+        "CROSS_BUILD_PROGRESS",
+        "the progress output when building images",
+    ),
+    (
+        "CROSS_COMPATIBILITY_VERSION",
+        "use compatibility behavior of a release",
+    ),
+    ("CROSS_DEBUG", "print debug information"),
+    (
+        "CROSS_COMMAND_LOG",
+        "append every command cross runs to this file",
+    ),
+    (
+        "CROSS_VERSION_CHECK",
+        "check for a newer release of cross once a day",
+    ),
+    (
+        "CROSS_STATS",
+        "record build statistics in `.cross/stats.json`",
+    ),
+    ("DOCKER_OPTS", "deprecated alias of `CROSS_CONTAINER_OPTS`"),
+    ("DOCKER_HOST", "the docker daemon to connect to"),
+    ("QEMU_STRACE", "trace system calls in qemu"),
+];
+
 #[derive(Debug)]
-struct Environment(&'static str, Option<HashMap<&'static str, &'static str>>);
+pub struct Environment(&'static str, Option<HashMap<&'static str, &'static str>>);
 
 impl Environment {
-    fn new(map: Option<HashMap<&'static str, &'static str>>) -> Self {
+    /// Create the environment, with values in `map` overriding the process environment.
+    pub fn new(map: Option<HashMap<&'static str, &'static str>>) -> Self {
         Environment("CROSS", map)
     }
 
+    /// Get the value of an environment variable.
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.get_var(name)
+    }
+
+    /// Get the value of a boolean environment variable.
+    pub fn bool_var(&self, name: &str) -> Option<bool> {
+        self.get_var(name).map(|s| bool_from_envvar(&s))
+    }
+
+    /// Get the current value of every recognized environment variable.
+    pub fn dump(&self) -> Vec<(&'static str, Option<String>)> {
+        RECOGNIZED_VARS
+            .iter()
+            .map(|(name, _)| (*name, self.get_var(name)))
+            .collect()
+    }
+
     fn build_var_name(&self, name: &str) -> String {
         format!("{}_{}", self.0, name.to_ascii_uppercase().replace('-', "_"))
     }
@@ -71,6 +230,22 @@ impl Environment {
         self.get_values_for("ZIG", target, bool_from_envvar)
     }
 
+    fn sbom(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_values_for("SBOM", target, bool_from_envvar)
+    }
+
+    fn install_qemu(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_values_for("INSTALL_QEMU", target, bool_from_envvar)
+    }
+
+    fn wine_version(&self, target: &Target) -> (Option<String>, Option<String>) {
+        self.get_values_for("WINE_VERSION", target, ToOwned::to_owned)
+    }
+
+    fn wine_dialogs(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_values_for("WINE_DIALOGS", target, bool_from_envvar)
+    }
+
     fn zig_version(&self, target: &Target) -> (Option<String>, Option<String>) {
         self.get_values_for("ZIG_VERSION", target, ToOwned::to_owned)
     }
@@ -98,7 +273,12 @@ impl Environment {
 
     fn image(&self, target: &Target) -> Result<Option<PossibleImage>> {
         let get_target = |env: &Environment, var: &str| env.get_target_var(target, var);
-        get_possible_image(self, "IMAGE", "IMAGE_TOOLCHAIN", get_target, get_target)
+        let image = get_possible_image(self, "IMAGE", "IMAGE_TOOLCHAIN", get_target, get_target)?;
+        Ok(image.map(|mut image| {
+            image.entrypoint = self.get_target_var(target, "IMAGE_ENTRYPOINT");
+            image.workdir = self.get_target_var(target, "IMAGE_WORKDIR");
+            image
+        }))
     }
 
     fn dockerfile(&self, target: &Target) -> (Option<String>, Option<String>) {
@@ -123,10 +303,32 @@ impl Environment {
         })
     }
 
+    fn remote(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_values_for("REMOTE", target, bool_from_envvar)
+    }
+
+    fn engine(&self, target: &Target) -> (Option<String>, Option<String>) {
+        self.get_values_for("ENGINE", target, ToOwned::to_owned)
+    }
+
+    fn context(&self, target: &Target) -> (Option<String>, Option<String>) {
+        self.get_values_for("CONTEXT", target, ToOwned::to_owned)
+    }
+
     fn runner(&self, target: &Target) -> Option<String> {
         self.get_target_var(target, "RUNNER")
     }
 
+    fn cargo_flags(&self, target: &Target) -> Option<Vec<String>> {
+        self.get_target_var(target, "CARGO_FLAGS")
+            .map(|s| split_to_cloned_by_ws(&s))
+    }
+
+    fn rustdocflags(&self, target: &Target) -> Option<Vec<String>> {
+        self.get_target_var(target, "RUSTDOCFLAGS")
+            .map(|s| split_to_cloned_by_ws(&s))
+    }
+
     fn passthrough(&self, target: &Target) -> (Option<Vec<String>>, Option<Vec<String>>) {
         self.get_values_for("ENV_PASSTHROUGH", target, split_to_cloned_by_ws)
     }
@@ -135,11 +337,56 @@ impl Environment {
         self.get_values_for("ENV_VOLUMES", target, split_to_cloned_by_ws)
     }
 
+    fn packages(&self, target: &Target) -> (Option<Vec<String>>, Option<Vec<String>>) {
+        self.get_values_for("PACKAGES", target, split_to_cloned_by_ws)
+    }
+
+    fn cache_from(&self, target: &Target) -> (Option<Vec<String>>, Option<Vec<String>>) {
+        self.get_values_for("CACHE_FROM", target, split_to_cloned_by_ws)
+    }
+
+    fn cache_to(&self, target: &Target) -> (Option<String>, Option<String>) {
+        self.get_values_for("CACHE_TO", target, ToOwned::to_owned)
+    }
+
     fn target(&self) -> Option<String> {
         self.get_build_var("TARGET")
             .or_else(|| std::env::var("CARGO_BUILD_TARGET").ok())
     }
 
+    fn image_tag(&self) -> Option<String> {
+        self.get_var("CROSS_IMAGE_TAG")
+    }
+
+    fn image_registry(&self) -> Option<String> {
+        self.get_var("CROSS_IMAGE_REGISTRY")
+    }
+
+    fn use_local_images(&self) -> Option<bool> {
+        self.bool_var("CROSS_USE_LOCAL_IMAGES")
+    }
+
+    fn local_image_tag(&self) -> Option<String> {
+        self.get_var("CROSS_LOCAL_IMAGE_TAG")
+    }
+
+    fn verify_images(&self) -> Option<bool> {
+        self.get_build_var("VERIFY_IMAGES")
+            .map(|s| bool_from_envvar(&s))
+    }
+
+    fn fallback_image(&self) -> Option<String> {
+        self.get_build_var("FALLBACK_IMAGE")
+    }
+
+    fn remote_mount_prefix(&self) -> Option<String> {
+        self.get_var("CROSS_REMOTE_MOUNT_PREFIX")
+    }
+
+    fn cosign_key(&self) -> Option<String> {
+        self.get_var("CROSS_COSIGN_KEY")
+    }
+
     fn doctests(&self) -> Option<bool> {
         self.get_var("CROSS_UNSTABLE_ENABLE_DOCTESTS")
             .map(|s| bool_from_envvar(&s))
@@ -342,10 +589,14 @@ impl Config {
     }
 
     #[cfg(test)]
-    fn new_with(toml: Option<CrossToml>, env: Environment) -> Self {
+    pub fn new_with(toml: Option<CrossToml>, env: Environment) -> Self {
         Config { toml, env }
     }
 
+    pub fn env(&self) -> &Environment {
+        &self.env
+    }
+
     pub fn xargo(&self, target: &Target) -> Option<bool> {
         self.bool_from_config(target, Environment::xargo, CrossToml::xargo)
     }
@@ -354,6 +605,22 @@ impl Config {
         self.bool_from_config(target, Environment::build_std, CrossToml::build_std)
     }
 
+    pub fn sbom(&self, target: &Target) -> Option<bool> {
+        self.bool_from_config(target, Environment::sbom, CrossToml::sbom)
+    }
+
+    pub fn install_qemu(&self, target: &Target) -> Option<bool> {
+        self.bool_from_config(target, Environment::install_qemu, CrossToml::install_qemu)
+    }
+
+    pub fn wine_version(&self, target: &Target) -> Result<Option<String>> {
+        self.get_from_ref(target, Environment::wine_version, CrossToml::wine_version)
+    }
+
+    pub fn wine_dialogs(&self, target: &Target) -> Option<bool> {
+        self.bool_from_config(target, Environment::wine_dialogs, CrossToml::wine_dialogs)
+    }
+
     pub fn zig(&self, target: &Target) -> Option<bool> {
         self.bool_from_config(target, Environment::zig, CrossToml::zig)
     }
@@ -384,6 +651,76 @@ impl Config {
         )
     }
 
+    pub fn cargo_flags(&self, target: &Target) -> Result<Option<Vec<String>>> {
+        self.get_from_ref(
+            target,
+            |env, target| (None, env.cargo_flags(target)),
+            |toml, target| (None, toml.cargo_flags(target)),
+        )
+    }
+
+    /// The default `RUSTDOCFLAGS` in the container, if it isn't set.
+    pub fn rustdocflags(&self, target: &Target) -> Result<Option<Vec<String>>> {
+        self.get_from_ref(
+            target,
+            |env, target| (None, env.rustdocflags(target)),
+            |toml, target| (None, toml.rustdocflags(target)),
+        )
+    }
+
+    /// Returns the features of a feature set selected with `--feature-set`.
+    pub fn feature_set(&self, name: &str) -> Result<Vec<String>> {
+        self.toml
+            .as_ref()
+            .and_then(|toml| toml.feature_set(name))
+            .map(<[String]>::to_vec)
+            .ok_or_else(|| eyre::eyre!("the feature set `{name}` is not defined in `Cross.toml`"))
+    }
+
+    /// Returns the services started in a pod with the container for
+    /// `cross run` and `cross test`.
+    pub fn pod(&self) -> Option<&CrossPodConfig> {
+        self.toml.as_ref().and_then(CrossToml::pod)
+    }
+
+    /// Returns the features passed to cargo if no `--feature-set` is selected.
+    pub fn default_features(&self) -> Vec<String> {
+        self.toml
+            .as_ref()
+            .and_then(CrossToml::default_features)
+            .map(<[String]>::to_vec)
+            .unwrap_or_default()
+    }
+
+    pub fn remote(&self, target: &Target) -> Option<bool> {
+        self.bool_from_config(target, Environment::remote, CrossToml::remote)
+    }
+
+    pub fn engine(&self, target: &Target) -> Result<Option<String>> {
+        self.get_from_ref(target, Environment::engine, CrossToml::engine)
+    }
+
+    pub fn context(&self, target: &Target) -> Result<Option<String>> {
+        // the target config has precedence over the builders,
+        // which have precedence over the build config.
+        let target_context = self.get_from_ref(
+            target,
+            |env, target| (None, env.context(target).1),
+            |toml, target| (None, toml.context(target).1),
+        )?;
+        if target_context.is_some() {
+            return Ok(target_context);
+        }
+        if let Some(builder) = self.toml.as_ref().and_then(|t| t.builder(target)) {
+            return Ok(Some(builder.clone()));
+        }
+        self.get_from_ref(
+            target,
+            |env, target| (env.context(target).0, None),
+            |toml, target| (toml.context(target).0, None),
+        )
+    }
+
     pub fn doctests(&self) -> Option<bool> {
         self.env.doctests()
     }
@@ -409,6 +746,27 @@ impl Config {
         )
     }
 
+    /// The system packages to install in the image, from both `build` and the target.
+    pub fn packages(&self, target: &Target) -> Result<Option<Vec<String>>> {
+        self.vec_from_config(target, Environment::packages, CrossToml::packages, true)
+    }
+
+    /// The caches to import when building custom images, such as
+    /// `type=registry,ref=...`, from the target or the build config.
+    pub fn cache_from(&self, target: &Target) -> Result<Option<Vec<String>>> {
+        self.vec_from_config(
+            target,
+            Environment::cache_from,
+            CrossToml::cache_from,
+            false,
+        )
+    }
+
+    /// The cache to export when building custom images.
+    pub fn cache_to(&self, target: &Target) -> Result<Option<String>> {
+        self.get_from_ref(target, Environment::cache_to, CrossToml::cache_to)
+    }
+
     pub fn env_volumes(&self, target: &Target) -> Result<Option<Vec<String>>> {
         self.get_from_ref(target, Environment::volumes, CrossToml::env_volumes)
     }
@@ -422,6 +780,78 @@ impl Config {
             .and_then(|t| t.default_target(target_list))
     }
 
+    /// The tag of the images provided by cross, overriding the default one.
+    pub fn image_tag(&self) -> Option<String> {
+        self.env
+            .image_tag()
+            .or_else(|| self.toml.as_ref().and_then(|t| t.image_tag().cloned()))
+    }
+
+    /// The registry the images provided by cross are pulled from, such as a mirror.
+    pub fn image_registry(&self) -> Option<String> {
+        self.env
+            .image_registry()
+            .or_else(|| self.toml.as_ref().and_then(|t| t.registry().cloned()))
+    }
+
+    /// If locally built images, such as the ones built by `cross-util
+    /// build-image`, are used instead of the provided images when they exist.
+    pub fn use_local_images(&self) -> bool {
+        self.env.use_local_images().unwrap_or(false)
+    }
+
+    /// The tag of the locally built images.
+    pub fn local_image_tag(&self) -> String {
+        self.env
+            .local_image_tag()
+            .unwrap_or_else(|| "local".to_owned())
+    }
+
+    /// If the signatures of the images provided by cross are verified before they're run.
+    pub fn verify_images(&self) -> bool {
+        self.env
+            .verify_images()
+            .or_else(|| self.toml.as_ref().and_then(CrossToml::verify_images))
+            .unwrap_or_default()
+    }
+
+    /// The image to use for targets `cross` doesn't provide an image for.
+    pub fn fallback_image(&self) -> Option<String> {
+        self.env
+            .fallback_image()
+            .or_else(|| self.toml.as_ref().and_then(|t| t.fallback_image().cloned()))
+    }
+
+    /// The directory the data volume is mounted at with a remote engine,
+    /// which must not have content in the image.
+    pub fn remote_mount_prefix(&self) -> Result<String> {
+        let prefix = match self.env.remote_mount_prefix() {
+            Some(prefix) => prefix,
+            None => match self.toml.as_ref().and_then(CrossToml::remote_mount_prefix) {
+                Some(prefix) => prefix.clone(),
+                None => return Ok(crate::docker::MOUNT_PREFIX.to_owned()),
+            },
+        };
+        // the prefix is used in shell scripts and mount arguments
+        let is_valid = prefix.starts_with('/')
+            && prefix.len() > 1
+            && !prefix.ends_with('/')
+            && !prefix
+                .chars()
+                .any(|c| c.is_whitespace() || "\"'`$\\:".contains(c));
+        if !is_valid {
+            eyre::bail!(
+                "invalid remote mount prefix `{prefix}`, it must be an absolute path to a directory other than `/`"
+            );
+        }
+        Ok(prefix)
+    }
+
+    /// The public key to verify the images with, instead of their keyless signatures.
+    pub fn cosign_key(&self) -> Option<String> {
+        self.env.cosign_key()
+    }
+
     pub fn dockerfile(&self, target: &Target) -> Result<Option<String>> {
         self.get_from_ref(target, Environment::dockerfile, CrossToml::dockerfile)
     }
@@ -706,6 +1136,42 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        pub fn environment_overrides_and_dump() {
+            let mut map = HashMap::new();
+            map.insert("CROSS_REMOTE_COPY_CACHE", "1");
+            map.insert("CROSS_CONTAINER_OPTS", "--env A=1");
+            let env = Environment::new(Some(map));
+            assert_eq!(env.bool_var("CROSS_REMOTE_COPY_CACHE"), Some(true));
+            assert_eq!(env.var("CROSS_CONTAINER_OPTS"), Some(s!("--env A=1")));
+
+            let dump = env.dump();
+            assert_eq!(dump.len(), RECOGNIZED_VARS.len());
+            assert!(dump.contains(&("CROSS_REMOTE_COPY_CACHE", Some(s!("1")))));
+        }
+
+        #[test]
+        pub fn context_use_target_then_build() -> Result<()> {
+            let map = HashMap::new();
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(TOML_BUILD_CONTEXT)?), env);
+            assert_eq!(config.context(&target())?, Some(s!("builder-arm")));
+            assert_eq!(config.context(&target2())?, Some(s!("default")));
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_BUILD_CONTEXT", "remote");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(TOML_BUILD_CONTEXT)?), env);
+            assert_eq!(config.context(&target())?, Some(s!("builder-arm")));
+            assert_eq!(config.context(&target2())?, Some(s!("remote")));
+
+            let config = Config::new_with(Some(toml(TOML_BUILDERS)?), Environment::new(None));
+            assert_eq!(config.context(&target())?, Some(s!("builder-arm")));
+            assert_eq!(config.context(&target2())?, Some(s!("default")));
+
+            Ok(())
+        }
+
         #[test]
         pub fn toml_build_passthrough_then_use_target_passthrough_both() -> Result<()> {
             let map = HashMap::new();
@@ -790,6 +1256,176 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        pub fn engine_use_target_then_build() -> Result<()> {
+            let toml_str = r#"
+            [build]
+            engine = "podman"
+
+            [target.aarch64-unknown-linux-gnu]
+            engine = "docker"
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(config.engine(&target())?, Some(s!("docker")));
+            assert_eq!(config.engine(&target2())?, Some(s!("podman")));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn wine_use_target_then_build() -> Result<()> {
+            let toml_str = r#"
+            [build]
+            wine-dialogs = true
+
+            [target.aarch64-unknown-linux-gnu]
+            wine-version = "8.0"
+            wine-dialogs = false
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(config.wine_version(&target())?, Some(s!("8.0")));
+            assert_eq!(config.wine_dialogs(&target()), Some(false));
+            assert_eq!(config.wine_version(&target2())?, None);
+            assert_eq!(config.wine_dialogs(&target2()), Some(true));
+
+            let mut map = std::collections::HashMap::new();
+            map.insert("CROSS_BUILD_WINE_VERSION", "9.0");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(config.wine_version(&target())?, Some(s!("8.0")));
+            assert_eq!(config.wine_version(&target2())?, Some(s!("9.0")));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn remote_use_target_then_build() -> Result<()> {
+            let toml_str = r#"
+            [build]
+            remote = true
+
+            [target.aarch64-unknown-linux-gnu]
+            remote = false
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(config.remote(&target()), Some(false));
+            assert_eq!(config.remote(&target2()), Some(true));
+
+            let mut map = std::collections::HashMap::new();
+            map.insert("CROSS_TARGET_AARCH64_UNKNOWN_LINUX_GNU_REMOTE", "1");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(config.remote(&target()), Some(true));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn cache_use_target_then_build() -> Result<()> {
+            let toml_str = r#"
+            [build]
+            cache-from = ["type=registry,ref=ghcr.io/org/cache:build"]
+            cache-to = "type=inline"
+
+            [target.aarch64-unknown-linux-gnu]
+            cache-from = ["type=gha"]
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(config.cache_from(&target())?, Some(vec![s!("type=gha")]));
+            assert_eq!(
+                config.cache_from(&target2())?,
+                Some(vec![s!("type=registry,ref=ghcr.io/org/cache:build")])
+            );
+            assert_eq!(config.cache_to(&target())?, Some(s!("type=inline")));
+
+            let mut map = std::collections::HashMap::new();
+            map.insert("CROSS_BUILD_CACHE_TO", "type=gha,mode=max");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(config.cache_to(&target())?, Some(s!("type=gha,mode=max")));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn packages_sum_build_and_target() -> Result<()> {
+            let toml_str = r#"
+            [build]
+            packages = ["pkg-config"]
+
+            [target.aarch64-unknown-linux-gnu]
+            packages = ["libssl-dev"]
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(
+                config.packages(&target())?,
+                Some(vec![s!("pkg-config"), s!("libssl-dev")])
+            );
+            assert_eq!(config.packages(&target2())?, Some(vec![s!("pkg-config")]));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn cargo_flags_use_env_over_toml() -> Result<()> {
+            let toml_str = r#"
+            [target.aarch64-unknown-linux-gnu]
+            cargo-flags = ["--features", "hw", "--no-default-features"]
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(
+                config.cargo_flags(&target())?,
+                Some(vec![
+                    s!("--features"),
+                    s!("hw"),
+                    s!("--no-default-features")
+                ])
+            );
+            assert_eq!(config.cargo_flags(&target2())?, None);
+
+            let mut map = HashMap::new();
+            map.insert(
+                "CROSS_TARGET_AARCH64_UNKNOWN_LINUX_GNU_CARGO_FLAGS",
+                "--release --locked",
+            );
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(
+                config.cargo_flags(&target())?,
+                Some(vec![s!("--release"), s!("--locked")])
+            );
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn rustdocflags_use_env_over_toml() -> Result<()> {
+            let toml_str = r#"
+            [target.aarch64-unknown-linux-gnu]
+            rustdocflags = ["--cfg", "docsrs"]
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(
+                config.rustdocflags(&target())?,
+                Some(vec![s!("--cfg"), s!("docsrs")])
+            );
+            assert_eq!(config.rustdocflags(&target2())?, None);
+
+            let mut map = HashMap::new();
+            map.insert(
+                "CROSS_TARGET_AARCH64_UNKNOWN_LINUX_GNU_RUSTDOCFLAGS",
+                "--cfg nightly",
+            );
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(
+                config.rustdocflags(&target())?,
+                Some(vec![s!("--cfg"), s!("nightly")])
+            );
+
+            Ok(())
+        }
+
         #[test]
         pub fn no_env_and_no_toml_default_target_then_none() -> Result<()> {
             let config = Config::new_with(None, Environment::new(None));
@@ -823,6 +1459,48 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        pub fn env_and_toml_image_tag_then_use_env() -> Result<()> {
+            let config = Config::new_with(Some(toml(TOML_IMAGE_TAG)?), Environment::new(None));
+            assert_eq!(config.image_tag(), Some(s!("0.2.4")));
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_IMAGE_TAG", "main");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(TOML_IMAGE_TAG)?), env);
+            assert_eq!(config.image_tag(), Some(s!("main")));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn env_and_toml_image_registry_then_use_env() -> Result<()> {
+            let config = Config::new_with(Some(toml(TOML_REGISTRY)?), Environment::new(None));
+            assert_eq!(config.image_registry(), Some(s!("registry.corp/cross")));
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_IMAGE_REGISTRY", "mirror.corp/cross-rs");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(TOML_REGISTRY)?), env);
+            assert_eq!(config.image_registry(), Some(s!("mirror.corp/cross-rs")));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn env_use_local_images() {
+            let config = Config::new_with(None, Environment::new(None));
+            assert!(!config.use_local_images());
+            assert_eq!(config.local_image_tag(), "local");
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_USE_LOCAL_IMAGES", "1");
+            map.insert("CROSS_LOCAL_IMAGE_TAG", "dev");
+            let config = Config::new_with(None, Environment::new(Some(map)));
+            assert!(config.use_local_images());
+            assert_eq!(config.local_image_tag(), "dev");
+        }
+
         static TOML_BUILD_XARGO_FALSE: &str = r#"
     [build]
     xargo = false
@@ -838,6 +1516,20 @@ mod tests {
     dockerfile = "Dockerfile1"
     [target.aarch64-unknown-linux-gnu]
     dockerfile = "Dockerfile2"
+    "#;
+
+        static TOML_BUILD_CONTEXT: &str = r#"
+    [build]
+    context = "default"
+    [target.aarch64-unknown-linux-gnu]
+    context = "builder-arm"
+    "#;
+
+        static TOML_BUILDERS: &str = r#"
+    [build]
+    context = "default"
+    [builders]
+    "aarch64-*" = "builder-arm"
     "#;
 
         static TOML_TARGET_XARGO_FALSE: &str = r#"
@@ -877,6 +1569,87 @@ mod tests {
         static TOML_DEFAULT_TARGET: &str = r#"
     [build]
     default-target = "aarch64-unknown-linux-gnu"
+    "#;
+
+        static TOML_IMAGE_TAG: &str = r#"
+    [build]
+    image-tag = "0.2.4"
+    "#;
+
+        #[test]
+        pub fn env_and_toml_verify_images_then_use_env() -> Result<()> {
+            let config = Config::new_with(None, Environment::new(None));
+            assert!(!config.verify_images());
+
+            let config = Config::new_with(Some(toml(TOML_VERIFY_IMAGES)?), Environment::new(None));
+            assert!(config.verify_images());
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_BUILD_VERIFY_IMAGES", "0");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(TOML_VERIFY_IMAGES)?), env);
+            assert!(!config.verify_images());
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn env_and_toml_fallback_image_then_use_env() -> Result<()> {
+            let toml_str = r#"
+            [build]
+            fallback-image = "ghcr.io/cross-rs/x86_64-unknown-linux-gnu:main"
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(
+                config.fallback_image(),
+                Some(s!("ghcr.io/cross-rs/x86_64-unknown-linux-gnu:main"))
+            );
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_BUILD_FALLBACK_IMAGE", "my/base:latest");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(config.fallback_image(), Some(s!("my/base:latest")));
+
+            Ok(())
+        }
+
+        #[test]
+        pub fn env_and_toml_remote_mount_prefix_then_use_env() -> Result<()> {
+            let config = Config::new_with(None, Environment::new(None));
+            assert_eq!(config.remote_mount_prefix()?, "/cross");
+
+            let toml_str = r#"
+            [build]
+            remote-mount-prefix = "/opt/cross-data"
+            "#;
+            let config = Config::new_with(Some(toml(toml_str)?), Environment::new(None));
+            assert_eq!(config.remote_mount_prefix()?, "/opt/cross-data");
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_REMOTE_MOUNT_PREFIX", "/mnt/cross");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(config.remote_mount_prefix()?, "/mnt/cross");
+
+            for prefix in ["cross", "/", "/cross/", "/my cross", "/a:b", "/$HOME"] {
+                let mut map = HashMap::new();
+                map.insert("CROSS_REMOTE_MOUNT_PREFIX", prefix);
+                let config = Config::new_with(None, Environment::new(Some(map)));
+                assert!(config.remote_mount_prefix().is_err(), "{prefix}");
+            }
+
+            Ok(())
+        }
+
+        static TOML_VERIFY_IMAGES: &str = r#"
+    [build]
+    verify-images = true
+    "#;
+
+        static TOML_REGISTRY: &str = r#"
+    [build]
+    registry = "registry.corp/cross"
     "#;
     }
 }
@@ -28,10 +28,26 @@ pub struct CrossBuildConfig {
     #[serde(default, deserialize_with = "opt_string_bool_or_struct")]
     zig: Option<CrossZigConfig>,
     default_target: Option<String>,
+    image_tag: Option<String>,
+    registry: Option<String>,
+    verify_images: Option<bool>,
+    fallback_image: Option<String>,
+    remote_mount_prefix: Option<String>,
+    default_features: Option<Vec<String>>,
     #[serde(default, deserialize_with = "opt_string_or_string_vec")]
     pre_build: Option<PreBuild>,
     #[serde(default, deserialize_with = "opt_string_or_struct")]
     dockerfile: Option<CrossTargetDockerfileConfig>,
+    context: Option<String>,
+    engine: Option<String>,
+    remote: Option<bool>,
+    packages: Option<Vec<String>>,
+    sbom: Option<bool>,
+    install_qemu: Option<bool>,
+    wine_version: Option<String>,
+    wine_dialogs: Option<bool>,
+    cache_from: Option<Vec<String>>,
+    cache_to: Option<String>,
 }
 
 /// Target configuration
@@ -49,6 +65,18 @@ pub struct CrossTargetConfig {
     #[serde(default, deserialize_with = "opt_string_or_string_vec")]
     pre_build: Option<PreBuild>,
     runner: Option<String>,
+    context: Option<String>,
+    engine: Option<String>,
+    remote: Option<bool>,
+    packages: Option<Vec<String>>,
+    sbom: Option<bool>,
+    install_qemu: Option<bool>,
+    wine_version: Option<String>,
+    wine_dialogs: Option<bool>,
+    cache_from: Option<Vec<String>>,
+    cache_to: Option<String>,
+    cargo_flags: Option<Vec<String>>,
+    rustdocflags: Option<Vec<String>>,
     #[serde(default)]
     env: CrossEnvConfig,
 }
@@ -112,6 +140,34 @@ impl FromStr for CrossZigConfig {
     }
 }
 
+/// Test configuration
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrossTestConfig {
+    pod: Option<CrossPodConfig>,
+}
+
+/// Pod configuration, the services sharing the network namespace of the
+/// container the tests are run in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrossPodConfig {
+    #[serde(default)]
+    pub services: Vec<CrossPodService>,
+}
+
+/// A service container in the pod
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrossPodService {
+    pub name: String,
+    pub image: String,
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
 /// Cross configuration
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct CrossToml {
@@ -119,6 +175,12 @@ pub struct CrossToml {
     pub targets: HashMap<Target, CrossTargetConfig>,
     #[serde(default)]
     pub build: CrossBuildConfig,
+    #[serde(default)]
+    pub builders: HashMap<String, String>,
+    #[serde(default)]
+    pub features: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub test: CrossTestConfig,
 }
 
 impl CrossToml {
@@ -307,6 +369,79 @@ impl CrossToml {
         self.get_target(target).and_then(|t| t.runner.as_ref())
     }
 
+    /// Returns the `target.{}.cargo-flags` part of `Cross.toml`
+    pub fn cargo_flags(&self, target: &Target) -> Option<&[String]> {
+        self.get_target(target)
+            .and_then(|t| t.cargo_flags.as_deref())
+    }
+
+    /// Returns the `target.{}.rustdocflags` part of `Cross.toml`
+    pub fn rustdocflags(&self, target: &Target) -> Option<&[String]> {
+        self.get_target(target)
+            .and_then(|t| t.rustdocflags.as_deref())
+    }
+
+    /// Returns the `build.engine` or the `target.{}.engine` part of `Cross.toml`
+    pub fn engine(&self, target: &Target) -> (Option<&String>, Option<&String>) {
+        self.get_ref(target, |b| b.engine.as_ref(), |t| t.engine.as_ref())
+    }
+
+    /// Returns the `build.remote` or the `target.{}.remote` part of `Cross.toml`
+    pub fn remote(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_value(target, |b| b.remote, |t| t.remote)
+    }
+
+    /// Returns the `build.packages` and `target.{}.packages` part of `Cross.toml`
+    pub fn packages(&self, target: &Target) -> (Option<&[String]>, Option<&[String]>) {
+        self.get_ref(target, |b| b.packages.as_deref(), |t| t.packages.as_deref())
+    }
+
+    /// Returns the `build.cache-from` or the `target.{}.cache-from` part of `Cross.toml`
+    pub fn cache_from(&self, target: &Target) -> (Option<&[String]>, Option<&[String]>) {
+        self.get_ref(
+            target,
+            |b| b.cache_from.as_deref(),
+            |t| t.cache_from.as_deref(),
+        )
+    }
+
+    /// Returns the `build.cache-to` or the `target.{}.cache-to` part of `Cross.toml`
+    pub fn cache_to(&self, target: &Target) -> (Option<&String>, Option<&String>) {
+        self.get_ref(target, |b| b.cache_to.as_ref(), |t| t.cache_to.as_ref())
+    }
+
+    /// Returns the `build.context` or the `target.{}.context` part of `Cross.toml`
+    pub fn context(&self, target: &Target) -> (Option<&String>, Option<&String>) {
+        self.get_ref(target, |b| b.context.as_ref(), |t| t.context.as_ref())
+    }
+
+    /// Returns the context of the `builders` pattern best matching the target.
+    ///
+    /// An exact match for the triple always wins, otherwise the longest
+    /// matching pattern is used.
+    pub fn builder(&self, target: &Target) -> Option<&String> {
+        self.builders
+            .iter()
+            .filter(|(pattern, _)| matches_pattern(pattern, target.triple()))
+            .max_by_key(|(pattern, _)| (pattern.as_str() == target.triple(), pattern.len()))
+            .map(|(_, context)| context)
+    }
+
+    /// Returns the `features.{}` part of `Cross.toml`
+    pub fn feature_set(&self, name: &str) -> Option<&[String]> {
+        self.features.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns the `build.default-features` part of `Cross.toml`
+    pub fn default_features(&self) -> Option<&[String]> {
+        self.build.default_features.as_deref()
+    }
+
+    /// Returns the `test.pod` part of `Cross.toml`
+    pub fn pod(&self) -> Option<&CrossPodConfig> {
+        self.test.pod.as_ref()
+    }
+
     /// Returns the `build.xargo` or the `target.{}.xargo` part of `Cross.toml`
     pub fn xargo(&self, target: &Target) -> (Option<bool>, Option<bool>) {
         self.get_value(target, |b| b.xargo, |t| t.xargo)
@@ -317,6 +452,30 @@ impl CrossToml {
         self.get_value(target, |b| b.build_std, |t| t.build_std)
     }
 
+    /// Returns the `build.sbom` or the `target.{}.sbom` part of `Cross.toml`
+    pub fn sbom(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_value(target, |b| b.sbom, |t| t.sbom)
+    }
+
+    /// Returns the `build.install-qemu` or the `target.{}.install-qemu` part of `Cross.toml`
+    pub fn install_qemu(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_value(target, |b| b.install_qemu, |t| t.install_qemu)
+    }
+
+    /// Returns the `build.wine-version` or the `target.{}.wine-version` part of `Cross.toml`
+    pub fn wine_version(&self, target: &Target) -> (Option<&String>, Option<&String>) {
+        self.get_ref(
+            target,
+            |b| b.wine_version.as_ref(),
+            |t| t.wine_version.as_ref(),
+        )
+    }
+
+    /// Returns the `build.wine-dialogs` or the `target.{}.wine-dialogs` part of `Cross.toml`
+    pub fn wine_dialogs(&self, target: &Target) -> (Option<bool>, Option<bool>) {
+        self.get_value(target, |b| b.wine_dialogs, |t| t.wine_dialogs)
+    }
+
     /// Returns the `{}.zig` or `{}.zig.version` part of `Cross.toml`
     pub fn zig(&self, target: &Target) -> (Option<bool>, Option<bool>) {
         self.get_value(
@@ -362,6 +521,31 @@ impl CrossToml {
         )
     }
 
+    /// Returns the `build.image-tag` part of `Cross.toml`
+    pub fn image_tag(&self) -> Option<&String> {
+        self.build.image_tag.as_ref()
+    }
+
+    /// Returns the `build.verify-images` part of `Cross.toml`
+    pub fn verify_images(&self) -> Option<bool> {
+        self.build.verify_images
+    }
+
+    /// Returns the `build.remote-mount-prefix` part of `Cross.toml`
+    pub fn remote_mount_prefix(&self) -> Option<&String> {
+        self.build.remote_mount_prefix.as_ref()
+    }
+
+    /// Returns the `build.fallback-image` part of `Cross.toml`
+    pub fn fallback_image(&self) -> Option<&String> {
+        self.build.fallback_image.as_ref()
+    }
+
+    /// Returns the `build.registry` part of `Cross.toml`
+    pub fn registry(&self) -> Option<&String> {
+        self.build.registry.as_ref()
+    }
+
     /// Returns the default target to build,
     pub fn default_target(&self, target_list: &TargetList) -> Option<Target> {
         self.build
@@ -398,6 +582,37 @@ impl CrossToml {
     }
 }
 
+/// Matches a target triple against a glob pattern, where `*` matches
+/// any number of characters and `?` matches a single character.
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp + 1;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 fn opt_string_or_struct<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
 where
     T: Deserialize<'de> + std::str::FromStr<Err = std::convert::Infallible>,
@@ -597,6 +812,9 @@ mod tests {
         let cfg = CrossToml {
             targets: HashMap::new(),
             build: CrossBuildConfig::default(),
+            builders: HashMap::new(),
+            features: HashMap::new(),
+            test: CrossTestConfig::default(),
         };
         let (parsed_cfg, unused) = CrossToml::parse_from_cross("", &mut m!())?;
 
@@ -619,9 +837,28 @@ mod tests {
                 build_std: None,
                 zig: None,
                 default_target: None,
+                image_tag: None,
+                registry: None,
+                verify_images: None,
+                fallback_image: None,
+                remote_mount_prefix: None,
+                default_features: None,
                 pre_build: Some(PreBuild::Lines(vec![p!("echo 'Hello World!'")])),
                 dockerfile: None,
+                context: None,
+                engine: None,
+                remote: None,
+                packages: None,
+                sbom: None,
+                install_qemu: None,
+                wine_version: None,
+                wine_dialogs: None,
+                cache_from: None,
+                cache_to: None,
             },
+            builders: HashMap::new(),
+            features: HashMap::new(),
+            test: CrossTestConfig::default(),
         };
 
         let test_str = r#"
@@ -658,6 +895,18 @@ mod tests {
                 zig: None,
                 image: Some("test-image".into()),
                 runner: None,
+                context: None,
+                engine: None,
+                remote: None,
+                packages: None,
+                sbom: None,
+                install_qemu: None,
+                wine_version: None,
+                wine_dialogs: None,
+                cache_from: None,
+                cache_to: None,
+                cargo_flags: None,
+                rustdocflags: None,
                 dockerfile: None,
                 pre_build: Some(PreBuild::Lines(vec![])),
             },
@@ -680,6 +929,18 @@ mod tests {
                 }),
                 image: None,
                 runner: None,
+                context: None,
+                engine: None,
+                remote: None,
+                packages: None,
+                sbom: None,
+                install_qemu: None,
+                wine_version: None,
+                wine_dialogs: None,
+                cache_from: None,
+                cache_to: None,
+                cargo_flags: None,
+                rustdocflags: None,
                 dockerfile: None,
                 pre_build: None,
             },
@@ -688,6 +949,9 @@ mod tests {
         let cfg = CrossToml {
             targets: target_map,
             build: CrossBuildConfig::default(),
+            builders: HashMap::new(),
+            features: HashMap::new(),
+            test: CrossTestConfig::default(),
         };
 
         let test_str = r#"
@@ -729,6 +993,8 @@ mod tests {
                     toolchain: vec![ImagePlatform::from_target(
                         "aarch64-unknown-linux-musl".into(),
                     )?],
+                    entrypoint: Some(p!("")),
+                    workdir: Some(p!("/project")),
                 }),
                 dockerfile: Some(CrossTargetDockerfileConfig {
                     file: p!("Dockerfile.test"),
@@ -737,6 +1003,18 @@ mod tests {
                 }),
                 pre_build: Some(PreBuild::Lines(vec![p!("echo 'Hello'")])),
                 runner: None,
+                context: None,
+                engine: None,
+                remote: None,
+                packages: None,
+                sbom: None,
+                install_qemu: None,
+                wine_version: None,
+                wine_dialogs: None,
+                cache_from: None,
+                cache_to: None,
+                cargo_flags: None,
+                rustdocflags: None,
                 env: CrossEnvConfig {
                     passthrough: None,
                     volumes: Some(vec![p!("VOL")]),
@@ -761,12 +1039,33 @@ mod tests {
                         toolchain: vec![ImagePlatform::from_target(
                             "aarch64-unknown-linux-gnu".into(),
                         )?],
+                        entrypoint: None,
+                        workdir: None,
                     }),
                 }),
                 default_target: None,
+                image_tag: None,
+                registry: None,
+                verify_images: None,
+                fallback_image: None,
+                remote_mount_prefix: None,
+                default_features: None,
                 pre_build: Some(PreBuild::Lines(vec![])),
                 dockerfile: None,
+                context: None,
+                engine: None,
+                remote: None,
+                packages: None,
+                sbom: None,
+                install_qemu: None,
+                wine_version: None,
+                wine_dialogs: None,
+                cache_from: None,
+                cache_to: None,
             },
+            builders: HashMap::new(),
+            features: HashMap::new(),
+            test: CrossTestConfig::default(),
         };
 
         let test_str = r#"
@@ -787,6 +1086,8 @@ mod tests {
             pre-build = ["echo 'Hello'"]
             image.name = "test-image"
             image.toolchain = ["aarch64-unknown-linux-musl"]
+            image.entrypoint = ""
+            image.workdir = "/project"
 
             [target.aarch64-unknown-linux-gnu.env]
             volumes = ["VOL"]
@@ -829,9 +1130,28 @@ mod tests {
                 xargo: Some(true),
                 zig: None,
                 default_target: None,
+                image_tag: None,
+                registry: None,
+                verify_images: None,
+                fallback_image: None,
+                remote_mount_prefix: None,
+                default_features: None,
                 pre_build: None,
                 dockerfile: None,
+                context: None,
+                engine: None,
+                remote: None,
+                packages: None,
+                sbom: None,
+                install_qemu: None,
+                wine_version: None,
+                wine_dialogs: None,
+                cache_from: None,
+                cache_to: None,
             },
+            builders: HashMap::new(),
+            features: HashMap::new(),
+            test: CrossTestConfig::default(),
         };
 
         let test_str = r#"
@@ -1004,6 +1324,97 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn builders() -> Result<()> {
+        let toml_str = r#"
+            [builders]
+            "aarch64-*" = "builder-arm"
+            "aarch64-unknown-linux-*" = "builder-arm-linux"
+            "aarch64-unknown-linux-musl" = "builder-musl"
+            "*-linux-?nu" = "builder-gnu"
+        "#;
+        let (toml, unused) = CrossToml::parse_from_cross(toml_str, &mut m!())?;
+        assert!(unused.is_empty());
+
+        let builder = |triple| {
+            toml.builder(&Target::new_built_in(triple))
+                .map(String::as_str)
+        };
+        assert_eq!(builder("aarch64-unknown-linux-musl"), Some("builder-musl"));
+        assert_eq!(
+            builder("aarch64-unknown-linux-gnu"),
+            Some("builder-arm-linux")
+        );
+        assert_eq!(builder("aarch64-linux-android"), Some("builder-arm"));
+        assert_eq!(builder("x86_64-unknown-linux-gnu"), Some("builder-gnu"));
+        assert_eq!(builder("x86_64-unknown-linux-musl"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn feature_sets() -> Result<()> {
+        let toml_str = r#"
+            [build]
+            default-features = ["foo"]
+
+            [features]
+            ci = ["foo", "bar"]
+            minimal = []
+        "#;
+        let (toml, unused) = CrossToml::parse_from_cross(toml_str, &mut m!())?;
+        assert!(unused.is_empty());
+
+        assert_eq!(
+            toml.feature_set("ci"),
+            Some(&["foo".to_owned(), "bar".to_owned()] as &[_])
+        );
+        assert_eq!(toml.feature_set("minimal"), Some(&[] as &[_]));
+        assert_eq!(toml.feature_set("release"), None);
+        assert_eq!(toml.default_features(), Some(&["foo".to_owned()] as &[_]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pod() -> Result<()> {
+        let toml_str = r#"
+            [[test.pod.services]]
+            name = "postgres"
+            image = "postgres:15"
+            env = ["POSTGRES_PASSWORD=cross"]
+
+            [[test.pod.services]]
+            name = "redis"
+            image = "redis:7"
+            args = ["--save", ""]
+        "#;
+        let (toml, unused) = CrossToml::parse_from_cross(toml_str, &mut m!())?;
+        assert!(unused.is_empty());
+
+        let pod = toml.pod().expect("the pod should be configured");
+        assert_eq!(
+            pod.services,
+            [
+                CrossPodService {
+                    name: "postgres".to_owned(),
+                    image: "postgres:15".to_owned(),
+                    env: vec!["POSTGRES_PASSWORD=cross".to_owned()],
+                    args: vec![],
+                },
+                CrossPodService {
+                    name: "redis".to_owned(),
+                    image: "redis:7".to_owned(),
+                    env: vec![],
+                    args: vec!["--save".to_owned(), String::new()],
+                },
+            ]
+        );
+        assert_eq!(CrossToml::default().pod(), None);
+
+        Ok(())
+    }
+
     #[test]
     fn pre_build_script() -> Result<()> {
         let toml_str = r#"
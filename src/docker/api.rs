@@ -0,0 +1,236 @@
+//! A minimal client for the Docker Engine API over a local socket.
+//!
+//! Simple queries and lifecycle operations, such as inspecting volumes
+//! or stopping containers, can be made directly against the daemon rather
+//! than spawning the container engine CLI for each call. Podman serves a
+//! compatible API. The client is opt-in with `CROSS_CONTAINER_ENGINE_API`,
+//! and is only used with local unix sockets: anything else, such as remote
+//! hosts or contexts, falls back to the CLI.
+//!
+//! Running containers, `exec` and copies always use the CLI. They stream
+//! the output of the build and tar archives over hijacked connections,
+//! which would need a streaming HTTP client and archive handling here,
+//! while the spawns they'd save are small next to the data they transfer.
+
+use std::env;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::engine::EngineType;
+use crate::config::bool_from_envvar;
+use crate::errors::*;
+use crate::shell::MessageInfo;
+
+pub const CROSS_CONTAINER_ENGINE_API_ENV: &str = "CROSS_CONTAINER_ENGINE_API";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiClient {
+    socket: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn json(&self) -> Result<serde_json::Value> {
+        serde_json::from_slice(&self.body).wrap_err("invalid JSON response from engine API")
+    }
+}
+
+impl ApiClient {
+    /// Find the socket for the engine, if the API is enabled and the daemon is local.
+    pub fn discover(kind: EngineType, context: Option<&str>, host: Option<&str>) -> Option<Self> {
+        let enabled = env::var(CROSS_CONTAINER_ENGINE_API_ENV)
+            .map(|s| bool_from_envvar(&s))
+            .unwrap_or_default();
+        if !enabled || context.is_some() || !cfg!(unix) {
+            return None;
+        }
+
+        let host = host
+            .map(ToOwned::to_owned)
+            .or_else(|| env::var("DOCKER_HOST").ok());
+        let socket = match host {
+            Some(host) if !host.is_empty() => PathBuf::from(host.strip_prefix("unix://")?),
+            _ => match kind {
+                EngineType::Docker => PathBuf::from("/var/run/docker.sock"),
+                EngineType::Podman => env::var_os("XDG_RUNTIME_DIR")
+                    .map(|dir| Path::new(&dir).join("podman/podman.sock"))
+                    .filter(|path| path.exists())
+                    .unwrap_or_else(|| PathBuf::from("/run/podman/podman.sock")),
+                _ => return None,
+            },
+        };
+
+        if socket.exists() {
+            Some(ApiClient { socket })
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, path: &str, msg_info: &mut MessageInfo) -> Result<Response> {
+        self.request("GET", path, None, msg_info)
+    }
+
+    pub fn post(
+        &self,
+        path: &str,
+        body: Option<&serde_json::Value>,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Response> {
+        self.request("POST", path, body, msg_info)
+    }
+
+    pub fn delete(&self, path: &str, msg_info: &mut MessageInfo) -> Result<Response> {
+        self.request("DELETE", path, None, msg_info)
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&serde_json::Value>,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Response> {
+        msg_info.debug(format_args!("+ {method} {path} ({:?})", self.socket))?;
+
+        let body = body
+            .map(serde_json::to_vec)
+            .transpose()?
+            .unwrap_or_default();
+        let mut request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+            body.len()
+        );
+        if !body.is_empty() {
+            request.push_str("Content-Type: application/json\r\n");
+        }
+        request.push_str("\r\n");
+
+        let mut stream = self.connect()?;
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(&body)?;
+        let mut raw = vec![];
+        stream.read_to_end(&mut raw)?;
+
+        parse_response(&raw).wrap_err_with(|| format!("invalid response for `{method} {path}`"))
+    }
+
+    #[cfg(unix)]
+    fn connect(&self) -> Result<std::os::unix::net::UnixStream> {
+        std::os::unix::net::UnixStream::connect(&self.socket)
+            .wrap_err_with(|| format!("could not connect to {:?}", self.socket))
+    }
+
+    #[cfg(not(unix))]
+    fn connect(&self) -> Result<std::fs::File> {
+        eyre::bail!("the engine API is only supported with unix sockets")
+    }
+}
+
+/// Encode a name used as a path segment of a request.
+#[must_use]
+pub fn encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char);
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response> {
+    let split = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| eyre::eyre!("missing end of headers"))?;
+    let head = std::str::from_utf8(&raw[..split])?;
+    let rest = &raw[split + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| eyre::eyre!("missing status line"))?
+        .parse()?;
+    let is_chunked = lines.any(|line| match line.split_once(':') {
+        Some((key, value)) => {
+            key.eq_ignore_ascii_case("transfer-encoding") && value.trim() == "chunked"
+        }
+        None => false,
+    });
+
+    let body = if is_chunked {
+        dechunk(rest)?
+    } else {
+        rest.to_vec()
+    };
+
+    Ok(Response { status, body })
+}
+
+fn dechunk(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let end = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| eyre::eyre!("missing chunk size"))?;
+        let size = std::str::from_utf8(&data[..end])?;
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)?;
+        if size == 0 {
+            return Ok(body);
+        }
+        let chunk = data
+            .get(end + 2..end + 2 + size)
+            .ok_or_else(|| eyre::eyre!("truncated chunk"))?;
+        body.extend_from_slice(chunk);
+        data = data.get(end + 4 + size..).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plain_response() -> Result<()> {
+        let raw = b"HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: 27\r\n\r\n{\"message\":\"no such volume\"}";
+        let response = parse_response(raw)?;
+        assert_eq!(response.status, 404);
+        assert!(!response.is_success());
+        assert_eq!(response.json()?["message"], "no such volume");
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_chunked_response() -> Result<()> {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n7\r\n{\"State\r\nC\r\n\":\"running\"}\r\n0\r\n\r\n";
+        let response = parse_response(raw)?;
+        assert!(response.is_success());
+        assert_eq!(response.json()?["State"], "running");
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_segment() {
+        assert_eq!(encode("cross-stable-x86_64"), "cross-stable-x86_64");
+        assert_eq!(encode("a/b c"), "a%2Fb%20c");
+    }
+}
@@ -0,0 +1,556 @@
+//! The operations on containers and volumes that builds depend on.
+//!
+//! Builds go through [`ContainerEngine`] rather than constructing engine
+//! commands directly, so other backends can be added without changing how
+//! builds are run. [`Engine`] implements it with the CLI of the container
+//! engine, using the engine API for the volume and container lifecycle
+//! operations when it's available.
+
+use std::ffi::OsString;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use eyre::WrapErr;
+
+use super::api;
+use super::build_script::{self, BuildScriptLog};
+use super::engine::Engine;
+use super::shared::ContainerState;
+use crate::errors::{CommandError, Result};
+use crate::extensions::{exit_status, CommandExt, OutputExt, MAX_DIAGNOSTIC_OUTPUT};
+use crate::file::{self, ToUtf8};
+use crate::shell::MessageInfo;
+
+pub trait ContainerEngine {
+    /// Create a named volume.
+    fn volume_create(
+        &self,
+        name: &str,
+        labels: &[(String, String)],
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+    /// Remove a named volume.
+    fn volume_remove(&self, name: &str, msg_info: &mut MessageInfo) -> Result<ExitStatus>;
+    /// Returns if a named volume exists.
+    fn volume_exists(&self, name: &str, msg_info: &mut MessageInfo) -> Result<bool>;
+    /// Stop a container, killing it after `timeout` seconds.
+    fn container_stop(
+        &self,
+        name: &str,
+        timeout: u32,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+    /// Remove a stopped container.
+    fn container_remove(&self, name: &str, msg_info: &mut MessageInfo) -> Result<ExitStatus>;
+    /// Get the state of a container, which may not exist.
+    fn container_state(&self, name: &str, msg_info: &mut MessageInfo) -> Result<ContainerState>;
+    /// Create a container, with the options of `create` followed by the image.
+    fn container_create(&self, args: &[OsString], msg_info: &mut MessageInfo)
+        -> Result<ExitStatus>;
+    /// Run a container, with the options of `run` followed by the image and
+    /// the command.
+    fn run_container(
+        &self,
+        args: &[OsString],
+        silence_stdout: bool,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+    /// Run a container like `run_container`, scanning its stderr for the
+    /// build scripts that failed.
+    fn run_container_and_scan(
+        &self,
+        args: &[OsString],
+        msg_info: &mut MessageInfo,
+    ) -> Result<(ExitStatus, BuildScriptLog)>;
+    /// Run the build in a running container, with the options of `exec`
+    /// followed by the container and the command.
+    fn exec_build(&self, args: &[OsString], msg_info: &mut MessageInfo) -> Result<ExitStatus>;
+    /// Run a shell script in a running container.
+    fn exec(
+        &self,
+        container: &str,
+        script: &str,
+        silence_stdout: bool,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+    /// Run a shell script in a running container and get its output.
+    fn exec_and_get_stdout(
+        &self,
+        container: &str,
+        script: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<String>;
+    /// Copy a host path into a container, preserving ownership and permissions.
+    fn copy_into(
+        &self,
+        container: &str,
+        src: &Path,
+        dst: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+    /// Copy the files listed, which are relative to `src`, into a directory
+    /// of a container, streaming them as a tar archive, which is compressed
+    /// with zstd if `compress` is set.
+    fn copy_files_into(
+        &self,
+        container: &str,
+        src: &Path,
+        files: &[String],
+        dst: &str,
+        compress: bool,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+    /// Copy the files in a directory of a container that were modified after
+    /// the file `newer` into the host directory `dst`, like `copy_from`
+    /// would, streaming them as a tar archive.
+    fn copy_newer_from(
+        &self,
+        container: &str,
+        src: &str,
+        newer: &str,
+        dst: &Path,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+    /// Copy a path in a container to the host, preserving ownership and permissions.
+    fn copy_from(
+        &self,
+        container: &str,
+        src: &str,
+        dst: &Path,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus>;
+}
+
+impl ContainerEngine for Engine {
+    #[track_caller]
+    fn volume_create(
+        &self,
+        name: &str,
+        labels: &[(String, String)],
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        if let Some(api) = &self.api {
+            let labels: serde_json::Map<_, _> = labels
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().into()))
+                .collect();
+            let body = serde_json::json!({ "Name": name, "Labels": labels });
+            let response = api.post("/volumes/create", Some(&body), msg_info)?;
+            return Ok(api_status(response.is_success()));
+        }
+        let mut volume = self.subcommand("volume");
+        volume.args(["create", name]);
+        for (key, value) in labels {
+            volume.args(["--label", &format!("{key}={value}")]);
+        }
+        volume.run_and_get_status_with_retry(msg_info, true, self.retry)
+    }
+
+    #[track_caller]
+    fn volume_remove(&self, name: &str, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        if let Some(api) = &self.api {
+            let path = format!("/volumes/{}", api::encode(name));
+            let response = api.delete(&path, msg_info)?;
+            return Ok(api_status(response.is_success()));
+        }
+        self.run_and_get_status(&["volume", "rm", name], msg_info)
+    }
+
+    #[track_caller]
+    fn volume_exists(&self, name: &str, msg_info: &mut MessageInfo) -> Result<bool> {
+        if let Some(api) = &self.api {
+            let path = format!("/volumes/{}", api::encode(name));
+            return Ok(api.get(&path, msg_info)?.is_success());
+        }
+        self.run_and_get_limited_output(&["volume", "inspect", name], msg_info)
+            .map(|output| output.status.success())
+    }
+
+    fn container_stop(
+        &self,
+        name: &str,
+        timeout: u32,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        if let Some(api) = &self.api {
+            let path = format!("/containers/{}/stop?t={timeout}", api::encode(name));
+            let response = api.post(&path, None, msg_info)?;
+            // 304 is returned if the container was already stopped
+            return Ok(api_status(response.is_success() || response.status == 304));
+        }
+        self.run_and_get_status(&["stop", name, "--time", &timeout.to_string()], msg_info)
+    }
+
+    fn container_remove(&self, name: &str, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        if let Some(api) = &self.api {
+            let path = format!("/containers/{}", api::encode(name));
+            let response = api.delete(&path, msg_info)?;
+            return Ok(api_status(response.is_success()));
+        }
+        self.run_and_get_limited_output(&["rm", name], msg_info)
+            .map(|output| output.status)
+    }
+
+    fn container_state(&self, name: &str, msg_info: &mut MessageInfo) -> Result<ContainerState> {
+        if let Some(api) = &self.api {
+            let path = format!("/containers/{}/json", api::encode(name));
+            let response = api.get(&path, msg_info)?;
+            if response.status == 404 {
+                return Ok(ContainerState::DoesNotExist);
+            }
+            let json = response.json()?;
+            return match json["State"]["Status"].as_str() {
+                Some("removing") => Ok(ContainerState::Dead),
+                Some(state) => ContainerState::new(state),
+                None => eyre::bail!("could not get state for container `{name}`"),
+            };
+        }
+        let mut ps = self.subcommand("ps");
+        ps.arg("-a")
+            .args(["--filter", &format!("name={name}")])
+            .args(["--format", "{{.State}}"]);
+        let output = self.retry.run(
+            msg_info,
+            |msg_info| ps.run_and_get_limited_output(msg_info, MAX_DIAGNOSTIC_OUTPUT),
+            |output| output.status,
+        )?;
+        ps.status_result(msg_info, output.status, Some(&output))
+            .map_err(CommandError::to_section_report)?;
+        ContainerState::new(output.stdout()?.trim())
+    }
+
+    #[track_caller]
+    fn container_create(
+        &self,
+        args: &[OsString],
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        self.subcommand("create")
+            .args(args)
+            .run_and_get_status(msg_info, true)
+    }
+
+    #[track_caller]
+    fn run_container(
+        &self,
+        args: &[OsString],
+        silence_stdout: bool,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        self.subcommand("run")
+            .args(args)
+            .run_and_get_status(msg_info, silence_stdout)
+    }
+
+    #[track_caller]
+    fn run_container_and_scan(
+        &self,
+        args: &[OsString],
+        msg_info: &mut MessageInfo,
+    ) -> Result<(ExitStatus, BuildScriptLog)> {
+        build_script::run_and_scan(self.subcommand("run").args(args), msg_info)
+    }
+
+    #[track_caller]
+    fn exec_build(&self, args: &[OsString], msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        self.subcommand("exec")
+            .args(args)
+            .run_and_get_status(msg_info, false)
+    }
+
+    #[track_caller]
+    fn exec(
+        &self,
+        container: &str,
+        script: &str,
+        silence_stdout: bool,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        self.subcommand("exec")
+            .arg(container)
+            .args(["sh", "-c", script])
+            .run_and_get_status(msg_info, silence_stdout)
+    }
+
+    #[track_caller]
+    fn exec_and_get_stdout(
+        &self,
+        container: &str,
+        script: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<String> {
+        self.subcommand("exec")
+            .arg(container)
+            .args(["sh", "-c", script])
+            .run_and_get_stdout(msg_info)
+    }
+
+    #[track_caller]
+    fn copy_into(
+        &self,
+        container: &str,
+        src: &Path,
+        dst: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let mut cp = self.subcommand("cp");
+        if self.capabilities.cp_archive {
+            cp.arg("-a");
+        }
+        cp.arg(src.to_utf8()?)
+            .arg(format!("{container}:{dst}"))
+            .run_and_get_status_with_retry(msg_info, false, self.retry)
+    }
+
+    #[track_caller]
+    fn copy_files_into(
+        &self,
+        container: &str,
+        src: &Path,
+        files: &[String],
+        dst: &str,
+        compress: bool,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        // the list of files is passed on stdin, since it can be too long for
+        // the command line. directories are listed with their contents, so
+        // tar shouldn't recurse into them.
+        let mut tar = Command::new("tar");
+        tar.args(["-c", "-f", "-", "--numeric-owner", "-C", src.to_utf8()?])
+            .args(["--no-recursion", "--null", "-T", "-"]);
+        tar.debug(msg_info)?;
+        let start = Instant::now();
+        let mut child = tar
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .wrap_err_with(|| format!("could not execute `{tar:?}`"))?;
+        let mut stdin = child.stdin.take().expect("stdin should be piped");
+        let mut list = vec![];
+        for file in files {
+            list.extend_from_slice(file.as_bytes());
+            list.push(b'\0');
+        }
+        // write on another thread, so tar can't block on a full stdout pipe.
+        let writer = thread::spawn(move || stdin.write_all(&list));
+        let archive = child.stdout.take().expect("stdout should be piped");
+
+        // the archive goes through cross, to report the progress of the copy.
+        let mut exec = self.subcommand("exec");
+        exec.args(["-i", container, "sh", "-c", &extract_script(dst, compress)]);
+        let mut compressor = None;
+        if compress {
+            let mut zstd = Command::new("zstd");
+            zstd.args(["-c", "-q"]);
+            zstd.debug(msg_info)?;
+            let mut zstd_child = zstd
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .wrap_err_with(|| format!("could not execute `{zstd:?}`"))?;
+            let compressed = zstd_child.stdout.take().expect("stdout should be piped");
+            exec.stdin(Stdio::from(compressed));
+            compressor = Some((zstd, zstd_child));
+        } else {
+            exec.stdin(Stdio::piped());
+        }
+        exec.debug(msg_info)?;
+        let mut exec_child = exec
+            .spawn()
+            .wrap_err_with(|| format!("could not execute `{exec:?}`"))?;
+        let input = match &mut compressor {
+            Some((_, zstd_child)) => zstd_child.stdin.take(),
+            None => exec_child.stdin.take(),
+        }
+        .expect("stdin should be piped");
+        let total = file::files_size(src, files);
+        let copied = copy_with_progress(archive, input, total, msg_info);
+        msg_info.finish_progress()?;
+
+        let status = exec_child.wait()?;
+        exec.log_finished(Some(&status), start, msg_info)?;
+        let written = writer.join().expect("the writer should not panic");
+        let tar_status = child.wait()?;
+        tar.log_finished(Some(&tar_status), start, msg_info)?;
+        if !tar_status.success() {
+            return Ok(tar_status);
+        }
+        if let Some((zstd, mut compressor)) = compressor {
+            let zstd_status = compressor.wait()?;
+            zstd.log_finished(Some(&zstd_status), start, msg_info)?;
+            if !zstd_status.success() {
+                return Ok(zstd_status);
+            }
+        }
+        if !status.success() {
+            return Ok(status);
+        }
+        written.wrap_err("could not write the list of files to `tar`")?;
+        copied.wrap_err("could not copy the files into the container")?;
+
+        Ok(status)
+    }
+
+    #[track_caller]
+    fn copy_newer_from(
+        &self,
+        container: &str,
+        src: &str,
+        newer: &str,
+        dst: &Path,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let mut exec = self.subcommand("exec");
+        exec.args([container, "sh", "-c", &archive_newer_script(src, newer)]);
+        exec.debug(msg_info)?;
+        let start = Instant::now();
+        let mut child = exec
+            .stdout(Stdio::piped())
+            .spawn()
+            .wrap_err_with(|| format!("could not execute `{exec:?}`"))?;
+        let archive = child.stdout.take().expect("stdout should be piped");
+
+        let status = Command::new("tar")
+            .args(["-x", "-f", "-", "-C", dst.to_utf8()?])
+            .stdin(Stdio::from(archive))
+            .run_and_get_status(msg_info, false)?;
+        let exec_status = child.wait()?;
+        exec.log_finished(Some(&exec_status), start, msg_info)?;
+        match exec_status.success() {
+            true => Ok(status),
+            false => Ok(exec_status),
+        }
+    }
+
+    #[track_caller]
+    fn copy_from(
+        &self,
+        container: &str,
+        src: &str,
+        dst: &Path,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let mut cp = self.subcommand("cp");
+        if self.capabilities.cp_archive {
+            cp.arg("-a");
+        }
+        cp.arg(format!("{container}:{src}"))
+            .arg(dst)
+            .run_and_get_status_with_retry(msg_info, false, self.retry)
+    }
+}
+
+/// The arguments assembled for an operation, such as `run`, to pass to the
+/// [`ContainerEngine`]. The arguments are built with the extensions for
+/// engine commands, so the program of `command` is only a placeholder.
+pub(crate) fn operation_args(command: &Command) -> Vec<OsString> {
+    command.get_args().map(ToOwned::to_owned).collect()
+}
+
+/// The script to extract a tar archive from stdin into `dst` in a container.
+fn extract_script(dst: &str, compress: bool) -> String {
+    let tar = format!("tar -x -f - --numeric-owner -C '{dst}'");
+    match compress {
+        true => format!("mkdir -p '{dst}' && zstd -d -c -q | {tar}"),
+        false => format!("mkdir -p '{dst}' && {tar}"),
+    }
+}
+
+/// The script to archive the files under `src` in a container modified after
+/// `newer` to stdout. Like `docker cp`, relative paths are relative to the
+/// root directory, and the archive has the last component of `src` as root.
+fn archive_newer_script(src: &str, newer: &str) -> String {
+    let src = src.trim_end_matches('/');
+    let (parent, name) = match src.rsplit_once('/') {
+        Some((parent, name)) => (parent, name),
+        None => ("", src),
+    };
+    format!(
+        "cd '/{}' && find -H '{name}' -newer '{newer}' \\( -type f -o -type l \\) -print0 | tar -c -f - --null -T -",
+        parent.trim_start_matches('/')
+    )
+}
+
+/// Copy the archive from `tar` to the engine, reporting the bytes copied out
+/// of the size of the files. The archive also has headers, so the size copied
+/// is capped at the total.
+fn copy_with_progress(
+    mut archive: impl Read,
+    mut input: impl Write,
+    total: u64,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut copied = 0;
+    let start = Instant::now();
+    let mut reported = start;
+    loop {
+        let read = match archive.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        input.write_all(&buffer[..read])?;
+        copied += read as u64;
+        if reported.elapsed() >= REPORT_INTERVAL {
+            reported = Instant::now();
+            msg_info.progress(format_args!(
+                "copied {} of {} in {:.1}s",
+                file::format_size(copied.min(total)),
+                file::format_size(total),
+                start.elapsed().as_secs_f64()
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The exit status for an operation done through the engine API.
+fn api_status(success: bool) -> ExitStatus {
+    exit_status(if success { 0 } else { 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_archive() -> Result<()> {
+        let archive = vec![1u8; 200 * 1024];
+        let mut input = vec![];
+        copy_with_progress(&archive[..], &mut input, 1024, &mut MessageInfo::default())?;
+        assert_eq!(input, archive);
+
+        Ok(())
+    }
+
+    #[test]
+    fn extract_scripts() {
+        assert_eq!(
+            extract_script("/cross/project", false),
+            "mkdir -p '/cross/project' && tar -x -f - --numeric-owner -C '/cross/project'"
+        );
+        assert_eq!(
+            extract_script("/cross/project", true),
+            "mkdir -p '/cross/project' && zstd -d -c -q | tar -x -f - --numeric-owner -C '/cross/project'"
+        );
+    }
+
+    #[test]
+    fn archive_newer_scripts() {
+        assert_eq!(
+            archive_newer_script("target", "/tmp/start"),
+            "cd '/' && find -H 'target' -newer '/tmp/start' \\( -type f -o -type l \\) -print0 | tar -c -f - --null -T -"
+        );
+        assert_eq!(
+            archive_newer_script("/home/user/project/target/", "/tmp/start"),
+            "cd '/home/user/project' && find -H 'target' -newer '/tmp/start' \\( -type f -o -type l \\) -print0 | tar -c -f - --null -T -"
+        );
+    }
+}
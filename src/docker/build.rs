@@ -82,6 +82,14 @@ impl BuildCommandExt for Command {
         self.args([
             "--label",
             &format!("{}.runs-with={platform}", crate::CROSS_LABEL_DOMAIN,),
+        ]);
+        self.args([
+            "--label",
+            &format!(
+                "{}.cross-version={}",
+                crate::CROSS_LABEL_DOMAIN,
+                env!("CARGO_PKG_VERSION")
+            ),
         ])
     }
 }
@@ -0,0 +1,254 @@
+//! Dumping the environment of failing build scripts, for
+//! `--emit-build-script-env`.
+//!
+//! cargo only prints the environment it gives build scripts when it's very
+//! verbose, as `Running `KEY=VALUE ... /path/to/build-script-build``, so the
+//! container is run with `-vv` and its `stderr` is scanned for these lines
+//! and the build scripts that failed. The paths in the environment are in the
+//! container, so they're mapped back to the host before being printed.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Instant;
+
+use super::shared::DockerPaths;
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::shell::MessageInfo;
+
+pub const EMIT_BUILD_SCRIPT_ENV_FLAG: &str = "--emit-build-script-env";
+const BUILD_SCRIPT: &str = "build-script-build";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BuildScriptLog {
+    /// The environment of each build script run, by program.
+    runs: BTreeMap<String, Vec<(String, String)>>,
+    /// The programs of the build scripts that failed, in order.
+    failed: Vec<String>,
+}
+
+fn is_env_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl BuildScriptLog {
+    pub(crate) fn scan_line(&mut self, line: &str) {
+        let line = line.trim();
+        if let Some(command) = line
+            .strip_prefix("Running `")
+            .and_then(|s| s.strip_suffix('`'))
+        {
+            let words = match shell_words::split(command) {
+                Ok(words) => words,
+                Err(_) => return,
+            };
+            let mut env = vec![];
+            for word in words {
+                match word.split_once('=') {
+                    Some((key, value)) if is_env_key(key) => {
+                        env.push((key.to_owned(), value.to_owned()));
+                    }
+                    _ => {
+                        if word.ends_with(BUILD_SCRIPT) {
+                            self.runs.insert(word, env);
+                        }
+                        return;
+                    }
+                }
+            }
+        } else if let Some(rest) = line.strip_prefix("process didn't exit successfully: `") {
+            if let Some((program, _)) = rest.split_once('`') {
+                if program.ends_with(BUILD_SCRIPT) && !self.failed.iter().any(|p| p == program) {
+                    self.failed.push(program.to_owned());
+                }
+            }
+        }
+    }
+
+    /// The failed build scripts, with their environment if it was printed.
+    pub(crate) fn failures(&self) -> impl Iterator<Item = (&str, Option<&[(String, String)]>)> {
+        self.failed
+            .iter()
+            .map(|program| (program.as_str(), self.runs.get(program).map(Vec::as_slice)))
+    }
+}
+
+/// Maps paths in the container to their location on the host.
+#[derive(Debug)]
+pub(crate) struct PathMapping(Vec<(String, PathBuf)>);
+
+impl PathMapping {
+    pub(crate) fn new(paths: &DockerPaths) -> PathMapping {
+        let toolchain_dirs = paths.directories.toolchain_directories();
+        let package_dirs = paths.directories.package_directories();
+        PathMapping::from_mounts(vec![
+            ("/target".to_owned(), package_dirs.target().to_owned()),
+            (
+                package_dirs.mount_root().to_owned(),
+                package_dirs.host_root().to_owned(),
+            ),
+            (
+                toolchain_dirs.cargo_mount_path().to_owned(),
+                toolchain_dirs.cargo().to_owned(),
+            ),
+            (
+                toolchain_dirs.xargo_mount_path().to_owned(),
+                toolchain_dirs.xargo().to_owned(),
+            ),
+            (
+                toolchain_dirs.sysroot_mount_path().to_owned(),
+                toolchain_dirs.get_sysroot().to_owned(),
+            ),
+        ])
+    }
+
+    fn from_mounts(mut mounts: Vec<(String, PathBuf)>) -> PathMapping {
+        // the most specific mount wins if they're nested
+        mounts.sort_by_key(|(mount, _)| Reverse(mount.len()));
+        PathMapping(mounts)
+    }
+
+    /// Map a value, or each entry of a list of paths, if it's in a mount.
+    pub(crate) fn map(&self, value: &str) -> String {
+        value
+            .split(':')
+            .map(|part| self.map_path(part))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    fn map_path(&self, path: &str) -> String {
+        for (mount, host) in &self.0 {
+            let rest = match path.strip_prefix(mount.as_str()) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+                _ => continue,
+            };
+            let mut host = host.to_string_lossy().into_owned();
+            host.push_str(rest);
+            return host;
+        }
+        path.to_owned()
+    }
+}
+
+/// Run the container, forwarding its `stderr` while scanning it for build
+/// scripts.
+pub(crate) fn run_and_scan(
+    docker: &mut Command,
+    msg_info: &mut MessageInfo,
+) -> Result<(ExitStatus, BuildScriptLog)> {
+    docker.debug(msg_info)?;
+    docker.stderr(Stdio::piped());
+    let start = Instant::now();
+    let mut child = docker
+        .spawn()
+        .wrap_err_with(|| format!("could not execute `{docker:?}`"))?;
+    let mut log = BuildScriptLog::default();
+    if let Some(stderr) = child.stderr.take() {
+        let mut out = io::stderr();
+        for line in BufReader::new(stderr).lines() {
+            let line = line?;
+            writeln!(out, "{line}")?;
+            log.scan_line(&line);
+        }
+    }
+    let status = child.wait()?;
+    docker.log_finished(Some(&status), start, msg_info)?;
+
+    Ok((status, log))
+}
+
+/// Print the environment and working directory of each failed build script,
+/// with the paths on the host.
+pub(crate) fn report(
+    log: &BuildScriptLog,
+    mapping: &PathMapping,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    for (program, env) in log.failures() {
+        let program = mapping.map(program);
+        let env = match env {
+            Some(env) => env,
+            None => {
+                msg_info.warn(format_args!(
+                    "the environment of the failed build script `{program}` was not printed by cargo"
+                ))?;
+                continue;
+            }
+        };
+        let mut message = format!("environment of the failed build script `{program}`:");
+        if let Some((_, dir)) = env.iter().find(|(key, _)| key == "CARGO_MANIFEST_DIR") {
+            message.push_str(&format!("\n  working directory: {}", mapping.map(dir)));
+        }
+        for (key, value) in env {
+            message.push_str(&format!(
+                "\n  {key}={}",
+                shell_words::quote(&mapping.map(value))
+            ));
+        }
+        msg_info.note(message)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_build_scripts() {
+        let mut log = BuildScriptLog::default();
+        for line in [
+            "   Compiling foo v0.1.0 (/project)",
+            "     Running `CARGO=/usr/local/cargo/bin/cargo CARGO_MANIFEST_DIR=/project OUT_DIR=/target/debug/build/foo-1234/out 'MESSAGE=a b' /target/debug/build/foo-abcd/build-script-build`",
+            "     Running `CARGO_MANIFEST_DIR=/project/bar /target/debug/build/bar-abcd/build-script-build`",
+            "     Running `rustc --crate-name foo src/lib.rs`",
+            "error: failed to run custom build command for `foo v0.1.0 (/project)`",
+            "Caused by:",
+            "  process didn't exit successfully: `/target/debug/build/foo-abcd/build-script-build` (exit status: 1)",
+        ] {
+            log.scan_line(line);
+        }
+        let failures: Vec<_> = log.failures().collect();
+        assert_eq!(failures.len(), 1);
+        let (program, env) = failures[0];
+        assert_eq!(program, "/target/debug/build/foo-abcd/build-script-build");
+        assert_eq!(
+            env.unwrap(),
+            &[
+                ("CARGO".to_owned(), "/usr/local/cargo/bin/cargo".to_owned()),
+                ("CARGO_MANIFEST_DIR".to_owned(), "/project".to_owned()),
+                (
+                    "OUT_DIR".to_owned(),
+                    "/target/debug/build/foo-1234/out".to_owned()
+                ),
+                ("MESSAGE".to_owned(), "a b".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_mapping() {
+        let mapping = PathMapping::from_mounts(vec![
+            ("/target".to_owned(), PathBuf::from("/home/user/foo/target")),
+            ("/project".to_owned(), PathBuf::from("/home/user/foo")),
+            (
+                "/project/vendor".to_owned(),
+                PathBuf::from("/home/user/vendor"),
+            ),
+        ]);
+        assert_eq!(mapping.map("/target/debug"), "/home/user/foo/target/debug");
+        assert_eq!(mapping.map("/project"), "/home/user/foo");
+        assert_eq!(mapping.map("/project/vendor/a"), "/home/user/vendor/a");
+        assert_eq!(mapping.map("/projects"), "/projects");
+        assert_eq!(
+            mapping.map("/target/lib:/usr/lib"),
+            "/home/user/foo/target/lib:/usr/lib"
+        );
+        assert_eq!(mapping.map("debug"), "debug");
+    }
+}
@@ -1,5 +1,5 @@
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use crate::docker::{self, DockerOptions, DockerPaths};
@@ -7,6 +7,7 @@ use crate::shell::MessageInfo;
 use crate::{errors::*, file, CommandExt, ToUtf8};
 use crate::{CargoMetadata, TargetTriple};
 
+use super::shared::str_hash;
 use super::{get_image_name, path_hash, BuildCommandExt, BuildResultExt, Engine, ImagePlatform};
 
 pub const CROSS_CUSTOM_DOCKERFILE_IMAGE_PREFIX: &str = "localhost/cross-rs/cross-custom-";
@@ -22,6 +23,8 @@ pub enum Dockerfile<'a> {
     Custom {
         content: String,
         runs_with: &'a ImagePlatform,
+        /// The hash of the base image and the hooks, see [`PreBuild::key`].
+        key: String,
     },
 }
 
@@ -60,6 +63,59 @@ impl PreBuild {
     pub fn is_lines(&self) -> bool {
         matches!(self, Self::Lines(..))
     }
+
+    /// A hook to install system packages with the package manager of the image.
+    pub fn install_packages(packages: &[String]) -> PreBuild {
+        // the names aren't quoted, so they can use `$CROSS_DEB_ARCH`
+        let packages = packages.join(" ");
+        PreBuild::Lines(vec![format!(
+            r#"if command -v apt-get >/dev/null 2>&1; then
+    apt-get update && apt-get install --assume-yes --no-install-recommends {packages} && rm -rf /var/lib/apt/lists/*
+elif command -v apk >/dev/null 2>&1; then
+    apk add --no-cache {packages}
+elif command -v yum >/dev/null 2>&1; then
+    yum install -y {packages}
+else
+    echo "no supported package manager to install: {packages}" >&2
+    exit 1
+fi"#
+        )])
+    }
+
+    /// A deterministic key for the image built with the hooks, which changes
+    /// if the hooks, a pre-build script, or the base image change. The built
+    /// image is tagged with it, so the hooks only run once.
+    pub fn key(
+        &self,
+        base_image: &str,
+        base_image_id: Option<&str>,
+        target: &str,
+        host_root: &Path,
+    ) -> Result<String> {
+        let hooks = match self {
+            PreBuild::Single { line, .. } if self.runs_script(host_root) => {
+                format!("{line}\n{}", file::read(host_root.join(line))?)
+            }
+            PreBuild::Single { line, .. } => line.clone(),
+            PreBuild::Lines(lines) => lines.join("\n"),
+        };
+        let id = base_image_id.unwrap_or_default();
+        Ok(str_hash(
+            &format!("{base_image}\n{id}\n{target}\n{hooks}"),
+            docker::PATH_HASH_UNIQUE,
+        ))
+    }
+
+    /// Returns if the hook is a script on the host to copy and run, rather
+    /// than commands.
+    pub(crate) fn runs_script(&self, host_root: &Path) -> bool {
+        match self {
+            PreBuild::Single { line, env } => {
+                !env || !line.contains('\n') && host_root.join(line).is_file()
+            }
+            PreBuild::Lines(_) => false,
+        }
+    }
 }
 
 impl<'a> Dockerfile<'a> {
@@ -134,6 +190,25 @@ impl<'a> Dockerfile<'a> {
         //  the Dockerfile path to be specified as `../Dockerfile`.
         docker_build.args(["--file".into(), path]);
 
+        // caches let custom images be reused across CI runs, rather than
+        // being rebuilt from scratch every time.
+        for cache in options
+            .config
+            .cache_from(&options.target)?
+            .unwrap_or_default()
+        {
+            docker_build.args(["--cache-from", &cache]);
+        }
+        if let Some(cache) = options.config.cache_to(&options.target)? {
+            if options.engine.kind.is_docker() && !Engine::has_buildkit() {
+                msg_info.warn(format_args!(
+                    "`cache-to` requires buildkit, not exporting the cache to `{cache}`."
+                ))?;
+            } else {
+                docker_build.args(["--cache-to", &cache]);
+            }
+        }
+
         if let Some(build_opts) = options.config.build_opts() {
             docker_build.args(Engine::parse_opts(&build_opts)?);
         }
@@ -175,10 +250,9 @@ impl<'a> Dockerfile<'a> {
                 CROSS_CUSTOM_DOCKERFILE_IMAGE_PREFIX,
                 package_name = docker_package_name(metadata),
                 path_hash = path_hash(&metadata.workspace_root, docker::PATH_HASH_SHORT)?,
-                custom = if matches!(self, Self::File { .. }) {
-                    ""
-                } else {
-                    "-pre-build"
+                custom = match self {
+                    Self::File { .. } => String::new(),
+                    Self::Custom { key, .. } => format!("-pre-build-{key}"),
                 }
             )),
         }
@@ -289,4 +363,44 @@ mod tests {
         assert_eq!(docker_tag_name("foo-123"), s!("foo-123"));
         assert_eq!(docker_tag_name("foo-123-"), s!("foo-123"));
     }
+
+    #[test]
+    fn pre_build_key() -> Result<()> {
+        let root = Path::new("/does/not/exist");
+        let lines = PreBuild::Lines(vec![
+            s!("apt-get update"),
+            s!("apt-get install -y libssl-dev"),
+        ]);
+        let key = |pre_build: &PreBuild, id: Option<&str>| {
+            pre_build.key(
+                "ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main",
+                id,
+                "aarch64-unknown-linux-gnu",
+                root,
+            )
+        };
+        let base = key(&lines, Some("sha256:0123"))?;
+        assert_eq!(base.len(), docker::PATH_HASH_UNIQUE);
+        assert_eq!(key(&lines, Some("sha256:0123"))?, base);
+        assert_ne!(key(&lines, Some("sha256:4567"))?, base);
+        assert_ne!(
+            key(
+                &PreBuild::Lines(vec![s!("apt-get update")]),
+                Some("sha256:0123")
+            )?,
+            base
+        );
+        // only a hook from the environment can be a command rather than a script
+        let single: PreBuild = "./missing.sh".parse().unwrap();
+        assert!(single.runs_script(root));
+        assert!(key(&single, None).is_err());
+        let env = PreBuild::Single {
+            line: s!("./missing.sh"),
+            env: true,
+        };
+        assert!(!env.runs_script(root));
+        assert!(key(&env, None).is_ok());
+
+        Ok(())
+    }
 }
@@ -2,16 +2,43 @@ use std::env;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use serde::Deserialize;
+
 use crate::config::bool_from_envvar;
-use crate::extensions::CommandExt;
+use crate::extensions::{CommandExt, RetryPolicy};
+use crate::file::{self, PathExt, ToUtf8};
 use crate::shell::MessageInfo;
 use crate::{errors::*, OutputExt};
 
-use super::{Architecture, ContainerOs};
+use super::api::ApiClient;
+use super::{Architecture, ContainerOs, RemoteProfile};
 
 pub const DOCKER: &str = "docker";
 pub const PODMAN: &str = "podman";
 
+/// How the container engine runs on Windows, which determines how host
+/// paths must be given to it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WindowsBackend {
+    /// Docker Desktop with a Hyper-V VM, reached over a named pipe.
+    HyperV,
+    /// Docker Desktop with the WSL2 backend, reached over a named pipe.
+    DockerDesktopWsl,
+    /// An engine in a WSL2 distribution reached over a socket, such as
+    /// podman machine or a docker daemon installed in the distribution.
+    Wsl,
+}
+
+impl WindowsBackend {
+    /// Returns `true` if host paths must be WSL paths, like `/mnt/c/Users`.
+    ///
+    /// Docker Desktop translates Windows paths itself, for both backends.
+    #[must_use]
+    pub const fn uses_wsl_paths(&self) -> bool {
+        matches!(self, Self::Wsl)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum EngineType {
     Docker,
@@ -56,6 +83,119 @@ impl EngineType {
     pub const fn supports_cache_from_type(&self) -> bool {
         matches!(self, Self::Docker | Self::Nerdctl)
     }
+
+    /// The oldest version with the features used by cross, such as `cp -a`,
+    /// `--userns` and volume filters.
+    #[must_use]
+    pub fn minimum_version(&self) -> Option<semver::Version> {
+        match self {
+            Self::Docker => Some(semver::Version::new(20, 10, 0)),
+            Self::Podman | Self::PodmanRemote => Some(semver::Version::new(3, 4, 0)),
+            Self::Nerdctl | Self::Other => None,
+        }
+    }
+
+    /// The commands cross requires an engine to support.
+    pub const REQUIRED_COMMANDS: &'static [&'static str] = &["run", "exec", "cp", "volume"];
+
+    /// The name of the engine, for messages.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+            Self::PodmanRemote => "podman-remote",
+            Self::Nerdctl => "nerdctl",
+            Self::Other => "the container engine",
+        }
+    }
+}
+
+impl std::str::FromStr for EngineType {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "docker" => Ok(Self::Docker),
+            "podman" => Ok(Self::Podman),
+            "podman-remote" => Ok(Self::PodmanRemote),
+            "nerdctl" => Ok(Self::Nerdctl),
+            "other" => Ok(Self::Other),
+            _ => eyre::bail!(
+                "unknown engine type `{s}`, expected docker, podman, podman-remote, nerdctl or other"
+            ),
+        }
+    }
+}
+
+/// Features of the engine that change how containers are run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// A seccomp profile can be provided as a path.
+    pub seccomp_profile: bool,
+    /// The path to the seccomp profile must be a POSIX path, even on windows.
+    pub posix_seccomp_path: bool,
+    /// The user namespace can be set with `--userns`.
+    pub userns: bool,
+    /// The engine is rootless, so containers shouldn't run as the current user.
+    pub rootless: bool,
+    /// The host uses cgroup v2.
+    pub cgroup_v2: bool,
+    /// The engine is rootless podman, which maps the host user into the
+    /// container with `--userns keep-id`.
+    pub keep_id: bool,
+    /// `cp -a` preserves ownership and permissions.
+    pub cp_archive: bool,
+}
+
+impl Capabilities {
+    /// The capabilities for the engine type, without probing the engine.
+    ///
+    /// Rootless engines can be overridden with `CROSS_ROOTLESS_CONTAINER_ENGINE`.
+    #[must_use]
+    pub fn new(kind: EngineType) -> Self {
+        // by default, docker runs as root and other engines are rootless.
+        let rootless = Self::rootless_override().unwrap_or(kind != EngineType::Docker);
+        Capabilities {
+            // docker on windows fails due to a bug in reading the profile
+            // https://github.com/docker/for-win/issues/12760
+            seccomp_profile: !(kind.is_docker() && cfg!(target_os = "windows")),
+            // podman weirdly expects a WSL path here, and fails otherwise
+            posix_seccomp_path: kind.is_podman() && cfg!(target_os = "windows"),
+            userns: kind != EngineType::Nerdctl,
+            rootless,
+            cgroup_v2: false,
+            keep_id: false,
+            cp_archive: kind != EngineType::Nerdctl,
+        }
+    }
+
+    /// If the engine is rootless from `CROSS_ROOTLESS_CONTAINER_ENGINE`, unless it's `auto`.
+    fn rootless_override() -> Option<bool> {
+        env::var("CROSS_ROOTLESS_CONTAINER_ENGINE")
+            .ok()
+            .and_then(|s| match s.as_ref() {
+                "auto" => None,
+                b => Some(bool_from_envvar(b)),
+            })
+    }
+
+    /// Update the capabilities from the output of `info`, formatted with [`get_capabilities`].
+    fn update_from_info(&mut self, kind: EngineType, info: &str) {
+        let mut fields = info.trim().split(",,,");
+        self.cgroup_v2 = matches!(fields.next(), Some("2" | "v2"));
+        match kind {
+            // rootless docker maps the host user to root in the container,
+            // so running as the host user breaks the permissions.
+            EngineType::Docker if Self::rootless_override().is_none() => {
+                self.rootless = fields.next().unwrap_or_default().contains("name=rootless");
+            }
+            EngineType::Podman | EngineType::PodmanRemote => {
+                self.keep_id = fields.next() == Some("true");
+            }
+            _ => (),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -66,35 +206,163 @@ pub struct Engine {
     pub arch: Option<Architecture>,
     pub os: Option<ContainerOs>,
     pub is_remote: bool,
+    pub context: Option<String>,
+    /// The daemon host found by cross, such as the socket of colima, which
+    /// is set on each command instead of in the environment of cross.
+    pub host: Option<String>,
+    pub api: Option<ApiClient>,
+    pub version: Option<semver::Version>,
+    pub capabilities: Capabilities,
+    pub windows_backend: Option<WindowsBackend>,
+    /// The retry policy for idempotent engine commands.
+    pub retry: RetryPolicy,
 }
 
 impl Engine {
     pub const CROSS_CONTAINER_ENGINE_NO_BUILDKIT_ENV: &'static str =
         "CROSS_CONTAINER_ENGINE_NO_BUILDKIT";
+    pub const CROSS_CONTAINER_CONTEXT_ENV: &'static str = "CROSS_CONTAINER_CONTEXT";
+    pub const CROSS_PODMAN_CONNECTION_ENV: &'static str = "CROSS_PODMAN_CONNECTION";
+    pub const CROSS_PODMAN_MACHINE_START_ENV: &'static str = "CROSS_PODMAN_MACHINE_START";
+    /// Find the container engine, and probe it.
+    ///
+    /// `engine` is the engine from the configuration, which is used unless
+    /// `CROSS_CONTAINER_ENGINE` is set.
     pub fn new(
         in_docker: Option<bool>,
         is_remote: Option<bool>,
+        context: Option<String>,
+        engine: Option<&str>,
         msg_info: &mut MessageInfo,
     ) -> Result<Engine> {
         #[allow(clippy::map_err_ignore)]
-        let path = get_container_engine()
-            .map_err(|_| eyre::eyre!("no container engine found"))
-            .with_suggestion(|| "is docker or podman installed?")?;
-        Self::from_path(path, in_docker, is_remote, msg_info)
+        let path = match engine {
+            Some(ce) if env::var_os("CROSS_CONTAINER_ENGINE").is_none() => which::which(ce)
+                .map_err(|_| eyre::eyre!("container engine `{ce}` not found"))
+                .with_suggestion(|| "is it installed? it was set with `engine` in `Cross.toml`.")?,
+            _ => get_container_engine()
+                .map_err(|_| eyre::eyre!("no container engine found"))
+                .with_suggestion(|| "is docker or podman installed?")?,
+        };
+        Self::from_path(path, in_docker, is_remote, context, msg_info)
+    }
+
+    /// Find the container engine of a remote builder, and probe it.
+    ///
+    /// The engine, context and host of the profile have precedence over
+    /// the environment and `Cross.toml`.
+    pub fn from_profile(profile: &RemoteProfile, msg_info: &mut MessageInfo) -> Result<Engine> {
+        #[allow(clippy::map_err_ignore)]
+        let path = match &profile.engine {
+            Some(ce) => which::which(ce)
+                .map_err(|_| eyre::eyre!("container engine `{ce}` not found"))
+                .with_suggestion(|| "is it installed? it was set in the remote builder.")?,
+            None => get_container_engine()
+                .map_err(|_| eyre::eyre!("no container engine found"))
+                .with_suggestion(|| "is docker or podman installed?")?,
+        };
+        Self::probe(
+            path,
+            None,
+            Some(true),
+            profile.context.clone(),
+            profile.host.clone(),
+            msg_info,
+        )
     }
 
     pub fn from_path(
         path: PathBuf,
         in_docker: Option<bool>,
         is_remote: Option<bool>,
+        context: Option<String>,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Engine> {
+        let kind = get_engine_type(&path, msg_info)?;
+        let context = context.or_else(|| Self::context(kind));
+        Self::probe(path, in_docker, is_remote, context, None, msg_info)
+    }
+
+    /// Probe the engine at `path`, connecting to `host` if provided.
+    fn probe(
+        path: PathBuf,
+        in_docker: Option<bool>,
+        is_remote: Option<bool>,
+        context: Option<String>,
+        mut host: Option<String>,
         msg_info: &mut MessageInfo,
     ) -> Result<Engine> {
         let in_docker = match in_docker {
             Some(v) => v,
             None => Self::in_docker(msg_info)?,
         };
-        let (kind, arch, os) = get_engine_info(&path, msg_info)?;
-        let is_remote = is_remote.unwrap_or_else(Self::is_remote);
+        let kind = get_engine_type(&path, msg_info)?;
+        if kind.is_docker() && context.is_none() && host.is_none() {
+            if let Some(socket) = home::home_dir().and_then(|home| find_docker_socket(&home)) {
+                msg_info.note(format_args!(
+                    "using the docker socket at {socket:?}, set `DOCKER_HOST` to override it."
+                ))?;
+                host = Some(format!("unix://{}", socket.to_utf8()?));
+            }
+        }
+        // podman runs containers in a VM on macOS and windows, so every
+        // command fails if it's stopped, unless a connection is selected.
+        if cfg!(any(target_os = "macos", target_os = "windows"))
+            && kind.is_podman()
+            && context.is_none()
+            && host.is_none()
+            && daemon_host(kind).is_none()
+        {
+            ensure_podman_machine(&path, msg_info)?;
+        }
+        let (arch, os) =
+            get_engine_info(&path, kind, context.as_deref(), host.as_deref(), msg_info)?;
+        let version =
+            get_engine_version(&path, kind, context.as_deref(), host.as_deref(), msg_info);
+        if let (Some(version), Some(minimum)) = (&version, kind.minimum_version()) {
+            if *version < minimum {
+                return Err(eyre::eyre!(
+                    "{} {version} is too old, cross requires {minimum} or later",
+                    kind.name()
+                ))
+                .with_suggestion(|| format!("upgrade {} to a newer version.", kind.name()));
+            }
+        }
+        let is_remote = match is_remote {
+            Some(v) => v,
+            // an explicit `CROSS_REMOTE` always has precedence over detection
+            None if env::var("CROSS_REMOTE").is_ok() => Self::is_remote(),
+            None => match context {
+                Some(ref context) if context_is_remote(&path, kind, context, msg_info)? => {
+                    msg_info.note(format_args!(
+                        "context `{context}` uses a remote daemon, copying data into volumes."
+                    ))?;
+                    true
+                }
+                Some(_) => false,
+                None => match daemon_host(kind) {
+                    Some((var, host)) if host_is_remote(&host) => {
+                        msg_info.note(format_args!(
+                            "`{var}={host}` uses a remote daemon, copying data into volumes."
+                        ))?;
+                        true
+                    }
+                    _ => false,
+                },
+            },
+        };
+        let mut capabilities =
+            get_capabilities(&path, kind, context.as_deref(), host.as_deref(), msg_info);
+        let windows_backend =
+            get_windows_backend(&path, kind, context.as_deref(), host.as_deref(), msg_info);
+        if let Some(backend) = windows_backend {
+            msg_info.debug(format_args!("using the {backend:?} backend on windows"))?;
+            capabilities.posix_seccomp_path = backend.uses_wsl_paths();
+        }
+        let api = match kind {
+            EngineType::Podman if is_remote => None,
+            _ => ApiClient::discover(kind, context.as_deref(), host.as_deref()),
+        };
         Ok(Engine {
             path,
             kind,
@@ -102,9 +370,27 @@ impl Engine {
             arch,
             os,
             is_remote,
+            context,
+            host,
+            api,
+            version,
+            capabilities,
+            windows_backend,
+            retry: RetryPolicy::from_env()?,
         })
     }
 
+    /// Convert a host path to the form the engine expects for mounts.
+    ///
+    /// These are Windows paths, unless the engine runs in a WSL2 distribution
+    /// without Docker Desktop to translate them.
+    pub fn host_path(&self, path: &Path) -> Result<String> {
+        match self.windows_backend {
+            Some(backend) if backend.uses_wsl_paths() => path.as_posix_absolute(),
+            _ => path.to_utf8().map(ToOwned::to_owned),
+        }
+    }
+
     #[must_use]
     pub fn needs_remote(&self) -> bool {
         self.is_remote && self.kind == EngineType::Podman
@@ -135,6 +421,20 @@ impl Engine {
             .unwrap_or_default()
     }
 
+    /// The docker context or podman connection to use, if set from the environment.
+    ///
+    /// For podman, `CROSS_PODMAN_CONNECTION` has precedence over `CROSS_CONTAINER_CONTEXT`.
+    #[must_use]
+    pub fn context(kind: EngineType) -> Option<String> {
+        let var = |name| env::var(name).ok().filter(|s: &String| !s.is_empty());
+        let connection = if kind.is_podman() {
+            var(Self::CROSS_PODMAN_CONNECTION_ENV)
+        } else {
+            None
+        };
+        connection.or_else(|| var(Self::CROSS_CONTAINER_CONTEXT_ENV))
+    }
+
     #[must_use]
     pub fn has_buildkit() -> bool {
         !env::var(Self::CROSS_CONTAINER_ENGINE_NO_BUILDKIT_ENV)
@@ -145,32 +445,90 @@ impl Engine {
 
 // determine if the container engine is docker. this fixes issues with
 // any aliases (#530), and doesn't fail if an executable suffix exists.
-fn get_engine_info(
-    ce: &Path,
-    msg_info: &mut MessageInfo,
-) -> Result<(EngineType, Option<Architecture>, Option<ContainerOs>)> {
+// `CROSS_CONTAINER_ENGINE_TYPE` overrides the detection.
+fn get_engine_type(ce: &Path, msg_info: &mut MessageInfo) -> Result<EngineType> {
+    if let Ok(kind) = env::var("CROSS_CONTAINER_ENGINE_TYPE") {
+        return kind
+            .parse()
+            .wrap_err("invalid value for `CROSS_CONTAINER_ENGINE_TYPE`");
+    }
+
     let stdout_help = Command::new(ce)
         .arg("--help")
         .run_and_get_stdout(msg_info)?
         .to_lowercase();
+    let kind = parse_engine_type(&stdout_help);
+    if stdout_help.contains("finch") {
+        msg_info.note("using finch, which is treated as nerdctl.")?;
+    } else if kind == EngineType::Other {
+        let missing = missing_commands(&stdout_help);
+        if !missing.is_empty() {
+            return Err(eyre::eyre!(
+                "the container engine {ce:?} doesn't support the {} commands cross requires",
+                missing.join(", ")
+            ))
+            .with_suggestion(|| "use docker, podman or nerdctl instead.");
+        }
+        msg_info.warn(format_args!(
+            "could not determine the type of the container engine {ce:?}, assuming it's compatible with docker.\n > Set `CROSS_CONTAINER_ENGINE_TYPE` to docker, podman or nerdctl to use its behavior, or to other to silence this warning."
+        ))?;
+    }
+
+    Ok(kind)
+}
 
-    let kind = if stdout_help.contains("podman-remote") {
+fn parse_engine_type(stdout_help: &str) -> EngineType {
+    if stdout_help.contains("podman-remote") {
         EngineType::PodmanRemote
     } else if stdout_help.contains("podman") {
         EngineType::Podman
-    } else if stdout_help.contains("nerdctl") {
+    } else if stdout_help.contains("nerdctl") || stdout_help.contains("finch") {
+        // finch runs nerdctl in a VM, and has the same CLI.
         EngineType::Nerdctl
     } else if stdout_help.contains("docker") && !stdout_help.contains("emulate") {
         EngineType::Docker
     } else {
         EngineType::Other
-    };
+    }
+}
+
+/// The required commands that aren't listed in the help of an engine.
+fn missing_commands(stdout_help: &str) -> Vec<&'static str> {
+    EngineType::REQUIRED_COMMANDS
+        .iter()
+        .filter(|&&command| {
+            !stdout_help
+                .lines()
+                .any(|line| line.split_whitespace().next() == Some(command))
+        })
+        .copied()
+        .collect()
+}
+
+/// Creates a command for the engine, selecting the context or connection if provided.
+fn engine_command(
+    ce: &Path,
+    kind: EngineType,
+    context: Option<&str>,
+    host: Option<&str>,
+) -> Command {
+    let mut command = Command::new(ce);
+    command.add_context(kind, context).add_host(kind, host);
+    command
+}
 
+fn get_engine_info(
+    ce: &Path,
+    kind: EngineType,
+    context: Option<&str>,
+    host: Option<&str>,
+    msg_info: &mut MessageInfo,
+) -> Result<(Option<Architecture>, Option<ContainerOs>)> {
     // this can fail: podman can give partial output
     //   linux,,,Error: template: version:1:15: executing "version" at <.Arch>:
     //   can't evaluate field Arch in type *define.Version
     let os_arch_server = engine_info(
-        ce,
+        engine_command(ce, kind, context, host),
         &["version", "-f", "{{ .Server.Os }},,,{{ .Server.Arch }}"],
         ",,,",
         msg_info,
@@ -179,10 +537,11 @@ fn get_engine_info(
     let (os_arch_other, os_arch_server_result) = match os_arch_server {
         Ok(Some(os_arch)) => (Ok(Some(os_arch)), None),
         result => {
+            let command = engine_command(ce, kind, context, host);
             if kind.is_podman() {
-                (get_podman_info(ce, msg_info), result.err())
+                (get_podman_info(command, msg_info), result.err())
             } else {
-                (get_custom_info(ce, msg_info), result.err())
+                (get_custom_info(command, msg_info), result.err())
             }
         }
     };
@@ -194,7 +553,284 @@ fn get_engine_info(
     };
 
     let (os, arch) = os_arch.map_or(<_>::default(), |(os, arch)| (Some(os), Some(arch)));
-    Ok((kind, arch, os))
+    Ok((arch, os))
+}
+
+/// Get the version of the engine, or of the daemon for docker.
+///
+/// This never fails: the version is only used to reject engines that are
+/// known to be too old.
+fn get_engine_version(
+    ce: &Path,
+    kind: EngineType,
+    context: Option<&str>,
+    host: Option<&str>,
+    msg_info: &mut MessageInfo,
+) -> Option<semver::Version> {
+    let format = match kind {
+        EngineType::Docker => "{{ .Server.Version }}",
+        EngineType::Podman | EngineType::PodmanRemote => "{{ .Client.Version }}",
+        EngineType::Nerdctl | EngineType::Other => return None,
+    };
+    let stdout = engine_command(ce, kind, context, host)
+        .args(["version", "-f", format])
+        .run_and_get_stdout(msg_info)
+        .ok()?;
+    parse_engine_version(&stdout)
+}
+
+/// Detect how the engine runs on Windows. Returns `None` on other hosts.
+fn get_windows_backend(
+    ce: &Path,
+    kind: EngineType,
+    context: Option<&str>,
+    host: Option<&str>,
+    msg_info: &mut MessageInfo,
+) -> Option<WindowsBackend> {
+    if !cfg!(target_os = "windows") {
+        return None;
+    }
+    match kind {
+        // podman machine always runs in a WSL2 distribution on windows
+        EngineType::Podman | EngineType::PodmanRemote => return Some(WindowsBackend::Wsl),
+        EngineType::Docker => (),
+        EngineType::Nerdctl | EngineType::Other => return None,
+    }
+    // docker desktop is only reached over a named pipe, while a daemon
+    // in a WSL2 distribution is reached over a socket.
+    if context.is_none() {
+        if let Some(host) = host
+            .map(ToOwned::to_owned)
+            .or_else(|| env::var("DOCKER_HOST").ok())
+        {
+            if !host.starts_with("npipe://") {
+                return Some(WindowsBackend::Wsl);
+            }
+        }
+    }
+    let stdout = engine_command(ce, kind, context, host)
+        .args([
+            "info",
+            "-f",
+            "{{ .OperatingSystem }},,,{{ .KernelVersion }}",
+        ])
+        .run_and_get_stdout(msg_info)
+        .ok()?;
+    parse_windows_backend(&stdout)
+}
+
+/// Parse the backend from the operating system and kernel version of the daemon.
+fn parse_windows_backend(info: &str) -> Option<WindowsBackend> {
+    let (os, kernel) = info.trim().split_once(",,,")?;
+    let is_wsl = kernel.to_ascii_lowercase().contains("microsoft");
+    match (os.contains("Docker Desktop"), is_wsl) {
+        (true, true) => Some(WindowsBackend::DockerDesktopWsl),
+        (true, false) => Some(WindowsBackend::HyperV),
+        (false, true) => Some(WindowsBackend::Wsl),
+        (false, false) => None,
+    }
+}
+
+/// Probe the engine for the capabilities that depend on its configuration.
+///
+/// Any capability that can't be probed keeps its default.
+fn get_capabilities(
+    ce: &Path,
+    kind: EngineType,
+    context: Option<&str>,
+    host: Option<&str>,
+    msg_info: &mut MessageInfo,
+) -> Capabilities {
+    let mut capabilities = Capabilities::new(kind);
+    let format = match kind {
+        EngineType::Docker => "{{ .CgroupVersion }},,,{{ json .SecurityOptions }}",
+        EngineType::Podman | EngineType::PodmanRemote => {
+            "{{ .Host.CgroupsVersion }},,,{{ .Host.Security.Rootless }}"
+        }
+        EngineType::Nerdctl | EngineType::Other => return capabilities,
+    };
+    let stdout = match engine_command(ce, kind, context, host)
+        .args(["info", "-f", format])
+        .run_and_get_stdout(msg_info)
+    {
+        Ok(stdout) => stdout,
+        Err(_) => return capabilities,
+    };
+
+    capabilities.update_from_info(kind, &stdout);
+    capabilities
+}
+
+/// Parse versions like `20.10.17+azure-1`, `17.06.0-ce` or `4.4.0-dev`,
+/// which aren't all valid semver.
+fn parse_engine_version(version: &str) -> Option<semver::Version> {
+    let version = version.trim();
+    let end = version
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(version.len());
+    let mut parts = version[..end].split('.').map(str::parse::<u64>);
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+
+    Some(semver::Version::new(major, minor, patch))
+}
+
+/// Determine if the daemon for a docker context or podman connection is remote.
+///
+/// Docker contexts using a local socket share the host filesystem, so bind
+/// mounts work as expected. Podman connections always go over SSH.
+fn context_is_remote(
+    ce: &Path,
+    kind: EngineType,
+    context: &str,
+    msg_info: &mut MessageInfo,
+) -> Result<bool> {
+    if kind.is_podman() {
+        return Ok(true);
+    }
+
+    let host = Command::new(ce)
+        .args([
+            "context",
+            "inspect",
+            "--format",
+            "{{ .Endpoints.docker.Host }}",
+        ])
+        .arg(context)
+        .run_and_get_stdout(msg_info)
+        .wrap_err_with(|| format!("could not inspect context `{context}`"))?;
+
+    Ok(host_is_remote(host.trim()))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PodmanMachine {
+    name: String,
+    #[serde(default)]
+    default: bool,
+    #[serde(default)]
+    running: bool,
+    #[serde(default)]
+    starting: bool,
+}
+
+/// Get the machine to start from the output of `podman machine list`,
+/// which is the default machine, if none are running or starting.
+fn stopped_podman_machine(list: &str) -> Result<Option<String>> {
+    if list.trim().is_empty() {
+        return Ok(None);
+    }
+    let machines: Vec<PodmanMachine> =
+        serde_json::from_str(list).wrap_err("could not parse the podman machines")?;
+    if machines.iter().any(|m| m.running || m.starting) {
+        return Ok(None);
+    }
+    let machine = machines
+        .iter()
+        .find(|m| m.default)
+        .or_else(|| machines.first());
+
+    // older versions mark the default machine with a `*`.
+    Ok(machine.map(|m| m.name.trim_end_matches('*').to_owned()))
+}
+
+/// Check a podman machine is running, and start it if
+/// `CROSS_PODMAN_MACHINE_START` is set.
+fn ensure_podman_machine(ce: &Path, msg_info: &mut MessageInfo) -> Result<()> {
+    let output = Command::new(ce)
+        .args(["machine", "list", "--format", "json"])
+        .run_and_get_output(msg_info)?;
+    if !output.status.success() {
+        // the podman version may not support machines.
+        return Ok(());
+    }
+    let machine = match stopped_podman_machine(&output.stdout()?)? {
+        Some(machine) => machine,
+        None => return Ok(()),
+    };
+
+    let start = env::var(Engine::CROSS_PODMAN_MACHINE_START_ENV)
+        .map(|s| bool_from_envvar(&s))
+        .unwrap_or_default();
+    if !start {
+        return Err(eyre::eyre!("the podman machine `{machine}` is not running")).with_suggestion(
+            || {
+                format!(
+                    "start it with `podman machine start {machine}`, or set `{}=1` to start it automatically.",
+                    Engine::CROSS_PODMAN_MACHINE_START_ENV
+                )
+            },
+        );
+    }
+    msg_info.note(format_args!("starting the podman machine `{machine}`."))?;
+    Command::new(ce)
+        .args(["machine", "start", &machine])
+        .run(msg_info, false)
+}
+
+/// Get the daemon host from the environment, if set.
+///
+/// Bind mounts from the host filesystem don't work with daemons over
+/// SSH or TCP, such as `DOCKER_HOST=ssh://user@host`.
+/// The variable the engine reads the daemon host from.
+fn host_var(kind: EngineType) -> &'static str {
+    if kind.is_podman() {
+        "CONTAINER_HOST"
+    } else {
+        "DOCKER_HOST"
+    }
+}
+
+fn daemon_host(kind: EngineType) -> Option<(&'static str, String)> {
+    let var = host_var(kind);
+    env::var(var).ok().map(|host| (var, host))
+}
+
+/// Find the socket of a docker daemon in a VM, such as colima, lima or rancher desktop.
+///
+/// These don't use `/var/run/docker.sock`, so the docker CLI can't find them
+/// unless `DOCKER_HOST` or a docker context is set.
+fn find_docker_socket(home: &Path) -> Option<PathBuf> {
+    if !cfg!(unix)
+        || env::var_os("DOCKER_HOST").is_some()
+        || env::var_os("DOCKER_CONTEXT").is_some()
+        || Path::new("/var/run/docker.sock").exists()
+    {
+        return None;
+    }
+    find_home_docker_socket(home)
+}
+
+/// Find the socket of a docker daemon in a VM in the home directory, unless
+/// the docker CLI selected another context.
+fn find_home_docker_socket(home: &Path) -> Option<PathBuf> {
+    // a docker context selected with `docker context use` knows the socket.
+    let current_context = file::read(home.join(".docker/config.json"))
+        .ok()
+        .and_then(|config| serde_json::from_str::<serde_json::Value>(&config).ok())
+        .and_then(|config| config["currentContext"].as_str().map(ToOwned::to_owned));
+    if !matches!(current_context.as_deref(), None | Some("default")) {
+        return None;
+    }
+
+    [
+        ".colima/default/docker.sock",
+        ".colima/docker.sock",
+        ".lima/docker/sock/docker.sock",
+        ".lima/default/sock/docker.sock",
+        ".rd/docker.sock",
+        ".docker/run/docker.sock",
+    ]
+    .iter()
+    .map(|socket| home.join(socket))
+    .find(|socket| socket.exists())
+}
+
+/// Returns if a daemon host, such as `unix:///var/run/docker.sock`, is remote.
+pub(crate) fn host_is_remote(host: &str) -> bool {
+    !(host.is_empty() || host.starts_with("unix://") || host.starts_with("npipe://"))
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -218,12 +854,11 @@ impl EngineInfoError {
 
 /// Get engine info
 fn engine_info(
-    ce: &Path,
+    mut cmd: Command,
     args: &[&str],
     sep: &str,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<(ContainerOs, Architecture)>, EngineInfoError> {
-    let mut cmd = Command::new(ce);
     cmd.args(args);
     let out = cmd
         .run_and_get_output(msg_info)
@@ -241,24 +876,48 @@ fn engine_info(
 }
 
 fn get_podman_info(
-    ce: &Path,
+    cmd: Command,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<(ContainerOs, Architecture)>, EngineInfoError> {
-    engine_info(ce, &["info", "-f", "{{ .Version.OsArch }}"], "/", msg_info)
+    engine_info(cmd, &["info", "-f", "{{ .Version.OsArch }}"], "/", msg_info)
 }
 
 fn get_custom_info(
-    ce: &Path,
+    cmd: Command,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<(ContainerOs, Architecture)>, EngineInfoError> {
     engine_info(
-        ce,
+        cmd,
         &["version", "-f", "{{ .Client.Os }},,,{{ .Client.Arch }}"],
         ",,,",
         msg_info,
     )
 }
 
+pub(crate) trait EngineCommandExt {
+    fn add_context(&mut self, kind: EngineType, context: Option<&str>) -> &mut Self;
+    /// Connect to the daemon at `host`, which was found by cross, rather
+    /// than set in the environment.
+    fn add_host(&mut self, kind: EngineType, host: Option<&str>) -> &mut Self;
+}
+
+impl EngineCommandExt for Command {
+    fn add_host(&mut self, kind: EngineType, host: Option<&str>) -> &mut Self {
+        match host {
+            Some(host) => self.env(host_var(kind), host),
+            None => self,
+        }
+    }
+
+    fn add_context(&mut self, kind: EngineType, context: Option<&str>) -> &mut Self {
+        match context {
+            Some(context) if kind.is_podman() => self.args(["--connection", context]),
+            Some(context) => self.args(["--context", context]),
+            None => self,
+        }
+    }
+}
+
 pub fn get_container_engine() -> Result<PathBuf, which::Error> {
     if let Ok(ce) = env::var("CROSS_CONTAINER_ENGINE") {
         which::which(ce)
@@ -266,3 +925,157 @@ pub fn get_container_engine() -> Result<PathBuf, which::Error> {
         which::which(DOCKER).or_else(|_| which::which(PODMAN))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn podman_machines() -> Result<()> {
+        let stopped = r#"[
+            {"Name": "dev", "Default": false, "Running": false, "Starting": false},
+            {"Name": "podman-machine-default*", "Default": true, "Running": false, "Starting": false}
+        ]"#;
+        assert_eq!(
+            stopped_podman_machine(stopped)?,
+            Some("podman-machine-default".to_owned())
+        );
+        let running = r#"[
+            {"Name": "dev", "Default": false, "Running": true},
+            {"Name": "podman-machine-default", "Default": true, "Running": false}
+        ]"#;
+        assert_eq!(stopped_podman_machine(running)?, None);
+        assert_eq!(
+            stopped_podman_machine(r#"[{"Name": "dev", "Running": false}]"#)?,
+            Some("dev".to_owned())
+        );
+        assert_eq!(stopped_podman_machine("[]")?, None);
+        assert_eq!(stopped_podman_machine("")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn engine_types() {
+        let docker = "usage:  docker [options] command\n\ncommon commands:\n  run  create and run a new container";
+        assert_eq!(parse_engine_type(docker), EngineType::Docker);
+        assert_eq!(
+            parse_engine_type(
+                "manage pods, containers and images\n\nusage:\n  podman [options] [command]"
+            ),
+            EngineType::Podman
+        );
+        assert_eq!(
+            parse_engine_type("finch is an open source client for container development"),
+            EngineType::Nerdctl
+        );
+        assert_eq!(
+            parse_engine_type("usage: ctr [global options] command"),
+            EngineType::Other
+        );
+        assert_eq!(
+            "podman-remote".parse::<EngineType>().ok(),
+            Some(EngineType::PodmanRemote)
+        );
+        assert!("finch".parse::<EngineType>().is_err());
+    }
+
+    #[test]
+    fn engine_missing_commands() {
+        let help = "commands:\n  run       run a command\n  exec      run a command in a container\n  cp        copy files\n  volume    manage volumes";
+        assert!(missing_commands(help).is_empty());
+        let help = "commands:\n  run       run a command\n  images    list images";
+        assert_eq!(missing_commands(help), vec!["exec", "cp", "volume"]);
+    }
+
+    #[test]
+    fn remote_hosts() {
+        assert!(!host_is_remote(""));
+        assert!(!host_is_remote("unix:///var/run/docker.sock"));
+        assert!(!host_is_remote("npipe:////./pipe/docker_engine"));
+        assert!(host_is_remote("ssh://user@builder"));
+        assert!(host_is_remote("tcp://10.0.0.2:2376"));
+    }
+
+    #[test]
+    fn engine_versions() {
+        let version = |s| parse_engine_version(s).map(|v| v.to_string());
+        assert_eq!(version("20.10.17+azure-1\n"), Some("20.10.17".to_owned()));
+        assert_eq!(version("17.06.0-ce"), Some("17.6.0".to_owned()));
+        assert_eq!(version("4.4.0-dev"), Some("4.4.0".to_owned()));
+        assert_eq!(version("24.0"), Some("24.0.0".to_owned()));
+        assert_eq!(version(""), None);
+        assert_eq!(version("<no value>"), None);
+        assert!(
+            parse_engine_version("1.13.1").unwrap() < EngineType::Docker.minimum_version().unwrap()
+        );
+    }
+
+    #[test]
+    fn windows_backends() {
+        assert_eq!(
+            parse_windows_backend("Docker Desktop,,,5.10.102.1-microsoft-standard-WSL2\n"),
+            Some(WindowsBackend::DockerDesktopWsl)
+        );
+        assert_eq!(
+            parse_windows_backend("Docker Desktop,,,5.10.104-linuxkit"),
+            Some(WindowsBackend::HyperV)
+        );
+        assert_eq!(
+            parse_windows_backend("Ubuntu 22.04.1 LTS,,,5.15.79.1-microsoft-standard-WSL2"),
+            Some(WindowsBackend::Wsl)
+        );
+        assert_eq!(
+            parse_windows_backend("Ubuntu 22.04.1 LTS,,,5.15.0-56-generic"),
+            None
+        );
+        assert!(WindowsBackend::Wsl.uses_wsl_paths());
+        assert!(!WindowsBackend::DockerDesktopWsl.uses_wsl_paths());
+    }
+
+    #[test]
+    fn capabilities_from_info() {
+        if env::var_os("CROSS_ROOTLESS_CONTAINER_ENGINE").is_some() {
+            return;
+        }
+
+        let mut docker = Capabilities::new(EngineType::Docker);
+        docker.update_from_info(
+            EngineType::Docker,
+            "2,,,[\"name=seccomp,profile=default\",\"name=rootless\",\"name=cgroupns\"]\n",
+        );
+        assert!(docker.cgroup_v2);
+        assert!(docker.rootless);
+
+        docker.update_from_info(EngineType::Docker, "1,,,[\"name=seccomp,profile=default\"]");
+        assert!(!docker.cgroup_v2);
+        assert!(!docker.rootless);
+
+        let mut podman = Capabilities::new(EngineType::Podman);
+        podman.update_from_info(EngineType::Podman, "v2,,,true");
+        assert!(podman.cgroup_v2);
+        assert!(podman.keep_id);
+        assert!(podman.rootless);
+    }
+
+    #[test]
+    fn docker_sockets() -> Result<()> {
+        let home = tempfile::tempdir()?;
+        let home = home.path();
+        assert_eq!(find_home_docker_socket(home), None);
+
+        let socket = home.join(".colima/default/docker.sock");
+        file::create_dir_all(socket.parent().expect("socket should have a parent"))?;
+        std::fs::write(&socket, b"")?;
+        assert_eq!(find_home_docker_socket(home), Some(socket));
+
+        file::create_dir_all(home.join(".docker"))?;
+        std::fs::write(
+            home.join(".docker/config.json"),
+            r#"{"currentContext": "remote"}"#,
+        )?;
+        assert_eq!(find_home_docker_socket(home), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! The environment and mounts of the build container, for tools that wrap
+//! `cross`, such as Nix-based CI.
+//!
+//! Only the variables and mounts derived from the directories of the
+//! toolchain and the workspace are described, not the ones passed through
+//! from the host or configured in `Cross.toml`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use super::shared::DockerPaths;
+use crate::errors::*;
+use crate::file::{PathExt, ToUtf8};
+
+/// A directory mounted in the container.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ContainerMount {
+    pub host: String,
+    pub container: String,
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ContainerEnvironment {
+    pub env: BTreeMap<String, String>,
+    pub mounts: Vec<ContainerMount>,
+}
+
+impl ContainerEnvironment {
+    pub fn new(paths: &DockerPaths, target: &str) -> Result<ContainerEnvironment> {
+        let toolchain_dirs = paths.directories.toolchain_directories();
+        let package_dirs = paths.directories.package_directories();
+        let mut environment = ContainerEnvironment::default();
+        for (key, value) in [
+            ("PKG_CONFIG_ALLOW_CROSS", "1"),
+            ("XARGO_HOME", toolchain_dirs.xargo_mount_path()),
+            ("CARGO_HOME", toolchain_dirs.cargo_mount_path()),
+            ("CROSS_RUST_SYSROOT", toolchain_dirs.sysroot_mount_path()),
+            ("CARGO_TARGET_DIR", "/target"),
+            (crate::test_env::TARGET_ENV, target),
+        ] {
+            environment.env.insert(key.to_owned(), value.to_owned());
+        }
+
+        let mut mount = |host: &str, container: &str, read_only| {
+            environment.mounts.push(ContainerMount {
+                host: host.to_owned(),
+                container: container.to_owned(),
+                read_only,
+            });
+        };
+        mount(
+            toolchain_dirs.xargo_host_path()?,
+            toolchain_dirs.xargo_mount_path(),
+            false,
+        );
+        mount(
+            toolchain_dirs.cargo_host_path()?,
+            toolchain_dirs.cargo_mount_path(),
+            false,
+        );
+        mount(
+            package_dirs.host_root().to_utf8()?,
+            package_dirs.mount_root(),
+            package_dirs.read_only(),
+        );
+        mount(
+            toolchain_dirs.get_sysroot().to_utf8()?,
+            toolchain_dirs.sysroot_mount_path(),
+            true,
+        );
+        mount(package_dirs.target().to_utf8()?, "/target", false);
+        if let Some(nix_store) = toolchain_dirs.nix_store() {
+            let path = nix_store.as_posix_absolute()?;
+            mount(
+                nix_store.to_utf8()?,
+                &path,
+                toolchain_dirs.nix_store_read_only(),
+            );
+            environment.env.insert("NIX_STORE".to_owned(), path);
+        }
+
+        Ok(environment)
+    }
+
+    /// The environment as a Nix attribute set.
+    pub fn to_nix(&self) -> String {
+        let mut nix = "{\n  env = {\n".to_owned();
+        for (key, value) in &self.env {
+            writeln!(nix, "    {} = {};", nix_string(key), nix_string(value))
+                .expect("writing to a string should not fail");
+        }
+        nix.push_str("  };\n  mounts = [\n");
+        for mount in &self.mounts {
+            writeln!(
+                nix,
+                "    {{ host = {}; container = {}; readOnly = {}; }}",
+                nix_string(&mount.host),
+                nix_string(&mount.container),
+                mount.read_only
+            )
+            .expect("writing to a string should not fail");
+        }
+        nix.push_str("  ];\n}\n");
+        nix
+    }
+}
+
+/// A Nix string literal, escaping interpolation.
+fn nix_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '$' if chars.peek() == Some(&'{') => quoted.push_str("\\$"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nix_expression() {
+        assert_eq!(nix_string("a\"b\\c${d}$e"), r#""a\"b\\c\${d}$e""#);
+
+        let mut environment = ContainerEnvironment::default();
+        environment
+            .env
+            .insert("CARGO_HOME".to_owned(), "/home/user/.cargo".to_owned());
+        environment.mounts.push(ContainerMount {
+            host: "/nix/store".to_owned(),
+            container: "/nix/store".to_owned(),
+            read_only: true,
+        });
+        assert_eq!(
+            environment.to_nix(),
+            r#"{
+  env = {
+    "CARGO_HOME" = "/home/user/.cargo";
+  };
+  mounts = [
+    { host = "/nix/store"; container = "/nix/store"; readOnly = true; }
+  ];
+}
+"#
+        );
+        assert_eq!(
+            serde_json::to_string(&environment).unwrap(),
+            r#"{"env":{"CARGO_HOME":"/home/user/.cargo"},"mounts":[{"host":"/nix/store","container":"/nix/store","read-only":true}]}"#
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! A fake [`ContainerEngine`] for tests, which doesn't need a daemon.
+//!
+//! [`FakeEngine`] keeps track of the volumes and containers it has been asked
+//! to create, records every operation, and returns canned output, so the
+//! flows built on [`ContainerEngine`], such as the lifecycle of a
+//! [`DockerVolume`](super::DockerVolume) or a
+//! [`DockerContainer`](super::DockerContainer), can be tested on any
+//! platform. It's only available with the `test-support` feature.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::ExitStatus;
+
+use super::backend::ContainerEngine;
+use super::build_script::BuildScriptLog;
+use super::shared::ContainerState;
+use crate::errors::Result;
+use crate::extensions::exit_status;
+use crate::shell::MessageInfo;
+
+#[derive(Debug, Default)]
+pub struct FakeEngine {
+    calls: RefCell<Vec<String>>,
+    volumes: RefCell<BTreeSet<String>>,
+    containers: RefCell<BTreeMap<String, ContainerState>>,
+    /// Exit codes for operations, keyed by the operation name, such as `exec`.
+    exit_codes: BTreeMap<String, i32>,
+    /// The stdout for scripts run with `exec_and_get_stdout`.
+    stdout: BTreeMap<String, String>,
+}
+
+impl FakeEngine {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an existing volume.
+    #[must_use]
+    pub fn with_volume(self, name: &str) -> Self {
+        self.volumes.borrow_mut().insert(name.to_owned());
+        self
+    }
+
+    /// Add an existing container in the given state.
+    #[must_use]
+    pub fn with_container(self, name: &str, state: ContainerState) -> Self {
+        self.containers.borrow_mut().insert(name.to_owned(), state);
+        self
+    }
+
+    /// Make an operation, such as `volume create` or `copy into`, exit with `code`.
+    #[must_use]
+    pub fn with_exit_code(mut self, operation: &str, code: i32) -> Self {
+        self.exit_codes.insert(operation.to_owned(), code);
+        self
+    }
+
+    /// Return `stdout` when `script` is run with `exec_and_get_stdout`.
+    #[must_use]
+    pub fn with_stdout(mut self, script: &str, stdout: &str) -> Self {
+        self.stdout.insert(script.to_owned(), stdout.to_owned());
+        self
+    }
+
+    /// The operations run so far, as the operation followed by its arguments.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    pub fn volumes(&self) -> Vec<String> {
+        self.volumes.borrow().iter().cloned().collect()
+    }
+
+    pub fn container_exists(&self, name: &str) -> bool {
+        self.containers.borrow().contains_key(name)
+    }
+
+    fn record(&self, operation: &str, args: &[&str]) -> ExitStatus {
+        let mut call = operation.to_owned();
+        for arg in args {
+            call.push(' ');
+            call.push_str(arg);
+        }
+        self.calls.borrow_mut().push(call);
+        exit_status(self.exit_codes.get(operation).copied().unwrap_or(0))
+    }
+
+    fn record_args(&self, operation: &str, args: &[OsString]) -> ExitStatus {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+        let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+        self.record(operation, &args)
+    }
+}
+
+impl ContainerEngine for FakeEngine {
+    fn volume_create(
+        &self,
+        name: &str,
+        _: &[(String, String)],
+        _: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let status = self.record("volume create", &[name]);
+        if status.success() {
+            self.volumes.borrow_mut().insert(name.to_owned());
+        }
+        Ok(status)
+    }
+
+    fn volume_remove(&self, name: &str, _: &mut MessageInfo) -> Result<ExitStatus> {
+        let status = self.record("volume rm", &[name]);
+        if status.success() {
+            self.volumes.borrow_mut().remove(name);
+        }
+        Ok(status)
+    }
+
+    fn volume_exists(&self, name: &str, _: &mut MessageInfo) -> Result<bool> {
+        self.record("volume inspect", &[name]);
+        Ok(self.volumes.borrow().contains(name))
+    }
+
+    fn container_stop(&self, name: &str, timeout: u32, _: &mut MessageInfo) -> Result<ExitStatus> {
+        let status = self.record("stop", &[name, "--time", &timeout.to_string()]);
+        if let (true, Some(state)) = (status.success(), self.containers.borrow_mut().get_mut(name))
+        {
+            *state = ContainerState::Exited;
+        }
+        Ok(status)
+    }
+
+    fn container_remove(&self, name: &str, _: &mut MessageInfo) -> Result<ExitStatus> {
+        let status = self.record("rm", &[name]);
+        if status.success() {
+            self.containers.borrow_mut().remove(name);
+        }
+        Ok(status)
+    }
+
+    fn container_state(&self, name: &str, _: &mut MessageInfo) -> Result<ContainerState> {
+        self.record("ps", &[name]);
+        Ok(self
+            .containers
+            .borrow()
+            .get(name)
+            .copied()
+            .unwrap_or(ContainerState::DoesNotExist))
+    }
+
+    fn container_create(&self, args: &[OsString], _: &mut MessageInfo) -> Result<ExitStatus> {
+        Ok(self.record_args("create", args))
+    }
+
+    fn run_container(&self, args: &[OsString], _: bool, _: &mut MessageInfo) -> Result<ExitStatus> {
+        Ok(self.record_args("run", args))
+    }
+
+    fn run_container_and_scan(
+        &self,
+        args: &[OsString],
+        _: &mut MessageInfo,
+    ) -> Result<(ExitStatus, BuildScriptLog)> {
+        Ok((self.record_args("run", args), BuildScriptLog::default()))
+    }
+
+    fn exec_build(&self, args: &[OsString], _: &mut MessageInfo) -> Result<ExitStatus> {
+        Ok(self.record_args("exec", args))
+    }
+
+    fn exec(
+        &self,
+        container: &str,
+        script: &str,
+        _: bool,
+        _: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        Ok(self.record("exec", &[container, script]))
+    }
+
+    fn exec_and_get_stdout(
+        &self,
+        container: &str,
+        script: &str,
+        _: &mut MessageInfo,
+    ) -> Result<String> {
+        let status = self.record("exec", &[container, script]);
+        match status.success() {
+            true => Ok(self.stdout.get(script).cloned().unwrap_or_default()),
+            false => eyre::bail!("`exec {container} {script}` failed with {status}"),
+        }
+    }
+
+    fn copy_into(
+        &self,
+        container: &str,
+        src: &Path,
+        dst: &str,
+        _: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let src = src.to_string_lossy();
+        Ok(self.record("copy into", &[&src, &format!("{container}:{dst}")]))
+    }
+
+    fn copy_files_into(
+        &self,
+        container: &str,
+        src: &Path,
+        files: &[String],
+        dst: &str,
+        _: bool,
+        _: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let src = src.to_string_lossy();
+        Ok(self.record(
+            "copy files into",
+            &[&src, &files.join(","), &format!("{container}:{dst}")],
+        ))
+    }
+
+    fn copy_newer_from(
+        &self,
+        container: &str,
+        src: &str,
+        newer: &str,
+        dst: &Path,
+        _: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let dst = dst.to_string_lossy();
+        Ok(self.record(
+            "copy newer from",
+            &[&format!("{container}:{src}"), newer, &dst],
+        ))
+    }
+
+    fn copy_from(
+        &self,
+        container: &str,
+        src: &str,
+        dst: &Path,
+        _: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let dst = dst.to_string_lossy();
+        Ok(self.record("copy from", &[&format!("{container}:{src}"), &dst]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::{CrossLabels, DockerContainer, DockerVolume};
+
+    #[test]
+    fn volume_lifecycle() -> Result<()> {
+        let mut msg_info = MessageInfo::default();
+        let engine = FakeEngine::new();
+        let volume = DockerVolume::new(&engine, "cross-stable-x86_64-unknown-linux-gnu");
+        assert!(!volume.exists(&mut msg_info)?);
+        assert!(volume
+            .create(&CrossLabels::default(), &mut msg_info)?
+            .success());
+        assert!(volume.exists(&mut msg_info)?);
+        assert!(volume.remove(&mut msg_info)?.success());
+        assert!(engine.volumes().is_empty());
+        assert_eq!(
+            engine.calls(),
+            [
+                "volume inspect cross-stable-x86_64-unknown-linux-gnu",
+                "volume create cross-stable-x86_64-unknown-linux-gnu",
+                "volume inspect cross-stable-x86_64-unknown-linux-gnu",
+                "volume rm cross-stable-x86_64-unknown-linux-gnu",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn container_lifecycle() -> Result<()> {
+        let mut msg_info = MessageInfo::default();
+        let engine = FakeEngine::new().with_container("cross-build", ContainerState::Running);
+        let container = DockerContainer::new(&engine, "cross-build");
+        assert_eq!(container.state(&mut msg_info)?, ContainerState::Running);
+        assert!(container.stop_default(&mut msg_info)?.success());
+        assert!(container.state(&mut msg_info)?.is_stopped());
+        assert!(container.remove(&mut msg_info)?.success());
+        assert!(!engine.container_exists("cross-build"));
+        assert_eq!(
+            container.state(&mut msg_info)?,
+            ContainerState::DoesNotExist
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn canned_output() -> Result<()> {
+        let mut msg_info = MessageInfo::default();
+        let engine = FakeEngine::new()
+            .with_stdout("uname -m", "aarch64\n")
+            .with_exit_code("copy into", 1);
+        assert_eq!(
+            engine.exec_and_get_stdout("cross-build", "uname -m", &mut msg_info)?,
+            "aarch64\n"
+        );
+        let status = engine.copy_into(
+            "cross-build",
+            Path::new("/project"),
+            "/project",
+            &mut msg_info,
+        )?;
+        assert_eq!(status.code(), Some(1));
+        assert_eq!(engine.calls()[1], "copy into /project cross-build:/project");
+
+        Ok(())
+    }
+
+    #[test]
+    fn assembled_args() -> Result<()> {
+        let mut msg_info = MessageInfo::default();
+        let engine = FakeEngine::new().with_exit_code("exec", 101);
+        let mut run = std::process::Command::new("run");
+        run.args(["--rm", "--name", "cross-build", "image", "cargo", "build"]);
+        let args = crate::docker::backend::operation_args(&run);
+        assert!(engine.run_container(&args, false, &mut msg_info)?.success());
+        let status = engine.exec_build(&["cross-build".into(), "cargo".into()], &mut msg_info)?;
+        assert_eq!(status.code(), Some(101));
+        assert_eq!(
+            engine.calls(),
+            [
+                "run --rm --name cross-build image cargo build",
+                "exec cross-build cargo",
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -11,6 +11,17 @@ pub struct Image {
     pub name: String,
     // The toolchain triple the image is built for
     pub platform: ImagePlatform,
+    // Overrides the entrypoint of the image, an empty string resets it
+    pub entrypoint: Option<String>,
+    // Overrides the working directory the command is run in
+    pub workdir: Option<String>,
+}
+
+impl Image {
+    /// The digest the image is pinned to, such as `sha256:...`.
+    pub fn digest(&self) -> Option<&str> {
+        reference_digest(&self.name)
+    }
 }
 
 impl std::fmt::Display for Image {
@@ -23,15 +34,25 @@ impl std::fmt::Display for Image {
 pub struct PossibleImage {
     pub name: String,
     // The toolchain triple the image is built for
+    #[serde(default)]
     pub toolchain: Vec<ImagePlatform>,
+    pub entrypoint: Option<String>,
+    pub workdir: Option<String>,
 }
 
 impl PossibleImage {
+    /// The digest the image is pinned to, such as `sha256:...`.
+    pub fn digest(&self) -> Option<&str> {
+        reference_digest(&self.name)
+    }
+
     pub(crate) fn to_definite_with(&self, engine: &Engine, msg_info: &mut MessageInfo) -> Image {
         if self.toolchain.is_empty() {
             Image {
                 name: self.name.clone(),
                 platform: ImagePlatform::DEFAULT,
+                entrypoint: self.entrypoint.clone(),
+                workdir: self.workdir.clone(),
             }
         } else {
             let platform = if self.toolchain.len() == 1 {
@@ -74,16 +95,25 @@ impl PossibleImage {
             Image {
                 platform: platform.clone(),
                 name: self.name.clone(),
+                entrypoint: self.entrypoint.clone(),
+                workdir: self.workdir.clone(),
             }
         }
     }
 }
 
+/// Get the digest of an image reference, like `name@sha256:...`.
+pub(crate) fn reference_digest(name: &str) -> Option<&str> {
+    name.split_once('@').map(|(_, digest)| digest)
+}
+
 impl<T: AsRef<str>> From<T> for PossibleImage {
     fn from(s: T) -> Self {
         PossibleImage {
             name: s.as_ref().to_owned(),
             toolchain: vec![],
+            entrypoint: None,
+            workdir: None,
         }
     }
 }
@@ -116,6 +146,9 @@ pub struct ImagePlatform {
     pub target: TargetTriple,
 }
 
+/// Override the platform of the images, such as `linux/arm64`.
+pub const PLATFORM_ENV: &str = "CROSS_CONTAINER_PLATFORM";
+
 impl ImagePlatform {
     pub const DEFAULT: Self = ImagePlatform::from_const_target(TargetTriple::DEFAULT);
     pub const X86_64_UNKNOWN_LINUX_GNU: Self =
@@ -369,12 +402,31 @@ impl ImagePlatform {
     }
 
     pub fn specify_platform(&self, engine: &Engine, cmd: &mut std::process::Command) {
-        if self.variant.is_none()
-            && Some(&self.architecture) == engine.arch.as_ref()
-            && Some(&self.os) == engine.os.as_ref()
-        {
-        } else {
-            cmd.args(["--platform", &self.docker_platform()]);
+        let platform_override = std::env::var(PLATFORM_ENV).ok();
+        let native = (engine.arch.as_ref(), engine.os.as_ref());
+        if let Some(platform) = self.platform_flag(native, platform_override.as_deref()) {
+            cmd.args(["--platform", &platform]);
+        }
+    }
+
+    /// The value for `--platform`, which is only needed if the image isn't
+    /// native to the engine, or if the platform is overridden. The flag is
+    /// given to both `run` and `build`, so the engine pulls that variant
+    /// of a multi-arch image.
+    fn platform_flag(
+        &self,
+        (arch, os): (Option<&Architecture>, Option<&Os>),
+        platform_override: Option<&str>,
+    ) -> Option<String> {
+        match platform_override {
+            Some(platform) if !platform.is_empty() => Some(platform.to_owned()),
+            _ if self.variant.is_none()
+                && Some(&self.architecture) == arch
+                && Some(&self.os) == os =>
+            {
+                None
+            }
+            _ => Some(self.docker_platform()),
         }
     }
 }
@@ -395,6 +447,27 @@ pub mod tests {
         };
     }
 
+    #[test]
+    fn platform_flag() {
+        let platform = ImagePlatform::AARCH64_UNKNOWN_LINUX_GNU;
+        let amd64 = (Some(&Architecture::Amd64), Some(&Os::Linux));
+        let arm64 = (Some(&Architecture::Arm64), Some(&Os::Linux));
+        assert_eq!(
+            platform.platform_flag(amd64, None),
+            Some("linux/arm64".to_owned())
+        );
+        assert_eq!(platform.platform_flag(arm64, None), None);
+        assert_eq!(
+            platform.platform_flag((None, None), None),
+            Some("linux/arm64".to_owned())
+        );
+        assert_eq!(
+            platform.platform_flag(arm64, Some("linux/arm64/v8")),
+            Some("linux/arm64/v8".to_owned())
+        );
+        assert_eq!(platform.platform_flag(arm64, Some("")), None);
+    }
+
     #[test]
     fn architecture_from_target() -> Result<()> {
         assert_eq!(arch!("x86_64-apple-darwin")?, Architecture::Amd64);
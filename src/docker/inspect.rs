@@ -0,0 +1,243 @@
+//! Typed deserialization for the subset of `inspect` output used by cross.
+//!
+//! The output of docker, podman and nerdctl differs slightly: fields may be
+//! missing or `null`, and some engines use different casing for keys. Every
+//! field is optional here, so a difference results in an error message rather
+//! than a panic.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer};
+
+use super::engine::EngineType;
+use crate::errors::*;
+
+/// The output of `inspect` for a container.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ContainerInspect {
+    #[serde(default, alias = "ID")]
+    pub id: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub graph_driver: GraphDriver,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub mounts: Vec<Mount>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct GraphDriver {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub data: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct Mount {
+    #[serde(default, alias = "source", alias = "Src")]
+    pub source: Option<PathBuf>,
+    #[serde(default, alias = "destination", alias = "Dest")]
+    pub destination: Option<PathBuf>,
+}
+
+impl ContainerInspect {
+    /// Parse the output of `inspect`, which is an array with one entry.
+    pub fn parse(output: &str) -> Result<Self> {
+        let mut entries: Vec<ContainerInspect> =
+            serde_json::from_str(output).wrap_err("failed to parse docker inspect output")?;
+        match entries.is_empty() {
+            true => eyre::bail!("docker inspect output has no containers"),
+            false => Ok(entries.swap_remove(0)),
+        }
+    }
+
+    /// The path of the root filesystem of the container on the host, if the
+    /// storage driver reports it.
+    pub fn root_path(&self, kind: EngineType) -> Result<PathBuf> {
+        let driver_name = self
+            .graph_driver
+            .name
+            .as_deref()
+            .ok_or_else(|| eyre::eyre!("no driver name found"))?;
+        let data = |key: &str| self.graph_driver.data.get(key).and_then(|v| v.as_str());
+
+        let driver = driver_name.to_lowercase();
+        let path = if let (true, Some(merged)) = (driver.contains("overlay"), data("MergedDir")) {
+            // `overlay2` and `fuse-overlayfs`
+            merged.to_owned()
+        } else if let (true, Some(mountpoint)) = (driver == "zfs", data("Mountpoint")) {
+            mountpoint.to_owned()
+        } else if driver == "overlayfs" {
+            // the containerd snapshotter doesn't report the merged directory,
+            // but it's always mounted in the task directory of the container.
+            // docker uses the `moby` namespace, and nerdctl the `default` one.
+            let id = self
+                .id
+                .as_deref()
+                .ok_or_else(|| eyre::eyre!("no container ID found"))?;
+            let namespace = match kind {
+                EngineType::Nerdctl => "default",
+                _ => "moby",
+            };
+            format!("/run/containerd/io.containerd.runtime.v2.task/{namespace}/{id}/rootfs")
+        } else {
+            eyre::bail!("unable to find the root filesystem for driver {driver_name}")
+        };
+
+        Ok(PathBuf::from(path))
+    }
+
+    /// The bind mounts and volumes as `(source, destination)`, skipping
+    /// any mount without a source, such as a `tmpfs`.
+    pub fn user_mounts(&self) -> impl Iterator<Item = (&PathBuf, &PathBuf)> {
+        self.mounts
+            .iter()
+            .filter_map(|mount| Some((mount.source.as_ref()?, mount.destination.as_ref()?)))
+    }
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // trimmed from `docker inspect` with the `overlay2` driver.
+    static DOCKER: &str = r#"[
+        {
+            "Id": "f107af83b37bc0a182d3d2661f3d84684f0fffa1a243566b338a388d5e54bef4",
+            "GraphDriver": {
+                "Data": {
+                    "LowerDir": "/var/lib/docker/overlay2/f107af83-init/diff",
+                    "MergedDir": "/var/lib/docker/overlay2/f107af83/merged",
+                    "UpperDir": "/var/lib/docker/overlay2/f107af83/diff",
+                    "WorkDir": "/var/lib/docker/overlay2/f107af83/work"
+                },
+                "Name": "overlay2"
+            },
+            "Mounts": [
+                {
+                    "Type": "bind",
+                    "Source": "/home/user/project",
+                    "Destination": "/project",
+                    "Mode": "",
+                    "RW": true,
+                    "Propagation": "rprivate"
+                }
+            ]
+        }
+    ]"#;
+
+    // trimmed from `podman inspect`, where a tmpfs has no source.
+    static PODMAN: &str = r#"[
+        {
+            "Id": "5c7a0d4ba0e1",
+            "GraphDriver": {
+                "Name": "overlay",
+                "Data": {
+                    "LowerDir": "/home/user/.local/share/containers/storage/overlay/a1/diff",
+                    "MergedDir": "/home/user/.local/share/containers/storage/overlay/b2/merged",
+                    "UpperDir": "/home/user/.local/share/containers/storage/overlay/b2/diff",
+                    "WorkDir": "/home/user/.local/share/containers/storage/overlay/b2/work"
+                }
+            },
+            "Mounts": [
+                {
+                    "Type": "bind",
+                    "Source": "/home/user/.cargo",
+                    "Destination": "/cargo",
+                    "Driver": "",
+                    "Mode": "",
+                    "Options": ["rbind"],
+                    "RW": true,
+                    "Propagation": "rprivate"
+                },
+                {
+                    "Type": "tmpfs",
+                    "Destination": "/tmp"
+                }
+            ]
+        }
+    ]"#;
+
+    // trimmed from `nerdctl inspect`, which has no graph driver data.
+    static NERDCTL: &str = r#"[
+        {
+            "Id": "9a0c2e7b4d1f",
+            "GraphDriver": {
+                "Name": "overlayfs",
+                "Data": null
+            },
+            "Mounts": null
+        }
+    ]"#;
+
+    #[test]
+    fn parse_docker() -> Result<()> {
+        let info = ContainerInspect::parse(DOCKER)?;
+        assert_eq!(
+            info.root_path(EngineType::Docker)?,
+            PathBuf::from("/var/lib/docker/overlay2/f107af83/merged")
+        );
+        assert_eq!(
+            info.user_mounts().collect::<Vec<_>>(),
+            vec![(
+                &PathBuf::from("/home/user/project"),
+                &PathBuf::from("/project")
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_podman() -> Result<()> {
+        let info = ContainerInspect::parse(PODMAN)?;
+        assert_eq!(
+            info.root_path(EngineType::Podman)?,
+            PathBuf::from("/home/user/.local/share/containers/storage/overlay/b2/merged")
+        );
+        assert_eq!(info.mounts.len(), 2);
+        assert_eq!(
+            info.user_mounts().collect::<Vec<_>>(),
+            vec![(
+                &PathBuf::from("/home/user/.cargo"),
+                &PathBuf::from("/cargo")
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_nerdctl() -> Result<()> {
+        let info = ContainerInspect::parse(NERDCTL)?;
+        assert_eq!(
+            info.root_path(EngineType::Nerdctl)?,
+            PathBuf::from(
+                "/run/containerd/io.containerd.runtime.v2.task/default/9a0c2e7b4d1f/rootfs"
+            )
+        );
+        assert_eq!(info.user_mounts().count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(ContainerInspect::parse("[]").is_err());
+        assert!(ContainerInspect::parse("{}").is_err());
+        let info = ContainerInspect::parse(r#"[{ "Id": "test" }]"#).unwrap();
+        assert!(info.root_path(EngineType::Docker).is_err());
+        assert_eq!(info.user_mounts().count(), 0);
+    }
+}
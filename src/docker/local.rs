@@ -3,29 +3,39 @@ use std::path::Path;
 use std::process::{Command, ExitStatus};
 use std::sync::atomic::Ordering;
 
+use super::backend::{operation_args, ContainerEngine};
+use super::build_script;
+use super::engine::Engine;
+use super::pod;
+use super::pull;
 use super::shared::*;
-use crate::errors::Result;
-use crate::extensions::CommandExt;
-use crate::file::{PathExt, ToUtf8};
+use super::spec::{EnvVar, MountSpec};
+use crate::errors::{ExitCode, FailureExt, Result};
+use crate::file::PathExt;
 use crate::shell::{MessageInfo, Stream};
 use eyre::Context;
 
 // NOTE: host path must be absolute
-fn mount(docker: &mut Command, host_path: &Path, absolute_path: &Path, prefix: &str) -> Result<()> {
+fn mount(
+    docker: &mut Command,
+    engine: &Engine,
+    host_path: &Path,
+    absolute_path: &Path,
+    prefix: &str,
+) -> Result<()> {
     let mount_path = absolute_path.as_posix_absolute()?;
-    docker.args([
-        "-v",
-        &format!("{}:{prefix}{}:z", host_path.to_utf8()?, mount_path),
-    ]);
+    docker.args(MountSpec::bind(engine, host_path, format!("{prefix}{mount_path}"))?.args());
     Ok(())
 }
 
 pub(crate) fn run(
-    options: DockerOptions,
+    mut options: DockerOptions,
     paths: DockerPaths,
     args: &[String],
     msg_info: &mut MessageInfo,
 ) -> Result<ExitStatus> {
+    options.record_image_use(msg_info)?;
+    options.verify_image(msg_info)?;
     let engine = &options.engine;
     let toolchain_dirs = paths.directories.toolchain_directories();
     let package_dirs = paths.directories.package_directories();
@@ -33,8 +43,9 @@ pub(crate) fn run(
     let mut cmd = options.cargo_variant.safe_command();
     cmd.args(args);
 
-    let mut docker = engine.subcommand("run");
-    docker.add_userns();
+    // the arguments of `run`, which the engine runs the container with
+    let mut docker = Command::new("run");
+    docker.add_userns(&engine.capabilities);
 
     options
         .image
@@ -45,7 +56,7 @@ pub(crate) fn run(
     docker.add_mounts(
         &options,
         &paths,
-        |docker, host, absolute| mount(docker, host, absolute, ""),
+        |docker, host, absolute| mount(docker, engine, host, absolute, ""),
         |_| {},
         msg_info,
     )?;
@@ -53,84 +64,138 @@ pub(crate) fn run(
     let container_id = toolchain_dirs.unique_container_identifier(options.target.target())?;
     docker.args(["--name", &container_id]);
     docker.arg("--rm");
+    docker.args(options.labels(&paths).args());
+    // the services are removed when the pod is dropped, after the container.
+    let pod = pod::Pod::start(&options, &paths, &container_id, msg_info)?;
+    if let Some(pod) = &pod {
+        docker.args(pod.container_args());
+    }
 
     docker
-        .add_seccomp(engine.kind, &options.target, &paths.metadata)
+        .add_seccomp(&engine.capabilities, &options.target, &paths.metadata)
         .wrap_err("when copying seccomp profile")?;
-    docker.add_user_id(engine.kind);
+    docker.add_user_id(&engine.capabilities);
 
     docker
-        .args([
-            "-v",
-            &format!(
-                "{}:{}:z",
-                toolchain_dirs.xargo_host_path()?,
-                toolchain_dirs.xargo_mount_path()
-            ),
-        ])
-        .args([
-            "-v",
-            &format!(
-                "{}:{}:z",
-                toolchain_dirs.cargo_host_path()?,
-                toolchain_dirs.cargo_mount_path()
-            ),
-        ])
+        .args(
+            MountSpec::bind(
+                engine,
+                toolchain_dirs.xargo(),
+                toolchain_dirs.xargo_mount_path(),
+            )?
+            .args(),
+        )
+        .args(
+            MountSpec::bind(
+                engine,
+                toolchain_dirs.cargo(),
+                toolchain_dirs.cargo_mount_path(),
+            )?
+            .args(),
+        )
         // Prevent `bin` from being mounted inside the Docker container.
-        .args(["-v", &format!("{}/bin", toolchain_dirs.cargo_mount_path())]);
-
-    docker.args([
-        "-v",
-        &format!(
-            "{}:{}:z",
-            package_dirs.host_root().to_utf8()?,
-            package_dirs.mount_root()
-        ),
-    ]);
+        .args(MountSpec::anonymous(format!("{}/bin", toolchain_dirs.cargo_mount_path()))?.args());
+
+    docker.args(
+        MountSpec::bind(engine, package_dirs.host_root(), package_dirs.mount_root())?
+            .read_only(package_dirs.read_only())
+            .args(),
+    );
     docker
-        .args([
-            "-v",
-            &format!(
-                "{}:{}:z,ro",
-                toolchain_dirs.get_sysroot().to_utf8()?,
-                toolchain_dirs.sysroot_mount_path()
-            ),
-        ])
-        .args([
-            "-v",
-            &format!("{}:/target:z", package_dirs.target().to_utf8()?),
-        ]);
-    docker.add_cwd(&paths)?;
+        .args(
+            MountSpec::bind(
+                engine,
+                toolchain_dirs.get_sysroot(),
+                toolchain_dirs.sysroot_mount_path(),
+            )?
+            .read_only(true)
+            .args(),
+        )
+        .args(MountSpec::bind(engine, package_dirs.target(), "/target")?.args());
+    docker.add_cwd(&options, &paths)?;
+
+    if let Some((qemu, path)) = options.managed_qemu(msg_info)? {
+        docker
+            .args(
+                MountSpec::bind(engine, &qemu, &*path)?
+                    .read_only(true)
+                    .args(),
+            )
+            .args(options.qemu_runner_env(&path)?.args());
+    }
 
     // When running inside NixOS or using Nix packaging we need to add the Nix
     // Store to the running container so it can load the needed binaries.
     if let Some(nix_store) = toolchain_dirs.nix_store() {
-        docker.args([
-            "-v",
-            &format!(
-                "{}:{}:z",
-                nix_store.to_utf8()?,
-                nix_store.as_posix_absolute()?
-            ),
-        ]);
+        docker.args(
+            MountSpec::bind(engine, nix_store, nix_store.as_posix_absolute()?)?
+                .read_only(toolchain_dirs.nix_store_read_only())
+                .args(),
+        );
     }
 
-    if io::Stdin::is_atty() && io::Stdout::is_atty() && io::Stderr::is_atty() {
+    // `stderr` is scanned for build scripts, so it can't be a terminal
+    if io::Stdin::is_atty()
+        && io::Stdout::is_atty()
+        && io::Stderr::is_atty()
+        && !options.emit_build_script_env
+    {
         docker.arg("-t");
     }
     let mut image_name = options.image.name.clone();
     if options.needs_custom_image() {
         image_name = options
             .custom_image_build(&paths, msg_info)
-            .wrap_err("when building custom image")?;
+            .wrap_err("when building custom image")
+            .failure(ExitCode::ContainerBuild)?;
+    }
+    pull::pull_image(engine, &options.image.platform, &image_name, msg_info)?;
+
+    // images without a shell, such as distroless ones, need the
+    // command to be run directly with the environment provided.
+    let has_shell = options.image_has_shell(&image_name, msg_info)?;
+    if !has_shell {
+        if options.is_nextest() {
+            eyre::bail!("`cargo nextest` requires `/bin/sh` in image `{image_name}`");
+        }
+        msg_info.note(format_args!(
+            "image `{image_name}` has no `/bin/sh`, running the command directly"
+        ))?;
+        let path = options.image_path(&image_name, msg_info)?;
+        docker.args(
+            EnvVar::new(
+                "PATH",
+                format!("{path}:{}/bin", toolchain_dirs.sysroot_mount_path()),
+            )?
+            .args(),
+        );
     }
 
+    options.check_entrypoint(&image_name, msg_info)?;
+    options.check_image_version(&image_name, msg_info)?;
+    if let Some(volume) = options.wine_volume(&image_name, msg_info)? {
+        docker.args(MountSpec::new(volume, WINE_PREFIX_DIR)?.args());
+    }
+    docker.add_entrypoint(&options);
+
     ChildContainer::create(engine.clone(), container_id)?;
-    let status = docker
-        .arg(&image_name)
-        .add_build_command(toolchain_dirs, &cmd)
-        .run_and_get_status(msg_info, false)
-        .map_err(Into::into);
+    docker.arg(&image_name);
+    if has_shell {
+        docker.add_build_command(&options, toolchain_dirs, &image_name, &cmd, msg_info)?;
+    } else {
+        docker.add_exec_command(&cmd);
+    }
+    let args = operation_args(&docker);
+    let status = if options.emit_build_script_env {
+        engine
+            .run_container_and_scan(&args, msg_info)
+            .and_then(|(status, log)| {
+                build_script::report(&log, &build_script::PathMapping::new(&paths), msg_info)?;
+                Ok(status)
+            })
+    } else {
+        engine.run_container(&args, false, msg_info)
+    };
 
     // `cargo` generally returns 0 or 101 on completion, but isn't guaranteed
     // to. `ExitStatus::code()` may be None if a signal caused the process to
@@ -1,16 +1,35 @@
+pub mod api;
+mod backend;
 mod build;
+mod build_script;
 pub(crate) mod custom;
 mod engine;
+mod environment;
+#[cfg(any(test, feature = "test-support"))]
+mod fake;
 mod image;
+mod inspect;
 mod local;
+mod pod;
+mod profile;
 mod provided_images;
+mod published_images;
+mod pull;
 pub mod remote;
 mod shared;
+mod spec;
 
+pub use self::backend::ContainerEngine;
 pub use self::build::{BuildCommandExt, BuildResultExt, Progress};
+pub use self::build_script::EMIT_BUILD_SCRIPT_ENV_FLAG;
 pub use self::engine::*;
+pub use self::environment::{ContainerEnvironment, ContainerMount};
+#[cfg(any(test, feature = "test-support"))]
+pub use self::fake::FakeEngine;
+pub use self::profile::{RemoteProfile, RemoteProfiles};
 pub use self::provided_images::PROVIDED_IMAGES;
 pub use self::shared::*;
+pub use self::spec::MountSpec;
 
 pub use image::{Architecture, Image, ImagePlatform, Os as ContainerOs, PossibleImage};
 
@@ -0,0 +1,197 @@
+//! Service containers for `cross run` and `cross test`.
+//!
+//! The services in `[test.pod]` share the network namespace of the container
+//! the binaries are run in, so they can be reached on `127.0.0.1`. With
+//! podman, the containers are grouped in a pod. With docker, which has no
+//! pods, the first service owns the network namespace and the other
+//! containers join it with `--network container:<name>`.
+//!
+//! The pod, or the first service, is attached to the `cross-<project-hash>`
+//! network of the project, which is reused by later runs.
+
+use super::shared::{DockerNetwork, DockerOptions, DockerPaths};
+use super::spec::EnvVar;
+use super::Engine;
+use crate::cross_toml::CrossPodService;
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::shell::{ColorChoice, MessageInfo, Verbosity};
+
+/// The services started for a container, which are removed when dropped.
+#[derive(Debug)]
+pub(crate) struct Pod {
+    engine: Engine,
+    name: String,
+    network: String,
+    services: Vec<String>,
+    color_choice: ColorChoice,
+    verbosity: Verbosity,
+}
+
+impl Pod {
+    /// Start the services for the container `container_id`, if the
+    /// subcommand runs binaries and `[test.pod]` has any services.
+    pub(crate) fn start(
+        options: &DockerOptions,
+        paths: &DockerPaths,
+        container_id: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Option<Pod>> {
+        let runs_binaries = matches!(options.subcommand, Some(s) if s.needs_interpreter());
+        let services = match options.config.pod() {
+            Some(pod) if runs_binaries && !pod.services.is_empty() => pod.services.clone(),
+            _ => return Ok(None),
+        };
+        let engine = &options.engine;
+        let mut pod = Pod {
+            engine: engine.clone(),
+            name: format!("{container_id}-pod"),
+            network: DockerNetwork::project_name(&paths.metadata.workspace_root)?,
+            services: vec![],
+            color_choice: msg_info.color_choice,
+            verbosity: msg_info.verbosity,
+        };
+        let labels = options.labels(paths).args();
+        DockerNetwork::new(engine, &pod.network).create_or_reuse(msg_info)?;
+
+        if engine.kind.is_podman() {
+            engine
+                .subcommand("pod")
+                .args(["create", "--name", &pod.name, "--share", "net"])
+                .args(["--network", &pod.network])
+                .args(&labels)
+                .run(msg_info, true)
+                .wrap_err_with(|| format!("could not create pod `{}`", pod.name))?;
+        }
+        for service in &services {
+            let name = format!("{}-{}", pod.name, service.name);
+            msg_info.note(format_args!(
+                "starting service `{}` from image `{}`",
+                service.name, service.image
+            ))?;
+            engine
+                .subcommand("run")
+                .args(service_args(
+                    &name,
+                    service,
+                    &pod.container_args(),
+                    &labels,
+                )?)
+                .run(msg_info, true)
+                .wrap_err_with(|| format!("could not start service `{}`", service.name))?;
+            pod.services.push(name);
+        }
+
+        Ok(Some(pod))
+    }
+
+    /// The arguments for a container, such as the one running the binaries,
+    /// to join the pod.
+    pub(crate) fn container_args(&self) -> Vec<String> {
+        network_args(
+            self.engine.kind.is_podman(),
+            &self.name,
+            &self.network,
+            &self.services,
+        )
+    }
+}
+
+impl Drop for Pod {
+    fn drop(&mut self) {
+        let mut msg_info = MessageInfo::new(self.color_choice, self.verbosity);
+        if !self.services.is_empty() {
+            self.engine
+                .subcommand("rm")
+                .arg("-f")
+                .args(&self.services)
+                .run_and_get_output(&mut msg_info)
+                .ok();
+        }
+        if self.engine.kind.is_podman() {
+            self.engine
+                .subcommand("pod")
+                .args(["rm", "-f", &self.name])
+                .run_and_get_output(&mut msg_info)
+                .ok();
+        }
+    }
+}
+
+/// The arguments for a container to join the network of the services
+/// started so far, or the network of the project for the first service.
+fn network_args(is_podman: bool, pod: &str, network: &str, services: &[String]) -> Vec<String> {
+    if is_podman {
+        vec!["--pod".to_owned(), pod.to_owned()]
+    } else {
+        match services.first() {
+            Some(owner) => vec!["--network".to_owned(), format!("container:{owner}")],
+            None => vec!["--network".to_owned(), network.to_owned()],
+        }
+    }
+}
+
+fn service_args(
+    name: &str,
+    service: &CrossPodService,
+    network: &[String],
+    labels: &[String],
+) -> Result<Vec<String>> {
+    let mut args = vec!["-d".to_owned(), "--name".to_owned(), name.to_owned()];
+    args.extend(network.iter().cloned());
+    args.extend(labels.iter().cloned());
+    for env in &service.env {
+        args.extend(EnvVar::parse(env)?.args());
+    }
+    args.push(service.image.clone());
+    args.extend(service.args.iter().cloned());
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pod_args() -> Result<()> {
+        let service = CrossPodService {
+            name: "postgres".to_owned(),
+            image: "postgres:15".to_owned(),
+            env: vec!["POSTGRES_PASSWORD=cross".to_owned()],
+            args: vec!["-c".to_owned(), "fsync=off".to_owned()],
+        };
+        assert_eq!(
+            service_args("c-pod-postgres", &service, &[], &[])?,
+            [
+                "-d",
+                "--name",
+                "c-pod-postgres",
+                "-e",
+                "POSTGRES_PASSWORD=cross",
+                "postgres:15",
+                "-c",
+                "fsync=off",
+            ]
+        );
+
+        let services = ["c-pod-postgres".to_owned(), "c-pod-redis".to_owned()];
+        assert_eq!(
+            network_args(true, "c-pod", "cross-1", &[]),
+            ["--pod", "c-pod"]
+        );
+        assert_eq!(
+            network_args(true, "c-pod", "cross-1", &services),
+            ["--pod", "c-pod"]
+        );
+        assert_eq!(
+            network_args(false, "c-pod", "cross-1", &[]),
+            ["--network", "cross-1"]
+        );
+        assert_eq!(
+            network_args(false, "c-pod", "cross-1", &services),
+            ["--network", "container:c-pod-postgres"]
+        );
+
+        Ok(())
+    }
+}
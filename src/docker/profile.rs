@@ -0,0 +1,133 @@
+//! Named remote builders, created with `cross-util remote setup` and
+//! selected with `--remote <name>`.
+//!
+//! The profiles are stored in the data directory of cross, and only
+//! describe how to reach the daemon: the engine of a selected profile
+//! connects to it with precedence over the environment and `Cross.toml`.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+use crate::file;
+
+const PROFILES_FILENAME: &str = "remotes.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RemoteProfile {
+    /// The daemon host, such as `ssh://user@host`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// The docker context or podman connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// The container engine, such as `docker` or `podman`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+}
+
+impl RemoteProfile {
+    /// The destination and port to check the connection with `ssh`, if the
+    /// host is reached over SSH.
+    pub fn ssh_destination(&self) -> Option<(&str, Option<&str>)> {
+        let host = self.host.as_deref()?.strip_prefix("ssh://")?;
+        let host = host.split('/').next().unwrap_or(host);
+        let address = host.rsplit_once('@').map_or(host, |(_, address)| address);
+        // a colon in an IPv6 address is within brackets
+        match address.rsplit_once(':') {
+            Some((_, port)) if !port.contains(']') => {
+                Some((&host[..host.len() - port.len() - 1], Some(port)))
+            }
+            _ => Some((host, None)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RemoteProfiles {
+    pub profiles: BTreeMap<String, RemoteProfile>,
+}
+
+impl RemoteProfiles {
+    pub fn path() -> Result<PathBuf> {
+        file::cross_data_dir().map(|data| data.join(PROFILES_FILENAME))
+    }
+
+    /// Read the profiles, which are empty if the file doesn't exist.
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(RemoteProfiles::default());
+        }
+        let contents = file::read(path)?;
+        serde_json::from_str(&contents).wrap_err_with(|| format!("could not parse `{path:?}`"))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut file = file::write_file(path, true)?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .wrap_err_with(|| format!("could not write `{path:?}`"))?;
+        file.write_all(b"\n")?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&RemoteProfile> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| eyre::eyre!("no remote builder named `{name}`"))
+            .with_suggestion(|| {
+                format!("create it with `cross-util remote setup {name} --host <host>`.")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles() -> Result<()> {
+        let mut profiles = RemoteProfiles::default();
+        profiles.profiles.insert(
+            "builder".to_owned(),
+            RemoteProfile {
+                host: Some("ssh://ci@builder.local:2222".to_owned()),
+                engine: Some("podman".to_owned()),
+                ..RemoteProfile::default()
+            },
+        );
+        let json = serde_json::to_string(&profiles)?;
+        assert_eq!(
+            json,
+            r#"{"builder":{"host":"ssh://ci@builder.local:2222","engine":"podman"}}"#
+        );
+        assert_eq!(serde_json::from_str::<RemoteProfiles>(&json)?, profiles);
+        assert_eq!(profiles.get("builder")?.engine.as_deref(), Some("podman"));
+        assert!(profiles.get("other").is_err());
+
+        let ssh = |host: &str| {
+            RemoteProfile {
+                host: Some(host.to_owned()),
+                ..RemoteProfile::default()
+            }
+            .ssh_destination()
+            .map(|(dest, port)| (dest.to_owned(), port.map(ToOwned::to_owned)))
+        };
+        assert_eq!(
+            ssh("ssh://ci@builder.local:2222"),
+            Some(("ci@builder.local".to_owned(), Some("2222".to_owned())))
+        );
+        assert_eq!(
+            ssh("ssh://builder.local/run/docker.sock"),
+            Some(("builder.local".to_owned(), None))
+        );
+        assert_eq!(ssh("tcp://builder.local:2376"), None);
+
+        Ok(())
+    }
+}
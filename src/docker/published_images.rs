@@ -0,0 +1,330 @@
+//! Finding images published after this version of `cross` was built.
+//!
+//! [`PROVIDED_IMAGES`](super::PROVIDED_IMAGES) is generated at compile time,
+//! so when a target isn't in it, the registry is asked if it has an image for
+//! it. The answer is cached for a day, and in offline mode only the cache is
+//! used, even if it's stale. The registry is queried with `curl`.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::ImagePlatform;
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::file;
+use crate::shell::MessageInfo;
+
+/// How long the images found in the registry are cached for.
+const CACHE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+const DOCKER_HUB: &str = "registry-1.docker.io";
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.v2+json";
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct Cache {
+    images: BTreeMap<String, CachedImage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct CachedImage {
+    /// When the registry was queried, in seconds since the Unix epoch.
+    checked: u64,
+    /// The platforms of the image, like `linux/amd64`, or none if it doesn't
+    /// exist.
+    platforms: Option<Vec<String>>,
+}
+
+fn cache_path() -> Option<PathBuf> {
+    directories::BaseDirs::new()
+        .map(|d| d.cache_dir().join("cross-rs").join("published-images.json"))
+}
+
+fn read_cache(path: &Option<PathBuf>) -> Cache {
+    path.as_ref()
+        .and_then(|path| file::read(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// An image reference split into the registry, repository and tag.
+#[derive(Debug, PartialEq, Eq)]
+struct Reference<'a> {
+    registry: &'a str,
+    repository: String,
+    tag: &'a str,
+}
+
+impl<'a> Reference<'a> {
+    fn parse(name: &'a str) -> Result<Reference<'a>> {
+        let (name, tag) = match name.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, tag),
+            _ => (name, "latest"),
+        };
+        let (registry, repository) = match name.split_once('/') {
+            Some((registry, repository))
+                if registry.contains('.') || registry.contains(':') || registry == "localhost" =>
+            {
+                (registry, repository.to_owned())
+            }
+            Some(_) => (DOCKER_HUB, name.to_owned()),
+            None => (DOCKER_HUB, format!("library/{name}")),
+        };
+        eyre::ensure!(!repository.is_empty(), "invalid image name `{name}`");
+
+        Ok(Reference {
+            registry,
+            repository,
+            tag,
+        })
+    }
+
+    /// The URL to get an anonymous token to pull the image, for the
+    /// registries that require one.
+    fn token_url(&self) -> Option<String> {
+        let scope = format!("repository:{}:pull", self.repository);
+        match self.registry {
+            DOCKER_HUB => Some(format!(
+                "https://auth.docker.io/token?service=registry.docker.io&scope={scope}"
+            )),
+            "ghcr.io" => Some(format!("https://ghcr.io/token?scope={scope}")),
+            _ => None,
+        }
+    }
+
+    fn manifest_url(&self) -> String {
+        format!(
+            "https://{}/v2/{}/manifests/{}",
+            self.registry, self.repository, self.tag
+        )
+    }
+}
+
+fn fetch_token(url: &str, msg_info: &mut MessageInfo) -> Result<String> {
+    let json = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", "10"])
+        .arg(url)
+        .run_and_get_stdout(msg_info)?;
+    let value: serde_json::Value =
+        serde_json::from_str(&json).wrap_err("could not parse the registry token")?;
+    value["token"]
+        .as_str()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| eyre::eyre!("the registry did not return a token"))
+}
+
+/// Split the output of `curl --write-out '\n%{http_code}'` into the body and
+/// the status code.
+fn split_response(output: &str) -> Result<(&str, u32)> {
+    let (body, code) = output.rsplit_once('\n').unwrap_or(("", output));
+    let code = code
+        .trim()
+        .parse()
+        .wrap_err_with(|| format!("invalid status code `{code}`"))?;
+    Ok((body, code))
+}
+
+/// Get the platforms of an image from its manifest. Single-platform images
+/// are assumed to be `linux/amd64`, like the images `cross` builds.
+fn parse_platforms(json: &str) -> Result<Vec<String>> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).wrap_err("could not parse the image manifest")?;
+    let manifests = match value["manifests"].as_array() {
+        Some(manifests) => manifests,
+        None => return Ok(vec!["linux/amd64".to_owned()]),
+    };
+    let mut platforms = vec![];
+    for manifest in manifests {
+        let platform = &manifest["platform"];
+        let (os, arch) = match (platform["os"].as_str(), platform["architecture"].as_str()) {
+            // attestations are stored as `unknown/unknown`
+            (Some(os), Some(arch)) if os != "unknown" && arch != "unknown" => (os, arch),
+            _ => continue,
+        };
+        let platform = match platform["variant"].as_str() {
+            Some(variant) => format!("{os}/{arch}/{variant}"),
+            None => format!("{os}/{arch}"),
+        };
+        if !platforms.contains(&platform) {
+            platforms.push(platform);
+        }
+    }
+
+    Ok(platforms)
+}
+
+/// Query the registry for the platforms of an image, or `None` if it doesn't
+/// exist.
+fn fetch_platforms(name: &str, msg_info: &mut MessageInfo) -> Result<Option<Vec<String>>> {
+    let reference = Reference::parse(name)?;
+    let mut curl = Command::new("curl");
+    curl.args(["--silent", "--show-error", "--location"])
+        .args(["--max-time", "10"])
+        .args(["--write-out", "\n%{http_code}"])
+        .args(["--header", &format!("Accept: {MANIFEST_TYPES}")]);
+    if let Some(url) = reference.token_url() {
+        let token = fetch_token(&url, msg_info)?;
+        curl.args(["--header", &format!("Authorization: Bearer {token}")]);
+    }
+    let output = curl
+        .arg(reference.manifest_url())
+        .run_and_get_stdout(msg_info)
+        .wrap_err_with(|| format!("could not query the registry for `{name}`"))?;
+    match split_response(&output)? {
+        (body, 200) => parse_platforms(body).map(Some),
+        (_, 404) => Ok(None),
+        (_, code) => eyre::bail!("the registry returned {code} for `{name}`"),
+    }
+}
+
+/// The platforms `cross` can run an image on, which are described by the
+/// toolchain they provide, like the images in `PROVIDED_IMAGES`.
+fn image_platforms(platforms: &[String]) -> Vec<ImagePlatform> {
+    platforms
+        .iter()
+        .filter_map(|platform| match platform.as_str() {
+            "linux/amd64" => Some(ImagePlatform::X86_64_UNKNOWN_LINUX_GNU),
+            "linux/arm64" | "linux/arm64/v8" => Some(ImagePlatform::AARCH64_UNKNOWN_LINUX_GNU),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Get the platforms of a published image, or `None` if the registry
+/// doesn't have it. If the registry can't be reached, a stale cache is used.
+pub(crate) fn published_image(
+    name: &str,
+    offline: bool,
+    msg_info: &mut MessageInfo,
+) -> Result<Option<Vec<ImagePlatform>>> {
+    let now = super::now_as_millis()? / 1000;
+    let path = cache_path();
+    let mut cache = read_cache(&path);
+    let cached = cache.images.get(name).cloned();
+    if let Some(cached) = &cached {
+        if offline || now.saturating_sub(cached.checked) < CACHE_DURATION.as_secs() {
+            return Ok(cached.platforms.as_deref().map(image_platforms));
+        }
+    }
+    if offline {
+        return Ok(None);
+    }
+
+    let platforms = match fetch_platforms(name, msg_info) {
+        Ok(platforms) => platforms,
+        Err(err) => match cached {
+            Some(cached) => {
+                msg_info.debug(format_args!(
+                    "using the cached platforms of `{name}`: {err}"
+                ))?;
+                return Ok(cached.platforms.as_deref().map(image_platforms));
+            }
+            None => return Err(err),
+        },
+    };
+    if let Some(path) = path {
+        cache.images.insert(
+            name.to_owned(),
+            CachedImage {
+                checked: now,
+                platforms: platforms.clone(),
+            },
+        );
+        // the cache only saves a query, so the platforms are still used.
+        let written = (|| -> Result<()> {
+            let contents = serde_json::to_string(&cache)?;
+            file::write_file(&path, true)?.write_all(contents.as_bytes())?;
+            Ok(())
+        })();
+        if let Err(err) = written {
+            msg_info.warn(format_args!(
+                "could not cache the platforms of `{name}` at {path:?}: {err}"
+            ))?;
+        }
+    }
+
+    Ok(platforms.as_deref().map(image_platforms))
+}
+
+/// Returns if an image was found in the registry earlier, without querying it.
+pub(crate) fn is_cached(name: &str) -> bool {
+    read_cache(&cache_path())
+        .images
+        .get(name)
+        .map(|cached| cached.platforms.is_some())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references() -> Result<()> {
+        let reference = Reference::parse("ghcr.io/cross-rs/x86_64-unknown-linux-gnu:main")?;
+        assert_eq!(
+            reference,
+            Reference {
+                registry: "ghcr.io",
+                repository: "cross-rs/x86_64-unknown-linux-gnu".to_owned(),
+                tag: "main",
+            }
+        );
+        assert_eq!(
+            reference.manifest_url(),
+            "https://ghcr.io/v2/cross-rs/x86_64-unknown-linux-gnu/manifests/main"
+        );
+        assert_eq!(
+            reference.token_url().as_deref(),
+            Some("https://ghcr.io/token?scope=repository:cross-rs/x86_64-unknown-linux-gnu:pull")
+        );
+
+        let reference = Reference::parse("localhost:5000/cross/zig")?;
+        assert_eq!(reference.registry, "localhost:5000");
+        assert_eq!(reference.tag, "latest");
+        assert_eq!(reference.token_url(), None);
+
+        let reference = Reference::parse("ubuntu:20.04")?;
+        assert_eq!(reference.registry, DOCKER_HUB);
+        assert_eq!(reference.repository, "library/ubuntu");
+
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_platforms() -> Result<()> {
+        let index = r#"{
+            "manifests": [
+                {"digest": "sha256:1", "platform": {"architecture": "amd64", "os": "linux"}},
+                {"digest": "sha256:2", "platform": {"architecture": "arm64", "os": "linux", "variant": "v8"}},
+                {"digest": "sha256:3", "platform": {"architecture": "unknown", "os": "unknown"}}
+            ]
+        }"#;
+        let platforms = parse_platforms(index)?;
+        assert_eq!(platforms, ["linux/amd64", "linux/arm64/v8"]);
+        assert_eq!(
+            image_platforms(&platforms),
+            [
+                ImagePlatform::X86_64_UNKNOWN_LINUX_GNU,
+                ImagePlatform::AARCH64_UNKNOWN_LINUX_GNU
+            ]
+        );
+        assert_eq!(
+            parse_platforms(r#"{"config": {}, "layers": []}"#)?,
+            ["linux/amd64"]
+        );
+
+        assert_eq!(split_response("{}\n200")?, ("{}", 200));
+        assert_eq!(split_response("404")?, ("", 404));
+        assert!(split_response("{}\n").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+//! Pulling images with progress.
+//!
+//! `docker run` pulls a missing image without any output when it isn't a
+//! terminal, which looks like a hang for the large images of cross, so the
+//! image is pulled first and the progress is reported by counting the layers
+//! that were pulled.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Read};
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use super::{Engine, ImagePlatform};
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::shell::MessageInfo;
+
+/// The layers of an image being pulled, from the output of `docker pull` or
+/// `podman pull`.
+#[derive(Debug, Default)]
+pub(crate) struct PullProgress {
+    layers: BTreeSet<String>,
+    complete: BTreeSet<String>,
+}
+
+impl PullProgress {
+    pub(crate) fn scan_line(&mut self, line: &str) {
+        let line = line.trim();
+        // podman: `Copying blob 4f4fb700ef54 done`
+        if let Some(rest) = line.strip_prefix("Copying blob ") {
+            let mut words = rest.split_whitespace();
+            if let Some(layer) = words.next() {
+                let layer = layer.trim_start_matches("sha256:");
+                self.layers.insert(layer.to_owned());
+                if words.any(|word| word == "done" || word == "skipped:") {
+                    self.complete.insert(layer.to_owned());
+                }
+            }
+        // docker: `4f4fb700ef54: Pull complete`
+        } else if let Some((layer, status)) = line.split_once(": ") {
+            let is_layer = !layer.is_empty() && layer.chars().all(|c| c.is_ascii_hexdigit());
+            if !is_layer {
+                return;
+            }
+            self.layers.insert(layer.to_owned());
+            if matches!(status, "Pull complete" | "Already exists") {
+                self.complete.insert(layer.to_owned());
+            }
+        }
+    }
+
+    pub(crate) fn message(&self, image_name: &str) -> String {
+        match self.layers.len() {
+            0 => format!("pulling image `{image_name}`"),
+            total => format!(
+                "pulling image `{image_name}`: {}/{total} layers",
+                self.complete.len()
+            ),
+        }
+    }
+}
+
+/// Pull the image if it doesn't exist, reporting the progress.
+pub(crate) fn pull_image(
+    engine: &Engine,
+    platform: &ImagePlatform,
+    image_name: &str,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    let exists = engine
+        .subcommand("image")
+        .args(["inspect", image_name])
+        .run_and_get_output(msg_info)?
+        .status
+        .success();
+    if exists {
+        return Ok(());
+    }
+
+    msg_info.status(format_args!("pulling image `{image_name}`"))?;
+    let mut pull = engine.subcommand("pull");
+    platform.specify_platform(engine, &mut pull);
+    pull.arg(image_name);
+    pull.debug(msg_info)?;
+    let start = Instant::now();
+    let mut child = pull
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("could not execute `{pull:?}`"))?;
+
+    // docker reports the progress on stdout and podman on stderr, so read
+    // both, keeping stderr for the error message.
+    let (sender, receiver) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, false, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, true, sender);
+    }
+    let mut progress = PullProgress::default();
+    let mut stderr = String::new();
+    for (is_stderr, line) in receiver {
+        progress.scan_line(&line);
+        msg_info.progress(progress.message(image_name))?;
+        if is_stderr {
+            stderr.push_str(&line);
+            stderr.push('\n');
+        }
+    }
+    let status = child.wait()?;
+    msg_info.finish_progress()?;
+    pull.log_finished(Some(&status), start, msg_info)?;
+
+    if !status.success() {
+        return Err(eyre::eyre!("could not pull image `{image_name}`"))
+            .with_note(|| stderr.trim().to_owned())
+            .failure(ExitCode::ImageMissing);
+    }
+
+    Ok(())
+}
+
+fn forward_lines(
+    stream: impl Read + Send + 'static,
+    is_stderr: bool,
+    sender: mpsc::Sender<(bool, String)>,
+) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if sender.send((is_stderr, line)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pull_progress() {
+        let mut progress = PullProgress::default();
+        assert_eq!(progress.message("a"), "pulling image `a`");
+        for line in [
+            "main: Pulling from cross-rs/aarch64-unknown-linux-gnu",
+            "4f4fb700ef54: Already exists",
+            "a1b2c3d4e5f6: Pulling fs layer",
+            "0123456789ab: Pulling fs layer",
+            "a1b2c3d4e5f6: Verifying Checksum",
+            "a1b2c3d4e5f6: Download complete",
+            "a1b2c3d4e5f6: Pull complete",
+            "Digest: sha256:0d6d8a3e1b2f",
+        ] {
+            progress.scan_line(line);
+        }
+        assert_eq!(progress.message("a"), "pulling image `a`: 2/3 layers");
+
+        let mut progress = PullProgress::default();
+        for line in [
+            "Trying to pull ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main...",
+            "Getting image source signatures",
+            "Copying blob sha256:4f4fb700ef54 skipped: already exists",
+            "Copying blob a1b2c3d4e5f6 done",
+            "Copying blob 0123456789ab",
+            "Copying config 9e8d7c6b5a4f done",
+        ] {
+            progress.scan_line(line);
+        }
+        assert_eq!(progress.message("a"), "pulling image `a`: 2/3 layers");
+    }
+}
@@ -1,21 +1,25 @@
 use std::collections::BTreeMap;
-use std::io::{self, BufRead, Read, Write};
+use std::io::{self, Read, Write};
 use std::path::Path;
 use std::process::{Command, ExitStatus};
-use std::{env, fs, time};
+use std::sync::mpsc;
+use std::time::Instant;
+use std::{env, fmt, fs, thread};
 
 use eyre::Context;
 
-use super::engine::Engine;
+use super::backend::{operation_args, ContainerEngine};
+use super::pod;
+use super::pull;
 use super::shared::*;
+use super::spec::MountSpec;
 use crate::config::bool_from_envvar;
-use crate::errors::Result;
-use crate::extensions::CommandExt;
+use crate::errors::{ExitCode, FailureExt, Result, Section};
 use crate::file::{self, PathExt, ToUtf8};
 use crate::rustc::{self, QualifiedToolchain, VersionMetaExt};
 use crate::shell::{MessageInfo, Stream};
 use crate::temp;
-use crate::TargetTriple;
+use crate::{CargoVariant, TargetTriple};
 
 // prevent further commands from running if we handled
 // a signal earlier, and the volume is exited.
@@ -29,12 +33,6 @@ macro_rules! bail_container_exited {
     }};
 }
 
-#[track_caller]
-fn subcommand_or_exit(engine: &Engine, cmd: &str) -> Result<Command> {
-    bail_container_exited!();
-    Ok(engine.subcommand(cmd))
-}
-
 pub fn posix_parent(path: &str) -> Option<&str> {
     Path::new(path).parent()?.to_str()
 }
@@ -51,10 +49,13 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         msg_info: &mut MessageInfo,
     ) -> Result<ExitStatus> {
         // make our parent directory if needed
-        subcommand_or_exit(self.engine, "exec")?
-            .arg(self.container)
-            .args(["sh", "-c", &format!("mkdir -p '{mount_prefix}/{reldir}'")])
-            .run_and_get_status(msg_info, false)
+        bail_container_exited!();
+        self.engine.exec(
+            self.container,
+            &format!("mkdir -p '{mount_prefix}/{reldir}'"),
+            false,
+            msg_info,
+        )
     }
 
     // copy files for a docker volume, for remote host support
@@ -67,11 +68,48 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         mount_prefix: &str,
         msg_info: &mut MessageInfo,
     ) -> Result<ExitStatus> {
-        subcommand_or_exit(self.engine, "cp")?
-            .arg("-a")
-            .arg(src.to_utf8()?)
-            .arg(format!("{}:{mount_prefix}/{reldst}", self.container))
-            .run_and_get_status(msg_info, false)
+        if self.compress && src.is_dir() {
+            return self.copy_dir_compressed(src, reldst, mount_prefix, msg_info);
+        }
+        self.add_copied(path_size(src));
+        bail_container_exited!();
+        self.engine.copy_into(
+            self.container,
+            src,
+            &format!("{mount_prefix}/{reldst}"),
+            msg_info,
+        )
+    }
+
+    // copy a directory like `docker cp`, as a compressed tar stream. like
+    // `docker cp`, the directory is copied into `reldst` if it exists,
+    // otherwise it's copied as `reldst`, and only the contents are copied
+    // if `src` ends with `/.`.
+    #[track_caller]
+    fn copy_dir_compressed(
+        &self,
+        src: &Path,
+        reldst: &str,
+        mount_prefix: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let contents_only =
+            matches!(src.to_str(), Some(s) if s.ends_with("/.") || s.ends_with("\\."));
+        let dst = match src.file_name() {
+            Some(name)
+                if !contents_only
+                    && self.container_path_exists(reldst, mount_prefix, msg_info)? =>
+            {
+                format!("{mount_prefix}/{reldst}/{}", name.to_utf8()?)
+            }
+            _ => format!("{mount_prefix}/{reldst}"),
+        };
+        let mut files = vec![];
+        list_dir(src, "", true, 0, |_, _| false, &mut files)?;
+        self.add_copied(file::files_size(src, &files));
+        bail_container_exited!();
+        self.engine
+            .copy_files_into(self.container, src, &files, &dst, true, msg_info)
     }
 
     // copy files for a docker volume, for remote host support
@@ -86,12 +124,21 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
     ) -> Result<ExitStatus> {
         // avoid any cached directories when copying
         // see https://bford.info/cachedir/
-        // SAFETY: safe, single-threaded execution.
-        let tempdir = unsafe { temp::TempDir::new()? };
-        let temppath = tempdir.path();
-        let had_symlinks = copy_dir(src, temppath, copy_symlinks, 0, |e, _| is_cachedir(e))?;
+        // the files are streamed with tar, rather than copied to a
+        // temporary directory first, to avoid copying everything twice.
+        let mut files = vec![];
+        let had_symlinks = list_dir(src, "", copy_symlinks, 0, |e, _| is_cachedir(e), &mut files)?;
         warn_symlinks(had_symlinks, msg_info)?;
-        self.copy_files(&temppath.join("."), reldst, mount_prefix, msg_info)
+        self.add_copied(file::files_size(src, &files));
+        bail_container_exited!();
+        self.engine.copy_files_into(
+            self.container,
+            src,
+            &files,
+            &format!("{mount_prefix}/{reldst}"),
+            self.compress,
+            msg_info,
+        )
     }
 
     // copy files for a docker volume, for remote host support
@@ -105,7 +152,8 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         files: &[&str],
         msg_info: &mut MessageInfo,
     ) -> Result<ExitStatus> {
-        // SAFETY: safe, single-threaded execution.
+        // SAFETY: safe, only the main thread uses temporary files: the copies
+        // run on other threads with `spawn_copy` never create them.
         let tempdir = unsafe { temp::TempDir::new()? };
         let temppath = tempdir.path();
         for file in files {
@@ -143,7 +191,8 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
     "
         ));
 
-        // SAFETY: safe, single-threaded execution.
+        // SAFETY: safe, only the main thread uses temporary files: the copies
+        // run on other threads with `spawn_copy` never create them.
         let mut tempfile = unsafe { temp::TempFile::new()? };
         for file in files {
             writeln!(tempfile.file(), "{mount_prefix}/{reldst}/{file}")?;
@@ -151,15 +200,202 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
 
         // need to avoid having hundreds of files on the command, so
         // just provide a single file name.
-        subcommand_or_exit(self.engine, "cp")?
-            .arg(tempfile.path())
-            .arg(format!("{}:{PATH}", self.container))
-            .run_and_get_status(msg_info, true)?;
+        bail_container_exited!();
+        self.engine
+            .copy_into(self.container, tempfile.path(), PATH, msg_info)?;
+
+        bail_container_exited!();
+        self.engine
+            .exec(self.container, &script.join("\n"), true, msg_info)
+    }
 
-        subcommand_or_exit(self.engine, "exec")?
-            .arg(self.container)
-            .args(["sh", "-c", &script.join("\n")])
-            .run_and_get_status(msg_info, true)
+    // read the fingerprint of a mount stored in the volume, if any.
+    #[track_caller]
+    fn read_fingerprint(
+        &self,
+        relpath: &str,
+        mount_prefix: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Option<Fingerprint>> {
+        bail_container_exited!();
+        let contents = self.engine.exec_and_get_stdout(
+            self.container,
+            &format!("cat '{mount_prefix}/{relpath}' 2>/dev/null || true"),
+            msg_info,
+        )?;
+        match contents.is_empty() {
+            true => Ok(None),
+            false => Fingerprint::parse(&contents).map(Some),
+        }
+    }
+
+    #[track_caller]
+    fn write_fingerprint(
+        &self,
+        fingerprint: &Fingerprint,
+        relpath: &str,
+        mount_prefix: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()> {
+        // SAFETY: safe, only the main thread uses temporary files: the copies
+        // run on other threads with `spawn_copy` never create them.
+        let mut tempfile = unsafe { temp::TempFile::new()? };
+        tempfile
+            .file()
+            .write_all(fingerprint.to_string().as_bytes())?;
+        self.create_dir(FINGERPRINT_DIR, mount_prefix, msg_info)?;
+        self.copy_files(tempfile.path(), relpath, mount_prefix, msg_info)?;
+
+        Ok(())
+    }
+
+    // files in the volume that conflict with files in the image are left
+    // over from earlier runs with a persistent volume, so they're removed
+    // from the volume, which leaves the files of the image in place. files
+    // under the mounts copied for this run are never removed, since the
+    // build would silently use the files of the image instead.
+    #[track_caller]
+    fn clean_conflicts(
+        &self,
+        options: &DockerOptions,
+        conflicts: &[&str],
+        mounts: &[String],
+        mount_prefix: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()> {
+        let (copied, stale): (Vec<&str>, Vec<&str>) = conflicts
+            .iter()
+            .partition(|path| is_under_mounts(path, mounts));
+        if !copied.is_empty() {
+            let list = copied.join("\n  ");
+            return Err(eyre::eyre!(
+                "files copied into the data volume conflict with files in the image:\n  {list}"
+            ))
+            .with_suggestion(|| {
+                "mount the project or volumes at a path the image doesn't provide."
+            });
+        }
+
+        let clean = options
+            .config
+            .env()
+            .bool_var("CROSS_REMOTE_CLEAN_CONFLICTS")
+            .unwrap_or_default();
+        let list = stale.join("\n  ");
+        if !clean {
+            return Err(eyre::eyre!(
+                "the data volume has files that conflict with the image:\n  {list}"
+            ))
+            .with_suggestion(|| {
+                "set `CROSS_REMOTE_CLEAN_CONFLICTS=1` to remove them from the volume and retry, or recreate the volume with `cross-util volumes remove`."
+            });
+        }
+
+        msg_info.warn(format_args!(
+            "removing files from the data volume that conflict with the image:\n  {list}"
+        ))?;
+        let relpaths: Vec<&str> = stale
+            .iter()
+            .map(|path| path.trim_start_matches('/'))
+            .collect();
+        self.remove_file_list("", mount_prefix, &relpaths, msg_info)?;
+
+        Ok(())
+    }
+
+    fn add_copied(&self, bytes: u64) {
+        self.copied.set(self.copied.get() + bytes);
+    }
+
+    // report the start and end of a copy, since copying to a remote daemon
+    // can take minutes without any other output.
+    fn report_copy(
+        &self,
+        what: &str,
+        msg_info: &mut MessageInfo,
+        copy: impl FnOnce(&mut MessageInfo) -> Result<()>,
+    ) -> Result<()> {
+        self.report(format!("copying {what}"), msg_info)?;
+        let start = Instant::now();
+        let before = self.copied.get();
+        copy(msg_info)?;
+        self.report(
+            format!(
+                "copied {what}: {} in {:.1}s",
+                file::format_size(self.copied.get() - before),
+                start.elapsed().as_secs_f64()
+            ),
+            msg_info,
+        )
+    }
+
+    // copies running in parallel send their reports to the thread printing
+    // them, rather than writing to stderr at the same time.
+    fn report(&self, message: String, msg_info: &mut MessageInfo) -> Result<()> {
+        match &self.reporter {
+            Some(reporter) => {
+                // the printer only stops once every copy has finished.
+                reporter.send(message).ok();
+                Ok(())
+            }
+            None => msg_info.status(message),
+        }
+    }
+
+    // a data volume for the same container, which sends its reports to `reporter`.
+    fn reporting_to(&self, reporter: &mpsc::Sender<String>) -> ContainerDataVolume<'a, 'b, 'c> {
+        let mut volume = ContainerDataVolume::new(self.engine, self.container, self.toolchain_dirs);
+        volume.compress = self.compress;
+        volume.reporter = Some(reporter.clone());
+        volume
+    }
+
+    // run a copy on another thread, with a data volume for the same container.
+    // the copy runs in parallel with others, so it reports to `reporter`, and
+    // without transient progress messages.
+    fn spawn_copy<F>(
+        &self,
+        reporter: &mpsc::Sender<String>,
+        msg_info: &MessageInfo,
+        copy: F,
+    ) -> thread::JoinHandle<Result<()>>
+    where
+        F: FnOnce(&ContainerDataVolume<'_, '_, '_>, &mut MessageInfo) -> Result<()>
+            + Send
+            + 'static,
+    {
+        let engine = self.engine.clone();
+        let container = self.container.to_owned();
+        let toolchain_dirs = self.toolchain_dirs.clone();
+        let compress = self.compress;
+        let reporter = reporter.clone();
+        let mut info = MessageInfo::new(msg_info.color_choice, msg_info.verbosity);
+        info.show_progress = false;
+        thread::spawn(move || {
+            let mut volume = ContainerDataVolume::new(&engine, &container, &toolchain_dirs);
+            volume.compress = compress;
+            volume.reporter = Some(reporter);
+            copy(&volume, &mut info)
+        })
+    }
+
+    // compression needs `zstd` on the host and in the image.
+    #[track_caller]
+    fn supports_zstd(&self, msg_info: &mut MessageInfo) -> Result<bool> {
+        if which::which("zstd").is_err() {
+            msg_info.warn("`zstd` was not found, copying data uncompressed")?;
+            return Ok(false);
+        }
+        bail_container_exited!();
+        let in_image = self
+            .engine
+            .exec(self.container, "command -v zstd", true, msg_info)?
+            .success();
+        if !in_image {
+            msg_info.warn("the image has no `zstd`, copying data uncompressed")?;
+        }
+
+        Ok(in_image)
     }
 
     #[track_caller]
@@ -169,22 +405,55 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         mount_prefix: &str,
         msg_info: &mut MessageInfo,
     ) -> Result<bool> {
-        Ok(subcommand_or_exit(self.engine, "exec")?
-            .arg(self.container)
-            .args([
-                "bash",
-                "-c",
-                &format!("[[ -d '{mount_prefix}/{relpath}' ]]"),
-            ])
-            .run_and_get_status(msg_info, true)?
+        bail_container_exited!();
+        Ok(self
+            .engine
+            .exec(
+                self.container,
+                &format!("[ -d '{mount_prefix}/{relpath}' ]"),
+                true,
+                msg_info,
+            )?
             .success())
     }
 
+    // `cp -a` should preserve ownership, but some engines and storage
+    // drivers silently drop it, so copy a sentinel file and check the
+    // ownership it ends up with inside the container.
+    #[track_caller]
+    fn copy_preserves_ownership(
+        &self,
+        mount_prefix: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<bool> {
+        const SENTINEL: &str = ".cross-ownership";
+        // SAFETY: safe, only the main thread uses temporary files: the copies
+        // run on other threads with `spawn_copy` never create them.
+        let tempdir = unsafe { temp::TempDir::new()? };
+        let temppath = tempdir.path();
+        fs::write(temppath.join(SENTINEL), b"")?;
+        self.copy_files(&temppath.join("."), "", mount_prefix, msg_info)?;
+
+        let path = format!("{mount_prefix}/{SENTINEL}");
+        bail_container_exited!();
+        let stdout = self.engine.exec_and_get_stdout(
+            self.container,
+            &format!("stat -c '%u:%g' '{path}' && rm -f '{path}'"),
+            msg_info,
+        )?;
+        let expected = format!("{}:{}", user_id(), group_id());
+
+        Ok(stdout.trim() == expected)
+    }
+
     #[track_caller]
     pub fn copy_xargo(&self, mount_prefix: &str, msg_info: &mut MessageInfo) -> Result<()> {
         let dirs = &self.toolchain_dirs;
         let reldst = dirs.xargo_mount_path_relative()?;
-        if dirs.xargo().exists() {
+        if !dirs.xargo().exists() {
+            return Ok(());
+        }
+        self.report_copy("xargo", msg_info, |msg_info| {
             self.create_dir(
                 // this always works, even if we have `/xargo`, since
                 // this will be an absolute path. passing an empty path
@@ -197,9 +466,8 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
                 msg_info,
             )?;
             self.copy_files(dirs.xargo(), &reldst, mount_prefix, msg_info)?;
-        }
-
-        Ok(())
+            Ok(())
+        })
     }
 
     #[track_caller]
@@ -215,11 +483,25 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
             .map(|s| bool_from_envvar(&s))
             .unwrap_or(copy_registry);
 
+        self.report_copy("cargo", msg_info, |msg_info| {
+            self.copy_cargo_files(&reldst, mount_prefix, copy_registry, msg_info)
+        })
+    }
+
+    #[track_caller]
+    fn copy_cargo_files(
+        &self,
+        reldst: &str,
+        mount_prefix: &str,
+        copy_registry: bool,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()> {
+        let dirs = &self.toolchain_dirs;
         if copy_registry {
-            self.copy_files(dirs.cargo(), &reldst, mount_prefix, msg_info)?;
+            self.copy_files(dirs.cargo(), reldst, mount_prefix, msg_info)?;
         } else {
             // can copy a limit subset of files: the rest is present.
-            self.create_dir(&reldst, mount_prefix, msg_info)?;
+            self.create_dir(reldst, mount_prefix, msg_info)?;
             for entry in fs::read_dir(dirs.cargo())
                 .wrap_err_with(|| format!("when reading directory {:?}", dirs.cargo()))?
             {
@@ -230,7 +512,7 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
                     .wrap_err_with(|| format!("when reading file {file:?}"))?
                     .to_owned();
                 if !basename.starts_with('.') && !matches!(basename.as_ref(), "git" | "registry") {
-                    self.copy_files(&file.path(), &reldst, mount_prefix, msg_info)?;
+                    self.copy_files(&file.path(), reldst, mount_prefix, msg_info)?;
                 }
             }
         }
@@ -258,7 +540,8 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         // we can pretty safely assume we don't have symlinks here.
 
         // first, copy the shared libraries inside lib, all except rustlib.
-        // SAFETY: safe, single-threaded execution.
+        // SAFETY: safe, only the main thread uses temporary files: the copies
+        // run on other threads with `spawn_copy` never create them.
         let tempdir = unsafe { temp::TempDir::new()? };
         let temppath = tempdir.path();
         file::create_dir_all(temppath.join(rustlib))?;
@@ -292,7 +575,8 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         let reldst = dirs.sysroot_mount_path_relative()?;
         let rustlib = "lib/rustlib";
 
-        // SAFETY: safe, single-threaded execution.
+        // SAFETY: safe, only the main thread uses temporary files: the copies
+        // run on other threads with `spawn_copy` never create them.
         let tempdir = unsafe { temp::TempDir::new()? };
         let temppath = tempdir.path();
         file::create_dir_all(temppath.join(rustlib))?;
@@ -335,16 +619,19 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         if skip_exists {
             skip = self.container_path_exists(&reldst_toolchain, mount_prefix, msg_info)?;
         }
-        if !skip {
-            self.copy_files(&src_toolchain, &reldst_rustlib, mount_prefix, msg_info)?;
-        }
-        if !skip && skip_exists {
-            // this means we have a persistent data volume and we have a
-            // new target, meaning we might have new manifests as well.
-            self.copy_rust_manifest(mount_prefix, msg_info)?;
+        if skip {
+            return Ok(());
         }
-
-        Ok(())
+        let what = format!("the rust toolchain for `{target_triple}`");
+        self.report_copy(&what, msg_info, |msg_info| {
+            self.copy_files(&src_toolchain, &reldst_rustlib, mount_prefix, msg_info)?;
+            if skip_exists {
+                // this means we have a persistent data volume and we have a
+                // new target, meaning we might have new manifests as well.
+                self.copy_rust_manifest(mount_prefix, msg_info)?;
+            }
+            Ok(())
+        })
     }
 
     #[track_caller]
@@ -356,8 +643,10 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
     ) -> Result<()> {
         let dirs = &self.toolchain_dirs;
 
-        self.copy_rust_base(mount_prefix, msg_info)?;
-        self.copy_rust_manifest(mount_prefix, msg_info)?;
+        self.report_copy("the rust toolchain", msg_info, |msg_info| {
+            self.copy_rust_base(mount_prefix, msg_info)?;
+            self.copy_rust_manifest(mount_prefix, msg_info)
+        })?;
         self.copy_rust_triple(dirs.host_target(), mount_prefix, false, msg_info)?;
         if let Some(target_triple) = target_triple {
             if target_triple.triple() != dirs.host_target().triple() {
@@ -385,43 +674,70 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
                 self.copy_files_nocache(src, reldst, mount_prefix, true, info)
             }
         };
-        match volume {
-            VolumeId::Keep(_) => {
-                let parent = temp::dir()?;
-                file::create_dir_all(&parent)?;
-
-                let toolchain = &self.toolchain_dirs.toolchain();
-                let filename = toolchain.unique_mount_identifier(src)?;
-                let fingerprint = parent.join(filename);
-                let current = Fingerprint::read_dir(src, copy_cache)?;
-                // need to check if the container path exists, otherwise we might
-                // have stale data: the persistent volume was deleted & recreated.
-                if fingerprint.exists()
-                    && self.container_path_exists(reldst, mount_prefix, msg_info)?
-                {
-                    let previous = Fingerprint::read_file(&fingerprint)?;
-                    let (to_copy, to_remove) = previous.difference(&current);
-                    if !to_copy.is_empty() {
-                        self.copy_file_list(src, reldst, mount_prefix, &to_copy, msg_info)?;
-                    }
-                    if !to_remove.is_empty() {
-                        self.remove_file_list(reldst, mount_prefix, &to_remove, msg_info)?;
+        self.report_copy(&format!("{src:?}"), msg_info, |msg_info| {
+            match volume {
+                VolumeId::Keep(_) => {
+                    let toolchain = &self.toolchain_dirs.toolchain();
+                    let relpath = format!(
+                        "{FINGERPRINT_DIR}/{}",
+                        toolchain.unique_mount_identifier(src)?
+                    );
+                    let mut current = Fingerprint::read_dir(src, copy_cache)?;
+                    // the fingerprint is stored in the volume, so it can't be
+                    // stale if the persistent volume was deleted & recreated.
+                    match self.read_fingerprint(&relpath, mount_prefix, msg_info)? {
+                        Some(previous) => {
+                            let (to_copy, to_remove) = previous.difference(&mut current, src)?;
+                            msg_info.debug(format_args!(
+                                "copying {} changed files and removing {} files from {src:?}",
+                                to_copy.len(),
+                                to_remove.len()
+                            ))?;
+                            if !to_copy.is_empty() {
+                                let to_copy: Vec<&str> =
+                                    to_copy.iter().map(String::as_str).collect();
+                                self.copy_file_list(src, reldst, mount_prefix, &to_copy, msg_info)?;
+                            }
+                            if !to_remove.is_empty() {
+                                let to_remove: Vec<&str> =
+                                    to_remove.iter().map(String::as_str).collect();
+                                self.remove_file_list(reldst, mount_prefix, &to_remove, msg_info)?;
+                            }
+                        }
+                        None => {
+                            copy_all(msg_info)?;
+                        }
                     }
 
                     // write fingerprint afterwards, in case any failure so we
                     // ensure any changes will be made on subsequent runs
-                    current.write_file(&fingerprint)?;
-                } else {
-                    current.write_file(&fingerprint)?;
+                    self.write_fingerprint(&current, &relpath, mount_prefix, msg_info)?;
+                }
+                VolumeId::Discard => {
                     copy_all(msg_info)?;
                 }
             }
-            VolumeId::Discard => {
-                copy_all(msg_info)?;
-            }
-        }
 
-        Ok(())
+            Ok(())
+        })
+    }
+}
+
+// the size of a file, or the files in a directory, without following symlinks.
+fn path_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| path_size(&entry.path()))
+            .sum(),
+        Err(_) => 0,
     }
 }
 
@@ -503,6 +819,52 @@ where
     Ok(had_symlinks)
 }
 
+// recursively list the files in a directory, relative to the root directory,
+// with directories before their contents.
+fn list_dir<Skip>(
+    src: &Path,
+    relpath: &str,
+    copy_symlinks: bool,
+    depth: u32,
+    skip: Skip,
+    files: &mut Vec<String>,
+) -> Result<bool>
+where
+    Skip: Copy + Fn(&fs::DirEntry, u32) -> bool,
+{
+    let mut had_symlinks = false;
+
+    for entry in fs::read_dir(src).wrap_err_with(|| format!("when reading directory {src:?}"))? {
+        let file = entry?;
+        if skip(&file, depth) {
+            continue;
+        }
+
+        let name = file.file_name();
+        let name = name
+            .to_utf8()
+            .wrap_err_with(|| format!("when reading file {file:?}"))?;
+        let path = match relpath.is_empty() {
+            true => name.to_owned(),
+            false => format!("{relpath}/{name}"),
+        };
+        let file_type = file.file_type()?;
+        if file_type.is_file() {
+            files.push(path);
+        } else if file_type.is_dir() {
+            files.push(path.clone());
+            had_symlinks |= list_dir(&file.path(), &path, copy_symlinks, depth + 1, skip, files)?;
+        } else {
+            had_symlinks = true;
+            if copy_symlinks {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(had_symlinks)
+}
+
 fn warn_symlinks(had_symlinks: bool, msg_info: &mut MessageInfo) -> Result<()> {
     if had_symlinks {
         msg_info.warn("copied directory contained symlinks. if the volume the link points to was not mounted, the remote build may fail")
@@ -511,9 +873,64 @@ fn warn_symlinks(had_symlinks: bool, msg_info: &mut MessageInfo) -> Result<()> {
     }
 }
 
-#[derive(Debug)]
+/// The file in the container touched before the build starts.
+const BUILD_START: &str = "/tmp/.cross-build-start";
+
+/// The prefix of the lines the symlink script prints for files in the image
+/// that are in the way of files in the volume.
+const CONFLICT_PREFIX: &str = "conflict: ";
+
+fn symlink_conflicts(stdout: &str) -> Vec<&str> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix(CONFLICT_PREFIX))
+        .collect()
+}
+
+/// Check if a path in the container is one of the mounts, or within them.
+fn is_under_mounts(path: &str, mounts: &[String]) -> bool {
+    mounts.iter().any(|mount| {
+        let mount = mount.trim_end_matches('/');
+        path == mount || matches!(path.strip_prefix(mount), Some(rest) if rest.starts_with('/'))
+    })
+}
+
+/// The directory in the data volume where the fingerprints of the mounts are
+/// stored, relative to the mount prefix.
+const FINGERPRINT_DIR: &str = ".cross-fingerprint";
+
+/// A 64-bit FNV-1a hash of the contents of a file, which is stable across
+/// versions of Rust, unlike the hashers of the standard library.
+fn file_hash(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = [0; 8192];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        for byte in &buffer[..count] {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    Ok(format!("{hash:016x}"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileFingerprint {
+    /// The modified time, in milliseconds since the Unix epoch.
+    modified: u64,
+    size: u64,
+    /// The hash of the contents, only computed once the file changed.
+    hash: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
 struct Fingerprint {
-    map: BTreeMap<String, time::SystemTime>,
+    map: BTreeMap<String, FileFingerprint>,
 }
 
 impl Fingerprint {
@@ -523,36 +940,33 @@ impl Fingerprint {
         }
     }
 
-    fn read_file(path: &Path) -> Result<Self> {
-        let file = fs::OpenOptions::new().read(true).open(path)?;
-        let reader = io::BufReader::new(file);
+    // each line has the format `modified\tsize\thash\trelpath`, with `-`
+    // as the hash if it's unknown.
+    fn parse(contents: &str) -> Result<Self> {
         let mut map = BTreeMap::new();
-        for line in reader.lines() {
-            let line = line?;
-            let (timestamp, relpath) = line
-                .split_once('\t')
-                .ok_or_else(|| eyre::eyre!("unable to parse fingerprint line '{line}'"))?;
-            let modified = time_from_millis(timestamp.parse::<u64>()?);
-            map.insert(relpath.to_owned(), modified);
+        for line in contents.lines() {
+            let mut fields = line.splitn(4, '\t');
+            let (modified, size, hash, relpath) =
+                match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                    (Some(modified), Some(size), Some(hash), Some(relpath)) => {
+                        (modified, size, hash, relpath)
+                    }
+                    _ => eyre::bail!("unable to parse fingerprint line '{line}'"),
+                };
+            let file = FileFingerprint {
+                modified: modified.parse()?,
+                size: size.parse()?,
+                hash: match hash {
+                    "-" => None,
+                    hash => Some(hash.to_owned()),
+                },
+            };
+            map.insert(relpath.to_owned(), file);
         }
 
         Ok(Self { map })
     }
 
-    fn write_file(&self, path: &Path) -> Result<()> {
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path)?;
-        for (relpath, modified) in &self.map {
-            let timestamp = time_to_millis(modified)?;
-            writeln!(file, "{timestamp}\t{relpath}")?;
-        }
-
-        Ok(())
-    }
-
     fn _read_dir(&mut self, home: &Path, path: &Path, copy_cache: bool) -> Result<()> {
         for entry in fs::read_dir(path)? {
             let file = entry?;
@@ -565,10 +979,16 @@ impl Fingerprint {
             } else if file_type.is_file() || file_type.is_symlink() {
                 // we're mounting to the same location, so this should fine
                 // we need to round the modified date to millis.
-                let modified = file.metadata()?.modified()?;
-                let rounded = time_from_millis(time_to_millis(&modified)?);
+                let metadata = file.metadata()?;
                 let relpath = file.path().strip_prefix(home)?.as_posix_relative()?;
-                self.map.insert(relpath, rounded);
+                self.map.insert(
+                    relpath,
+                    FileFingerprint {
+                        modified: time_to_millis(&metadata.modified()?)?,
+                        size: metadata.len(),
+                        hash: None,
+                    },
+                );
             }
         }
 
@@ -581,26 +1001,71 @@ impl Fingerprint {
         Ok(result)
     }
 
-    // returns to_copy (added + modified) and to_remove (removed).
-    fn difference<'a, 'b>(&'a self, current: &'b Fingerprint) -> (Vec<&'b str>, Vec<&'a str>) {
-        let to_copy: Vec<&str> = current
-            .map
-            .iter()
-            .filter(|(k, v1)| self.map.get(*k).map_or(true, |v2| v1 != &v2))
-            .map(|(k, _)| k.as_str())
-            .collect();
-        let to_remove: Vec<&str> = self
+    // returns to_copy (added + modified) and to_remove (removed). files
+    // with a new modified time but the same size are hashed, so files that
+    // were only touched, such as after switching branches, aren't copied.
+    // the hashes are stored in `current` for the next run.
+    fn difference(
+        &self,
+        current: &mut Fingerprint,
+        home: &Path,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let mut to_copy = vec![];
+        for (relpath, file) in &mut current.map {
+            let previous = match self.map.get(relpath) {
+                Some(previous) if previous.size == file.size => previous,
+                _ => {
+                    to_copy.push(relpath.clone());
+                    continue;
+                }
+            };
+            if previous.modified == file.modified {
+                file.hash = previous.hash.clone();
+                continue;
+            }
+            let hash = file_hash(&home.join(relpath))?;
+            if previous.hash.as_ref() != Some(&hash) {
+                to_copy.push(relpath.clone());
+            }
+            file.hash = Some(hash);
+        }
+        let to_remove = self
             .map
-            .iter()
-            .filter(|(k, _)| !current.map.contains_key(*k))
-            .map(|(k, _)| k.as_str())
+            .keys()
+            .filter(|relpath| !current.map.contains_key(*relpath))
+            .cloned()
             .collect();
-        (to_copy, to_remove)
+
+        Ok((to_copy, to_remove))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (relpath, file) in &self.map {
+            let hash = file.hash.as_deref().unwrap_or("-");
+            writeln!(f, "{}\t{}\t{hash}\t{relpath}", file.modified, file.size)?;
+        }
+
+        Ok(())
     }
 }
 
 impl QualifiedToolchain {
     pub fn unique_toolchain_identifier(&self) -> Result<String> {
+        Ok(clamp_identifier(
+            self.toolchain_identifier(PATH_HASH_SHORT)?,
+            MAX_IDENTIFIER_LENGTH,
+        ))
+    }
+
+    // the identifier used by older versions, with a shorter hash and no
+    // length limit, so existing persistent volumes can still be found.
+    pub fn legacy_toolchain_identifier(&self) -> Result<String> {
+        self.toolchain_identifier(PATH_HASH_LEGACY)
+    }
+
+    fn toolchain_identifier(&self, hash_length: usize) -> Result<String> {
         // try to get the commit hash for the currently toolchain, if possible
         // if not, get the default rustc and use the path hash for uniqueness
         let commit_hash = if let Some(version) = self.rustc_version_string()? {
@@ -614,7 +1079,7 @@ impl QualifiedToolchain {
             .file_name()
             .expect("should be able to get toolchain name")
             .to_utf8()?;
-        let toolchain_hash = path_hash(self.get_sysroot(), PATH_HASH_SHORT)?;
+        let toolchain_hash = path_hash(self.get_sysroot(), hash_length)?;
         Ok(format!(
             "{VOLUME_PREFIX}{toolchain_name}-{toolchain_hash}-{commit_hash}"
         ))
@@ -623,32 +1088,62 @@ impl QualifiedToolchain {
     // unique identifier for a given container. allows the ID to
     // be generated outside a rust package and run multiple times.
     pub fn unique_container_identifier(&self, triple: &TargetTriple) -> Result<String> {
+        let prefix = self.container_identifier_prefix(triple)?;
+        let system_time = now_as_millis()?;
+        Ok(format!("{prefix}-{system_time}"))
+    }
+
+    // the prefix shared by all containers for a target run from the current directory.
+    pub fn container_identifier_prefix(&self, triple: &TargetTriple) -> Result<String> {
         let toolchain_id = self.unique_toolchain_identifier()?;
         let cwd_path = path_hash(&env::current_dir()?, PATH_HASH_SHORT)?;
-        let system_time = now_as_millis()?;
-        Ok(format!("{toolchain_id}-{triple}-{cwd_path}-{system_time}"))
+        // leave room for the `-{millis}` suffix of the container identifier
+        Ok(clamp_identifier(
+            format!("{toolchain_id}-{triple}-{cwd_path}"),
+            MAX_IDENTIFIER_LENGTH - 14,
+        ))
+    }
+
+    // unique identifier for the xargo sysroot cache of a target. the
+    // sysroot only depends on the target and the rustc commit.
+    pub fn unique_xargo_identifier(&self, triple: &TargetTriple) -> Result<String> {
+        let commit_hash = if let Some(version) = self.rustc_version_string()? {
+            rustc::hash_from_version_string(&version, 1)
+        } else {
+            rustc::version_meta()?.commit_hash()
+        };
+        Ok(clamp_identifier(
+            format!("{VOLUME_PREFIX}xargo-{triple}-{commit_hash}"),
+            MAX_IDENTIFIER_LENGTH,
+        ))
     }
 
     // unique identifier for a given mounted volume
     pub fn unique_mount_identifier(&self, path: &Path) -> Result<String> {
         let toolchain_id = self.unique_toolchain_identifier()?;
         let mount_hash = path_hash(path, PATH_HASH_UNIQUE)?;
-        Ok(format!("{toolchain_id}-{mount_hash}"))
+        Ok(clamp_identifier(
+            format!("{toolchain_id}-{mount_hash}"),
+            MAX_IDENTIFIER_LENGTH,
+        ))
     }
 }
 
 pub(crate) fn run(
-    options: DockerOptions,
+    mut options: DockerOptions,
     paths: DockerPaths,
     args: &[String],
     msg_info: &mut MessageInfo,
 ) -> Result<ExitStatus> {
+    options.record_image_use(msg_info)?;
+    options.verify_image(msg_info)?;
     let engine = &options.engine;
     let target = &options.target;
     let toolchain_dirs = paths.directories.toolchain_directories();
     let package_dirs = paths.directories.package_directories();
 
-    let mount_prefix = MOUNT_PREFIX;
+    let prefix = options.config.remote_mount_prefix()?;
+    let mount_prefix = prefix.as_str();
 
     if options.in_docker() {
         msg_info.warn("remote and docker-in-docker are unlikely to work together when using cross. remote cross uses data volumes, so docker-in-docker should not be required.")?;
@@ -663,6 +1158,8 @@ pub(crate) fn run(
     //      if not present, the toolchain for the current target.
     //      otherwise, copy the entire toolchain, cargo, and crate data
     //      if `CROSS_REMOTE_COPY_CACHE`, copy over the target dir as well
+    //      with xargo and a discarded data volume, the sysroot is cached
+    //      in a volume keyed by the target and rustc commit.
     // 5. create symlinks for all mounted data
     //      ensure the paths are the same as local cross
     // 6. execute our cargo command inside the container
@@ -680,8 +1177,15 @@ pub(crate) fn run(
     let container_id = toolchain_dirs.unique_container_identifier(target.target())?;
     let volume = {
         let existing = DockerVolume::existing(engine, toolchain_dirs.toolchain(), msg_info)?;
+        let legacy_id = toolchain_dirs.legacy_toolchain_identifier()?;
         if existing.iter().any(|v| v == &toolchain_id) {
             VolumeId::Keep(toolchain_id)
+        } else if existing.iter().any(|v| v == &legacy_id) {
+            msg_info.note(format_args!(
+                "using the persistent volume `{legacy_id}` created by an older version of cross.\n > Recreate it with `cross-util volumes remove --toolchain {0}` and `cross-util volumes create --toolchain {0}`",
+                toolchain_dirs.toolchain()
+            ))?;
+            VolumeId::Keep(legacy_id)
         } else {
             let partial = format!("{VOLUME_PREFIX}{}", toolchain_dirs.toolchain());
             if existing.iter().any(|v| v.starts_with(&partial)) {
@@ -710,15 +1214,51 @@ pub(crate) fn run(
     // if we're using a discarded volume.
 
     // 3. create our start container command here
-    let mut docker = engine.subcommand("run");
-    docker.add_userns();
+    let mut docker = Command::new("run");
+    docker.add_userns(&engine.capabilities);
     options
         .image
         .platform
         .specify_platform(&options.engine, &mut docker);
     docker.args(["--name", &container_id]);
     docker.arg("--rm");
-    docker.args(["-v", &volume.mount(mount_prefix)]);
+    docker.args(options.labels(&paths).args());
+    // the services are removed when the pod is dropped, after the container.
+    let pod = pod::Pod::start(&options, &paths, &container_id, msg_info)?;
+    if let Some(pod) = &pod {
+        docker.args(pod.container_args());
+    }
+    docker.args(volume.mount(mount_prefix)?.args());
+
+    // the sysroot built by xargo would otherwise be rebuilt on every run
+    // with a discarded data volume, so store it in a volume keyed by the
+    // target and rustc commit, like a persistent data volume would.
+    let cache_xargo = options
+        .config
+        .env()
+        .bool_var("CROSS_REMOTE_XARGO_CACHE")
+        .unwrap_or(true);
+    if cache_xargo
+        && options.cargo_variant == CargoVariant::Xargo
+        && matches!(volume, VolumeId::Discard)
+    {
+        let xargo_id = toolchain_dirs.unique_xargo_identifier(target.target())?;
+        msg_info.debug(format_args!(
+            "caching the xargo sysroot in volume `{xargo_id}`"
+        ))?;
+        // create it first, so it has the labels of cross
+        let xargo_volume = DockerVolume::new(engine, &xargo_id);
+        if !xargo_volume.exists(msg_info)? {
+            xargo_volume.create(&options.labels(&paths), msg_info)?;
+        }
+        docker.args(
+            MountSpec::new(
+                &xargo_id,
+                format!("{mount_prefix}{}", toolchain_dirs.xargo_mount_path()),
+            )?
+            .args(),
+        );
+    }
 
     let mut volumes = vec![];
     docker
@@ -732,11 +1272,11 @@ pub(crate) fn run(
         .wrap_err("could not determine mount points")?;
 
     docker
-        .add_seccomp(engine.kind, target, &paths.metadata)
+        .add_seccomp(&engine.capabilities, target, &paths.metadata)
         .wrap_err("when copying seccomp profile")?;
 
     // Prevent `bin` from being mounted inside the Docker container.
-    docker.args(["-v", &format!("{mount_prefix}/cargo/bin")]);
+    docker.args(MountSpec::anonymous(format!("{mount_prefix}/cargo/bin"))?.args());
 
     // When running inside NixOS or using Nix packaging we need to add the Nix
     // Store to the running container so it can load the needed binaries.
@@ -756,9 +1296,25 @@ pub(crate) fn run(
     if options.needs_custom_image() {
         image_name = options
             .custom_image_build(&paths, msg_info)
-            .wrap_err("when building custom image")?;
+            .wrap_err("when building custom image")
+            .failure(ExitCode::ContainerBuild)?;
+    }
+    pull::pull_image(engine, &options.image.platform, &image_name, msg_info)?;
+
+    if !options.image_has_shell(&image_name, msg_info)? {
+        return Err(eyre::eyre!("image `{image_name}` has no `/bin/sh`"))
+            .with_note(|| "remote cross requires a shell to copy data into the container.")
+            .with_suggestion(|| "unset `CROSS_REMOTE` to run the command directly.");
     }
 
+    options.check_entrypoint(&image_name, msg_info)?;
+    options.check_image_version(&image_name, msg_info)?;
+    // get this before starting the container, since it may run another one.
+    let qemu = options.managed_qemu(msg_info)?;
+    if let Some(volume) = options.wine_volume(&image_name, msg_info)? {
+        docker.args(MountSpec::new(volume, WINE_PREFIX_DIR)?.args());
+    }
+    docker.add_entrypoint(&options);
     docker.arg(&image_name);
 
     if !is_tty {
@@ -772,32 +1328,26 @@ pub(crate) fn run(
 
     // store first, since failing to non-existing container is fine
     ChildContainer::create(engine.clone(), container_id.clone())?;
-    docker.run_and_get_status(msg_info, true)?;
+    engine.run_container(&operation_args(&docker), true, msg_info)?;
 
     // 4. copy all mounted volumes over
-    let data_volume = ContainerDataVolume::new(engine, &container_id, toolchain_dirs);
-    let copy_cache = env::var("CROSS_REMOTE_COPY_CACHE")
-        .map(|s| bool_from_envvar(&s))
+    let mut data_volume = ContainerDataVolume::new(engine, &container_id, toolchain_dirs);
+    let compress = options
+        .config
+        .env()
+        .bool_var("CROSS_REMOTE_COMPRESS")
+        .unwrap_or_default();
+    if compress {
+        data_volume.compress = data_volume.supports_zstd(msg_info)?;
+    }
+    let copy_cache = options
+        .config
+        .env()
+        .bool_var("CROSS_REMOTE_COPY_CACHE")
         .unwrap_or_default();
     let copy = |src, reldst: &str, info: &mut MessageInfo| {
         data_volume.copy_mount(src, reldst, mount_prefix, &volume, copy_cache, info)
     };
-    if let VolumeId::Discard = volume {
-        data_volume
-            .copy_xargo(mount_prefix, msg_info)
-            .wrap_err("when copying xargo")?;
-        data_volume
-            .copy_cargo(mount_prefix, false, msg_info)
-            .wrap_err("when copying cargo")?;
-        data_volume
-            .copy_rust(Some(target.target()), mount_prefix, msg_info)
-            .wrap_err("when copying rust")?;
-    } else {
-        // need to copy over the target triple if it hasn't been previously copied
-        data_volume
-            .copy_rust_triple(target.target(), mount_prefix, true, msg_info)
-            .wrap_err("when copying rust target files")?;
-    }
     // cannot panic: absolute unix path, must have root
     let rel_mount_root = package_dirs
         .mount_root()
@@ -812,7 +1362,73 @@ pub(crate) fn run(
             )
             .wrap_err("when creating mount root")?;
     }
-    copy(package_dirs.host_root(), rel_mount_root, msg_info).wrap_err("when copying project")?;
+    if let VolumeId::Discard = volume {
+        // the copies have different destinations, so they're run at the same
+        // time. the rust toolchain is copied on this thread, since it's the
+        // only one that uses temporary files, which aren't thread-safe. the
+        // reports of every copy are printed by a single thread, and transient
+        // progress messages are disabled until they've all finished.
+        let (reporter, reports) = mpsc::channel::<String>();
+        let mut printer_info = MessageInfo::new(msg_info.color_choice, msg_info.verbosity);
+        let printer = thread::spawn(move || -> Result<()> {
+            for report in reports {
+                printer_info.status(report)?;
+            }
+            Ok(())
+        });
+        let host_root = package_dirs.host_root().to_owned();
+        let reldst = rel_mount_root.to_owned();
+        let (xargo_prefix, cargo_prefix, project_prefix) =
+            (prefix.clone(), prefix.clone(), prefix.clone());
+        let copies = [
+            data_volume.spawn_copy(&reporter, msg_info, move |volume, info| {
+                volume
+                    .copy_xargo(&xargo_prefix, info)
+                    .wrap_err("when copying xargo")
+            }),
+            data_volume.spawn_copy(&reporter, msg_info, move |volume, info| {
+                volume
+                    .copy_cargo(&cargo_prefix, false, info)
+                    .wrap_err("when copying cargo")
+            }),
+            data_volume.spawn_copy(&reporter, msg_info, move |volume, info| {
+                volume
+                    .copy_mount(
+                        &host_root,
+                        &reldst,
+                        &project_prefix,
+                        &VolumeId::Discard,
+                        copy_cache,
+                        info,
+                    )
+                    .wrap_err("when copying project")
+            }),
+        ];
+        let rust_volume = data_volume.reporting_to(&reporter);
+        drop(reporter);
+        msg_info.show_progress = false;
+        let copied_rust = rust_volume
+            .copy_rust(Some(target.target()), mount_prefix, msg_info)
+            .wrap_err("when copying rust");
+        let copied: Vec<_> = copies
+            .into_iter()
+            .map(|copy| copy.join().expect("the copy should not panic"))
+            .collect();
+        drop(rust_volume);
+        msg_info.show_progress = true;
+        printer.join().expect("the printer should not panic")?;
+        for copy in copied {
+            copy?;
+        }
+        copied_rust?;
+    } else {
+        // need to copy over the target triple if it hasn't been previously copied
+        data_volume
+            .copy_rust_triple(target.target(), mount_prefix, true, msg_info)
+            .wrap_err("when copying rust target files")?;
+        copy(package_dirs.host_root(), rel_mount_root, msg_info)
+            .wrap_err("when copying project")?;
+    }
     let sysroot = toolchain_dirs.get_sysroot().to_owned();
     let mut copied = vec![
         (
@@ -827,6 +1443,8 @@ pub(crate) fn run(
         (package_dirs.host_root(), rel_mount_root.to_owned()),
     ];
     let mut to_symlink = vec![];
+    // the paths in the container of everything copied for this run.
+    let mut mounts: Vec<String> = copied.iter().map(|(_, dst)| format!("/{dst}")).collect();
     let target_dir = file::canonicalize(package_dirs.target())?;
     let target_dir = if let Ok(relpath) = target_dir.strip_prefix(package_dirs.host_root()) {
         relpath.as_posix_relative()?
@@ -841,6 +1459,7 @@ pub(crate) fn run(
         }
 
         copied.push((package_dirs.target(), target_dir.clone()));
+        mounts.push(format!("/{target_dir}"));
         target_dir
     };
     for (src, dst) in &volumes {
@@ -864,6 +1483,7 @@ pub(crate) fn run(
                 )?;
             }
             copy(src, reldst, msg_info)?;
+            mounts.push(format!("/{reldst}"));
         }
     }
 
@@ -901,21 +1521,31 @@ pub(crate) fn run(
     if msg_info.is_verbose() {
         symlink.push("set -x".to_owned());
     }
-    symlink.push(format!(
-        "chown -R {uid}:{gid} {mount_prefix}",
-        uid = user_id(),
-        gid = group_id(),
-    ));
+    if !engine.capabilities.cp_archive
+        || !data_volume
+            .copy_preserves_ownership(mount_prefix, msg_info)
+            .wrap_err("when checking ownership of copied files")?
+    {
+        msg_info.debug("copied files lost their ownership, fixing permissions in the container")?;
+        symlink.push(format!(
+            "chown -R {uid}:{gid} {mount_prefix}",
+            uid = user_id(),
+            gid = group_id(),
+        ));
+    }
     // need a simple script to add symlinks, but not override existing files.
-    symlink.push(format!(
+    // the files in the way are reported, so stale files in the volume can be
+    // cleaned up, and the links from an earlier pass are kept.
+    let symlink_mounts = format!(
         "prefix=\"{mount_prefix}\"
 
 symlink_recurse() {{
     for f in \"${{1}}\"/*; do
         dst=${{f#\"$prefix\"}}
-        if [ -f \"${{dst}}\" ]; then
-            echo \"invalid: got unexpected file at ${{dst}}\" 1>&2
-            exit 1
+        if [ -L \"${{dst}}\" ] && [ \"$(readlink \"${{dst}}\")\" = \"${{f}}\" ]; then
+            continue
+        elif [ -f \"${{dst}}\" ]; then
+            echo \"{CONFLICT_PREFIX}${{dst}}\"
         elif [ -d \"${{dst}}\" ]; then
             symlink_recurse \"${{f}}\"
         else
@@ -926,49 +1556,188 @@ symlink_recurse() {{
 
 symlink_recurse \"${{prefix}}\"
 "
-    ));
+    );
+    symlink.push(symlink_mounts.clone());
     for (src, dst) in to_symlink {
         symlink.push(format!("ln -s \"{src}\" \"{dst}\"",));
     }
-    subcommand_or_exit(engine, "exec")?
-        .arg(&container_id)
-        .args(["sh", "-c", &symlink.join("\n")])
-        .run_and_get_status(msg_info, false)
+    bail_container_exited!();
+    let stdout = engine
+        .exec_and_get_stdout(&container_id, &symlink.join("\n"), msg_info)
         .wrap_err("when creating symlinks to provide consistent host/mount paths")?;
+    let conflicts = symlink_conflicts(&stdout);
+    if !conflicts.is_empty() {
+        data_volume.clean_conflicts(&options, &conflicts, &mounts, mount_prefix, msg_info)?;
+        // retry once, which only links the files that were in the way.
+        bail_container_exited!();
+        let stdout = engine
+            .exec_and_get_stdout(&container_id, &symlink_mounts, msg_info)
+            .wrap_err("when creating symlinks to provide consistent host/mount paths")?;
+        let conflicts = symlink_conflicts(&stdout);
+        if !conflicts.is_empty() {
+            eyre::bail!(
+                "the data volume still has files that conflict with the image:\n  {}",
+                conflicts.join("\n  ")
+            );
+        }
+    }
 
     // 6. execute our cargo command inside the container
-    let mut docker = engine.subcommand("exec");
-    docker.add_user_id(engine.kind);
+    let mut docker = Command::new("exec");
+    docker.add_user_id(&engine.capabilities);
     docker.add_envvars(&options, toolchain_dirs, msg_info)?;
-    docker.add_cwd(&paths)?;
+    if let Some((qemu, path)) = qemu {
+        docker.args(options.qemu_runner_env(&path)?.args());
+        bail_container_exited!();
+        engine
+            .exec(
+                &container_id,
+                &format!("mkdir -p {QEMU_DIR}"),
+                false,
+                msg_info,
+            )
+            .wrap_err("when creating the qemu directory")?;
+        engine
+            .copy_into(&container_id, &qemu, &path, msg_info)
+            .wrap_err("when installing qemu")?;
+    }
+    docker.add_cwd(&options, &paths)?;
     docker.arg(&container_id);
-    docker.add_build_command(toolchain_dirs, &cmd);
+    docker.add_build_command(&options, toolchain_dirs, &image_name, &cmd, msg_info)?;
+    // only the artifacts changed by the build are copied back, so mark when
+    // it started.
+    bail_container_exited!();
+    engine
+        .exec(
+            &container_id,
+            &format!("touch '{BUILD_START}'"),
+            true,
+            msg_info,
+        )
+        .wrap_err("when marking the start of the build")?;
     bail_container_exited!();
-    let status = docker
-        .run_and_get_status(msg_info, false)
-        .map_err(Into::into);
+    let status = engine.exec_build(&operation_args(&docker), msg_info);
 
     // 7. copy data from our target dir back to host
     // this might not exist if we ran `clean`.
-    let skip_artifacts = env::var("CROSS_REMOTE_SKIP_BUILD_ARTIFACTS")
-        .map(|s| bool_from_envvar(&s))
-        .unwrap_or_default();
+    let skip_artifacts = options.skip_build_artifacts
+        || options
+            .config
+            .env()
+            .bool_var("CROSS_REMOTE_SKIP_BUILD_ARTIFACTS")
+            .unwrap_or_default();
     bail_container_exited!();
     if !skip_artifacts && data_volume.container_path_exists(&target_dir, mount_prefix, msg_info)? {
-        subcommand_or_exit(engine, "cp")?
-            .arg("-a")
-            .arg(&format!("{container_id}:{target_dir}",))
-            .arg(
-                package_dirs
-                    .target()
-                    .parent()
-                    .expect("target directory should have a parent"),
-            )
-            .run_and_get_status(msg_info, false)
-            .map_err::<eyre::ErrReport, _>(Into::into)?;
+        let host_target = package_dirs.target();
+        let host_parent = host_target
+            .parent()
+            .expect("target directory should have a parent");
+        // the whole directory is copied if the artifacts on the host were
+        // removed, since the unchanged ones wouldn't be restored otherwise.
+        let host_has_artifacts = fs::read_dir(host_target)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or_default();
+        if host_has_artifacts {
+            engine.copy_newer_from(
+                &container_id,
+                &target_dir,
+                BUILD_START,
+                host_parent,
+                msg_info,
+            )?;
+        } else {
+            engine.copy_from(&container_id, &target_dir, host_parent, msg_info)?;
+        }
     }
 
     ChildContainer::finish_static(is_tty, msg_info);
 
     status
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts() {
+        let stdout =
+            "+ ln -s /cross/a /a\nconflict: /etc/os-release\nconflict: /project/Cargo.lock\n";
+        assert_eq!(
+            symlink_conflicts(stdout),
+            ["/etc/os-release", "/project/Cargo.lock"]
+        );
+        assert!(symlink_conflicts("").is_empty());
+
+        let mounts = ["/project".to_owned(), "/cross/cargo/".to_owned()];
+        assert!(is_under_mounts("/project", &mounts));
+        assert!(is_under_mounts("/project/Cargo.lock", &mounts));
+        assert!(is_under_mounts("/cross/cargo/config.toml", &mounts));
+        assert!(!is_under_mounts("/project2/Cargo.lock", &mounts));
+        assert!(!is_under_mounts("/etc/os-release", &mounts));
+    }
+
+    #[test]
+    fn list_dir_skips_cachedirs() -> Result<()> {
+        let src = tempfile::tempdir()?;
+        let src = src.path();
+        file::create_dir_all(src.join("src/bin"))?;
+        file::create_dir_all(src.join("target/debug"))?;
+        fs::write(src.join("Cargo.toml"), b"")?;
+        fs::write(src.join("src/bin/main.rs"), b"")?;
+        fs::write(
+            src.join("target/CACHEDIR.TAG"),
+            b"Signature: 8a477f597d28d172789f06886806bc55",
+        )?;
+
+        let mut files = vec![];
+        let had_symlinks = list_dir(src, "", true, 0, |e, _| is_cachedir(e), &mut files)?;
+        assert!(!had_symlinks);
+        files.sort();
+        assert_eq!(files, ["Cargo.toml", "src", "src/bin", "src/bin/main.rs"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprint_difference() -> Result<()> {
+        let home = tempfile::tempdir()?;
+        let home = home.path();
+        file::create_dir_all(home.join("src"))?;
+        fs::write(home.join("Cargo.toml"), "[package]")?;
+        fs::write(home.join("src/lib.rs"), "pub fn a() {}")?;
+        fs::write(home.join("src/main.rs"), "fn main() {}")?;
+        let previous = Fingerprint::read_dir(home, false)?;
+        assert_eq!(Fingerprint::parse(&previous.to_string())?, previous);
+
+        // a file only touched, one modified with the same size, one removed and one added
+        let mut current = Fingerprint::read_dir(home, false)?;
+        let bump = |file: &mut FileFingerprint| file.modified += 1000;
+        bump(current.map.get_mut("Cargo.toml").unwrap());
+        fs::write(home.join("src/lib.rs"), "pub fn b() {}")?;
+        bump(current.map.get_mut("src/lib.rs").unwrap());
+        current.map.remove("src/main.rs");
+        fs::write(home.join("build.rs"), "fn main() {}")?;
+        current.map.insert(
+            "build.rs".to_owned(),
+            FileFingerprint {
+                modified: 0,
+                size: 12,
+                hash: None,
+            },
+        );
+        // the hashes are unknown after the first copy, so touched files are copied once
+        let (to_copy, to_remove) = previous.difference(&mut current, home)?;
+        assert_eq!(to_copy, ["Cargo.toml", "build.rs", "src/lib.rs"]);
+        assert_eq!(to_remove, ["src/main.rs"]);
+        assert!(current.map["Cargo.toml"].hash.is_some());
+
+        let previous = Fingerprint::parse(&current.to_string())?;
+        bump(current.map.get_mut("Cargo.toml").unwrap());
+        let (to_copy, to_remove) = previous.difference(&mut current, home)?;
+        assert!(to_copy.is_empty());
+        assert!(to_remove.is_empty());
+
+        Ok(())
+    }
+}
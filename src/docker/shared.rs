@@ -1,22 +1,33 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus, Output};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 use std::{env, fs, time};
 
+use super::backend::{operation_args, ContainerEngine};
 use super::custom::{Dockerfile, PreBuild};
 use super::engine::*;
 use super::image::PossibleImage;
-use super::Image;
+use super::inspect::ContainerInspect;
+use super::published_images;
+use super::pull;
+use super::spec::{EnvVar, MountSpec};
 use super::PROVIDED_IMAGES;
-use crate::cargo::CargoMetadata;
-use crate::config::{bool_from_envvar, Config};
+use super::{image_name, Image};
+use crate::cargo::{CargoMetadata, Subcommand};
+use crate::cli::PrintKind;
+use crate::config::{bool_from_envvar, Config, RECOGNIZED_VARS};
 use crate::errors::*;
-use crate::extensions::{CommandExt, SafeCommand};
+use crate::extensions::{CommandExt, SafeCommand, MAX_DIAGNOSTIC_OUTPUT};
 use crate::file::{self, write_file, PathExt, ToUtf8};
 use crate::id;
 use crate::rustc::QualifiedToolchain;
+use crate::sbom;
 use crate::shell::{ColorChoice, MessageInfo, Verbosity};
+use crate::test_env;
 use crate::{CargoVariant, OutputExt, Target, TargetTriple};
 
 use rustc_version::Version as RustcVersion;
@@ -26,6 +37,17 @@ pub use super::custom::CROSS_CUSTOM_DOCKERFILE_IMAGE_PREFIX;
 pub const CROSS_IMAGE: &str = "ghcr.io/cross-rs";
 // note: this is the most common base image for our images
 pub const UBUNTU_BASE: &str = "ubuntu:20.04";
+// the default `PATH` for images that don't define one
+pub const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+// the tool configuration file generated for `cargo nextest` inside the container
+pub const NEXTEST_TOOL_CONFIG: &str = "/tmp/cross-nextest.toml";
+// the directory a managed qemu-user binary is installed to in the container
+pub const QEMU_DIR: &str = "/cross-qemu";
+// the directory the persistent wine prefix is mounted to in the container
+pub const WINE_PREFIX_DIR: &str = "/cross-wine";
+// the keyless signatures of our images are issued to the workflows publishing them
+const COSIGN_IDENTITY: &str = "^https://github.com/cross-rs/cross/.github/workflows/";
+const COSIGN_OIDC_ISSUER: &str = "https://token.actions.githubusercontent.com";
 
 #[derive(Debug)]
 pub struct DockerOptions {
@@ -36,6 +58,95 @@ pub struct DockerOptions {
     pub cargo_variant: CargoVariant,
     // not all toolchains will provide this
     pub rustc_version: Option<RustcVersion>,
+    pub subcommand: Option<Subcommand>,
+    // dump the environment of failing build scripts
+    pub emit_build_script_env: bool,
+    // fail, instead of warning, if the image is for an incompatible version
+    pub strict: bool,
+    // the flags docs.rs passes to rustdoc, with `--docs-rs`
+    pub docs_rs_rustdocflags: Vec<String>,
+    // don't copy the target directory back from a remote engine
+    pub skip_build_artifacts: bool,
+}
+
+/// The file in the data directory with the last time cross used each image.
+const IMAGE_USES_FILENAME: &str = "image-uses.json";
+
+/// The name of an image as the engine lists it, with the default tag.
+fn tagged_image_name(image_name: &str) -> String {
+    let name = image_name.rsplit('/').next().unwrap_or(image_name);
+    if name.contains(':') || name.contains('@') {
+        image_name.to_owned()
+    } else {
+        format!("{image_name}:latest")
+    }
+}
+
+/// The last time cross used each image, in seconds since the epoch, by the
+/// name of the image. It's empty if no image was used yet.
+pub fn image_uses() -> Result<BTreeMap<String, u64>> {
+    let path = file::cross_data_dir()?.join(IMAGE_USES_FILENAME);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let contents = file::read(&path)?;
+    serde_json::from_str(&contents).wrap_err_with(|| format!("could not parse `{path:?}`"))
+}
+
+/// Record that cross used the image now, so unused images can be pruned.
+fn record_image_use(image_name: &str) -> Result<()> {
+    let path = file::cross_data_dir()?.join(IMAGE_USES_FILENAME);
+    let mut uses = image_uses()?;
+    let now = time::SystemTime::now()
+        .duration_since(time::SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    uses.insert(tagged_image_name(image_name), now);
+    let file = file::write_file(&path, true)?;
+    serde_json::to_writer_pretty(file, &uses)
+        .wrap_err_with(|| format!("could not write `{path:?}`"))
+}
+
+/// The cache of the images checked for `/bin/sh`, by their IDs.
+fn image_shells_path() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|d| d.cache_dir().join("cross-rs").join("image-shells.json"))
+}
+
+/// If the engine failed to run `/bin/sh` because it's not in the image,
+/// rather than because of the engine or the daemon.
+fn is_missing_shell(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr).to_lowercase();
+    stderr.contains("executable file not found")
+        || stderr.contains("/bin/sh") && stderr.contains("no such file or directory")
+}
+
+/// The manifest digest of the image verified by `cosign verify`, from the
+/// payloads of the signatures printed on stdout.
+fn verified_digest(stdout: &[u8]) -> Result<String> {
+    let signatures: Vec<serde_json::Value> = serde_json::from_slice(stdout)?;
+    let mut digests = signatures.iter().map(|signature| {
+        signature["critical"]["image"]["docker-manifest-digest"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("signature without a manifest digest"))
+    });
+    let digest = digests
+        .next()
+        .ok_or_else(|| eyre::eyre!("no signatures were verified"))??;
+    for other in digests {
+        eyre::ensure!(other? == digest, "the signatures are for different digests");
+    }
+
+    Ok(digest.to_owned())
+}
+
+/// Refer to the image by its digest, replacing any tag or digest.
+fn pin_digest(image_name: &str, digest: &str) -> String {
+    let name = image_name.split('@').next().unwrap_or(image_name);
+    let repository = match name.rsplit_once(':') {
+        // a colon before the last `/` is the port of the registry
+        Some((repository, tag)) if !tag.contains('/') => repository,
+        _ => name,
+    };
+    format!("{repository}@{digest}")
 }
 
 impl DockerOptions {
@@ -46,6 +157,7 @@ impl DockerOptions {
         image: Image,
         cargo_variant: CargoVariant,
         rustc_version: Option<RustcVersion>,
+        subcommand: Option<Subcommand>,
     ) -> DockerOptions {
         DockerOptions {
             engine,
@@ -54,9 +166,433 @@ impl DockerOptions {
             image,
             cargo_variant,
             rustc_version,
+            subcommand,
+            emit_build_script_env: false,
+            docs_rs_rustdocflags: vec![],
+            strict: false,
+            skip_build_artifacts: false,
         }
     }
 
+    #[must_use]
+    pub fn is_nextest(&self) -> bool {
+        self.subcommand == Some(Subcommand::Nextest)
+    }
+
+    /// If the image is provided by cross, from the default registry or a mirror.
+    pub(crate) fn is_provided_image(&self, image_name: &str) -> bool {
+        provided_image_target(image_name, &self.config).is_some()
+    }
+
+    /// Returns if the image provides `/bin/sh`.
+    ///
+    /// Our images always provide a shell, but custom images, such as
+    /// those based on distroless or scratch images, might not.
+    ///
+    /// The result is cached by the ID of the image, so each image is only
+    /// run to check for a shell once.
+    pub(crate) fn image_has_shell(
+        &self,
+        image_name: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<bool> {
+        if self.is_provided_image(image_name) {
+            return Ok(true);
+        }
+
+        let (id, _) = sbom::image_digests(&self.engine, image_name, msg_info)?;
+        let path = image_shells_path();
+        let mut shells: BTreeMap<String, bool> = path
+            .as_ref()
+            .and_then(|path| file::read(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        if let Some(&has_shell) = shells.get(&id) {
+            return Ok(has_shell);
+        }
+
+        let mut docker = self.engine.subcommand("run");
+        docker.arg("--rm");
+        self.image
+            .platform
+            .specify_platform(&self.engine, &mut docker);
+        docker.args(["--entrypoint", "/bin/sh", image_name, "-c", "true"]);
+        let output = docker.run_and_get_output(msg_info)?;
+        let has_shell = match output.status.success() {
+            true => true,
+            false if is_missing_shell(&output.stderr) => false,
+            false => eyre::bail!(
+                "could not check if image `{image_name}` has `/bin/sh`:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        };
+
+        if let Some(path) = path {
+            shells.insert(id, has_shell);
+            // the cache only saves running the image again
+            let written = file::write_file(&path, true).and_then(|mut file| {
+                Ok(file.write_all(serde_json::to_string(&shells)?.as_bytes())?)
+            });
+            if let Err(err) = written {
+                msg_info.debug(format_args!(
+                    "could not cache if `{image_name}` has a shell: {err}"
+                ))?;
+            }
+        }
+
+        Ok(has_shell)
+    }
+
+    /// Warn if the image defines an entrypoint that could conflict with our command.
+    pub(crate) fn check_entrypoint(
+        &self,
+        image_name: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()> {
+        // our images, and custom images built from them, use entrypoints
+        // that run the provided command.
+        if self.is_provided_image(&self.image.name) || self.image.entrypoint.is_some() {
+            return Ok(());
+        }
+
+        let entrypoint = self
+            .engine
+            .subcommand("image")
+            .args(["inspect", "--format", "{{json .Config.Entrypoint}}"])
+            .arg(image_name)
+            .run_and_get_stdout(msg_info)
+            .wrap_err_with(|| format!("could not find image `{image_name}`"))
+            .failure(ExitCode::ImageMissing)?;
+        let entrypoint = entrypoint.trim();
+        if !matches!(entrypoint, "" | "null" | "[]") {
+            msg_info.warn(format_args!(
+                "image `{image_name}` has the entrypoint `{entrypoint}`, which may conflict with the command run by cross.\n > Set `target.{}.image.entrypoint = \"\"` to reset it.",
+                self.target
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Record that the image is used now, which `cross-util images prune`
+    /// uses for `--older-than`. This is before the image is pinned to its
+    /// digest, since images are pruned by their tags.
+    pub(crate) fn record_image_use(&self, msg_info: &mut MessageInfo) -> Result<()> {
+        let image_name = &self.image.name;
+        match record_image_use(image_name) {
+            Ok(()) => Ok(()),
+            Err(err) => msg_info.warn(format_args!(
+                "could not record the use of image `{image_name}`: {err}"
+            )),
+        }
+    }
+
+    /// Verify the signature of the image with `cosign`, if `build.verify-images`
+    /// is set and the image is provided by cross.
+    ///
+    /// By default, the keyless signature is checked against the identity of the
+    /// workflows publishing the images, unless `CROSS_COSIGN_KEY` is set.
+    ///
+    /// `cosign` checks the image in the registry, so the image is pinned to
+    /// the verified digest, rather than running whatever is tagged locally.
+    pub(crate) fn verify_image(&mut self, msg_info: &mut MessageInfo) -> Result<()> {
+        let image_name = &self.image.name;
+        if !self.config.verify_images() {
+            return Ok(());
+        } else if !self.is_provided_image(image_name) {
+            return msg_info.debug(format_args!(
+                "not verifying image `{image_name}`, which is not provided by cross"
+            ));
+        }
+
+        let cosign = which::which("cosign")
+            .wrap_err("`cosign` is required to verify images with `build.verify-images`")?;
+        let mut command = Command::new(cosign);
+        command.arg("verify");
+        match self.config.cosign_key() {
+            Some(key) => {
+                command.args(["--key", &key]);
+            }
+            None => {
+                command
+                    .args(["--certificate-identity-regexp", COSIGN_IDENTITY])
+                    .args(["--certificate-oidc-issuer", COSIGN_OIDC_ISSUER]);
+            }
+        }
+        command.arg(image_name);
+        let output = command.run_and_get_output(msg_info)?;
+        if !output.status.success() {
+            eyre::bail!(
+                "could not verify the signature of image `{image_name}`:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let digest = verified_digest(&output.stdout)
+            .wrap_err_with(|| format!("could not read the verified digest of `{image_name}`"))?;
+        msg_info.info(format_args!(
+            "verified the signature of image `{image_name}` at `{digest}`"
+        ))?;
+        self.image.name = pin_digest(image_name, &digest);
+
+        Ok(())
+    }
+
+    /// Check that the image was built for a version of cross compatible with
+    /// this one, from the version in its labels.
+    ///
+    /// Images without the label, such as the official images published before
+    /// it was added, aren't checked.
+    pub(crate) fn check_image_version(
+        &self,
+        image_name: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()> {
+        let output = self
+            .engine
+            .subcommand("image")
+            .args([
+                "inspect",
+                "--format",
+                &format!(
+                    r#"{{{{index .Config.Labels "{}.cross-version"}}}}"#,
+                    crate::CROSS_LABEL_DOMAIN
+                ),
+            ])
+            .arg(image_name)
+            .run_and_get_output(msg_info)?;
+        let label = String::from_utf8_lossy(&output.stdout);
+        let label = label.trim();
+        if !output.status.success() || matches!(label, "" | "<no value>") {
+            return Ok(());
+        }
+        let version = match RustcVersion::parse(label) {
+            Ok(version) => version,
+            Err(_) => {
+                return msg_info.debug(format_args!(
+                    "image `{image_name}` has the invalid cross version `{label}`"
+                ))
+            }
+        };
+        let current = RustcVersion::parse(env!("CARGO_PKG_VERSION"))?;
+        if is_compatible_version(&version, &current) {
+            return Ok(());
+        }
+
+        let message = format!(
+            "image `{image_name}` was built for cross {version}, which is incompatible with cross {current}."
+        );
+        if self.strict {
+            Err(eyre::eyre!(message)).failure(ExitCode::ImageIncompatible)
+        } else {
+            msg_info.warn(format_args!(
+                "{message}\n > Update the image or cross, or pass `--strict` to fail instead."
+            ))
+        }
+    }
+
+    /// Returns the short ID of the image, which identifies its contents
+    /// rather than its name.
+    pub(crate) fn image_id(&self, image_name: &str, msg_info: &mut MessageInfo) -> Result<String> {
+        let id = self
+            .engine
+            .subcommand("image")
+            .args(["inspect", "--format", "{{.Id}}"])
+            .arg(image_name)
+            .run_and_get_stdout(msg_info)
+            .wrap_err_with(|| format!("could not inspect image `{image_name}`"))?;
+        let id = id.trim();
+        let id = id.split_once(':').map_or(id, |(_, hash)| hash);
+
+        Ok(id.chars().take(12).collect())
+    }
+
+    /// Returns the `PATH` environment variable defined by the image.
+    pub(crate) fn image_path(
+        &self,
+        image_name: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<String> {
+        let env = self
+            .engine
+            .subcommand("image")
+            .args([
+                "inspect",
+                "--format",
+                "{{range .Config.Env}}{{println .}}{{end}}",
+            ])
+            .arg(image_name)
+            .run_and_get_stdout(msg_info)?;
+
+        Ok(env
+            .lines()
+            .find_map(|line| line.strip_prefix("PATH="))
+            .unwrap_or(DEFAULT_PATH)
+            .to_owned())
+    }
+
+    /// Returns the tool configuration passed to `cargo nextest`.
+    ///
+    /// Tests running under emulation are much slower than native ones,
+    /// so extend the slow timeout to avoid spurious failures. The
+    /// repository's `.config/nextest.toml` takes precedence over this.
+    ///
+    /// The runner isn't configured here: `cargo nextest` reads it from the
+    /// `CARGO_TARGET_{TARGET}_RUNNER` variable the image sets, like `cargo`.
+    pub fn nextest_config(&self) -> Result<String> {
+        let runner = self.config.runner(&self.target)?;
+        let is_emulated =
+            self.target.needs_interpreter() && runner.as_deref().unwrap_or("qemu-user") != "native";
+        let mut config = "# generated by cross\n".to_owned();
+        if is_emulated {
+            config.push_str(
+                "[profile.default]\nslow-timeout = { period = \"120s\", terminate-after = 5 }\n",
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// If the target runs its binaries with wine.
+    #[must_use]
+    pub fn uses_wine(&self) -> bool {
+        self.target.triple().ends_with("windows-gnu")
+    }
+
+    /// Returns the name of the volume with the wine prefix, creating it if
+    /// it doesn't exist.
+    ///
+    /// Bootstrapping the prefix is slow, so it's kept in a volume for each
+    /// target and image, which is found and removed like other volumes.
+    pub(crate) fn wine_volume(
+        &self,
+        image_name: &str,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Option<String>> {
+        if !self.uses_wine() {
+            return Ok(None);
+        }
+        let name = format!(
+            "{VOLUME_PREFIX}wine-{}-{}",
+            self.target,
+            self.image_id(image_name, msg_info)?
+        );
+        let volume = DockerVolume::new(&self.engine, &name);
+        if !volume.exists(msg_info)? {
+            let labels = CrossLabels {
+                target: Some(self.target.triple().to_owned()),
+                ..CrossLabels::default()
+            };
+            volume.create(&labels, msg_info)?;
+            // wine requires the prefix to be owned by the user running it
+            if !self.engine.capabilities.rootless {
+                let mut chown = Command::new("run");
+                chown
+                    .arg("--rm")
+                    .args(MountSpec::new(&name, WINE_PREFIX_DIR)?.args())
+                    .args(["--entrypoint", "chown"])
+                    .arg(image_name)
+                    .args([&format!("{}:{}", user_id(), group_id()), WINE_PREFIX_DIR]);
+                let status = self
+                    .engine
+                    .run_container(&operation_args(&chown), true, msg_info)
+                    .wrap_err("when creating the wine prefix volume")?;
+                if !status.success() {
+                    eyre::bail!("could not change the owner of the wine prefix volume `{name}`");
+                }
+            }
+        }
+
+        Ok(Some(name))
+    }
+
+    /// Returns the host and container paths of a qemu-user binary to install
+    /// into the container.
+    ///
+    /// This is only used with `install-qemu`, for targets that need an
+    /// interpreter in images that aren't provided by `cross`. The binary is
+    /// extracted from the image `cross` provides for the target, and cached
+    /// on the host so it's only extracted once.
+    pub(crate) fn managed_qemu(
+        &self,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Option<(PathBuf, String)>> {
+        let runner = self.config.runner(&self.target)?;
+        if !self.config.install_qemu(&self.target).unwrap_or_default()
+            || !self.target.needs_interpreter()
+            || matches!(runner.as_deref(), Some(r) if r != "qemu-user")
+        {
+            return Ok(None);
+        }
+        if self.is_provided_image(&self.image.name) {
+            msg_info.debug("the image is provided by cross and already has qemu installed")?;
+            return Ok(None);
+        }
+        let qarch = match qemu_arch(self.target.triple()) {
+            Some(qarch) => qarch,
+            None => eyre::bail!("qemu-user is not supported for target {}", self.target),
+        };
+        let companion = provided_image_name(&self.config, self.target.triple())?;
+        // the binary runs in the container, so it's for the platform of the image
+        let platform = &self.image.platform;
+        let home = home::home_dir().ok_or_else(|| eyre::eyre!("could not find home directory"))?;
+        let cache =
+            home.join(".cache").join("cross").join("qemu").join(
+                format!("{companion}-{}", platform.docker_platform()).replace(['/', ':'], "-"),
+            );
+        let qemu = cache.join(format!("qemu-{qarch}"));
+        let path = format!("{QEMU_DIR}/qemu-{qarch}");
+        if qemu.exists() {
+            return Ok(Some((qemu, path)));
+        }
+
+        msg_info.note(format_args!(
+            "installing qemu-{qarch} from image `{companion}`"
+        ))?;
+        file::create_dir_all(&cache)?;
+        let container = format!("cross-qemu-{}", std::process::id());
+        let mut create = Command::new("create");
+        platform.specify_platform(&self.engine, &mut create);
+        create.args(["--name", &container, &companion]);
+        let created = self
+            .engine
+            .container_create(&operation_args(&create), msg_info)?;
+        if !created.success() {
+            eyre::bail!("could not create a container from image `{companion}`");
+        }
+
+        // copy to a temporary file, so other builds never see a partial binary
+        let partial = cache.join(format!("qemu-{qarch}.{}.partial", std::process::id()));
+        let copied = self
+            .engine
+            .copy_from(
+                &container,
+                &format!("/usr/local/bin/qemu-{qarch}"),
+                &partial,
+                msg_info,
+            )
+            .and_then(|status| match status.success() {
+                true => Ok(fs::rename(&partial, &qemu)?),
+                false => eyre::bail!("`cp` failed with {status}"),
+            })
+            .wrap_err_with(|| format!("could not copy qemu-{qarch} from image `{companion}`"));
+        let removed = self.engine.container_remove(&container, msg_info);
+        if copied.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        copied?;
+        if !removed?.success() {
+            msg_info.warn(format_args!("could not remove container `{container}`"))?;
+        }
+
+        Ok(Some((qemu, path)))
+    }
+
+    /// Returns the runner environment variable for a qemu binary in the container.
+    pub(crate) fn qemu_runner_env(&self, path: &str) -> Result<EnvVar> {
+        let triple = self.target.triple().to_ascii_uppercase().replace('-', "_");
+        EnvVar::new(format!("CARGO_TARGET_{triple}_RUNNER"), path)
+    }
+
     #[must_use]
     pub fn in_docker(&self) -> bool {
         self.engine.in_docker
@@ -67,6 +603,21 @@ impl DockerOptions {
         self.engine.is_remote
     }
 
+    /// The labels of the containers and volumes created for this build.
+    pub(crate) fn labels(&self, paths: &DockerPaths) -> CrossLabels {
+        CrossLabels {
+            project: Some(paths.metadata.workspace_root.to_string_lossy().into_owned()),
+            target: Some(self.target.triple().to_owned()),
+            toolchain: Some(
+                paths
+                    .directories
+                    .toolchain_directories()
+                    .toolchain()
+                    .to_string(),
+            ),
+        }
+    }
+
     #[must_use]
     pub fn needs_custom_image(&self) -> bool {
         self.config
@@ -78,6 +629,138 @@ impl DockerOptions {
                 .pre_build(&self.target)
                 .unwrap_or_default()
                 .is_some()
+            || self
+                .config
+                .packages(&self.target)
+                .unwrap_or_default()
+                .map(|packages| !packages.is_empty())
+                .unwrap_or_default()
+    }
+
+    /// Returns the name of the image the container is run with, without building it.
+    pub(crate) fn custom_image_name(
+        &self,
+        paths: &DockerPaths,
+        msg_info: &mut MessageInfo,
+    ) -> Result<String> {
+        let target = self.target.target();
+        let mut name = self.custom_base_image_name(paths)?;
+        for (index, pre_build) in self.pre_build_hooks()?.into_iter().enumerate() {
+            name = Dockerfile::Custom {
+                content: String::new(),
+                runs_with: &self.image.platform,
+                key: self.pre_build_key(&pre_build, &name, index == 0, paths, msg_info)?,
+            }
+            .image_name(target, &paths.metadata)?;
+        }
+        Ok(name)
+    }
+
+    /// The hooks applied on top of the base image, each in its own image:
+    /// the system packages to install, followed by the `pre-build` hook.
+    fn pre_build_hooks(&self) -> Result<Vec<PreBuild>> {
+        let mut hooks = vec![];
+        if let Some(packages) = self.config.packages(&self.target)? {
+            if !packages.is_empty() {
+                hooks.push(PreBuild::install_packages(&packages));
+            }
+        }
+        match self.config.pre_build(&self.target)? {
+            Some(PreBuild::Lines(lines)) if lines.is_empty() => (),
+            Some(pre_build) => hooks.push(pre_build),
+            None => (),
+        }
+
+        Ok(hooks)
+    }
+
+    /// The image the pre-build hooks are applied to, built from the `dockerfile`.
+    fn custom_base_image_name(&self, paths: &DockerPaths) -> Result<String> {
+        let target = self.target.target();
+        if let Some(path) = self.config.dockerfile(&self.target)? {
+            let is_custom_image = self.config.image(&self.target)?.is_some();
+            if let (true, Some(digest)) = (is_custom_image, self.image.digest()) {
+                eyre::bail!(
+                    "image `{}` is pinned to `{digest}` and can't be used as the tag of the image built from `{path}`",
+                    self.image.name
+                );
+            }
+            Dockerfile::File {
+                path: &path,
+                context: None,
+                name: if is_custom_image {
+                    Some(&self.image.name)
+                } else {
+                    None
+                },
+                runs_with: &self.image.platform,
+            }
+            .image_name(target, &paths.metadata)
+        } else {
+            Ok(self.image.name.clone())
+        }
+    }
+
+    /// Resolve a value for `--print`.
+    pub fn print_value(
+        &self,
+        kind: PrintKind,
+        paths: &DockerPaths,
+        msg_info: &mut MessageInfo,
+    ) -> Result<String> {
+        let toolchain_dirs = paths.directories.toolchain_directories();
+        match kind {
+            PrintKind::Image => self.custom_image_name(paths, msg_info),
+            PrintKind::Sysroot => Ok(toolchain_dirs.get_sysroot().to_utf8()?.to_owned()),
+            PrintKind::Volume => toolchain_dirs.unique_toolchain_identifier(),
+            PrintKind::Container => {
+                toolchain_dirs.container_identifier_prefix(self.target.target())
+            }
+            PrintKind::Env => Ok(self
+                .config
+                .env()
+                .dump()
+                .into_iter()
+                .zip(RECOGNIZED_VARS)
+                .map(|((name, value), (_, description))| match value {
+                    Some(value) => format!("{name}={value}"),
+                    None => format!("# {name}: {description}"),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+        }
+    }
+
+    /// The key of the image built with `pre_build` applied to `base_image`.
+    ///
+    /// The images built by the earlier hooks are named by their keys, so only
+    /// the first base image is identified by its ID. It's pulled first, so the
+    /// key is the same before and after the first build.
+    fn pre_build_key(
+        &self,
+        pre_build: &PreBuild,
+        base_image: &str,
+        is_first: bool,
+        paths: &DockerPaths,
+        msg_info: &mut MessageInfo,
+    ) -> Result<String> {
+        let id = match is_first {
+            true => {
+                // images from a dockerfile are built locally, before the hooks
+                if self.config.dockerfile(&self.target)?.is_none() {
+                    pull::pull_image(&self.engine, &self.image.platform, base_image, msg_info)?;
+                }
+                let (id, _) = sbom::image_digests(&self.engine, base_image, msg_info)?;
+                Some(id)
+            }
+            false => None,
+        };
+        pre_build.key(
+            base_image,
+            id.as_deref(),
+            self.target.triple(),
+            paths.host_root(),
+        )
     }
 
     pub(crate) fn custom_image_build(
@@ -117,75 +800,88 @@ impl DockerOptions {
                 )
                 .wrap_err("when building dockerfile")?;
         }
-        let pre_build = self.config.pre_build(&self.target)?;
-
-        if let Some(pre_build) = pre_build {
-            match pre_build {
-                super::custom::PreBuild::Single {
-                    line: pre_build_script,
-                    env,
-                } if !env
-                    || !pre_build_script.contains('\n')
-                        && paths.host_root().join(&pre_build_script).is_file() =>
-                {
-                    let custom = Dockerfile::Custom {
+        for (index, pre_build) in self.pre_build_hooks()?.into_iter().enumerate() {
+            image.name = self.pre_build_image(&image, pre_build, index == 0, paths, msg_info)?;
+        }
+        Ok(image.name.clone())
+    }
+
+    /// Build the image with a pre-build hook applied to `image`, unless it
+    /// already exists, and return its name.
+    fn pre_build_image(
+        &self,
+        image: &Image,
+        pre_build: PreBuild,
+        is_first: bool,
+        paths: &DockerPaths,
+        msg_info: &mut MessageInfo,
+    ) -> Result<String> {
+        let key = self.pre_build_key(&pre_build, &image.name, is_first, paths, msg_info)?;
+        let runs_script = pre_build.runs_script(paths.host_root());
+        let (custom, build_args, notes) = match pre_build {
+            PreBuild::Single { line: script, .. } if runs_script => (
+                Dockerfile::Custom {
+                    content: format!(
+                        r#"
+            FROM {image}
+            ARG CROSS_DEB_ARCH=
+            ARG CROSS_SCRIPT
+            ARG CROSS_TARGET
+            COPY $CROSS_SCRIPT /pre-build-script
+            RUN chmod +x /pre-build-script
+            RUN ./pre-build-script $CROSS_TARGET"#
+                    ),
+                    runs_with: &image.platform,
+                    key,
+                },
+                vec![
+                    ("CROSS_SCRIPT", script.clone()),
+                    ("CROSS_TARGET", self.target.triple().to_owned()),
+                ],
+                vec![
+                    format!("CROSS_SCRIPT={script}"),
+                    format!("CROSS_TARGET={}", self.target),
+                ],
+            ),
+            this => {
+                let pre_build = match this {
+                    PreBuild::Single { line, .. } => vec![line],
+                    PreBuild::Lines(lines) => lines,
+                };
+                let cmd = pre_build.join("\n");
+                (
+                    Dockerfile::Custom {
                         content: format!(
                             r#"
-                FROM {image}
-                ARG CROSS_DEB_ARCH=
-                ARG CROSS_SCRIPT
-                ARG CROSS_TARGET
-                COPY $CROSS_SCRIPT /pre-build-script
-                RUN chmod +x /pre-build-script
-                RUN ./pre-build-script $CROSS_TARGET"#
+            FROM {image}
+            ARG CROSS_DEB_ARCH=
+            ARG CROSS_CMD
+            RUN eval "${{CROSS_CMD}}""#
                         ),
                         runs_with: &image.platform,
-                    };
-
-                    image.name = custom
-                        .build(
-                            self,
-                            paths,
-                            vec![
-                                ("CROSS_SCRIPT", &*pre_build_script),
-                                ("CROSS_TARGET", self.target.triple()),
-                            ],
-                            msg_info,
-                        )
-                        .wrap_err("when pre-building")
-                        .with_note(|| format!("CROSS_SCRIPT={pre_build_script}"))
-                        .with_note(|| format!("CROSS_TARGET={}", self.target))?;
-                }
-                this => {
-                    let pre_build = match this {
-                        PreBuild::Single { line, .. } => vec![line],
-                        PreBuild::Lines(lines) => lines,
-                    };
-                    if !pre_build.is_empty() {
-                        let custom = Dockerfile::Custom {
-                            content: format!(
-                                r#"
-                FROM {image}
-                ARG CROSS_DEB_ARCH=
-                ARG CROSS_CMD
-                RUN eval "${{CROSS_CMD}}""#
-                            ),
-                            runs_with: &image.platform,
-                        };
-                        image.name = custom
-                            .build(
-                                self,
-                                paths,
-                                Some(("CROSS_CMD", pre_build.join("\n"))),
-                                msg_info,
-                            )
-                            .wrap_err("when pre-building")
-                            .with_note(|| format!("CROSS_CMD={}", pre_build.join("\n")))?;
-                    }
-                }
+                        key,
+                    },
+                    vec![("CROSS_CMD", cmd.clone())],
+                    vec![format!("CROSS_CMD={cmd}")],
+                )
+            }
+        };
+
+        // the image is tagged with the hash of the hooks and base image,
+        // so an existing image already has the hooks applied.
+        let name = custom.image_name(self.target.target(), &paths.metadata)?;
+        if sbom::image_digests(&self.engine, &name, msg_info).is_ok() {
+            msg_info.info(format_args!("using the cached pre-build image `{name}`."))?;
+            Ok(name)
+        } else {
+            let mut result = custom
+                .build(self, paths, build_args, msg_info)
+                .wrap_err("when pre-building");
+            for note in notes {
+                result = result.with_note(|| note);
             }
+            result
         }
-        Ok(image.name.clone())
     }
 }
 
@@ -208,6 +904,12 @@ impl DockerPaths {
         let mount_finder = MountFinder::create(engine, msg_info)?;
         let (directories, metadata) =
             Directories::assemble(&mount_finder, metadata, &cwd, toolchain)?;
+        if directories.package_directories().read_only() {
+            msg_info.note(format_args!(
+                "the workspace {:?} is read-only, so it's mounted read-only with the target directory in {:?}.\n > Pass `--locked` if cargo fails to update `Cargo.lock`.",
+                metadata.workspace_root, metadata.target_directory
+            ))?;
+        }
         Ok(Self {
             mount_finder,
             metadata,
@@ -248,11 +950,12 @@ impl DockerPaths {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ToolchainDirectories {
     cargo: PathBuf,
     xargo: PathBuf,
     nix_store: Option<PathBuf>,
+    nix_store_read_only: bool,
     toolchain: QualifiedToolchain,
     cargo_mount_path: String,
     xargo_mount_path: String,
@@ -267,9 +970,14 @@ impl ToolchainDirectories {
         let xargo =
             env::var_os("XARGO_HOME").map_or_else(|| home_dir.join(".xargo"), PathBuf::from);
         // NIX_STORE_DIR is an override of NIX_STORE, which is the path in derivations.
-        let nix_store = env::var_os("NIX_STORE_DIR")
+        // CROSS_NIX_STORE overrides both, without changing the environment of nix.
+        let nix_store = env::var_os("CROSS_NIX_STORE")
+            .or_else(|| env::var_os("NIX_STORE_DIR"))
             .or_else(|| env::var_os("NIX_STORE"))
             .map(PathBuf::from);
+        let nix_store_read_only = env::var("CROSS_NIX_STORE_READ_ONLY")
+            .map(|s| bool_from_envvar(&s))
+            .unwrap_or_default();
 
         // create the directories we are going to mount before we mount them,
         // otherwise `docker` will create them but they will be owned by `root`
@@ -317,6 +1025,7 @@ impl ToolchainDirectories {
             cargo,
             xargo,
             nix_store,
+            nix_store_read_only,
             toolchain,
             cargo_mount_path,
             xargo_mount_path,
@@ -328,10 +1037,22 @@ impl ToolchainDirectories {
         self.toolchain.unique_toolchain_identifier()
     }
 
+    pub fn legacy_toolchain_identifier(&self) -> Result<String> {
+        self.toolchain.legacy_toolchain_identifier()
+    }
+
     pub fn unique_container_identifier(&self, triple: &TargetTriple) -> Result<String> {
         self.toolchain.unique_container_identifier(triple)
     }
 
+    pub fn container_identifier_prefix(&self, triple: &TargetTriple) -> Result<String> {
+        self.toolchain.container_identifier_prefix(triple)
+    }
+
+    pub fn unique_xargo_identifier(&self, triple: &TargetTriple) -> Result<String> {
+        self.toolchain.unique_xargo_identifier(triple)
+    }
+
     pub fn toolchain(&self) -> &QualifiedToolchain {
         &self.toolchain
     }
@@ -372,10 +1093,18 @@ impl ToolchainDirectories {
         &self.sysroot_mount_path
     }
 
+    pub fn nextest_mount_path(&self) -> String {
+        format!("{}/cross/nextest", self.cargo_mount_path())
+    }
+
     pub fn nix_store(&self) -> Option<&Path> {
         self.nix_store.as_deref()
     }
 
+    pub fn nix_store_read_only(&self) -> bool {
+        self.nix_store_read_only
+    }
+
     pub fn cargo_mount_path_relative(&self) -> Result<String> {
         self.cargo_mount_path()
             .strip_prefix('/')
@@ -405,6 +1134,25 @@ pub struct PackageDirectories {
     // both mount fields are WSL paths on windows: they already are POSIX paths
     mount_root: String,
     mount_cwd: String,
+    // the workspace can't be written to, so it's mounted read-only
+    read_only: bool,
+}
+
+/// The target directory of the workspace on the host. Sources are sometimes
+/// mounted read-only, such as in CI: the target directory is then moved to
+/// the data directory if it's in them.
+pub fn writable_target_dir(metadata: &CargoMetadata) -> Result<PathBuf> {
+    let target = &metadata.target_directory;
+    if target.starts_with(&metadata.workspace_root)
+        && !file::is_writable(&metadata.workspace_root)
+        && !file::is_writable(target)
+    {
+        Ok(file::cross_data_dir()?
+            .join("target")
+            .join(path_hash(&metadata.workspace_root, PATH_HASH_SHORT)?))
+    } else {
+        Ok(target.clone())
+    }
 }
 
 impl PackageDirectories {
@@ -413,6 +1161,8 @@ impl PackageDirectories {
         mut metadata: CargoMetadata,
         cwd: &Path,
     ) -> Result<(Self, CargoMetadata)> {
+        let read_only = !file::is_writable(&metadata.workspace_root);
+        metadata.target_directory = writable_target_dir(&metadata)?;
         let target = &metadata.target_directory;
         // see ToolchainDirectories::assemble for creating directories
         create_target_dir(target)?;
@@ -437,6 +1187,7 @@ impl PackageDirectories {
                 host_root,
                 mount_root,
                 mount_cwd,
+                read_only,
             },
             metadata,
         ))
@@ -457,6 +1208,10 @@ impl PackageDirectories {
     pub fn mount_cwd(&self) -> &str {
         &self.mount_cwd
     }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
 }
 
 #[derive(Debug)]
@@ -487,7 +1242,7 @@ impl Directories {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ContainerState {
     Created,
     Running,
@@ -529,6 +1284,7 @@ pub const MOUNT_PREFIX: &str = "/cross";
 pub const VOLUME_PREFIX: &str = "cross-";
 // default timeout to stop a container (in seconds)
 pub const DEFAULT_TIMEOUT: u32 = 2;
+
 // instant kill in case of a non-graceful exit
 pub const NO_TIMEOUT: u32 = 0;
 
@@ -657,6 +1413,13 @@ pub struct ContainerDataVolume<'a, 'b, 'c> {
     pub(crate) engine: &'a Engine,
     pub(crate) container: &'b str,
     pub(crate) toolchain_dirs: &'c ToolchainDirectories,
+    // compress the directories copied into the volume with zstd
+    pub(crate) compress: bool,
+    // the bytes copied into the volume so far, to report progress
+    pub(crate) copied: Cell<u64>,
+    // where the copies are reported while they run in parallel, so only one
+    // thread writes the reports.
+    pub(crate) reporter: Option<mpsc::Sender<String>>,
 }
 
 impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
@@ -669,6 +1432,9 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
             engine,
             container,
             toolchain_dirs,
+            compress: false,
+            copied: Cell::new(0),
+            reporter: None,
         }
     }
 }
@@ -680,88 +1446,148 @@ pub enum VolumeId {
 }
 
 impl VolumeId {
-    pub fn mount(&self, mount_prefix: &str) -> String {
+    pub(crate) fn mount(&self, mount_prefix: &str) -> Result<MountSpec> {
         match self {
-            VolumeId::Keep(ref id) => format!("{id}:{mount_prefix}"),
-            VolumeId::Discard => mount_prefix.to_owned(),
+            VolumeId::Keep(ref id) => MountSpec::new(id, mount_prefix),
+            VolumeId::Discard => MountSpec::anonymous(mount_prefix),
+        }
+    }
+}
+
+/// The labels of the containers and volumes created by cross, which are
+/// used to find them instead of their names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrossLabels {
+    pub project: Option<String>,
+    pub target: Option<String>,
+    pub toolchain: Option<String>,
+}
+
+impl CrossLabels {
+    /// The labels as pairs of keys and values. The version of cross is
+    /// always set, with the same key as for the images built by cross, so
+    /// every resource created by cross has a label.
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = vec![(
+            format!("{}.cross-version", crate::CROSS_LABEL_DOMAIN),
+            env!("CARGO_PKG_VERSION").to_owned(),
+        )];
+        for (key, value) in [
+            ("project", &self.project),
+            ("target", &self.target),
+            ("toolchain", &self.toolchain),
+        ] {
+            if let Some(value) = value {
+                pairs.push((
+                    format!("{}.{key}", crate::CROSS_LABEL_DOMAIN),
+                    value.clone(),
+                ));
+            }
+        }
+        pairs
+    }
+
+    /// The `--label` flags to add the labels when creating a resource.
+    pub fn args(&self) -> Vec<String> {
+        self.pairs()
+            .into_iter()
+            .flat_map(|(key, value)| ["--label".to_owned(), format!("{key}={value}")])
+            .collect()
+    }
+
+    /// The `--filter` values matching the resources created by cross. The
+    /// name prefix finds the resources created before they were labeled.
+    pub fn filters() -> [String; 2] {
+        [
+            format!("label={}.cross-version", crate::CROSS_LABEL_DOMAIN),
+            // handles simple regex: ^ for start of line.
+            format!("name=^{VOLUME_PREFIX}"),
+        ]
+    }
+
+    /// Run a list subcommand once for each filter, and merge the lines of
+    /// the output, since the filters of different kinds must all match.
+    pub fn list(engine: &Engine, args: &[&str], msg_info: &mut MessageInfo) -> Result<Vec<String>> {
+        let mut lines = BTreeSet::new();
+        for filter in Self::filters() {
+            let stdout = engine
+                .command()
+                .args(args)
+                .args(["--filter", &filter])
+                .run_and_get_stdout(msg_info)?;
+            lines.extend(stdout.lines().map(ToOwned::to_owned));
         }
+
+        Ok(lines.into_iter().collect())
     }
 }
 
 #[derive(Debug)]
-pub struct DockerVolume<'a, 'b> {
-    pub(crate) engine: &'a Engine,
+pub struct DockerVolume<'a, 'b, E = Engine> {
+    pub(crate) engine: &'a E,
     pub(crate) name: &'b str,
 }
 
-impl<'a, 'b> DockerVolume<'a, 'b> {
-    pub const fn new(engine: &'a Engine, name: &'b str) -> Self {
+impl<'a, 'b, E> DockerVolume<'a, 'b, E> {
+    pub const fn new(engine: &'a E, name: &'b str) -> Self {
         Self { engine, name }
     }
+}
 
+impl<'a, 'b, E: ContainerEngine> DockerVolume<'a, 'b, E> {
     #[track_caller]
-    pub fn create(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+    pub fn create(&self, labels: &CrossLabels, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
         self.engine
-            .run_and_get_status(&["volume", "create", self.name], msg_info)
+            .volume_create(self.name, &labels.pairs(), msg_info)
     }
 
     #[track_caller]
     pub fn remove(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
-        self.engine
-            .run_and_get_status(&["volume", "rm", self.name], msg_info)
+        self.engine.volume_remove(self.name, msg_info)
     }
 
     #[track_caller]
     pub fn exists(&self, msg_info: &mut MessageInfo) -> Result<bool> {
-        self.engine
-            .run_and_get_output(&["volume", "inspect", self.name], msg_info)
-            .map(|output| output.status.success())
+        self.engine.volume_exists(self.name, msg_info)
     }
+}
 
+impl<'a, 'b> DockerVolume<'a, 'b> {
     #[track_caller]
     pub fn existing(
         engine: &Engine,
         toolchain: &QualifiedToolchain,
         msg_info: &mut MessageInfo,
     ) -> Result<Vec<String>> {
-        let list = engine
-            .run_and_get_output(
-                &[
-                    "volume",
-                    "list",
-                    "--format",
-                    "{{.Name}}",
-                    "--filter",
-                    &format!("name=^{VOLUME_PREFIX}{}", toolchain),
-                ],
-                msg_info,
-            )?
-            .stdout()?;
+        let prefix = format!("{VOLUME_PREFIX}{toolchain}");
+        let list = CrossLabels::list(
+            engine,
+            &["volume", "list", "--format", "{{.Name}}"],
+            msg_info,
+        )?;
 
-        if list.is_empty() {
-            Ok(vec![])
-        } else {
-            Ok(list.split('\n').map(ToOwned::to_owned).collect())
-        }
+        Ok(list
+            .into_iter()
+            .filter(|name| name.starts_with(&prefix))
+            .collect())
     }
 }
 
 #[derive(Debug)]
-pub struct DockerContainer<'a, 'b> {
-    pub(crate) engine: &'a Engine,
+pub struct DockerContainer<'a, 'b, E = Engine> {
+    pub(crate) engine: &'a E,
     pub(crate) name: &'b str,
 }
 
-impl<'a, 'b> DockerContainer<'a, 'b> {
-    pub const fn new(engine: &'a Engine, name: &'b str) -> Self {
+impl<'a, 'b, E> DockerContainer<'a, 'b, E> {
+    pub const fn new(engine: &'a E, name: &'b str) -> Self {
         Self { engine, name }
     }
+}
 
+impl<'a, 'b, E: ContainerEngine> DockerContainer<'a, 'b, E> {
     pub fn stop(&self, timeout: u32, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
-        self.engine.run_and_get_status(
-            &["stop", self.name, "--time", &timeout.to_string()],
-            msg_info,
-        )
+        self.engine.container_stop(self.name, timeout, msg_info)
     }
 
     pub fn stop_default(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
@@ -776,20 +1602,88 @@ impl<'a, 'b> DockerContainer<'a, 'b> {
     /// the container was killed, we need to cleanup the exited container.
     /// just silence any warnings.
     pub fn remove(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        self.engine.container_remove(self.name, msg_info)
+    }
+
+    pub fn state(&self, msg_info: &mut MessageInfo) -> Result<ContainerState> {
+        self.engine.container_state(self.name, msg_info)
+    }
+}
+
+/// A named network shared by the containers of a project, so they can
+/// resolve each other by name.
+#[derive(Debug)]
+pub struct DockerNetwork<'a, 'b> {
+    pub(crate) engine: &'a Engine,
+    pub(crate) name: &'b str,
+}
+
+impl<'a, 'b> DockerNetwork<'a, 'b> {
+    pub const fn new(engine: &'a Engine, name: &'b str) -> Self {
+        Self { engine, name }
+    }
+
+    /// The name of the network for the project in `workspace_root`.
+    pub fn project_name(workspace_root: &Path) -> Result<String> {
+        Ok(format!(
+            "{VOLUME_PREFIX}{}",
+            path_hash(workspace_root, PATH_HASH_SHORT)?
+        ))
+    }
+
+    #[track_caller]
+    pub fn create(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
         self.engine
-            .run_and_get_output(&["rm", self.name], msg_info)
-            .map(|output| output.status)
+            .run_and_get_status(&["network", "create", self.name], msg_info)
+    }
+
+    /// Create the network, unless it already exists.
+    #[track_caller]
+    pub fn create_or_reuse(&self, msg_info: &mut MessageInfo) -> Result<()> {
+        if !self.exists(msg_info)? {
+            let status = self.create(msg_info)?;
+            if !status.success() {
+                eyre::bail!("unable to create the network `{}`", self.name);
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn state(&self, msg_info: &mut MessageInfo) -> Result<ContainerState> {
-        let stdout = self
-            .engine
-            .command()
-            .args(["ps", "-a"])
-            .args(["--filter", &format!("name={}", self.name)])
-            .args(["--format", "{{.State}}"])
-            .run_and_get_stdout(msg_info)?;
-        ContainerState::new(stdout.trim())
+    #[track_caller]
+    pub fn remove(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        self.engine
+            .run_and_get_status(&["network", "rm", self.name], msg_info)
+    }
+
+    #[track_caller]
+    pub fn exists(&self, msg_info: &mut MessageInfo) -> Result<bool> {
+        self.engine
+            .run_and_get_output(&["network", "inspect", self.name], msg_info)
+            .map(|output| output.status.success())
+    }
+
+    /// The names of the networks created by cross.
+    #[track_caller]
+    pub fn existing(engine: &Engine, msg_info: &mut MessageInfo) -> Result<Vec<String>> {
+        let list = engine
+            .run_and_get_output(
+                &[
+                    "network",
+                    "ls",
+                    "--format",
+                    "{{.Name}}",
+                    "--filter",
+                    &format!("name=^{VOLUME_PREFIX}"),
+                ],
+                msg_info,
+            )?
+            .stdout()?;
+
+        let mut networks: Vec<String> = list.lines().map(ToOwned::to_owned).collect();
+        networks.sort();
+
+        Ok(networks)
     }
 }
 
@@ -799,10 +1693,6 @@ pub(crate) fn time_to_millis(timestamp: &time::SystemTime) -> Result<u64> {
         .as_millis() as u64)
 }
 
-pub(crate) fn time_from_millis(millis: u64) -> time::SystemTime {
-    time::SystemTime::UNIX_EPOCH + time::Duration::from_millis(millis)
-}
-
 pub(crate) fn now_as_millis() -> Result<u64> {
     time_to_millis(&time::SystemTime::now())
 }
@@ -833,6 +1723,9 @@ impl Engine {
             command.arg("--remote");
         }
         command
+            .add_context(self.kind, self.context.as_deref())
+            .add_host(self.kind, self.host.as_deref());
+        command
     }
 
     pub fn subcommand(&self, cmd: &str) -> Command {
@@ -859,17 +1752,26 @@ impl Engine {
         self.command().args(args).run_and_get_output(msg_info)
     }
 
+    /// Runs the command like [`run_and_get_output`](Self::run_and_get_output),
+    /// for commands whose output is only used for diagnostics.
+    #[track_caller]
+    pub(crate) fn run_and_get_limited_output(
+        &self,
+        args: &[&str],
+        msg_info: &mut MessageInfo,
+    ) -> Result<Output> {
+        self.command()
+            .args(args)
+            .run_and_get_limited_output(msg_info, MAX_DIAGNOSTIC_OUTPUT)
+    }
+
     pub fn parse_opts(value: &str) -> Result<Vec<String>> {
         shell_words::split(value)
             .wrap_err_with(|| format!("could not parse docker opts of {}", value))
     }
 
     /// Register binfmt interpreters
-    pub(crate) fn register_binfmt(
-        &self,
-        target: &Target,
-        msg_info: &mut MessageInfo,
-    ) -> Result<()> {
+    pub fn register_binfmt(&self, target: &Target, msg_info: &mut MessageInfo) -> Result<()> {
         let cmd = if target.is_windows() {
             // https://www.kernel.org/doc/html/latest/admin-guide/binfmt-misc.html
             "mount binfmt_misc -t binfmt_misc /proc/sys/fs/binfmt_misc && \
@@ -880,7 +1782,7 @@ impl Engine {
         };
 
         let mut docker = self.subcommand("run");
-        docker.add_userns();
+        docker.add_userns(&self.capabilities);
         docker.arg("--privileged");
         docker.arg("--rm");
         docker.arg(UBUNTU_BASE);
@@ -937,13 +1839,22 @@ pub(crate) trait DockerCommandExt {
         dirs: &ToolchainDirectories,
         msg_info: &mut MessageInfo,
     ) -> Result<()>;
-    fn add_cwd(&mut self, paths: &DockerPaths) -> Result<()>;
-    fn add_build_command(&mut self, dirs: &ToolchainDirectories, cmd: &SafeCommand) -> &mut Self;
-    fn add_user_id(&mut self, engine_type: EngineType);
-    fn add_userns(&mut self);
+    fn add_cwd(&mut self, options: &DockerOptions, paths: &DockerPaths) -> Result<()>;
+    fn add_entrypoint(&mut self, options: &DockerOptions);
+    fn add_build_command(
+        &mut self,
+        options: &DockerOptions,
+        dirs: &ToolchainDirectories,
+        image_name: &str,
+        cmd: &SafeCommand,
+        msg_info: &mut MessageInfo,
+    ) -> Result<&mut Self>;
+    fn add_exec_command(&mut self, cmd: &SafeCommand) -> &mut Self;
+    fn add_user_id(&mut self, capabilities: &Capabilities);
+    fn add_userns(&mut self, capabilities: &Capabilities);
     fn add_seccomp(
         &mut self,
-        engine_type: EngineType,
+        capabilities: &Capabilities,
         target: &Target,
         metadata: &CargoMetadata,
     ) -> Result<()>;
@@ -969,6 +1880,10 @@ impl DockerCommandExt for Command {
             "HTTP_TIMEOUT",
             "https_proxy",
             "QEMU_STRACE",
+            "WINEDEBUG",
+            "WINEDLLOVERRIDES",
+            "NO_COLOR",
+            "CLICOLOR_FORCE",
         ];
         let cargo_prefix_skip = &[
             "CARGO_HOME",
@@ -981,6 +1896,9 @@ impl DockerCommandExt for Command {
         ];
         let cross_prefix_skip = &[
             "CROSS_RUNNER",
+            test_env::TARGET_ENV,
+            test_env::RUNNER_KIND_ENV,
+            test_env::EMULATED_ENV,
             "CROSS_RUSTC_MAJOR_VERSION",
             "CROSS_RUSTC_MINOR_VERSION",
             "CROSS_RUSTC_PATCH_VERSION",
@@ -989,13 +1907,16 @@ impl DockerCommandExt for Command {
             other.contains(&key)
                 || key.starts_with("CARGO_") && !cargo_prefix_skip.contains(&key)
                 || key.starts_with("CROSS_") && !cross_prefix_skip.contains(&key)
+                || key.starts_with("NEXTEST_")
         };
 
         // also need to accept any additional flags used to configure
         // cargo or cross, but only pass what's actually present.
         for (key, _) in env::vars() {
             if is_passthrough(&key) {
-                self.args(["-e", &key]);
+                if let Ok(var) = EnvVar::forward(key) {
+                    self.args(var.args());
+                }
             }
         }
     }
@@ -1022,36 +1943,67 @@ impl DockerCommandExt for Command {
 
             // Only specifying the environment variable name in the "-e"
             // flag forwards the value from the parent shell
-            self.args(["-e", var]);
+            self.args(EnvVar::parse(var)?.args());
         }
 
         let runner = options.config.runner(&options.target)?;
-        let cross_runner = format!("CROSS_RUNNER={}", runner.unwrap_or_default());
-        self.args(["-e", "PKG_CONFIG_ALLOW_CROSS=1"])
-            .args(["-e", &format!("XARGO_HOME={}", dirs.xargo_mount_path())])
-            .args(["-e", &format!("CARGO_HOME={}", dirs.cargo_mount_path())])
-            .args([
-                "-e",
-                &format!("CROSS_RUST_SYSROOT={}", dirs.sysroot_mount_path()),
-            ])
-            .args(["-e", "CARGO_TARGET_DIR=/target"])
-            .args(["-e", &cross_runner]);
+        for var in [
+            EnvVar::new("PKG_CONFIG_ALLOW_CROSS", "1")?,
+            EnvVar::new("XARGO_HOME", dirs.xargo_mount_path())?,
+            EnvVar::new("CARGO_HOME", dirs.cargo_mount_path())?,
+            EnvVar::new("CROSS_RUST_SYSROOT", dirs.sysroot_mount_path())?,
+            EnvVar::new("CARGO_TARGET_DIR", "/target")?,
+            EnvVar::new("CROSS_RUNNER", runner.as_deref().unwrap_or_default())?,
+        ] {
+            self.args(var.args());
+        }
+        let runner_kind = test_env::RunnerKind::for_target(
+            &options.target,
+            runner.as_deref(),
+            &options.image.platform.architecture,
+        );
+        for var in test_env::container_env(&options.target, runner_kind) {
+            self.args(EnvVar::parse(&var)?.args());
+        }
         if options.cargo_variant.uses_zig() {
             // otherwise, zig has a permission error trying to create the cache
-            self.args(["-e", "XDG_CACHE_HOME=/target/.zig-cache"]);
+            self.args(EnvVar::new("XDG_CACHE_HOME", "/target/.zig-cache")?.args());
+        }
+        if options.uses_wine() {
+            // the prefix is in a volume, so it's only bootstrapped once
+            self.args(EnvVar::new("WINEPREFIX", WINE_PREFIX_DIR)?.args());
+            if let Some(version) = options.config.wine_version(&options.target)? {
+                self.args(EnvVar::new("CROSS_WINE_VERSION", version)?.args());
+            }
+            if options
+                .config
+                .wine_dialogs(&options.target)
+                .unwrap_or_default()
+            {
+                self.args(EnvVar::new("CROSS_WINE_DIALOGS", "1")?.args());
+            }
         }
         self.add_configuration_envvars();
+        if let Some(flags) = rustdocflags(
+            env::var("RUSTDOCFLAGS").ok(),
+            options.config.rustdocflags(&options.target)?,
+            &options.docs_rs_rustdocflags,
+        ) {
+            // this overrides the value forwarded from the parent shell
+            self.args(EnvVar::new("RUSTDOCFLAGS", flags)?.args());
+        }
 
         if let Some(username) = id::username().wrap_err("could not get username")? {
-            self.args(["-e", &format!("USER={username}")]);
+            self.args(EnvVar::new("USER", username)?.args());
         }
 
-        if let Ok(value) = env::var("CROSS_CONTAINER_OPTS") {
-            if env::var("DOCKER_OPTS").is_ok() {
+        let env = options.config.env();
+        if let Some(value) = env.var("CROSS_CONTAINER_OPTS") {
+            if env.var("DOCKER_OPTS").is_some() {
                 msg_info.warn("using both `CROSS_CONTAINER_OPTS` and `DOCKER_OPTS`.")?;
             }
             self.args(&Engine::parse_opts(&value)?);
-        } else if let Ok(value) = env::var("DOCKER_OPTS") {
+        } else if let Some(value) = env.var("DOCKER_OPTS") {
             // FIXME: remove this when we deprecate DOCKER_OPTS.
             self.args(&Engine::parse_opts(&value)?);
         };
@@ -1064,47 +2016,93 @@ impl DockerCommandExt for Command {
             // the builtins.
             None => (1, 0, 0),
         };
-        self.args(["-e", &format!("CROSS_RUSTC_MAJOR_VERSION={}", major)]);
-        self.args(["-e", &format!("CROSS_RUSTC_MINOR_VERSION={}", minor)]);
-        self.args(["-e", &format!("CROSS_RUSTC_PATCH_VERSION={}", patch)]);
+        for (name, version) in [
+            ("CROSS_RUSTC_MAJOR_VERSION", major),
+            ("CROSS_RUSTC_MINOR_VERSION", minor),
+            ("CROSS_RUSTC_PATCH_VERSION", patch),
+        ] {
+            self.args(EnvVar::new(name, version.to_string())?.args());
+        }
 
         Ok(())
     }
 
-    fn add_cwd(&mut self, paths: &DockerPaths) -> Result<()> {
-        self.args(["-w", paths.mount_cwd()]);
+    fn add_cwd(&mut self, options: &DockerOptions, paths: &DockerPaths) -> Result<()> {
+        let workdir = options.image.workdir.as_deref();
+        self.args(["-w", workdir.unwrap_or_else(|| paths.mount_cwd())]);
 
         Ok(())
     }
 
-    fn add_build_command(&mut self, dirs: &ToolchainDirectories, cmd: &SafeCommand) -> &mut Self {
-        let build_command = format!(
-            "PATH=\"$PATH\":\"{}/bin\" {:?}",
-            dirs.sysroot_mount_path(),
-            cmd
-        );
-        self.args(["sh", "-c", &build_command])
+    fn add_entrypoint(&mut self, options: &DockerOptions) {
+        if let Some(entrypoint) = &options.image.entrypoint {
+            self.args(["--entrypoint", entrypoint]);
+        }
+    }
+
+    fn add_build_command(
+        &mut self,
+        options: &DockerOptions,
+        dirs: &ToolchainDirectories,
+        image_name: &str,
+        cmd: &SafeCommand,
+        msg_info: &mut MessageInfo,
+    ) -> Result<&mut Self> {
+        let probe = probe_script(&options.target, dirs.cargo_mount_path(), cmd);
+        let build_command = if options.is_nextest() {
+            // `cargo-nextest` isn't shipped with our images, and the host
+            // binary in `$CARGO_HOME/bin` isn't mounted, so install it
+            // once into the cargo home and re-use it for later runs. A
+            // binary built in one image may not run in another with an
+            // older libc, so each image gets its own install root.
+            let root = format!(
+                "{}/{}",
+                dirs.nextest_mount_path(),
+                options.image_id(image_name, msg_info)?
+            );
+            format!(
+                r#"export PATH="{root}/bin":"$PATH":"{sysroot}/bin"
+{probe}
+if ! cargo nextest --version >/dev/null 2>&1; then
+    cargo install cargo-nextest --locked --force --root "{root}" --target-dir "{root}/build" || exit 1
+fi
+cat > "{NEXTEST_TOOL_CONFIG}" <<'EOF'
+{config}EOF
+{cmd:?}"#,
+                sysroot = dirs.sysroot_mount_path(),
+                config = options.nextest_config()?,
+            )
+        } else {
+            format!(
+                "export PATH=\"$PATH\":\"{}/bin\"\n{probe}\n{:?}",
+                dirs.sysroot_mount_path(),
+                cmd
+            )
+        };
+        Ok(self.args(["sh", "-c", &build_command]))
+    }
+
+    fn add_exec_command(&mut self, cmd: &SafeCommand) -> &mut Self {
+        self.arg(cmd.get_program()).args(cmd.get_args())
     }
 
-    fn add_user_id(&mut self, engine_type: EngineType) {
+    fn add_user_id(&mut self, capabilities: &Capabilities) {
         // by default, docker runs as root so we need to specify the user
         // so the resulting file permissions are for the current user.
-        // since we can have rootless docker, we provide an override.
-        let is_rootless = env::var("CROSS_ROOTLESS_CONTAINER_ENGINE")
-            .ok()
-            .and_then(|s| match s.as_ref() {
-                "auto" => None,
-                b => Some(bool_from_envvar(b)),
-            })
-            .unwrap_or_else(|| engine_type != EngineType::Docker);
-        if !is_rootless {
+        if !capabilities.rootless {
             self.args(["--user", &format!("{}:{}", user_id(), group_id(),)]);
         }
     }
 
-    fn add_userns(&mut self) {
+    fn add_userns(&mut self, capabilities: &Capabilities) {
+        if !capabilities.userns {
+            return;
+        }
         let userns = match env::var("CROSS_CONTAINER_USER_NAMESPACE").ok().as_deref() {
             Some("none") => None,
+            // rootless podman would otherwise map the host user to root,
+            // so artifacts would be owned by a subordinate user on the host.
+            None | Some("auto") if capabilities.keep_id => Some("keep-id".to_owned()),
             None | Some("auto") => Some("host".to_owned()),
             Some(ns) => Some(ns.to_owned()),
         };
@@ -1116,7 +2114,7 @@ impl DockerCommandExt for Command {
     #[allow(unused_mut, clippy::let_and_return)]
     fn add_seccomp(
         &mut self,
-        engine_type: EngineType,
+        capabilities: &Capabilities,
         target: &Target,
         metadata: &CargoMetadata,
     ) -> Result<()> {
@@ -1128,9 +2126,7 @@ impl DockerCommandExt for Command {
 
         // docker uses seccomp now on all installations
         if target.needs_docker_seccomp() {
-            let seccomp = if engine_type.is_docker() && cfg!(target_os = "windows") {
-                // docker on windows fails due to a bug in reading the profile
-                // https://github.com/docker/for-win/issues/12760
+            let seccomp = if !capabilities.seccomp_profile {
                 "unconfined".to_owned()
             } else {
                 #[allow(unused_mut)] // target_os = "windows"
@@ -1141,13 +2137,11 @@ impl DockerCommandExt for Command {
                 if !path.exists() {
                     write_file(&path, false)?.write_all(SECCOMP.as_bytes())?;
                 }
-                let mut path_string = path.to_utf8()?.to_owned();
-                #[cfg(target_os = "windows")]
-                if matches!(engine_type, EngineType::Podman | EngineType::PodmanRemote) {
-                    // podman weirdly expects a WSL path here, and fails otherwise
-                    path_string = path.as_posix_absolute()?;
+                if capabilities.posix_seccomp_path {
+                    path.as_posix_absolute()?
+                } else {
+                    path.to_utf8()?.to_owned()
                 }
-                path_string
             };
 
             self.args(["--security-opt", &format!("seccomp={}", seccomp)]);
@@ -1198,7 +2192,7 @@ impl DockerCommandExt for Command {
                     .mount_finder
                     .find_path(Path::new(&absolute_path), true)?;
                 mount_cb(self, host_path.as_ref(), mount_path.as_ref())?;
-                self.args(["-e", &format!("{}={}", var, mount_path)]);
+                self.args(EnvVar::new(var, &*mount_path)?.args());
                 store_cb((val, mount_path));
             }
         }
@@ -1229,6 +2223,16 @@ pub(crate) fn group_id() -> String {
     env::var("CROSS_CONTAINER_GID").unwrap_or_else(|_| id::group().to_string())
 }
 
+/// Returns if an image built for a version of cross can be used with the
+/// current one, following semver: `0.x` releases are only compatible with
+/// the same minor version.
+pub(crate) fn is_compatible_version(image: &RustcVersion, current: &RustcVersion) -> bool {
+    match (image.major, current.major) {
+        (0, 0) => image.minor == current.minor,
+        (image, current) => image == current,
+    }
+}
+
 /// Simpler version of [get_image]
 pub fn get_image_name(config: &Config, target: &Target, uses_zig: bool) -> Result<String> {
     if let Some(image) = config.image(target)? {
@@ -1247,26 +2251,232 @@ pub fn get_image_name(config: &Config, target: &Target, uses_zig: bool) -> Resul
         .filter(|p| p.name == target_name)
         .collect::<Vec<_>>();
 
-    if compatible.is_empty() {
+    let published = compatible.is_empty() && {
+        let name = image_name(
+            target_name,
+            None,
+            &provided_image_repository(config),
+            &provided_image_tag(config),
+        );
+        published_images::is_cached(&name)
+    };
+    if compatible.is_empty() && !published {
+        if let Some(fallback) = config.fallback_image() {
+            return Ok(fallback);
+        }
         eyre::bail!(
             "`cross` does not provide a Docker image for target {target_name}, \
                    specify a custom image in `Cross.toml`."
         );
     }
 
-    let version = if crate::commit_info().is_empty() {
-        env!("CARGO_PKG_VERSION")
+    provided_image_name(config, target_name)
+}
+
+/// The repository of the images `cross` provides, which can be a mirror set
+/// with `CROSS_IMAGE_REGISTRY` or `build.registry`.
+pub(crate) fn provided_image_repository(config: &Config) -> String {
+    match config.image_registry() {
+        Some(registry) => registry.trim_end_matches('/').to_owned(),
+        None => CROSS_IMAGE.to_owned(),
+    }
+}
+
+/// The repositories of the images `cross` provides: the default one, and
+/// the mirror set with `CROSS_IMAGE_REGISTRY` or `build.registry`, if any.
+pub fn provided_image_repositories(config: &Config) -> Vec<String> {
+    let mut repositories = vec![CROSS_IMAGE.to_owned()];
+    let mirror = provided_image_repository(config);
+    if mirror != CROSS_IMAGE {
+        repositories.push(mirror);
+    }
+    repositories
+}
+
+/// The target of an image `cross` provides, from the default registry or a
+/// mirror, such as `aarch64-unknown-linux-gnu` for
+/// `ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main`. Repositories that only
+/// share a prefix, like `ghcr.io/cross-rs-fork`, aren't provided by cross.
+pub fn provided_image_target<'a>(image_name: &'a str, config: &Config) -> Option<&'a str> {
+    provided_image_repositories(config)
+        .iter()
+        .find_map(|repository| {
+            image_name
+                .strip_prefix(repository.as_str())?
+                .strip_prefix('/')
+        })
+        .map(|name| name.split([':', '@']).next().unwrap_or(name))
+        .filter(|name| !name.is_empty())
+}
+
+/// The tag of the images `cross` provides, which can be overridden with
+/// `CROSS_IMAGE_TAG` or `build.image-tag`.
+pub(crate) fn provided_image_tag(config: &Config) -> String {
+    config.image_tag().unwrap_or_else(|| {
+        if crate::commit_info().is_empty() {
+            env!("CARGO_PKG_VERSION")
+        } else {
+            "main"
+        }
+        .to_owned()
+    })
+}
+
+/// The name of the locally built image for an image `cross` provides, with
+/// the local tag instead of the provided one, and the same sub.
+fn local_image_name(name: &str, repository: &str, tag: &str, local_tag: &str) -> Option<String> {
+    let (target, image_tag) = name
+        .strip_prefix(repository)?
+        .strip_prefix('/')?
+        .rsplit_once(':')?;
+    let sub = match image_tag.strip_prefix(tag)? {
+        "" => None,
+        sub => Some(sub.strip_prefix('-')?),
+    };
+    Some(image_name(target, sub, repository, local_tag))
+}
+
+/// With `CROSS_USE_LOCAL_IMAGES`, use the locally built image instead of an
+/// image `cross` provides, if it exists in local storage.
+pub(crate) fn prefer_local_image(
+    config: &Config,
+    target: &Target,
+    engine: &Engine,
+    mut image: PossibleImage,
+    msg_info: &mut MessageInfo,
+) -> Result<PossibleImage> {
+    if !config.use_local_images() || config.image(target)?.is_some() {
+        return Ok(image);
+    }
+    let local = match local_image_name(
+        &image.name,
+        &provided_image_repository(config),
+        &provided_image_tag(config),
+        &config.local_image_tag(),
+    ) {
+        Some(local) => local,
+        None => return Ok(image),
+    };
+    let exists = engine
+        .subcommand("image")
+        .args(["inspect", &local])
+        .run_and_get_output(msg_info)?
+        .status
+        .success();
+    if exists {
+        msg_info.info(format_args!("using the local image `{local}`"))?;
+        image.name = local;
     } else {
-        "main"
+        msg_info.debug(format_args!(
+            "the local image `{local}` does not exist, using `{}`",
+            image.name
+        ))?;
+    }
+
+    Ok(image)
+}
+
+/// Get the name of the image `cross` provides for a target.
+pub(crate) fn provided_image_name(config: &Config, target_name: &str) -> Result<String> {
+    let repository = provided_image_repository(config);
+    let tag = provided_image_tag(config);
+    match PROVIDED_IMAGES.iter().find(|p| p.name == target_name) {
+        Some(provided) => Ok(provided.image_name(&repository, &tag)),
+        None => {
+            let name = image_name(target_name, None, &repository, &tag);
+            eyre::ensure!(
+                published_images::is_cached(&name),
+                "`cross` does not provide a Docker image for target {target_name}"
+            );
+            Ok(name)
+        }
+    }
+}
+
+/// A quick check of the container before running the build command, so
+/// problems with the image fail fast with a targeted message instead of deep
+/// into the build: the toolchain must be in the `PATH`, the linker of the
+/// target must exist, and the target directory must be writable.
+fn probe_script(target: &Target, cargo_home: &str, cmd: &SafeCommand) -> String {
+    let program = cmd.get_program();
+    let linker_var = format!(
+        "CARGO_TARGET_{}_LINKER",
+        target
+            .triple()
+            .to_ascii_uppercase()
+            .replace(['-', '.'], "_")
+    );
+    format!(
+        r#"if ! command -v {program} >/dev/null 2>&1; then
+    echo "error: \`{program}\` was not found in the container, the image may not have a Rust toolchain in its PATH" 1>&2
+    exit 1
+fi
+linker="${{{linker_var}:-}}"
+if [ -n "$linker" ] && ! command -v "$linker" >/dev/null 2>&1; then
+    echo "error: the linker \`$linker\` for {target} was not found in the container, set with {linker_var}" 1>&2
+    exit 1
+fi
+if [ ! -w "${{CARGO_TARGET_DIR:-/target}}" ]; then
+    echo "error: the target directory \`${{CARGO_TARGET_DIR:-/target}}\` is not writable in the container" 1>&2
+    exit 1
+fi
+if [ ! -w "{cargo_home}" ]; then
+    echo "warning: the cargo home \`{cargo_home}\` is not writable in the container, so dependencies can't be fetched" 1>&2
+fi"#
+    )
+}
+
+/// The `RUSTDOCFLAGS` to set in the container, if they differ from the
+/// environment. Like for cargo, `RUSTDOCFLAGS` takes precedence over the
+/// config, and the flags for docs.rs are added to either.
+fn rustdocflags(
+    env: Option<String>,
+    config: Option<Vec<String>>,
+    docs_rs: &[String],
+) -> Option<String> {
+    if env.is_some() && docs_rs.is_empty() {
+        return None;
+    }
+    let mut flags = match env {
+        Some(env) => env.split_whitespace().map(ToOwned::to_owned).collect(),
+        None => config.unwrap_or_default(),
     };
+    flags.extend(docs_rs.iter().cloned());
+    match flags.is_empty() {
+        true => None,
+        false => Some(flags.join(" ")),
+    }
+}
 
-    Ok(compatible
-        .get(0)
-        .expect("should not be empty")
-        .image_name(CROSS_IMAGE, version))
+/// The architecture of the qemu-user binary for a target, like `qemu_arch` in the images.
+pub(crate) fn qemu_arch(triple: &str) -> Option<&'static str> {
+    let arch = triple.split('-').next()?;
+    Some(match arch {
+        "aarch64" => "aarch64",
+        "i586" | "i686" => "i386",
+        "mips" => "mips",
+        "mipsel" => "mipsel",
+        "mips64" => "mips64",
+        "mips64el" => "mips64el",
+        "powerpc" => "ppc",
+        "powerpc64" => "ppc64",
+        "powerpc64le" => "ppc64le",
+        "riscv64gc" => "riscv64",
+        "s390x" => "s390x",
+        "sparc64" => "sparc64",
+        "x86_64" => "x86_64",
+        _ if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+        _ => return None,
+    })
 }
 
-pub(crate) fn get_image(config: &Config, target: &Target, uses_zig: bool) -> Result<PossibleImage> {
+pub(crate) fn get_image(
+    config: &Config,
+    target: &Target,
+    uses_zig: bool,
+    offline: bool,
+    msg_info: &mut MessageInfo,
+) -> Result<PossibleImage> {
     if let Some(image) = config.image(target)? {
         return Ok(image);
     }
@@ -1283,19 +2493,42 @@ pub(crate) fn get_image(config: &Config, target: &Target, uses_zig: bool) -> Res
         .filter(|p| p.name == target_name)
         .collect::<Vec<_>>();
 
+    let repository = provided_image_repository(config);
+    let version = provided_image_tag(config);
     if compatible.is_empty() {
-        eyre::bail!(
-            "`cross` does not provide a Docker image for target {target_name}, \
+        // the image may have been published after this version of cross
+        let name = image_name(target_name, None, &repository, &version);
+        let platforms = match published_images::published_image(&name, offline, msg_info) {
+            Ok(platforms) => platforms,
+            Err(err) => {
+                msg_info.debug(format_args!(
+                    "unable to query the registry for `{name}`: {err}"
+                ))?;
+                None
+            }
+        };
+        return match platforms {
+            Some(platforms) if !platforms.is_empty() => {
+                let mut image: PossibleImage = name.into();
+                image.toolchain = platforms;
+                Ok(image)
+            }
+            _ => match config.fallback_image() {
+                Some(fallback) => {
+                    msg_info.warn(format_args!(
+                        "`cross` does not provide a Docker image for target {target_name}, \
+                         using the fallback image `{fallback}`."
+                    ))?;
+                    Ok(fallback.into())
+                }
+                None => eyre::bail!(
+                    "`cross` does not provide a Docker image for target {target_name}, \
                specify a custom image in `Cross.toml`."
-        );
+                ),
+            },
+        };
     }
 
-    let version = if crate::commit_info().is_empty() {
-        env!("CARGO_PKG_VERSION")
-    } else {
-        "main"
-    };
-
     let pick = if compatible.len() == 1 {
         // If only one match, use that
         compatible.get(0).expect("should not be empty")
@@ -1321,14 +2554,14 @@ pub(crate) fn get_image(config: &Config, target: &Target, uses_zig: bool) -> Res
                 "candidates: {}",
                 compatible
                     .iter()
-                    .map(|provided| format!("\"{}\"", provided.image_name(CROSS_IMAGE, version)))
+                    .map(|provided| format!("\"{}\"", provided.image_name(&repository, &version)))
                     .collect::<Vec<_>>()
                     .join(", ")
             )
         }));
     };
 
-    let mut image: PossibleImage = pick.image_name(CROSS_IMAGE, version).into();
+    let mut image: PossibleImage = pick.image_name(&repository, &version).into();
 
     eyre::ensure!(
         !pick.platforms.is_empty(),
@@ -1343,65 +2576,102 @@ fn docker_read_mount_paths(
     engine: &Engine,
     msg_info: &mut MessageInfo,
 ) -> Result<Vec<MountDetail>> {
-    let hostname = env::var("HOSTNAME").wrap_err("HOSTNAME environment variable not found")?;
+    // with cgroup v2, the container ID isn't in `/proc/self/cgroup`, and
+    // `HOSTNAME` differs from it if `--hostname` was used. the mount for
+    // `/etc/hostname` is in the directory of the container, however.
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok();
+    let container = match mountinfo.as_deref().and_then(mountinfo_parse_container_id) {
+        Some(id) => id,
+        None => env::var("HOSTNAME").wrap_err("HOSTNAME environment variable not found")?,
+    };
 
     let mut docker: Command = {
         let mut command = engine.subcommand("inspect");
-        command.arg(hostname);
+        command.arg(container);
         command
     };
 
     let output = docker.run_and_get_stdout(msg_info)?;
-    let info = serde_json::from_str(&output).wrap_err("failed to parse docker inspect output")?;
-    dockerinfo_parse_mounts(&info)
+    let info = ContainerInspect::parse(&output)?;
+    dockerinfo_parse_mounts(&info, engine.kind, mountinfo.as_deref())
 }
 
-fn dockerinfo_parse_mounts(info: &serde_json::Value) -> Result<Vec<MountDetail>> {
+fn dockerinfo_parse_mounts(
+    info: &ContainerInspect,
+    kind: EngineType,
+    mountinfo: Option<&str>,
+) -> Result<Vec<MountDetail>> {
     let mut mounts = dockerinfo_parse_user_mounts(info);
-    let root_info = dockerinfo_parse_root_mount_path(info)?;
+    let root_info = match dockerinfo_parse_root_mount_path(info, kind) {
+        Ok(root_info) => root_info,
+        // some drivers don't report where the root filesystem is, but the
+        // kernel may know it.
+        Err(err) => match mountinfo.and_then(mountinfo_parse_root_mount_path) {
+            Some(root_info) => root_info,
+            None => return Err(err),
+        },
+    };
     mounts.push(root_info);
     Ok(mounts)
 }
 
-fn dockerinfo_parse_root_mount_path(info: &serde_json::Value) -> Result<MountDetail> {
-    let driver_name = info
-        .pointer("/0/GraphDriver/Name")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| eyre::eyre!("no driver name found"))?;
+fn dockerinfo_parse_root_mount_path(
+    info: &ContainerInspect,
+    kind: EngineType,
+) -> Result<MountDetail> {
+    Ok(MountDetail {
+        source: info.root_path(kind)?,
+        destination: PathBuf::from("/"),
+    })
+}
 
-    if driver_name.to_lowercase().contains("overlay") {
-        let path = info
-            .pointer("/0/GraphDriver/Data/MergedDir")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| eyre::eyre!("No merge directory found"))?;
+/// Get the fields of each mount in `/proc/self/mountinfo` as `(root, mount point)`.
+fn mountinfo_entries(mountinfo: &str) -> impl Iterator<Item = (&str, &str)> {
+    mountinfo.lines().filter_map(|line| {
+        let mut fields = line.split(' ').skip(3);
+        Some((fields.next()?, fields.next()?))
+    })
+}
 
-        Ok(MountDetail {
-            source: PathBuf::from(&path),
-            destination: PathBuf::from("/"),
-        })
+/// Find the container ID from the directory `/etc/hostname` is mounted from.
+fn mountinfo_parse_container_id(mountinfo: &str) -> Option<String> {
+    let (root, _) = mountinfo_entries(mountinfo).find(|(_, mount)| *mount == "/etc/hostname")?;
+    let id = Path::new(root).parent()?.file_name()?.to_str()?;
+    if id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Some(id.to_owned())
     } else {
-        eyre::bail!("want driver overlay2, got {driver_name}")
+        None
     }
 }
 
-fn dockerinfo_parse_user_mounts(info: &serde_json::Value) -> Vec<MountDetail> {
-    info.pointer("/0/Mounts")
-        .and_then(|v| v.as_array())
-        .map_or_else(Vec::new, |v| {
-            let make_path = |v: &serde_json::Value| {
-                PathBuf::from(&v.as_str().expect("docker mount should be defined"))
-            };
-            let mut mounts = vec![];
-            for details in v {
-                let source = make_path(&details["Source"]);
-                let destination = make_path(&details["Destination"]);
-                mounts.push(MountDetail {
-                    source,
-                    destination,
-                });
-            }
-            mounts
+/// Find the root filesystem from the path of the root mount in its filesystem.
+///
+/// This is used for drivers like `btrfs`, where each container is a subvolume,
+/// and assumes the filesystem is mounted at `/` on the host. A top-level
+/// subvolume like `@` is stripped, since it's what is usually mounted.
+fn mountinfo_parse_root_mount_path(mountinfo: &str) -> Option<MountDetail> {
+    let (root, _) = mountinfo_entries(mountinfo).find(|(_, mount)| *mount == "/")?;
+    if root == "/" {
+        return None;
+    }
+    let root = match root.strip_prefix("/@") {
+        Some(stripped) => stripped.find('/').map_or(root, |index| &stripped[index..]),
+        None => root,
+    };
+
+    Some(MountDetail {
+        source: PathBuf::from(root),
+        destination: PathBuf::from("/"),
+    })
+}
+
+fn dockerinfo_parse_user_mounts(info: &ContainerInspect) -> Vec<MountDetail> {
+    info.user_mounts()
+        .map(|(source, destination)| MountDetail {
+            source: source.clone(),
+            destination: destination.clone(),
         })
+        .collect()
 }
 
 #[derive(Debug, Default)]
@@ -1461,23 +2731,46 @@ impl MountFinder {
 }
 
 /// Short hash for identifiers with minimal risk of collision.
-pub const PATH_HASH_SHORT: usize = 5;
+/// Collision chance is ~10^-6 for 100 projects on the same builder.
+pub const PATH_HASH_SHORT: usize = 8;
+
+/// The length of the short hash in identifiers created by older versions,
+/// which is only used to find existing persistent volumes.
+pub const PATH_HASH_LEGACY: usize = 5;
 
 /// Longer hash to minimize risk of random collisions
 /// Collision chance is ~10^-6
 pub const PATH_HASH_UNIQUE: usize = 10;
 
-fn path_digest(path: &Path) -> Result<const_sha1::Digest> {
-    let buffer = const_sha1::ConstBuffer::from_slice(path.to_utf8()?.as_bytes());
-    Ok(const_sha1::sha1(&buffer))
-}
+/// The maximum length of the identifiers for volumes and containers.
+pub const MAX_IDENTIFIER_LENGTH: usize = 128;
 
-pub fn path_hash(path: &Path, count: usize) -> Result<String> {
-    Ok(path_digest(path)?
+pub(crate) fn str_hash(s: &str, count: usize) -> String {
+    let buffer = const_sha1::ConstBuffer::from_slice(s.as_bytes());
+    const_sha1::sha1(&buffer)
         .to_string()
         .get(..count)
         .unwrap_or_else(|| panic!("sha1 is expected to be at least {count} characters long"))
-        .to_owned())
+        .to_owned()
+}
+
+pub fn path_hash(path: &Path, count: usize) -> Result<String> {
+    Ok(str_hash(path.to_utf8()?, count))
+}
+
+/// Limit an identifier to `max_length` bytes. Longer identifiers are truncated
+/// and end with a hash of the full identifier, so they stay unique and the
+/// same identifier is always truncated the same way.
+pub fn clamp_identifier(id: String, max_length: usize) -> String {
+    if id.len() <= max_length {
+        return id;
+    }
+    let hash = str_hash(&id, PATH_HASH_UNIQUE);
+    let mut end = max_length.saturating_sub(hash.len() + 1);
+    while !id.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}-{hash}", &id[..end])
 }
 
 #[cfg(test)]
@@ -1488,6 +2781,98 @@ mod tests {
     #[cfg(not(target_os = "windows"))]
     use crate::file::PathExt;
 
+    #[test]
+    fn missing_shells() {
+        // docker, and podman with crun and runc
+        assert!(is_missing_shell(
+            br#"docker: Error response from daemon: failed to create task for container: failed to create shim task: OCI runtime create failed: runc create failed: unable to start container process: exec: "/bin/sh": stat /bin/sh: no such file or directory: unknown."#
+        ));
+        assert!(is_missing_shell(
+            b"Error: crun: executable file `/bin/sh` not found: No such file or directory: OCI runtime attempted to invoke a command that was not found"
+        ));
+        assert!(is_missing_shell(
+            br#"Error: runc: exec failed: unable to start container process: exec: "/bin/sh": executable file not found in $PATH: OCI runtime attempted to invoke a command that was not found"#
+        ));
+        assert!(!is_missing_shell(
+            b"Cannot connect to the Docker daemon at unix:///var/run/docker.sock. Is the docker daemon running?"
+        ));
+        assert!(!is_missing_shell(
+            b"Error response from daemon: pull access denied for image, repository does not exist or may require 'docker login'"
+        ));
+    }
+
+    #[test]
+    fn tagged_image_names() {
+        assert_eq!(
+            tagged_image_name("ghcr.io/cross-rs/aarch64-unknown-linux-gnu"),
+            "ghcr.io/cross-rs/aarch64-unknown-linux-gnu:latest"
+        );
+        assert_eq!(
+            tagged_image_name("localhost:5000/cross-rs/aarch64-unknown-linux-gnu:main"),
+            "localhost:5000/cross-rs/aarch64-unknown-linux-gnu:main"
+        );
+        assert_eq!(
+            tagged_image_name("localhost:5000/image"),
+            "localhost:5000/image:latest"
+        );
+    }
+
+    #[test]
+    fn verified_digests() -> Result<()> {
+        let digest = "sha256:8f7e1e0e6b0a9ce4e1c50d0d2d2c5f8a3cfbbd38d3a0b3b16e5a4e77c2ef1a10";
+        let signature = format!(
+            r#"{{"critical":{{"identity":{{"docker-reference":"ghcr.io/cross-rs/aarch64-unknown-linux-gnu"}},"image":{{"docker-manifest-digest":"{digest}"}},"type":"cosign container image signature"}},"optional":null}}"#
+        );
+        assert_eq!(
+            verified_digest(format!("[{signature},{signature}]").as_bytes())?,
+            digest
+        );
+        assert!(verified_digest(b"[]").is_err());
+        assert!(verified_digest(br#"[{"critical":{}}]"#).is_err());
+
+        assert_eq!(
+            pin_digest("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:0.2.5", digest),
+            format!("ghcr.io/cross-rs/aarch64-unknown-linux-gnu@{digest}")
+        );
+        assert_eq!(
+            pin_digest("localhost:5000/cross-rs/aarch64-unknown-linux-gnu", digest),
+            format!("localhost:5000/cross-rs/aarch64-unknown-linux-gnu@{digest}")
+        );
+        assert_eq!(
+            pin_digest(
+                "ghcr.io/cross-rs/aarch64-unknown-linux-gnu@sha256:0",
+                digest
+            ),
+            format!("ghcr.io/cross-rs/aarch64-unknown-linux-gnu@{digest}")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clamp_identifier() {
+        let short = "cross-stable-x86_64-unknown-linux-gnu-0a1b2c3d".to_owned();
+        assert_eq!(
+            clamp_identifier(short.clone(), MAX_IDENTIFIER_LENGTH),
+            short
+        );
+
+        let long = format!("cross-{}-x86_64-unknown-linux-gnu", "custom".repeat(30));
+        let clamped = clamp_identifier(long.clone(), MAX_IDENTIFIER_LENGTH);
+        assert_eq!(clamped.len(), MAX_IDENTIFIER_LENGTH);
+        assert!(clamped.starts_with("cross-custom"));
+        assert_eq!(
+            clamp_identifier(long.clone(), MAX_IDENTIFIER_LENGTH),
+            clamped
+        );
+        let other = clamp_identifier(format!("{long}-other"), MAX_IDENTIFIER_LENGTH);
+        assert_ne!(other, clamped);
+
+        // never split a character
+        let unicode = "é".repeat(100);
+        assert!(clamp_identifier(unicode, 51).len() <= 51);
+    }
+
     #[test]
     fn test_docker_user_id() {
         let var = "CROSS_ROOTLESS_CONTAINER_ENGINE";
@@ -1499,7 +2884,7 @@ mod tests {
 
         let test = |engine, expected| {
             let mut cmd = Command::new("engine");
-            cmd.add_user_id(engine);
+            cmd.add_user_id(&Capabilities::new(engine));
             assert_eq!(expected, &format!("{cmd:?}"));
         };
         test(EngineType::Docker, &rootful);
@@ -1531,6 +2916,192 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compatible_version() -> Result<()> {
+        let compatible = |image: &str, current: &str| -> Result<bool> {
+            Ok(is_compatible_version(
+                &RustcVersion::parse(image)?,
+                &RustcVersion::parse(current)?,
+            ))
+        };
+        assert!(compatible("0.2.4", "0.2.5-dev")?);
+        assert!(compatible("0.2.5-dev", "0.2.4")?);
+        assert!(!compatible("0.1.16", "0.2.4")?);
+        assert!(!compatible("0.3.0", "0.2.4")?);
+        assert!(compatible("1.0.0", "1.2.0")?);
+        assert!(!compatible("2.0.0", "1.2.0")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_image() -> Result<()> {
+        let name = "ghcr.io/cross-rs/aarch64-unknown-linux-gnu@sha256:\
+            0d6d8a3e1b2f0a4c8f8e1c5d9b7a6e4f3c2b1a0d9e8f7c6b5a4d3c2b1a0f9e8d";
+        let mut map = std::collections::HashMap::new();
+        map.insert("CROSS_TARGET_AARCH64_UNKNOWN_LINUX_GNU_IMAGE", name);
+        let config = Config::new_with(None, crate::config::Environment::new(Some(map)));
+        let target = Target::from(
+            "aarch64-unknown-linux-gnu",
+            &crate::TargetList {
+                triples: vec!["aarch64-unknown-linux-gnu".to_owned()],
+            },
+        );
+
+        let image = get_image(&config, &target, false, true, &mut MessageInfo::default())?;
+        assert_eq!(image.name, name);
+        assert_eq!(
+            image.digest(),
+            Some("sha256:0d6d8a3e1b2f0a4c8f8e1c5d9b7a6e4f3c2b1a0d9e8f7c6b5a4d3c2b1a0f9e8d")
+        );
+        assert_eq!(get_image_name(&config, &target, false)?, name);
+        assert_eq!(PossibleImage::from("my/image:latest").digest(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_provided_image_target() {
+        let mut map = std::collections::HashMap::new();
+        map.insert("CROSS_IMAGE_REGISTRY", "registry.corp/cross");
+        let config = Config::new_with(None, crate::config::Environment::new(Some(map)));
+        let target = |name| provided_image_target(name, &config);
+        assert_eq!(
+            target("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main"),
+            Some("aarch64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            target("registry.corp/cross/thumbv8m.main-none-eabi@sha256:0d6d"),
+            Some("thumbv8m.main-none-eabi")
+        );
+        assert_eq!(
+            target("registry.corp/cross/armv7-unknown-linux-gnueabihf"),
+            Some("armv7-unknown-linux-gnueabihf")
+        );
+        assert_eq!(
+            target("ghcr.io/cross-rs-fork/aarch64-unknown-linux-gnu"),
+            None
+        );
+        assert_eq!(
+            target("registry.corp/crossbuild/aarch64-unknown-linux-gnu"),
+            None
+        );
+        assert_eq!(target("ghcr.io/cross-rs"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_probe_script() -> Result<()> {
+        let target = Target::from(
+            "aarch64-unknown-linux-gnu",
+            &crate::TargetList {
+                triples: vec!["aarch64-unknown-linux-gnu".to_owned()],
+            },
+        );
+        let dir = env::temp_dir();
+        let dir = dir.to_utf8()?;
+        let probe = |program: &str, linker: &str| {
+            Command::new("sh")
+                .args([
+                    "-c",
+                    &probe_script(&target, dir, &SafeCommand::new(program)),
+                ])
+                .env("CARGO_TARGET_DIR", dir)
+                .env("CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_LINKER", linker)
+                .output()
+        };
+
+        assert!(probe("sh", "sh")?.status.success());
+        let output = probe("cross-missing-cargo", "sh")?;
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("`cross-missing-cargo` was not found")
+        );
+        let output = probe("sh", "cross-missing-gcc")?;
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("the linker `cross-missing-gcc` for aarch64-unknown-linux-gnu"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cross_labels() {
+        let labels = CrossLabels {
+            target: Some("aarch64-unknown-linux-gnu".to_owned()),
+            ..CrossLabels::default()
+        };
+        assert_eq!(
+            labels.args(),
+            [
+                "--label".to_owned(),
+                format!("org.cross-rs.cross-version={}", env!("CARGO_PKG_VERSION")),
+                "--label".to_owned(),
+                "org.cross-rs.target=aarch64-unknown-linux-gnu".to_owned(),
+            ]
+        );
+        assert_eq!(
+            CrossLabels::filters(),
+            ["label=org.cross-rs.cross-version", "name=^cross-"]
+        );
+    }
+
+    #[test]
+    fn test_rustdocflags() {
+        let flags = |v: &[&str]| v.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        let config = Some(flags(&["--cfg", "foo"]));
+        let docs_rs = flags(&["--cfg", "docsrs"]);
+        assert_eq!(rustdocflags(None, None, &[]), None);
+        assert_eq!(
+            rustdocflags(None, config.clone(), &[]).as_deref(),
+            Some("--cfg foo")
+        );
+        assert_eq!(
+            rustdocflags(Some("-D warnings".to_owned()), config.clone(), &[]),
+            None
+        );
+        assert_eq!(
+            rustdocflags(Some("-D warnings".to_owned()), config.clone(), &docs_rs).as_deref(),
+            Some("-D warnings --cfg docsrs")
+        );
+        assert_eq!(
+            rustdocflags(None, config, &docs_rs).as_deref(),
+            Some("--cfg foo --cfg docsrs")
+        );
+    }
+
+    #[test]
+    fn test_local_image_name() {
+        let local = |name| local_image_name(name, "ghcr.io/cross-rs", "0.2.5", "local");
+        assert_eq!(
+            local("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:0.2.5").as_deref(),
+            Some("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:local")
+        );
+        assert_eq!(
+            local("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:0.2.5-centos").as_deref(),
+            Some("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:local-centos")
+        );
+        assert_eq!(
+            local("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main"),
+            None
+        );
+        assert_eq!(local("my/image:0.2.5"), None);
+    }
+
+    #[test]
+    fn test_qemu_arch() {
+        assert_eq!(qemu_arch("aarch64-unknown-linux-gnu"), Some("aarch64"));
+        assert_eq!(qemu_arch("armv7-unknown-linux-gnueabihf"), Some("arm"));
+        assert_eq!(
+            qemu_arch("thumbv7neon-unknown-linux-gnueabihf"),
+            Some("arm")
+        );
+        assert_eq!(qemu_arch("i686-unknown-linux-gnu"), Some("i386"));
+        assert_eq!(qemu_arch("powerpc64le-unknown-linux-gnu"), Some("ppc64le"));
+        assert_eq!(qemu_arch("riscv64gc-unknown-linux-gnu"), Some("riscv64"));
+        assert_eq!(qemu_arch("wasm32-unknown-emscripten"), None);
+    }
+
     #[test]
     fn test_docker_userns() {
         let var = "CROSS_CONTAINER_USER_NAMESPACE";
@@ -1541,21 +3112,34 @@ mod tests {
         let custom = "\"engine\" \"--userns\" \"custom\"".to_owned();
         let none = "\"engine\"".to_owned();
 
+        let keep_id = "\"engine\" \"--userns\" \"keep-id\"".to_owned();
+        let rootless_podman = Capabilities {
+            keep_id: true,
+            ..Capabilities::new(EngineType::Podman)
+        };
+        let test_podman = |expected| {
+            let mut cmd = Command::new("engine");
+            cmd.add_userns(&rootless_podman);
+            assert_eq!(expected, &format!("{cmd:?}"));
+        };
         let test = |expected| {
             let mut cmd = Command::new("engine");
-            cmd.add_userns();
+            cmd.add_userns(&Capabilities::new(EngineType::Docker));
             assert_eq!(expected, &format!("{cmd:?}"));
         };
         test(&host);
+        test_podman(&keep_id);
 
         env::set_var(var, "auto");
         test(&host);
+        test_podman(&keep_id);
 
         env::set_var(var, "none");
         test(&none);
 
         env::set_var(var, "host");
         test(&host);
+        test_podman(&host);
 
         env::set_var(var, "custom");
         test(&custom);
@@ -1592,7 +3176,7 @@ mod tests {
         }
 
         fn create_engine(msg_info: &mut MessageInfo) -> Result<Engine> {
-            Engine::from_path(get_container_engine()?, None, Some(false), msg_info)
+            Engine::from_path(get_container_engine()?, None, Some(false), None, msg_info)
         }
 
         fn cargo_metadata(subdir: bool, msg_info: &mut MessageInfo) -> Result<CargoMetadata> {
@@ -1799,9 +3383,13 @@ mod tests {
         use super::*;
         use serde_json::json;
 
+        fn inspect(value: serde_json::Value) -> ContainerInspect {
+            ContainerInspect::parse(&value.to_string()).unwrap()
+        }
+
         #[test]
         fn test_parse_container_root() {
-            let actual = dockerinfo_parse_root_mount_path(&json!([{
+            let actual = dockerinfo_parse_root_mount_path(&inspect(json!([{
                 "GraphDriver": {
                     "Data": {
                         "LowerDir": "/var/lib/docker/overlay2/f107af83b37bc0a182d3d2661f3d84684f0fffa1a243566b338a388d5e54bef4-init/diff:/var/lib/docker/overlay2/dfe81d459bbefada7aa897a9d05107a77145b0d4f918855f171ee85789ab04a0/diff:/var/lib/docker/overlay2/1f704696915c75cd081a33797ecc66513f9a7a3ffab42d01a3f17c12c8e2dc4c/diff:/var/lib/docker/overlay2/0a4f6cb88f4ace1471442f9053487a6392c90d2c6e206283d20976ba79b38a46/diff:/var/lib/docker/overlay2/1ee3464056f9cdc968fac8427b04e37ec96b108c5050812997fa83498f2499d1/diff:/var/lib/docker/overlay2/0ec5a47f1854c0f5cfe0e3f395b355b5a8bb10f6e622710ce95b96752625f874/diff:/var/lib/docker/overlay2/f24c8ad76303838b49043d17bf2423fe640836fd9562d387143e68004f8afba0/diff:/var/lib/docker/overlay2/462f89d5a0906805a6f2eec48880ed1e48256193ed506da95414448d435db2b7/diff",
@@ -1811,7 +3399,7 @@ mod tests {
                     },
                     "Name": "overlay2"
                 },
-            }])).unwrap();
+            }])), EngineType::Docker).unwrap();
             let want = MountDetail {
                 source: PathBuf::from("/var/lib/docker/overlay2/f107af83b37bc0a182d3d2661f3d84684f0fffa1a243566b338a388d5e54bef4/merged"),
                 destination: PathBuf::from("/"),
@@ -1819,19 +3407,82 @@ mod tests {
             assert_eq!(want, actual);
         }
 
+        #[test]
+        fn test_parse_container_root_drivers() {
+            let actual = dockerinfo_parse_root_mount_path(
+                &inspect(json!([{
+                    "Id": "3b2c7d5e",
+                    "GraphDriver": { "Data": null, "Name": "overlayfs" },
+                }])),
+                EngineType::Docker,
+            )
+            .unwrap();
+            assert_eq!(
+                PathBuf::from("/run/containerd/io.containerd.runtime.v2.task/moby/3b2c7d5e/rootfs"),
+                actual.source
+            );
+
+            let actual = dockerinfo_parse_root_mount_path(&inspect(json!([{
+                "GraphDriver": {
+                    "Data": { "Dataset": "tank/docker/8a3f", "Mountpoint": "/var/lib/docker/zfs/graph/8a3f" },
+                    "Name": "zfs"
+                },
+            }])), EngineType::Docker)
+            .unwrap();
+            assert_eq!(
+                PathBuf::from("/var/lib/docker/zfs/graph/8a3f"),
+                actual.source
+            );
+
+            let btrfs = inspect(json!([{ "GraphDriver": { "Data": null, "Name": "btrfs" } }]));
+            assert!(dockerinfo_parse_root_mount_path(&btrfs, EngineType::Docker).is_err());
+            let mountinfo = "\
+1021 980 0:42 /@/var/lib/docker/btrfs/subvolumes/6f1c / rw,relatime - btrfs /dev/sda2 rw,subvol=/@/var/lib/docker/btrfs/subvolumes/6f1c
+1022 1021 0:44 / /proc rw,nosuid,nodev,noexec,relatime - proc proc rw
+";
+            let mounts =
+                dockerinfo_parse_mounts(&btrfs, EngineType::Docker, Some(mountinfo)).unwrap();
+            assert_eq!(
+                vec![MountDetail {
+                    source: PathBuf::from("/var/lib/docker/btrfs/subvolumes/6f1c"),
+                    destination: PathBuf::from("/"),
+                }],
+                mounts
+            );
+        }
+
+        #[test]
+        fn test_parse_mountinfo_container_id() {
+            let id = "0f2b7b8e5f4f4c3a8b6c2f1d9e7a5c3b1f0e2d4c6a8b9c7d5e3f1a2b4c6d8e0f";
+            let mountinfo = format!(
+                "\
+1030 1021 0:45 / /dev rw,nosuid - tmpfs tmpfs rw,size=65536k,mode=755
+1040 1021 8:2 /var/lib/docker/containers/{id}/hostname /etc/hostname rw,relatime - ext4 /dev/sda2 rw
+"
+            );
+            assert_eq!(
+                mountinfo_parse_container_id(&mountinfo),
+                Some(id.to_owned())
+            );
+            assert_eq!(
+                mountinfo_parse_container_id("1030 1021 0:45 / /dev rw - tmpfs tmpfs rw"),
+                None
+            );
+        }
+
         #[test]
         fn test_parse_empty_user_mounts() {
-            let actual = dockerinfo_parse_user_mounts(&json!([{
+            let actual = dockerinfo_parse_user_mounts(&inspect(json!([{
                 "Mounts": [],
-            }]));
+            }])));
             assert_eq!(Vec::<MountDetail>::new(), actual);
         }
 
         #[test]
         fn test_parse_missing_user_moutns() {
-            let actual = dockerinfo_parse_user_mounts(&json!([{
+            let actual = dockerinfo_parse_user_mounts(&inspect(json!([{
                 "Id": "test",
-            }]));
+            }])));
             assert_eq!(Vec::<MountDetail>::new(), actual);
         }
     }
@@ -0,0 +1,246 @@
+//! Environment variables and mounts passed to the container engine.
+//!
+//! The values are validated when they're created, so a bad name or path is
+//! reported where it comes from, rather than as a confusing error from the
+//! engine, and they're rendered as `-e` and `-v` arguments in one place.
+
+use std::fmt;
+use std::path::Path;
+
+use super::Engine;
+use crate::errors::*;
+
+/// An environment variable set in the container, or forwarded from the host
+/// if it has no value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EnvVar {
+    name: String,
+    value: Option<String>,
+}
+
+impl EnvVar {
+    pub(crate) fn new(name: impl Into<String>, value: impl Into<String>) -> Result<EnvVar> {
+        let var = EnvVar {
+            name: name.into(),
+            value: Some(value.into()),
+        };
+        var.validate()?;
+        Ok(var)
+    }
+
+    /// Forward the variable from the environment of the engine.
+    pub(crate) fn forward(name: impl Into<String>) -> Result<EnvVar> {
+        let var = EnvVar {
+            name: name.into(),
+            value: None,
+        };
+        var.validate()?;
+        Ok(var)
+    }
+
+    /// Parse `NAME=value`, or `NAME` to forward the variable.
+    pub(crate) fn parse(var: &str) -> Result<EnvVar> {
+        match var.split_once('=') {
+            Some((name, value)) => EnvVar::new(name, value),
+            None => EnvVar::forward(var),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        eyre::ensure!(
+            !self.name.is_empty(),
+            "environment variable names cannot be empty"
+        );
+        eyre::ensure!(
+            !self
+                .name
+                .chars()
+                .any(|c| c == '=' || c == '\0' || c.is_whitespace()),
+            "invalid environment variable name `{}`",
+            self.name
+        );
+        if let Some(value) = &self.value {
+            eyre::ensure!(
+                !value.contains('\0'),
+                "the value of environment variable `{}` contains a nul byte",
+                self.name
+            );
+        }
+        Ok(())
+    }
+
+    /// The arguments to set the variable with `docker run` or `docker exec`.
+    pub(crate) fn args(&self) -> [String; 2] {
+        ["-e".to_owned(), self.to_string()]
+    }
+}
+
+impl fmt::Display for EnvVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={value}", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// The SELinux label of a mount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Relabel {
+    /// `z`, the content is shared between containers.
+    Shared,
+}
+
+/// A bind mount, named volume or anonymous volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountSpec {
+    source: Option<String>,
+    target: String,
+    read_only: bool,
+    relabel: Option<Relabel>,
+}
+
+impl MountSpec {
+    /// Mount a host path or named volume at `target` in the container.
+    pub fn new(source: impl Into<String>, target: impl Into<String>) -> Result<MountSpec> {
+        let source = source.into();
+        eyre::ensure!(!source.is_empty(), "the source of a mount cannot be empty");
+        // a drive letter is the only colon allowed, as in `C:\Users`
+        let without_drive = match source.get(1..2) {
+            Some(":") => &source[2..],
+            _ => &source,
+        };
+        eyre::ensure!(
+            !without_drive.contains(':'),
+            "the source of a mount cannot contain `:`, got `{source}`"
+        );
+        MountSpec::anonymous(target).map(|mount| MountSpec {
+            source: Some(source),
+            ..mount
+        })
+    }
+
+    /// Bind mount a host path at `target`, shared with other containers.
+    pub(crate) fn bind(
+        engine: &Engine,
+        host_path: &Path,
+        target: impl Into<String>,
+    ) -> Result<MountSpec> {
+        Ok(MountSpec::new(engine.host_path(host_path)?, target)?.relabel(Relabel::Shared))
+    }
+
+    /// An anonymous volume at `target`, which hides what's mounted there.
+    pub(crate) fn anonymous(target: impl Into<String>) -> Result<MountSpec> {
+        let target = target.into();
+        eyre::ensure!(
+            target.starts_with('/'),
+            "the mount path in the container must be absolute, got `{target}`"
+        );
+        eyre::ensure!(
+            !target.contains(':'),
+            "the mount path in the container cannot contain `:`, got `{target}`"
+        );
+        Ok(MountSpec {
+            source: None,
+            target,
+            read_only: false,
+            relabel: None,
+        })
+    }
+
+    #[must_use]
+    pub fn read_only(mut self, read_only: bool) -> MountSpec {
+        self.read_only = read_only;
+        self
+    }
+
+    #[must_use]
+    pub(crate) fn relabel(mut self, relabel: Relabel) -> MountSpec {
+        self.relabel = Some(relabel);
+        self
+    }
+
+    /// The arguments to add the mount with `docker run`.
+    pub fn args(&self) -> [String; 2] {
+        ["-v".to_owned(), self.to_string()]
+    }
+}
+
+impl fmt::Display for MountSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "{source}:")?;
+        }
+        f.write_str(&self.target)?;
+        let mut options = vec![];
+        match self.relabel {
+            Some(Relabel::Shared) => options.push("z"),
+            None => (),
+        }
+        if self.read_only {
+            options.push("ro");
+        }
+        if !options.is_empty() {
+            write!(f, ":{}", options.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_vars() -> Result<()> {
+        assert_eq!(
+            EnvVar::new("CARGO_HOME", "/cargo")?.args(),
+            ["-e", "CARGO_HOME=/cargo"]
+        );
+        assert_eq!(EnvVar::forward("TERM")?.to_string(), "TERM");
+        assert_eq!(EnvVar::parse("A=b=c")?.to_string(), "A=b=c");
+        assert_eq!(EnvVar::parse("A=")?.to_string(), "A=");
+        assert_eq!(EnvVar::parse("http_proxy")?.args(), ["-e", "http_proxy"]);
+        assert!(EnvVar::parse("").is_err());
+        assert!(EnvVar::parse("=value").is_err());
+        assert!(EnvVar::forward("A B").is_err());
+        assert!(EnvVar::new("A", "b\0").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mounts() -> Result<()> {
+        assert_eq!(
+            MountSpec::new("/home/user/project", "/project")?
+                .relabel(Relabel::Shared)
+                .args(),
+            ["-v", "/home/user/project:/project:z"]
+        );
+        assert_eq!(
+            MountSpec::new("/nix/store", "/nix/store")?
+                .relabel(Relabel::Shared)
+                .read_only(true)
+                .to_string(),
+            "/nix/store:/nix/store:z,ro"
+        );
+        assert_eq!(
+            MountSpec::new("cross-volume", "/cross")?.to_string(),
+            "cross-volume:/cross"
+        );
+        assert_eq!(
+            MountSpec::new(r"C:\Users\user", "/c/Users/user")?.to_string(),
+            r"C:\Users\user:/c/Users/user"
+        );
+        assert_eq!(
+            MountSpec::anonymous("/cargo/bin")?.to_string(),
+            "/cargo/bin"
+        );
+        assert!(MountSpec::new("", "/project").is_err());
+        assert!(MountSpec::new("/a:b", "/project").is_err());
+        assert!(MountSpec::new("/project", "project").is_err());
+        assert!(MountSpec::anonymous("/a:b").is_err());
+
+        Ok(())
+    }
+}
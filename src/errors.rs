@@ -1,7 +1,7 @@
 use crate::docker;
 use crate::temp;
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 
 pub use color_eyre::Section;
 pub use eyre::Context;
@@ -9,6 +9,63 @@ pub use eyre::Result;
 
 pub static mut TERMINATED: AtomicBool = AtomicBool::new(false);
 
+static FAILURE: AtomicI32 = AtomicI32::new(ExitCode::Other as i32);
+
+/// Exit codes for failures in `cross` itself.
+///
+/// These are stable, so CI can branch on the kind of failure. When the
+/// cargo command in the container fails, its exit code is returned
+/// unchanged instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(i32)]
+pub enum ExitCode {
+    /// An error that doesn't belong to any other category.
+    Other = 1,
+    /// `Cross.toml`, `Cargo.toml` or the configuration from the environment is invalid.
+    Config = 3,
+    /// The container engine could not be found or queried.
+    EngineUnavailable = 4,
+    /// The image for the target could not be found.
+    ImageMissing = 5,
+    /// The toolchain, target or a component could not be installed.
+    ToolchainMissing = 6,
+    /// The custom image for the target could not be built.
+    ContainerBuild = 7,
+    /// The image was built for an incompatible version of `cross`, with `--strict`.
+    ImageIncompatible = 8,
+}
+
+impl ExitCode {
+    /// The exit code for the first categorized failure, or [`Other`](Self::Other).
+    #[must_use]
+    pub fn failure() -> i32 {
+        FAILURE.load(Ordering::SeqCst)
+    }
+}
+
+pub trait FailureExt {
+    /// Record the category of the failure, if this is an error.
+    ///
+    /// Only the first category is kept, which is the most specific one
+    /// since errors are recorded as they propagate.
+    #[must_use]
+    fn failure(self, code: ExitCode) -> Self;
+}
+
+impl<T> FailureExt for Result<T> {
+    fn failure(self, code: ExitCode) -> Self {
+        if self.is_err() {
+            let _ = FAILURE.compare_exchange(
+                ExitCode::Other as i32,
+                code as i32,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+        }
+        self
+    }
+}
+
 pub fn install_panic_hook() -> Result<()> {
     let is_dev = !crate::commit_info().is_empty() || std::env::var("CROSS_DEBUG").is_ok();
     color_eyre::config::HookBuilder::new()
@@ -1,12 +1,155 @@
 use std::borrow::Cow;
 use std::fmt;
-use std::process::{Command, ExitStatus, Output};
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use crate::config::Environment;
 use crate::errors::*;
-use crate::shell::MessageInfo;
+use crate::shell::{self, ColorChoice, MessageInfo};
 
 pub const STRIPPED_BINS: &[&str] = &[crate::docker::DOCKER, crate::docker::PODMAN, "cargo"];
 
+/// The most output kept from each stream of a command run for diagnostics.
+pub const MAX_DIAGNOSTIC_OUTPUT: usize = 64 * 1024;
+
+/// A buffer that keeps the start and the end of a stream, up to `max` bytes,
+/// and marks where the rest was dropped.
+#[derive(Debug)]
+struct LimitedBuffer {
+    max: usize,
+    head: Vec<u8>,
+    tail: Vec<u8>,
+    dropped: usize,
+}
+
+impl LimitedBuffer {
+    fn new(max: usize) -> Self {
+        LimitedBuffer {
+            max,
+            head: vec![],
+            tail: vec![],
+            dropped: 0,
+        }
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        let head_max = self.max / 2;
+        let take = head_max.saturating_sub(self.head.len()).min(bytes.len());
+        self.head.extend_from_slice(&bytes[..take]);
+        self.tail.extend_from_slice(&bytes[take..]);
+        let tail_max = self.max - head_max;
+        if self.tail.len() > tail_max {
+            let extra = self.tail.len() - tail_max;
+            self.tail.drain(..extra);
+            self.dropped += extra;
+        }
+    }
+
+    fn read_from(mut self, mut reader: impl Read) -> std::io::Result<Vec<u8>> {
+        let mut chunk = [0u8; 8192];
+        loop {
+            match reader.read(&mut chunk)? {
+                0 => break,
+                read => self.extend(&chunk[..read]),
+            }
+        }
+        Ok(self.finish())
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.dropped != 0 {
+            let marker = format!("\n[... {} bytes truncated ...]\n", self.dropped);
+            self.head.extend_from_slice(marker.as_bytes());
+        }
+        self.head.append(&mut self.tail);
+        self.head
+    }
+}
+
+/// How often to retry a command that exited with a non-zero status.
+///
+/// The delay doubles after each failed attempt. This should only be used for
+/// idempotent commands, since a failed attempt may have partially succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    pub const CROSS_CONTAINER_ENGINE_RETRIES_ENV: &'static str = "CROSS_CONTAINER_ENGINE_RETRIES";
+    pub const CROSS_CONTAINER_ENGINE_RETRY_DELAY_ENV: &'static str =
+        "CROSS_CONTAINER_ENGINE_RETRY_DELAY";
+
+    /// Never retry a failed command.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        retries: 0,
+        delay: Duration::from_secs(1),
+    };
+
+    /// Get the policy for container engine commands, which is opt-in.
+    ///
+    /// `CROSS_CONTAINER_ENGINE_RETRIES` is the number of retries, and
+    /// `CROSS_CONTAINER_ENGINE_RETRY_DELAY` the initial delay in milliseconds.
+    pub fn from_env() -> Result<RetryPolicy> {
+        let env = Environment::new(None);
+        let var = |name: &str| -> Result<Option<u64>> {
+            match env.var(name) {
+                Some(value) => value
+                    .trim()
+                    .parse()
+                    .map(Some)
+                    .wrap_err_with(|| format!("invalid value for `{name}`, got `{value}`")),
+                None => Ok(None),
+            }
+        };
+        let mut policy = Self::NONE;
+        if let Some(retries) = var(Self::CROSS_CONTAINER_ENGINE_RETRIES_ENV)? {
+            policy.retries = u32::try_from(retries)?;
+        }
+        if let Some(delay) = var(Self::CROSS_CONTAINER_ENGINE_RETRY_DELAY_ENV)? {
+            policy.delay = Duration::from_millis(delay);
+        }
+
+        Ok(policy)
+    }
+
+    /// The delay before the retry after `attempt` failed attempts.
+    #[must_use]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+
+    #[track_caller]
+    pub(crate) fn run<T>(
+        &self,
+        msg_info: &mut MessageInfo,
+        mut attempt: impl FnMut(&mut MessageInfo) -> Result<T>,
+        status: impl Fn(&T) -> ExitStatus,
+    ) -> Result<T> {
+        let mut failures = 0;
+        loop {
+            let result = attempt(msg_info)?;
+            if status(&result).success() || failures == self.retries {
+                return Ok(result);
+            }
+            failures += 1;
+            let delay = self.backoff(failures);
+            msg_info.warn(format_args!(
+                "command failed, retrying in {delay:?} (attempt {} of {}).",
+                failures + 1,
+                self.retries + 1
+            ))?;
+            thread::sleep(delay);
+        }
+    }
+}
+
 pub trait CommandExt {
     fn fmt_message(&self, msg_info: &mut MessageInfo) -> String;
 
@@ -34,6 +177,14 @@ pub trait CommandExt {
         status: ExitStatus,
         output: Option<&Output>,
     ) -> Result<(), CommandError>;
+    /// Logs a command the caller spawned, once it finished, like the commands
+    /// run with the other methods. `status` is `None` if it couldn't be run.
+    fn log_finished(
+        &self,
+        status: Option<&ExitStatus>,
+        start: Instant,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()>;
     #[track_caller]
     fn run(&mut self, msg_info: &mut MessageInfo, silence_stdout: bool) -> Result<()>;
     #[track_caller]
@@ -46,6 +197,44 @@ pub trait CommandExt {
     fn run_and_get_stdout(&mut self, msg_info: &mut MessageInfo) -> Result<String>;
     #[track_caller]
     fn run_and_get_output(&mut self, msg_info: &mut MessageInfo) -> Result<std::process::Output>;
+    /// Runs the command like [`run_and_get_output`](Self::run_and_get_output),
+    /// keeping at most `max_bytes` of each stream, so large outputs captured
+    /// for diagnostics don't use unbounded memory.
+    #[track_caller]
+    fn run_and_get_limited_output(
+        &mut self,
+        msg_info: &mut MessageInfo,
+        max_bytes: usize,
+    ) -> Result<std::process::Output>;
+    /// Runs the command like [`run_and_get_status`](Self::run_and_get_status),
+    /// retrying it according to the policy if it fails.
+    #[track_caller]
+    fn run_and_get_status_with_retry(
+        &mut self,
+        msg_info: &mut MessageInfo,
+        silence_stdout: bool,
+        retry: RetryPolicy,
+    ) -> Result<ExitStatus> {
+        retry.run(
+            msg_info,
+            |msg_info| self.run_and_get_status(msg_info, silence_stdout),
+            |status| *status,
+        )
+    }
+    /// Runs the command like [`run_and_get_output`](Self::run_and_get_output),
+    /// retrying it according to the policy if it fails.
+    #[track_caller]
+    fn run_and_get_output_with_retry(
+        &mut self,
+        msg_info: &mut MessageInfo,
+        retry: RetryPolicy,
+    ) -> Result<std::process::Output> {
+        retry.run(
+            msg_info,
+            |msg_info| self.run_and_get_output(msg_info),
+            |output| output.status,
+        )
+    }
     fn command_pretty(
         &self,
         msg_info: &mut MessageInfo,
@@ -129,16 +318,34 @@ impl CommandExt for Command {
         if status.success() {
             Ok(())
         } else {
+            // the output is shown in the error report, so remove any
+            // colors the command used if color output is disabled.
+            let color = msg_info.should_color(&std::io::stderr());
+            let captured = |bytes: &[u8]| match color {
+                true => bytes.to_vec(),
+                false => shell::strip_ansi(&String::from_utf8_lossy(bytes))
+                    .into_owned()
+                    .into(),
+            };
             Err(CommandError::NonZeroExitCode {
                 status,
                 command: self
                     .command_pretty(msg_info, |cmd| STRIPPED_BINS.iter().any(|f| f == &cmd)),
-                stderr: output.map(|out| out.stderr.clone()).unwrap_or_default(),
-                stdout: output.map(|out| out.stdout.clone()).unwrap_or_default(),
+                stderr: output.map(|out| captured(&out.stderr)).unwrap_or_default(),
+                stdout: output.map(|out| captured(&out.stdout)).unwrap_or_default(),
             })
         }
     }
 
+    fn log_finished(
+        &self,
+        status: Option<&ExitStatus>,
+        start: Instant,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()> {
+        log_command(self, status, start, msg_info)
+    }
+
     /// Runs the command to completion
     fn run(&mut self, msg_info: &mut MessageInfo, silence_stdout: bool) -> Result<()> {
         let status = self.run_and_get_status(msg_info, silence_stdout)?;
@@ -154,10 +361,14 @@ impl CommandExt for Command {
         silence_stdout: bool,
     ) -> Result<ExitStatus> {
         self.debug(msg_info)?;
+        disable_color(self, msg_info);
         if silence_stdout && !msg_info.is_verbose() {
             self.stdout(std::process::Stdio::null());
         }
-        self.status()
+        let start = Instant::now();
+        let status = self.status();
+        log_command(self, status.as_ref().ok(), start, msg_info)?;
+        status
             .map_err(|e| CommandError::CouldNotExecute {
                 source: Box::new(e),
                 command: self
@@ -183,7 +394,16 @@ impl CommandExt for Command {
     #[track_caller]
     fn run_and_get_output(&mut self, msg_info: &mut MessageInfo) -> Result<std::process::Output> {
         self.debug(msg_info)?;
-        self.output().map_err(|e| {
+        disable_color(self, msg_info);
+        let start = Instant::now();
+        let output = self.output();
+        log_command(
+            self,
+            output.as_ref().ok().map(|o| &o.status),
+            start,
+            msg_info,
+        )?;
+        output.map_err(|e| {
             CommandError::CouldNotExecute {
                 source: Box::new(e),
                 command: self
@@ -192,6 +412,120 @@ impl CommandExt for Command {
             .to_section_report()
         })
     }
+
+    #[track_caller]
+    fn run_and_get_limited_output(
+        &mut self,
+        msg_info: &mut MessageInfo,
+        max_bytes: usize,
+    ) -> Result<std::process::Output> {
+        self.debug(msg_info)?;
+        disable_color(self, msg_info);
+        let start = Instant::now();
+        let output = (|| -> std::io::Result<Output> {
+            let mut child = self
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            // read both streams at once, so the command can't block on a full pipe.
+            let stderr = child.stderr.take().map(|stderr| {
+                thread::spawn(move || LimitedBuffer::new(max_bytes).read_from(stderr))
+            });
+            let stdout = match child.stdout.take() {
+                Some(stdout) => LimitedBuffer::new(max_bytes).read_from(stdout)?,
+                None => vec![],
+            };
+            let stderr = match stderr {
+                Some(handle) => handle.join().expect("could not join the stderr reader")?,
+                None => vec![],
+            };
+            Ok(Output {
+                status: child.wait()?,
+                stdout,
+                stderr,
+            })
+        })();
+        log_command(
+            self,
+            output.as_ref().ok().map(|o| &o.status),
+            start,
+            msg_info,
+        )?;
+        output.map_err(|e| {
+            CommandError::CouldNotExecute {
+                source: Box::new(e),
+                command: self
+                    .command_pretty(msg_info, |cmd| STRIPPED_BINS.iter().any(|f| f == &cmd)),
+            }
+            .to_section_report()
+        })
+    }
+}
+
+// ask subprocesses, such as the container engine, not to
+// use colors if color output is explicitly disabled.
+fn disable_color(command: &mut Command, msg_info: &MessageInfo) {
+    if msg_info.color_choice == ColorChoice::Never {
+        command.env("NO_COLOR", "1");
+    }
+}
+
+/// Set once writing to the command log failed, so it's only reported once.
+static COMMAND_LOG_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Append the command, its exit status and duration to the file in
+/// `CROSS_COMMAND_LOG`, if set. `status` is `None` if it couldn't be run.
+///
+/// The log is only for diagnostics, so failing to write it is a warning.
+fn log_command(
+    command: &Command,
+    status: Option<&ExitStatus>,
+    start: Instant,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    let path = match Environment::new(None).var("CROSS_COMMAND_LOG") {
+        Some(path) if !path.is_empty() => path,
+        _ => return Ok(()),
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let line = format_log_line(
+        &command.command_pretty(msg_info, |_| false),
+        status,
+        timestamp,
+        start.elapsed(),
+    );
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    match written {
+        Err(err) if !COMMAND_LOG_FAILED.swap(true, Ordering::SeqCst) => msg_info.warn(
+            format_args!("could not write to the command log at `{path}`: {err}"),
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn format_log_line(
+    command: &str,
+    status: Option<&ExitStatus>,
+    timestamp: Duration,
+    duration: Duration,
+) -> String {
+    let status = match status.map(ExitStatus::code) {
+        Some(Some(code)) => format!("exit {code}"),
+        Some(None) => "killed".to_owned(),
+        None => "not run".to_owned(),
+    };
+    format!(
+        "[{}] [{:.3}s] [{status}] {command}\n",
+        timestamp.as_secs(),
+        duration.as_secs_f64()
+    )
 }
 
 pub trait OutputExt {
@@ -241,6 +575,14 @@ impl SafeCommand {
         }
         self
     }
+
+    pub fn get_program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn get_args(&self) -> &[String] {
+        &self.args
+    }
 }
 
 impl fmt::Debug for SafeCommand {
@@ -262,8 +604,99 @@ impl From<SafeCommand> for Command {
     }
 }
 
+/// An exit status with the given code, for operations without a process.
+pub(crate) fn exit_status(code: i32) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+    }
+}
+
 pub(crate) fn env_program(envvar: &str, program: &str) -> String {
     std::env::var(envvar)
         .ok()
         .unwrap_or_else(|| program.to_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limited_buffer() {
+        let mut buffer = LimitedBuffer::new(8);
+        buffer.extend(b"abc");
+        assert_eq!(buffer.finish(), b"abc");
+
+        let mut buffer = LimitedBuffer::new(8);
+        buffer.extend(b"0123456789");
+        buffer.extend(b"abcdef");
+        assert_eq!(
+            String::from_utf8(buffer.finish()).unwrap(),
+            "0123\n[... 8 bytes truncated ...]\ncdef"
+        );
+    }
+
+    #[test]
+    fn retry_policy_backoff() {
+        let policy = RetryPolicy {
+            retries: 3,
+            delay: Duration::from_millis(500),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn command_log_line() {
+        let line = format_log_line(
+            "docker volume create cross-stable",
+            Some(&exit_status(1)),
+            Duration::from_secs(1654077600),
+            Duration::from_millis(1250),
+        );
+        assert_eq!(
+            line,
+            "[1654077600] [1.250s] [exit 1] docker volume create cross-stable\n"
+        );
+        let line = format_log_line("docker", None, Duration::ZERO, Duration::ZERO);
+        assert_eq!(line, "[0] [0.000s] [not run] docker\n");
+    }
+
+    #[test]
+    fn retry_policy_run() -> Result<()> {
+        let policy = RetryPolicy {
+            retries: 2,
+            delay: Duration::from_millis(1),
+        };
+        let mut msg_info = MessageInfo::create(0, true, None)?;
+        let mut attempts = 0;
+        let success = policy.run(
+            &mut msg_info,
+            |_| {
+                attempts += 1;
+                Ok(attempts)
+            },
+            |&attempt| exit_status(if attempt == 2 { 0 } else { 1 }),
+        )?;
+        assert_eq!(success, 2);
+
+        attempts = 0;
+        let failure = policy.run(
+            &mut msg_info,
+            |_| {
+                attempts += 1;
+                Ok(attempts)
+            },
+            |_| exit_status(1),
+        )?;
+        assert_eq!(failure, 3);
+
+        Ok(())
+    }
+}
@@ -291,6 +291,61 @@ pub fn write_file(path: impl AsRef<Path>, overwrite: bool) -> Result<File> {
         .wrap_err(format!("couldn't write to file `{path:?}`"))
 }
 
+/// Returns if files can be created in the directory. This isn't the case
+/// for read-only mounts, even if the permissions allow it.
+#[cfg(not(target_os = "windows"))]
+pub fn is_writable(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    match std::ffi::CString::new(dir.as_os_str().as_bytes()) {
+        // SAFETY: safe, the path is a valid C string.
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+/// Returns if files can be created in the directory. The read-only
+/// attribute of directories is ignored on Windows, so a file is created.
+#[cfg(target_os = "windows")]
+pub fn is_writable(dir: &Path) -> bool {
+    tempfile::tempfile_in(dir).is_ok()
+}
+
+/// The directory for the data of cross, such as the files it would otherwise
+/// write to a read-only workspace.
+pub fn cross_data_dir() -> Result<PathBuf> {
+    directories::BaseDirs::new()
+        .map(|d| d.data_dir().join("cross-rs"))
+        .ok_or_else(|| eyre::eyre!("unable to get data directory"))
+}
+
+/// The size of the files in a list relative to `src`, which lists the
+/// contents of directories as separate files, without following symlinks.
+pub fn files_size(src: &Path, files: &[String]) -> u64 {
+    files
+        .iter()
+        .filter_map(|file| fs::symlink_metadata(src.join(file)).ok())
+        .filter(|metadata| !metadata.is_dir())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Format a size in bytes with decimal units, like the container engines do.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "kB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{size:.2}{}", UNITS[unit])
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -327,6 +382,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(0), "0B");
+        assert_eq!(format_size(999), "999B");
+        assert_eq!(format_size(1500), "1.50kB");
+        assert_eq!(format_size(2_340_000_000), "2.34GB");
+    }
+
+    #[test]
+    fn writable_dirs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert!(is_writable(dir.path()));
+        assert!(!is_writable(&dir.path().join("missing")));
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(target_family = "windows")]
     fn as_posix_prefix() {
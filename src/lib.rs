@@ -31,6 +31,7 @@
 mod tests;
 
 pub mod cargo;
+pub mod cargo_config;
 pub mod cli;
 pub mod config;
 mod cross_toml;
@@ -40,13 +41,19 @@ mod extensions;
 pub mod file;
 mod id;
 mod interpreter;
+pub mod manifest;
+mod run_bin;
 pub mod rustc;
 pub mod rustup;
+mod sbom;
 pub mod shell;
+pub mod stats;
 pub mod temp;
+pub mod test_env;
+pub mod version_check;
 
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
 
 use cli::Args;
@@ -59,11 +66,11 @@ use serde::{Deserialize, Serialize, Serializer};
 
 pub use self::cargo::{cargo_command, cargo_metadata_with_args, CargoMetadata, Subcommand};
 use self::cross_toml::CrossToml;
-use self::errors::Context;
+use self::errors::{Context, ExitCode, FailureExt};
 use self::shell::{MessageInfo, Verbosity};
 
 pub use self::errors::{install_panic_hook, install_termination_hook, Result};
-pub use self::extensions::{CommandExt, OutputExt};
+pub use self::extensions::{CommandExt, OutputExt, RetryPolicy};
 pub use self::file::{pretty_path, ToUtf8};
 pub use self::rustc::{TargetList, VersionMetaExt};
 
@@ -500,6 +507,7 @@ pub fn run(
     target_list: TargetList,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<ExitStatus>> {
+    version_check::startup(args.version_check, &args.cargo_args, msg_info)?;
     if args.version && args.subcommand.is_none() {
         msg_info.print(concat!(
             "cross ",
@@ -507,26 +515,313 @@ pub fn run(
             crate::commit_info!()
         ))?;
     }
+    let profile = match &args.remote {
+        Some(name) => {
+            let profiles = docker::RemoteProfiles::read(&docker::RemoteProfiles::path()?)
+                .failure(ExitCode::Config)?;
+            Some(profiles.get(name).failure(ExitCode::Config)?.clone())
+        }
+        None => None,
+    };
+
+    if args.subcommand == Some(Subcommand::RunBin) {
+        run_bin::run(args, &target_list, msg_info).map(Some)
+    } else if args.targets.len() > 1 {
+        run_targets(args, &target_list, profile.as_ref(), msg_info).map(Some)
+    } else {
+        run_target(args, &target_list, profile.as_ref(), None, msg_info)
+    }
+}
+
+/// Runs each target provided via multiple `--target` flags separately.
+///
+/// Every target may use a different image or builder, so each is run in
+/// its own container. The first failure stops the remaining targets, unless
+/// `--keep-going` is given, and the first failing exit status is returned. A
+/// summary of every target is written to [`manifest::MANIFEST_FILENAME`].
+fn run_targets(
+    args: Args,
+    target_list: &TargetList,
+    profile: Option<&docker::RemoteProfile>,
+    msg_info: &mut MessageInfo,
+) -> Result<ExitStatus> {
+    // the target directory is the one the targets are built in, which is
+    // in the data directory for a read-only workspace.
+    let target_dir = cargo_metadata_with_args(None, Some(&args), msg_info)?
+        .map(|metadata| docker::writable_target_dir(&metadata))
+        .transpose()?;
+    run_each_target(
+        &args,
+        target_dir.as_deref(),
+        msg_info,
+        |target_args, record, msg_info| {
+            run_target(target_args, target_list, profile, Some(record), msg_info)
+        },
+    )
+}
+
+/// Runs every target of `args` with `run`, which returns `None` if the
+/// target should be built with `cargo` on the host.
+fn run_each_target(
+    args: &Args,
+    target_dir: Option<&Path>,
+    msg_info: &mut MessageInfo,
+    mut run: impl FnMut(
+        Args,
+        &mut manifest::TargetManifest,
+        &mut MessageInfo,
+    ) -> Result<Option<ExitStatus>>,
+) -> Result<ExitStatus> {
+    let mut cargo_args = vec![];
+    let mut args_iter = args.cargo_args.iter().cloned();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--target" {
+            args_iter.next();
+        } else if !arg.starts_with("--target=") && arg != "--keep-going" {
+            cargo_args.push(arg);
+        }
+    }
 
+    let profile = manifest::profile_dir(&cargo_args);
+    let mut summary = manifest::Manifest::default();
+    let mut failed = vec![];
+    let mut errors = vec![];
+    // the first target that failed, with an error or a failing exit status
+    let mut first_failure = None;
+    let mut last_success = None;
+    for target in &args.targets {
+        if !failed.is_empty() && !args.keep_going {
+            msg_info.note(format_args!(
+                "skipping `{target}` after a failure, pass `--keep-going` to build it."
+            ))?;
+            continue;
+        }
+        let target_args = Args {
+            cargo_args: cargo_args.clone(),
+            target: Some(target.clone()),
+            targets: vec![],
+            ..args.clone()
+        };
+        let start = std::time::Instant::now();
+        let mut record = manifest::TargetManifest {
+            target: target.triple().to_owned(),
+            ..Default::default()
+        };
+        let result = match run(target_args.clone(), &mut record, msg_info) {
+            Ok(Some(status)) => Ok(status),
+            Err(err) => Err(err),
+            Ok(None) => {
+                msg_info.note(format_args!(
+                    "Falling back to `cargo` on the host for `{target}`."
+                ))?;
+                host_cargo_args(&target_args, msg_info).and_then(|argv| cargo::run(&argv, msg_info))
+            }
+        };
+        let status = match result {
+            Ok(status) => status,
+            Err(err) => {
+                record.duration_secs = start.elapsed().as_secs_f64();
+                summary.targets.push(record);
+                failed.push(target.triple());
+                errors.push(target.triple());
+                // the first failure is returned once the manifest is written,
+                // after every target has run with `--keep-going`.
+                match first_failure {
+                    Some(_) => msg_info.error(format_args!("{err:?}"))?,
+                    None => first_failure = Some(Err(err)),
+                }
+                continue;
+            }
+        };
+        record.duration_secs = start.elapsed().as_secs_f64();
+        record.exit_code = status.code();
+        record.success = status.success();
+        if let (true, Some(target_dir)) = (status.success(), target_dir) {
+            record.artifacts = manifest::find_artifacts(target_dir, target.triple(), &profile)?;
+        }
+        summary.targets.push(record);
+        if status.success() {
+            last_success = Some(status);
+        } else {
+            failed.push(target.triple());
+            first_failure.get_or_insert(Ok(status));
+        }
+    }
+
+    if let Some(target_dir) = target_dir {
+        let path = target_dir.join(manifest::MANIFEST_FILENAME);
+        summary.write(&path)?;
+        msg_info.info(format_args!(
+            "wrote the build manifest to `{}`",
+            path.to_utf8()?
+        ))?;
+    }
+    if args.keep_going {
+        for record in &summary.targets {
+            let errored = errors.contains(&record.target.as_str());
+            msg_info.status(target_summary(record, errored))?;
+        }
+    }
+    if !failed.is_empty() {
+        msg_info.error(format_args!(
+            "failed to run for target(s): {}",
+            failed.join(", ")
+        ))?;
+    }
+
+    // a success is only returned if no target failed
+    match (first_failure, last_success) {
+        (Some(failure), _) => failure,
+        (None, Some(status)) => Ok(status),
+        (None, None) => eyre::bail!("could not run any target"),
+    }
+}
+
+/// The `--features` of the feature sets selected with `--feature-set`, or
+/// of `build.default-features` if none are.
+fn feature_args(args: &Args, config: &Config) -> Result<Vec<String>> {
+    let mut features = vec![];
+    if args.feature_sets.is_empty() {
+        if matches!(args.subcommand, Some(sc) if sc.accepts_features()) {
+            features = config.default_features();
+        }
+    } else {
+        for name in &args.feature_sets {
+            features.extend(config.feature_set(name).failure(ExitCode::Config)?);
+        }
+    }
+    Ok(match features.is_empty() {
+        true => vec![],
+        false => vec!["--features".to_owned(), features.join(",")],
+    })
+}
+
+/// The arguments to run `cargo` on the host with, if the target isn't built
+/// in a container.
+///
+/// They're built from the parsed arguments, so the flags only used by cross
+/// aren't passed to cargo and the feature sets are expanded.
+pub fn host_cargo_args(args: &Args, msg_info: &mut MessageInfo) -> Result<Vec<String>> {
+    if args.docs_rs {
+        return Err(eyre::eyre!(
+            "`--docs-rs` can only be used when building in a container"
+        ))
+        .failure(ExitCode::Config);
+    }
+
+    let mut argv = vec![];
+    if let Some(channel) = &args.channel {
+        argv.push(format!("+{channel}"));
+    }
+    if args.version {
+        argv.push("--version".to_owned());
+    }
+    let mut args_iter = args.cargo_args.iter();
+    while let Some(arg) = args_iter.next() {
+        // the target directory is stored as its path in the container
+        match &args.target_dir {
+            Some(target_dir) if arg == "--target-dir" => {
+                args_iter.next();
+                argv.push(arg.clone());
+                argv.push(target_dir.to_utf8()?.to_owned());
+            }
+            Some(target_dir) if arg.starts_with("--target-dir=") => {
+                argv.push(format!("--target-dir={}", target_dir.to_utf8()?));
+            }
+            _ => argv.push(arg.clone()),
+        }
+    }
+    // the targets of a multi-target build are run one at a time
+    let has_target = args
+        .cargo_args
+        .iter()
+        .any(|a| a == "--target" || a.starts_with("--target="));
+    if let (Some(target), false) = (&args.target, has_target) {
+        argv.push("--target".to_owned());
+        argv.push(target.triple().to_owned());
+    }
+    if !args.feature_sets.is_empty() || matches!(args.subcommand, Some(sc) if sc.accepts_features())
+    {
+        let toml = match cargo_metadata_with_args(None, Some(args), msg_info)? {
+            Some(metadata) => toml(&metadata, msg_info).failure(ExitCode::Config)?,
+            None => None,
+        };
+        argv.extend(feature_args(args, &Config::new(toml))?);
+    }
+    argv.extend(args.rest_args.iter().cloned());
+
+    Ok(argv)
+}
+
+/// A line of the summary of a target printed with `--keep-going`.
+fn target_summary(record: &manifest::TargetManifest, errored: bool) -> String {
+    let status = match (errored, record.success, record.exit_code) {
+        (true, _, _) => "error".to_owned(),
+        (false, true, _) => "ok".to_owned(),
+        (false, false, Some(code)) => format!("failed (exit code {code})"),
+        (false, false, None) => "failed (terminated)".to_owned(),
+    };
+    format!(
+        "{}: {status} in {:.1}s",
+        record.target, record.duration_secs
+    )
+}
+
+fn run_target(
+    args: Args,
+    target_list: &TargetList,
+    profile: Option<&docker::RemoteProfile>,
+    record: Option<&mut manifest::TargetManifest>,
+    msg_info: &mut MessageInfo,
+) -> Result<Option<ExitStatus>> {
     let host_version_meta = rustc::version_meta()?;
 
     let cwd = std::env::current_dir()?;
     if let Some(metadata) = cargo_metadata_with_args(None, Some(&args), msg_info)? {
         let host = host_version_meta.host();
-        let toml = toml(&metadata, msg_info)?;
+        let toml = toml(&metadata, msg_info).failure(ExitCode::Config)?;
         let config = Config::new(toml);
+        let features = feature_args(&args, &config)?;
+        let docs_rs = match args.docs_rs {
+            true => {
+                eyre::ensure!(
+                    args.subcommand == Some(Subcommand::Doc),
+                    "`--docs-rs` can only be used with `cross doc`"
+                );
+                let docs_rs = metadata.docs_rs(&cwd).failure(ExitCode::Config)?;
+                if docs_rs.is_none() {
+                    msg_info.note(
+                        "no `[package.metadata.docs.rs]` found, using the defaults of docs.rs.",
+                    )?;
+                }
+                Some(docs_rs.unwrap_or_default())
+            }
+            false => None,
+        };
         let target = args
             .target
-            .or_else(|| config.target(&target_list))
-            .unwrap_or_else(|| Target::from(host.triple(), &target_list));
+            .or_else(|| {
+                docs_rs
+                    .as_ref()
+                    .and_then(|d| d.target())
+                    .map(|t| Target::from(t, target_list))
+            })
+            .or_else(|| config.target(target_list))
+            .unwrap_or_else(|| Target::from(host.triple(), target_list));
         config.confusable_target(&target, msg_info)?;
 
         let uses_zig = config.zig(&target).unwrap_or(false);
-        let zig_version = config.zig_version(&target)?;
+        let zig_version = config.zig_version(&target).failure(ExitCode::Config)?;
         // Get the image we're supposed to base all our next actions on.
         // The image we actually run in might get changed with
         // `target.{{TARGET}}.dockerfile` or `target.{{TARGET}}.pre-build`
-        let image = match docker::get_image(&config, &target, uses_zig) {
+        let image = match docker::get_image(
+            &config,
+            &target,
+            uses_zig,
+            version_check::is_offline(&args.cargo_args),
+            msg_info,
+        ) {
             Ok(i) => i,
             Err(err) => {
                 msg_info.warn(err)?;
@@ -562,12 +857,49 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
             default_toolchain
         };
 
-        let is_remote = docker::Engine::is_remote();
-        let engine = docker::Engine::new(None, Some(is_remote), msg_info)?;
+        // a remote builder selected with `--remote` has precedence over
+        // the context, engine and remote of every target in `Cross.toml`.
+        let engine = match profile {
+            Some(profile) => docker::Engine::from_profile(profile, msg_info),
+            None => {
+                let context = match args.context {
+                    Some(context) => Some(context),
+                    None => config.context(&target).failure(ExitCode::Config)?,
+                };
+                let engine_name = config.engine(&target).failure(ExitCode::Config)?;
+                // an explicit `CROSS_REMOTE` applies to all targets, and has
+                // precedence over the `remote` key in `Cross.toml`.
+                let remote = match env::var("CROSS_REMOTE") {
+                    Ok(_) => None,
+                    Err(_) => config.remote(&target),
+                };
+                docker::Engine::new(None, remote, context, engine_name.as_deref(), msg_info)
+            }
+        }
+        .failure(ExitCode::EngineUnavailable)?;
+        let is_remote = engine.is_remote;
 
+        let image = docker::prefer_local_image(&config, &target, &engine, image, msg_info)?;
         let image = image.to_definite_with(&engine, msg_info);
 
         toolchain.replace_host(&image.platform);
+
+        if let Some(kind) = args.print {
+            let paths = docker::DockerPaths::create(&engine, metadata, cwd, toolchain, msg_info)?;
+            let options = docker::DockerOptions::new(
+                engine,
+                target,
+                config,
+                image,
+                CargoVariant::Cargo,
+                None,
+                args.subcommand,
+            );
+            let value = options.print_value(kind, &paths, msg_info)?;
+            msg_info.print(value)?;
+            return Ok(Some(extensions::exit_status(0)));
+        }
+
         let maybe_warn = matches!(toolchain.channel.as_str(), "stable" | "beta" | "nightly");
 
         if image.platform.target.is_supported(Some(&target)) {
@@ -584,7 +916,8 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
                     .into_iter()
                     .any(|t| t == toolchain.to_string())
             {
-                rustup::install_toolchain(&toolchain, msg_info)?;
+                rustup::install_toolchain(&toolchain, msg_info)
+                    .failure(ExitCode::ToolchainMissing)?;
             }
             let available_targets = if !toolchain.is_custom {
                 rustup::available_targets(&toolchain.full, msg_info)?
@@ -597,6 +930,7 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
             };
 
             let mut rustc_version = None;
+            let mut rustc_commit = None;
             if let Some((version, channel, commit)) = toolchain.rustc_version()? {
                 if maybe_warn && toolchain.date.is_none() {
                     warn_host_version_mismatch(
@@ -609,6 +943,7 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
                 }
                 is_nightly = channel == Channel::Nightly;
                 rustc_version = Some(version);
+                rustc_commit = Some(commit);
             }
 
             let uses_build_std = config.build_std(&target).unwrap_or(false);
@@ -620,10 +955,11 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
                 // tool but not an available target or doesn't have rust-std.
 
                 if !is_nightly && uses_build_std {
-                    eyre::bail!(
+                    return Err(eyre::eyre!(
                         "no rust-std component available for {}: must use nightly",
                         target.triple()
-                    );
+                    ))
+                    .failure(ExitCode::ToolchainMissing);
                 }
 
                 if !uses_xargo
@@ -631,15 +967,30 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
                     && !available_targets.is_installed(&target)
                     && available_targets.contains(&target)
                 {
-                    rustup::install(&target, &toolchain, msg_info)?;
+                    rustup::install(&target, &toolchain, msg_info)
+                        .failure(ExitCode::ToolchainMissing)?;
                 } else if !rustup::component_is_installed("rust-src", &toolchain, msg_info)? {
-                    rustup::install_component("rust-src", &toolchain, msg_info)?;
+                    rustup::install_component("rust-src", &toolchain, msg_info)
+                        .failure(ExitCode::ToolchainMissing)?;
+                }
+                // proc-macros and build scripts are compiled for the host of
+                // the toolchain, so its standard library must be in the sysroot.
+                let host = Target::new_built_in(toolchain.host().target.triple());
+                if !toolchain.has_std(host.triple()) {
+                    rustup::install(&host, &toolchain, msg_info)
+                        .failure(ExitCode::ToolchainMissing)?;
                 }
                 if args.subcommand.map_or(false, |sc| sc == Subcommand::Clippy)
                     && !rustup::component_is_installed("clippy", &toolchain, msg_info)?
                 {
-                    rustup::install_component("clippy", &toolchain, msg_info)?;
+                    rustup::install_component("clippy", &toolchain, msg_info)
+                        .failure(ExitCode::ToolchainMissing)?;
                 }
+            } else if !toolchain.has_std(toolchain.host().target.triple()) {
+                msg_info.warn(format_args!(
+                    "toolchain `{toolchain}` has no standard library for its host `{}`, so proc-macros and build scripts will fail to build.",
+                    toolchain.host().target
+                ))?;
             }
 
             let needs_interpreter = args.subcommand.map_or(false, |sc| sc.needs_interpreter());
@@ -694,6 +1045,31 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
             if uses_build_std {
                 filtered_args.push("-Zbuild-std".to_owned());
             }
+            let is_nextest = args.subcommand == Some(Subcommand::Nextest);
+            if is_nextest {
+                if cargo_variant != CargoVariant::Cargo {
+                    eyre::bail!(
+                        "`cargo nextest` cannot be used with `{}`",
+                        cargo_variant.to_str()
+                    );
+                }
+                filtered_args.push("--tool-config-file".to_owned());
+                filtered_args.push(format!("cross:{}", docker::NEXTEST_TOOL_CONFIG));
+            }
+            // cargo only prints the environment of build scripts when it's
+            // very verbose. the output is only scanned with local engines.
+            if args.emit_build_script_env && !is_remote && args.verbose < 2 {
+                filtered_args.push("-vv".to_owned());
+            }
+            filtered_args.extend(features);
+            if let Some(docs_rs) = &docs_rs {
+                filtered_args.extend(docs_rs.cargo_flags());
+            }
+            // the flags from the config come after the ones on the command
+            // line, but before any arguments following `--`.
+            if let Some(cargo_flags) = config.cargo_flags(&target)? {
+                filtered_args.extend(cargo_flags);
+            }
             filtered_args.extend(args.rest_args.iter().cloned());
 
             let needs_docker = args
@@ -715,19 +1091,80 @@ To override the toolchain mounted in the image, set `target.{}.image.toolchain =
                     toolchain.clone(),
                     msg_info,
                 )?;
-                let options = docker::DockerOptions::new(
+                let sbom = config.sbom(&target).unwrap_or_default().then(|| {
+                    sbom::Sbom::new(
+                        &paths.metadata,
+                        &target,
+                        &image.name,
+                        &toolchain,
+                        rustc_commit.as_deref(),
+                    )
+                });
+                let sbom_engine = sbom.as_ref().map(|_| engine.clone());
+                let record_engine = record.as_ref().map(|_| engine.clone());
+                let image_name = image.name.clone();
+                let stats_root = stats::enabled().then(|| paths.metadata.workspace_root.clone());
+                let start = std::time::Instant::now();
+                let mut options = docker::DockerOptions::new(
                     engine,
                     target.clone(),
                     config,
                     image,
                     cargo_variant,
                     rustc_version,
+                    args.subcommand,
                 );
+                options.strict = args.strict;
+                if args.no_copy_back {
+                    if options.is_remote() {
+                        options.skip_build_artifacts = true;
+                    } else {
+                        msg_info.warn(
+                            "`--no-copy-back` only applies to remote engines, the target directory is mounted.",
+                        )?;
+                    }
+                }
+                if let Some(docs_rs) = &docs_rs {
+                    options.docs_rs_rustdocflags = docs_rs.rustdoc_flags();
+                }
+                if args.emit_build_script_env {
+                    if options.is_remote() {
+                        msg_info.warn(format_args!(
+                            "`{}` is not supported with remote engines.",
+                            docker::EMIT_BUILD_SCRIPT_ENV_FLAG
+                        ))?;
+                    } else {
+                        options.emit_build_script_env = true;
+                    }
+                }
                 let status = docker::run(options, paths, &filtered_args, msg_info)
                     .wrap_err("could not run container")?;
+                if let Some(root) = stats_root {
+                    let run = stats::Run {
+                        timestamp: docker::now_as_millis()? / 1000,
+                        subcommand: args.subcommand.map(|sc| sc.as_str().to_owned()),
+                        duration_secs: start.elapsed().as_secs_f64(),
+                        success: status.success(),
+                    };
+                    // statistics are informational, so don't fail the build without them
+                    if let Err(err) = stats::record(&root, target.triple(), run) {
+                        msg_info.warn(format_args!("could not record build statistics: {err}"))?;
+                    }
+                }
+                if let (Some(record), Some(engine)) = (record, record_engine) {
+                    // the digest is informational, so don't fail the build without it
+                    if let Ok((id, digests)) = sbom::image_digests(&engine, &image_name, msg_info) {
+                        record.image_id = Some(id);
+                        record.image_digests = digests;
+                    }
+                    record.image = Some(image_name);
+                }
                 let needs_host = args.subcommand.map_or(false, |sc| sc.needs_host(is_remote));
                 if !status.success() {
                     warn_on_failure(&target, &toolchain, msg_info)?;
+                } else if let (Some(sbom), Some(engine)) = (sbom, sbom_engine) {
+                    sbom.write(&engine, msg_info)
+                        .wrap_err("could not write software bill of materials")?;
                 }
                 if !(status.success() && needs_host) {
                     return Ok(Some(status));
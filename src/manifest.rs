@@ -0,0 +1,141 @@
+//! A summary of the builds for multiple targets in one invocation.
+//!
+//! When several targets are passed via `--target`, `cross` writes
+//! `cross-manifest.json` to the target directory, with the image, artifacts,
+//! duration and exit status of each target, so release pipelines only need
+//! to read a single file.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+use crate::file;
+
+pub const MANIFEST_FILENAME: &str = "cross-manifest.json";
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    pub targets: Vec<TargetManifest>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct TargetManifest {
+    pub target: String,
+    /// The image the container was run with, if the target was built in one.
+    pub image: Option<String>,
+    pub image_id: Option<String>,
+    #[serde(default)]
+    pub image_digests: Vec<String>,
+    /// The files in the output directory of the profile.
+    #[serde(default)]
+    pub artifacts: Vec<PathBuf>,
+    pub duration_secs: f64,
+    /// The exit code, which is missing if the build was terminated by a signal.
+    pub exit_code: Option<i32>,
+    pub success: bool,
+}
+
+impl Manifest {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = file::read(path)?;
+        serde_json::from_str(&contents).wrap_err_with(|| format!("could not parse `{path:?}`"))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut file = file::write_file(path, true)?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .wrap_err_with(|| format!("could not write `{path:?}`"))?;
+        file.write_all(b"\n")?;
+
+        Ok(())
+    }
+}
+
+/// Get the name of the output directory for the profile selected by the cargo arguments.
+pub fn profile_dir(cargo_args: &[String]) -> String {
+    let mut profile = "debug".to_owned();
+    let mut args = cargo_args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--release" || arg == "-r" {
+            profile = "release".to_owned();
+        } else if arg == "--profile" {
+            if let Some(name) = args.next() {
+                profile = name.clone();
+            }
+        } else if let Some(name) = arg.strip_prefix("--profile=") {
+            profile = name.to_owned();
+        } else if arg == "--" {
+            break;
+        }
+    }
+    match profile.as_str() {
+        "dev" | "test" => "debug".to_owned(),
+        "bench" => "release".to_owned(),
+        _ => profile,
+    }
+}
+
+/// Find the artifacts for a target, which are the files in the output
+/// directory of the profile, except for the dependency info of cargo.
+pub fn find_artifacts(target_dir: &Path, target: &str, profile: &str) -> Result<Vec<PathBuf>> {
+    let dir = target_dir.join(target).join(profile);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut artifacts = vec![];
+    for entry in fs::read_dir(&dir).wrap_err_with(|| format!("could not read `{dir:?}`"))? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+        let is_dep_info = path.extension() == Some(OsStr::new("d"));
+        if entry.file_type()?.is_file() && !is_hidden && !is_dep_info {
+            artifacts.push(path);
+        }
+    }
+    artifacts.sort();
+
+    Ok(artifacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_dirs() {
+        let args = |args: &[&str]| args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        assert_eq!(profile_dir(&args(&["build"])), "debug");
+        assert_eq!(profile_dir(&args(&["build", "--release"])), "release");
+        assert_eq!(profile_dir(&args(&["build", "--profile", "dist"])), "dist");
+        assert_eq!(profile_dir(&args(&["build", "--profile=dev"])), "debug");
+        assert_eq!(profile_dir(&args(&["run", "--", "--release"])), "debug");
+    }
+
+    #[test]
+    fn manifest_roundtrip() -> Result<()> {
+        let manifest = Manifest {
+            targets: vec![TargetManifest {
+                target: "aarch64-unknown-linux-gnu".to_owned(),
+                image: Some("ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main".to_owned()),
+                image_id: Some("sha256:0123".to_owned()),
+                image_digests: vec![],
+                artifacts: vec![PathBuf::from(
+                    "/project/target/aarch64-unknown-linux-gnu/release/app",
+                )],
+                duration_secs: 12.5,
+                exit_code: Some(0),
+                success: true,
+            }],
+        };
+        let json = serde_json::to_string(&manifest)?;
+        assert!(json.contains("\"image-id\":\"sha256:0123\""));
+        assert_eq!(serde_json::from_str::<Manifest>(&json)?, manifest);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,413 @@
+//! Running an existing binary in the image for its target, with `cross run-bin`.
+//!
+//! The target is inferred from the headers of the ELF or PE binary, unless
+//! it's given with `--target`, and the binary is run with the runner the
+//! image defines for the target, such as `qemu-user` or `wine`.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use crate::cli::Args;
+use crate::config::Config;
+use crate::docker::{self, DockerCommandExt, MountSpec};
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::file::{self, ToUtf8};
+use crate::rustc::TargetList;
+use crate::shell::{MessageInfo, Stream};
+use crate::{cargo_metadata_with_args, Target};
+
+/// The directory containing the binary in the container.
+const MOUNT_DIR: &str = "/cross-run-bin";
+
+/// The C library an ELF binary is linked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Libc {
+    Gnu,
+    Musl,
+    Android,
+}
+
+fn read_u16(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes: [u8; 2] = bytes.get(offset..offset.checked_add(2)?)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u16::from_be_bytes(bytes),
+        false => u16::from_le_bytes(bytes),
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes: [u8; 4] = bytes.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    })
+}
+
+fn read_u64(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u64> {
+    let bytes: [u8; 8] = bytes.get(offset..offset.checked_add(8)?)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u64::from_be_bytes(bytes),
+        false => u64::from_le_bytes(bytes),
+    })
+}
+
+// read an offset or size, which may not fit in a `usize`.
+fn read_usize(bytes: &[u8], offset: usize, is_64: bool, big_endian: bool) -> Option<usize> {
+    match is_64 {
+        true => usize::try_from(read_u64(bytes, offset, big_endian)?).ok(),
+        false => usize::try_from(read_u32(bytes, offset, big_endian)?).ok(),
+    }
+}
+
+/// Get the program interpreter of an ELF binary, from its `PT_INTERP` header.
+///
+/// The offsets come from the binary, so a truncated or malformed binary
+/// has no interpreter, rather than overflowing.
+fn elf_interpreter(bytes: &[u8], is_64: bool, big_endian: bool) -> Option<String> {
+    const PT_INTERP: u32 = 3;
+    let (phoff, phentsize, phnum) = match is_64 {
+        true => (
+            read_usize(bytes, 32, is_64, big_endian)?,
+            read_u16(bytes, 54, big_endian)? as usize,
+            read_u16(bytes, 56, big_endian)? as usize,
+        ),
+        false => (
+            read_usize(bytes, 28, is_64, big_endian)?,
+            read_u16(bytes, 42, big_endian)? as usize,
+            read_u16(bytes, 44, big_endian)? as usize,
+        ),
+    };
+    for index in 0..phnum {
+        let header = index.checked_mul(phentsize)?.checked_add(phoff)?;
+        if read_u32(bytes, header, big_endian)? != PT_INTERP {
+            continue;
+        }
+        let (offset, size) = match is_64 {
+            true => (
+                read_usize(bytes, header.checked_add(8)?, is_64, big_endian)?,
+                read_usize(bytes, header.checked_add(32)?, is_64, big_endian)?,
+            ),
+            false => (
+                read_usize(bytes, header.checked_add(4)?, is_64, big_endian)?,
+                read_usize(bytes, header.checked_add(16)?, is_64, big_endian)?,
+            ),
+        };
+        let interp = bytes.get(offset..offset.checked_add(size)?)?;
+        return Some(
+            String::from_utf8_lossy(interp)
+                .trim_end_matches('\0')
+                .to_owned(),
+        );
+    }
+
+    None
+}
+
+fn linux_triple(arch: &str, libc: Libc, hard_float: bool) -> String {
+    let is_arm = arch.starts_with("arm");
+    let float = match hard_float {
+        true => "hf",
+        false => "",
+    };
+    match libc {
+        Libc::Android if is_arm => "armv7-linux-androideabi".to_owned(),
+        Libc::Android => format!("{arch}-linux-android"),
+        Libc::Gnu if is_arm => format!("{arch}-unknown-linux-gnueabi{float}"),
+        Libc::Gnu if arch.starts_with("mips64") => format!("{arch}-unknown-linux-gnuabi64"),
+        Libc::Gnu => format!("{arch}-unknown-linux-gnu"),
+        Libc::Musl if is_arm => format!("{arch}-unknown-linux-musleabi{float}"),
+        Libc::Musl if arch.starts_with("mips64") => format!("{arch}-unknown-linux-muslabi64"),
+        Libc::Musl => format!("{arch}-unknown-linux-musl"),
+    }
+}
+
+fn infer_elf(bytes: &[u8]) -> Result<String> {
+    // flag for the hard-float ABI in `e_flags`
+    const EF_ARM_ABI_FLOAT_HARD: u32 = 0x400;
+    let is_64 = match bytes.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => eyre::bail!("invalid ELF class"),
+    };
+    let big_endian = match bytes.get(5) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => eyre::bail!("invalid ELF data encoding"),
+    };
+    let machine =
+        read_u16(bytes, 18, big_endian).ok_or_else(|| eyre::eyre!("truncated ELF header"))?;
+    let flags_offset = match is_64 {
+        true => 48,
+        false => 36,
+    };
+    let flags = read_u32(bytes, flags_offset, big_endian).unwrap_or(0);
+    let hard_float = flags & EF_ARM_ABI_FLOAT_HARD != 0;
+    let arch = match (machine, is_64, big_endian) {
+        (3, false, _) => "i686",
+        (62, true, _) => "x86_64",
+        (40, false, false) if hard_float => "armv7",
+        (40, false, false) => "arm",
+        (183, true, false) => "aarch64",
+        (8, false, true) => "mips",
+        (8, false, false) => "mipsel",
+        (8, true, true) => "mips64",
+        (8, true, false) => "mips64el",
+        (20, false, true) => "powerpc",
+        (21, true, true) => "powerpc64",
+        (21, true, false) => "powerpc64le",
+        (243, true, false) => "riscv64gc",
+        (22, true, true) => "s390x",
+        (43, true, true) => "sparc64",
+        _ => eyre::bail!("unsupported ELF machine {machine}"),
+    };
+    // statically-linked binaries have no interpreter, and are usually musl.
+    let libc = match elf_interpreter(bytes, is_64, big_endian) {
+        Some(interp) if interp.contains("ld-musl") => Libc::Musl,
+        Some(interp) if interp.starts_with("/system/") => Libc::Android,
+        Some(_) => Libc::Gnu,
+        None => Libc::Musl,
+    };
+
+    Ok(linux_triple(arch, libc, hard_float))
+}
+
+fn infer_pe(bytes: &[u8]) -> Result<String> {
+    let offset =
+        read_u32(bytes, 0x3c, false).ok_or_else(|| eyre::eyre!("truncated DOS header"))? as usize;
+    if bytes.get(offset..offset + 4) != Some(b"PE\0\0") {
+        eyre::bail!("invalid PE signature");
+    }
+    let machine =
+        read_u16(bytes, offset + 4, false).ok_or_else(|| eyre::eyre!("truncated PE header"))?;
+    match machine {
+        0x14c => Ok("i686-pc-windows-gnu".to_owned()),
+        0x8664 => Ok("x86_64-pc-windows-gnu".to_owned()),
+        _ => eyre::bail!("unsupported PE machine {machine:#x}"),
+    }
+}
+
+/// Infer the target triple of an ELF or PE binary from its headers.
+pub fn infer_target(bytes: &[u8]) -> Result<String> {
+    if bytes.starts_with(b"\x7fELF") {
+        infer_elf(bytes)
+    } else if bytes.starts_with(b"MZ") {
+        infer_pe(bytes)
+    } else {
+        eyre::bail!("not an ELF or PE binary")
+    }
+}
+
+/// Get the path of the binary, from `run-bin <path> [-- args]`.
+fn binary_path(args: &Args) -> Result<PathBuf> {
+    args.cargo_args
+        .iter()
+        .skip_while(|arg| *arg != "run-bin")
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .map(PathBuf::from)
+        .ok_or_else(|| eyre::eyre!("`cross run-bin` requires the path to a binary"))
+}
+
+pub(crate) fn run(
+    args: Args,
+    target_list: &TargetList,
+    msg_info: &mut MessageInfo,
+) -> Result<ExitStatus> {
+    if let Some(name) = &args.remote {
+        eyre::bail!("`cross run-bin` requires bind mounts, which the remote builder `{name}` doesn't support");
+    }
+    let path = file::canonicalize(binary_path(&args)?)?;
+    let target = match args.target {
+        Some(target) => target,
+        None => {
+            let bytes = fs::read(&path).wrap_err_with(|| format!("could not read {path:?}"))?;
+            let triple =
+                infer_target(&bytes).wrap_err_with(|| format!("could not inspect {path:?}"))?;
+            msg_info.info(format_args!("inferred target `{triple}` for {path:?}"))?;
+            Target::from(&triple, target_list)
+        }
+    };
+
+    let toml = match cargo_metadata_with_args(None, None, msg_info)? {
+        Some(metadata) => crate::toml(&metadata, msg_info).failure(ExitCode::Config)?,
+        None => None,
+    };
+    let config = Config::new(toml);
+    let image = docker::get_image(
+        &config,
+        &target,
+        false,
+        crate::version_check::is_offline(&[]),
+        msg_info,
+    )
+    .failure(ExitCode::ImageMissing)?;
+    let context = match args.context {
+        Some(context) => Some(context),
+        None => config.context(&target).failure(ExitCode::Config)?,
+    };
+    let engine_name = config.engine(&target).failure(ExitCode::Config)?;
+    let engine = docker::Engine::new(None, None, context, engine_name.as_deref(), msg_info)
+        .failure(ExitCode::EngineUnavailable)?;
+    if engine.is_remote {
+        eyre::bail!("`cross run-bin` requires bind mounts, which a remote engine doesn't support");
+    }
+    let image = docker::prefer_local_image(&config, &target, &engine, image, msg_info)?;
+    let image = image.to_definite_with(&engine, msg_info);
+
+    let dir = path
+        .parent()
+        .ok_or_else(|| eyre::eyre!("{path:?} has no parent directory"))?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| eyre::eyre!("{path:?} has no file name"))?
+        .to_utf8()?;
+
+    let mut docker = engine.subcommand("run");
+    docker.add_userns(&engine.capabilities);
+    image.platform.specify_platform(&engine, &mut docker);
+    docker.add_user_id(&engine.capabilities);
+    docker.args(["--rm", "-i"]);
+    if io::Stdin::is_atty() && io::Stdout::is_atty() && io::Stderr::is_atty() {
+        docker.arg("-t");
+    }
+    docker.args(
+        MountSpec::bind(&engine, dir, MOUNT_DIR)?
+            .read_only(true)
+            .args(),
+    );
+    docker.args(["-w", MOUNT_DIR]);
+    docker.arg(&image.name);
+    // the runner can have arguments, such as `/linux-runner aarch64`.
+    let runner = format!(
+        "CARGO_TARGET_{}_RUNNER",
+        target.triple().to_ascii_uppercase().replace('-', "_")
+    );
+    docker.args(["sh", "-c", &format!("exec ${{{runner}:-}} \"$0\" \"$@\"")]);
+    docker.arg(format!("{MOUNT_DIR}/{name}"));
+    docker.args(args.rest_args.iter().skip(1));
+
+    docker.run_and_get_status(msg_info, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elf(class: u8, data: u8, machine: u16, flags: u32, interp: Option<&str>) -> Vec<u8> {
+        let is_64 = class == 2;
+        let big_endian = data == 2;
+        let u16_bytes = |v: u16| match big_endian {
+            true => v.to_be_bytes().to_vec(),
+            false => v.to_le_bytes().to_vec(),
+        };
+        let u32_bytes = |v: u32| match big_endian {
+            true => v.to_be_bytes().to_vec(),
+            false => v.to_le_bytes().to_vec(),
+        };
+        let u64_bytes = |v: u64| match big_endian {
+            true => v.to_be_bytes().to_vec(),
+            false => v.to_le_bytes().to_vec(),
+        };
+        let (ehsize, phentsize) = match is_64 {
+            true => (64, 56),
+            false => (52, 32),
+        };
+        let mut bytes = vec![0u8; ehsize];
+        bytes[..4].copy_from_slice(b"\x7fELF");
+        bytes[4] = class;
+        bytes[5] = data;
+        bytes[18..20].copy_from_slice(&u16_bytes(machine));
+        let interp = match interp {
+            Some(interp) => format!("{interp}\0"),
+            None => return bytes,
+        };
+        let interp_offset = (ehsize + phentsize) as u64;
+        if is_64 {
+            bytes[32..40].copy_from_slice(&u64_bytes(ehsize as u64));
+            bytes[48..52].copy_from_slice(&u32_bytes(flags));
+            bytes[54..56].copy_from_slice(&u16_bytes(phentsize as u16));
+            bytes[56..58].copy_from_slice(&u16_bytes(1));
+            let mut header = vec![0u8; phentsize];
+            header[..4].copy_from_slice(&u32_bytes(3));
+            header[8..16].copy_from_slice(&u64_bytes(interp_offset));
+            header[32..40].copy_from_slice(&u64_bytes(interp.len() as u64));
+            bytes.extend(header);
+        } else {
+            bytes[28..32].copy_from_slice(&u32_bytes(ehsize as u32));
+            bytes[36..40].copy_from_slice(&u32_bytes(flags));
+            bytes[42..44].copy_from_slice(&u16_bytes(phentsize as u16));
+            bytes[44..46].copy_from_slice(&u16_bytes(1));
+            let mut header = vec![0u8; phentsize];
+            header[..4].copy_from_slice(&u32_bytes(3));
+            header[4..8].copy_from_slice(&u32_bytes(interp_offset as u32));
+            header[16..20].copy_from_slice(&u32_bytes(interp.len() as u32));
+            bytes.extend(header);
+        }
+        bytes.extend(interp.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn infer_elf_targets() -> Result<()> {
+        let gnu = Some("/lib/ld-linux-aarch64.so.1");
+        assert_eq!(
+            infer_target(&elf(2, 1, 183, 0, gnu))?,
+            "aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            infer_target(&elf(2, 1, 62, 0, Some("/lib/ld-musl-x86_64.so.1")))?,
+            "x86_64-unknown-linux-musl"
+        );
+        assert_eq!(
+            infer_target(&elf(2, 1, 62, 0, None))?,
+            "x86_64-unknown-linux-musl"
+        );
+        assert_eq!(
+            infer_target(&elf(1, 1, 40, 0x400, Some("/lib/ld-linux-armhf.so.3")))?,
+            "armv7-unknown-linux-gnueabihf"
+        );
+        assert_eq!(
+            infer_target(&elf(2, 2, 8, 0, Some("/lib64/ld.so.1")))?,
+            "mips64-unknown-linux-gnuabi64"
+        );
+        assert_eq!(
+            infer_target(&elf(2, 1, 183, 0, Some("/system/bin/linker64")))?,
+            "aarch64-linux-android"
+        );
+        assert!(infer_target(&elf(2, 1, 0x1234, 0, gnu)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_elf_interpreter() {
+        let mut bytes = elf(2, 1, 183, 0, Some("/lib/ld-linux-aarch64.so.1"));
+        assert_eq!(
+            elf_interpreter(&bytes, true, false).as_deref(),
+            Some("/lib/ld-linux-aarch64.so.1")
+        );
+        // an interpreter size that overflows the offset
+        bytes[64 + 32..64 + 40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(elf_interpreter(&bytes, true, false), None);
+        // a program header offset that overflows
+        bytes[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(elf_interpreter(&bytes, true, false), None);
+        assert_eq!(elf_interpreter(&bytes[..40], true, false), None);
+    }
+
+    #[test]
+    fn infer_pe_targets() -> Result<()> {
+        let mut bytes = vec![0u8; 0x80];
+        bytes[..2].copy_from_slice(b"MZ");
+        bytes[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        bytes[0x40..0x44].copy_from_slice(b"PE\0\0");
+        bytes[0x44..0x46].copy_from_slice(&0x8664u16.to_le_bytes());
+        assert_eq!(infer_target(&bytes)?, "x86_64-pc-windows-gnu");
+        assert!(infer_target(b"#!/bin/sh").is_err());
+
+        Ok(())
+    }
+}
@@ -195,6 +195,15 @@ impl QualifiedToolchain {
         &self.sysroot
     }
 
+    /// Returns if the sysroot has the standard library for the target.
+    pub fn has_std(&self, triple: &str) -> bool {
+        self.sysroot
+            .join("lib/rustlib")
+            .join(triple)
+            .join("lib")
+            .exists()
+    }
+
     /// Grab the current default toolchain
     pub fn default(config: &crate::config::Config, msg_info: &mut MessageInfo) -> Result<Self> {
         let sysroot = sysroot(msg_info)?;
@@ -368,6 +377,28 @@ pub fn version_meta() -> Result<rustc_version::VersionMeta> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn has_std() -> Result<()> {
+        let sysroot = tempfile::tempdir()?;
+        let toolchain = QualifiedToolchain::new(
+            "stable",
+            &None,
+            &ImagePlatform::from_const_target("x86_64-unknown-linux-gnu".into()),
+            sysroot.path(),
+            true,
+        );
+        assert!(!toolchain.has_std("x86_64-unknown-linux-gnu"));
+        crate::file::create_dir_all(
+            sysroot
+                .path()
+                .join("lib/rustlib/x86_64-unknown-linux-gnu/lib"),
+        )?;
+        assert!(toolchain.has_std("x86_64-unknown-linux-gnu"));
+        assert!(!toolchain.has_std("aarch64-unknown-linux-gnu"));
+
+        Ok(())
+    }
+
     #[test]
     fn bisect() {
         QualifiedToolchain::custom(
@@ -0,0 +1,224 @@
+//! Software bill of materials for builds run in a container.
+//!
+//! This records the image, toolchain, dependencies and `cross` version
+//! used for a build into a [CycloneDX](https://cyclonedx.org/) document,
+//! written next to the artifacts for the target.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::cargo::Package;
+use crate::docker::Engine;
+use crate::errors::*;
+use crate::file::{self, ToUtf8};
+use crate::rustc::QualifiedToolchain;
+use crate::shell::MessageInfo;
+use crate::{CargoMetadata, CommandExt, Target};
+
+pub const SBOM_FILENAME: &str = "cross-sbom.cdx.json";
+
+#[derive(Debug)]
+pub struct Sbom {
+    document: Value,
+    image: String,
+    path: PathBuf,
+}
+
+impl Sbom {
+    pub fn new(
+        metadata: &CargoMetadata,
+        target: &Target,
+        image: &str,
+        toolchain: &QualifiedToolchain,
+        rustc_commit: Option<&str>,
+    ) -> Self {
+        let is_member = |id: &String| metadata.workspace_members.contains(id);
+        let component = |package: &Package, kind: &str| {
+            let mut component = json!({
+                "type": kind,
+                "bom-ref": package.id,
+                "name": package.name,
+                "version": package.version,
+                "purl": format!("pkg:cargo/{}@{}", package.name, package.version),
+            });
+            if let Some(license) = &package.license {
+                component["licenses"] = json!([{ "expression": license }]);
+            }
+            component
+        };
+        let members: Vec<_> = metadata
+            .packages
+            .iter()
+            .filter(|p| is_member(&p.id))
+            .map(|p| component(p, "application"))
+            .collect();
+        let dependencies: Vec<_> = metadata
+            .packages
+            .iter()
+            .filter(|p| !is_member(&p.id))
+            .map(|p| component(p, "library"))
+            .collect();
+
+        let mut properties = vec![
+            property("cross:target", target.triple()),
+            property("cross:image", image),
+            property("cross:toolchain", &toolchain.full),
+        ];
+        if let Some(commit) = rustc_commit {
+            properties.push(property("cross:rustc-commit", commit));
+        }
+
+        let mut metadata_section = json!({
+            "tools": [{
+                "vendor": "cross-rs",
+                "name": "cross",
+                "version": concat!(env!("CARGO_PKG_VERSION"), crate::commit_info!()).trim(),
+            }],
+            "properties": properties,
+        });
+        if let [member] = members.as_slice() {
+            metadata_section["component"] = member.clone();
+        }
+
+        let document = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.4",
+            "version": 1,
+            "metadata": metadata_section,
+            "components": members.into_iter().chain(dependencies).collect::<Vec<_>>(),
+        });
+
+        Sbom {
+            document,
+            image: image.to_owned(),
+            path: metadata
+                .target_directory
+                .join(target.triple())
+                .join(SBOM_FILENAME),
+        }
+    }
+
+    /// Record the image digest and write the document next to the artifacts.
+    pub fn write(mut self, engine: &Engine, msg_info: &mut MessageInfo) -> Result<PathBuf> {
+        let (id, digests) = image_digests(engine, &self.image, msg_info)?;
+        let properties = self.document["metadata"]["properties"]
+            .as_array_mut()
+            .expect("properties should be an array");
+        properties.push(property("cross:image-id", &id));
+        for digest in digests {
+            properties.push(property("cross:image-digest", &digest));
+        }
+
+        write_document(&self.path, &self.document)?;
+        msg_info.info(format_args!(
+            "wrote software bill of materials to `{}`",
+            self.path.to_utf8()?
+        ))?;
+
+        Ok(self.path)
+    }
+}
+
+fn property(name: &str, value: &str) -> Value {
+    json!({ "name": name, "value": value })
+}
+
+fn write_document(path: &Path, document: &Value) -> Result<()> {
+    let mut file = file::write_file(path, true)?;
+    serde_json::to_writer_pretty(&mut file, document)
+        .wrap_err_with(|| format!("could not write `{path:?}`"))?;
+    file.write_all(b"\n")?;
+
+    Ok(())
+}
+
+/// Get the image ID and the repository digests of an image.
+pub(crate) fn image_digests(
+    engine: &Engine,
+    image: &str,
+    msg_info: &mut MessageInfo,
+) -> Result<(String, Vec<String>)> {
+    let stdout = engine
+        .subcommand("image")
+        .args(["inspect", "--format", "{{.Id}} {{join .RepoDigests \" \"}}"])
+        .arg(image)
+        .run_and_get_stdout(msg_info)
+        .wrap_err_with(|| format!("could not inspect image `{image}`"))?;
+    let mut fields = stdout.split_whitespace().map(ToOwned::to_owned);
+    let id = fields
+        .next()
+        .ok_or_else(|| eyre::eyre!("no image ID found for `{image}`"))?;
+
+    Ok((id, fields.collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::ImagePlatform;
+
+    fn package(id: &str, name: &str, license: Option<&str>) -> Package {
+        Package {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            manifest_path: PathBuf::from(format!("/project/{name}/Cargo.toml")),
+            source: None,
+            version: "0.1.0".to_owned(),
+            license: license.map(ToOwned::to_owned),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn cyclonedx_document() -> Result<()> {
+        let metadata = CargoMetadata {
+            workspace_root: PathBuf::from("/project"),
+            target_directory: PathBuf::from("/project/target"),
+            packages: vec![
+                package("app 0.1.0", "app", Some("MIT")),
+                package("dep 0.1.0", "dep", None),
+            ],
+            workspace_members: vec!["app 0.1.0".to_owned()],
+        };
+        let target = Target::new_built_in("aarch64-unknown-linux-gnu");
+        let toolchain = QualifiedToolchain::new(
+            "stable",
+            &None,
+            &ImagePlatform::from_const_target("x86_64-unknown-linux-gnu".into()),
+            Path::new("/toolchains"),
+            false,
+        );
+        let sbom = Sbom::new(
+            &metadata,
+            &target,
+            "ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main",
+            &toolchain,
+            Some("abcdef"),
+        );
+
+        assert_eq!(
+            sbom.path,
+            PathBuf::from("/project/target/aarch64-unknown-linux-gnu").join(SBOM_FILENAME)
+        );
+        let document = &sbom.document;
+        assert_eq!(document["bomFormat"], "CycloneDX");
+        assert_eq!(document["metadata"]["component"]["name"], "app");
+        assert_eq!(document["components"][0]["type"], "application");
+        assert_eq!(
+            document["components"][0]["licenses"][0]["expression"],
+            "MIT"
+        );
+        assert_eq!(document["components"][1]["type"], "library");
+        assert_eq!(document["components"][1]["purl"], "pkg:cargo/dep@0.1.0");
+        assert!(document["components"][1].get("licenses").is_none());
+        let properties = document["metadata"]["properties"]
+            .as_array()
+            .expect("properties should be an array");
+        assert!(properties.contains(&property("cross:rustc-commit", "abcdef")));
+        assert!(properties.contains(&property("cross:target", "aarch64-unknown-linux-gnu")));
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 // This file was adapted from:
 //   https://github.com/rust-lang/cargo/blob/ca4edabb28fc96fdf2a1d56fe3851831ac166f8a/src/cargo/core/shell.rs
 
+use std::borrow::Cow;
 use std::env;
 use std::fmt;
 use std::io::{self, Write};
@@ -21,14 +22,9 @@ macro_rules! cross_prefix {
 // generate the color style
 macro_rules! write_style {
     ($stream:ident, $msg_info:expr, $message:expr $(, $style:ident)* $(,)?) => {{
-        match $msg_info.color_choice {
-            ColorChoice::Always => write!($stream, "{}", $message $(.$style())*),
-            ColorChoice::Never => write!($stream, "{}", $message),
-            ColorChoice::Auto => write!(
-                $stream,
-                "{}",
-                $message $(.if_supports_color($stream.owo(), |text| text.$style()))*
-            ),
+        match $msg_info.should_color(&$stream) {
+            true => write!($stream, "{}", $message $(.$style())*),
+            false => write!($stream, "{}", $message),
         }?;
     }};
 }
@@ -145,6 +141,9 @@ pub struct MessageInfo {
     pub stdout_needs_erase: bool,
     pub stderr_needs_erase: bool,
     pub cross_debug: bool,
+    // if transient progress messages are shown, which they can't be while
+    // other threads write to stderr.
+    pub show_progress: bool,
 }
 
 impl MessageInfo {
@@ -158,6 +157,7 @@ impl MessageInfo {
                 .as_deref()
                 .map(bool_from_envvar)
                 .unwrap_or_default(),
+            show_progress: true,
         }
     }
 
@@ -183,6 +183,16 @@ impl MessageInfo {
         self.verbosity.verbose()
     }
 
+    /// Returns if output to the stream should be colored.
+    #[must_use]
+    pub fn should_color<S: Stream>(&self, _stream: &S) -> bool {
+        match self.color_choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => S::is_atty(),
+        }
+    }
+
     fn as_verbosity<T, C: Fn(&mut MessageInfo) -> T>(&mut self, call: C, new: Verbosity) -> T {
         let old = self.verbosity;
         self.verbosity = new;
@@ -205,8 +215,8 @@ impl MessageInfo {
     }
 
     fn erase_line<S: Stream + Write>(&mut self, stream: &mut S) -> Result<()> {
-        // this is the Erase in Line sequence
-        stream.write_all(b"\x1B[K").map_err(Into::into)
+        // return to the start of the line, and use the Erase in Line sequence
+        stream.write_all(b"\r\x1B[K").map_err(Into::into)
     }
 
     fn stdout_check_erase(&mut self) -> Result<()> {
@@ -273,6 +283,24 @@ impl MessageInfo {
         }
     }
 
+    /// prints a transient progress message to stderr, which replaces the
+    /// previous one. since it's erased afterwards, it's shown even when
+    /// quiet, but only if stderr is a terminal.
+    pub fn progress<T: fmt::Display>(&mut self, message: T) -> Result<()> {
+        if self.show_progress && io::Stderr::is_atty() {
+            let mut stderr = io::stderr();
+            write!(stderr, "\r\x1B[K{message}")?;
+            stderr.flush()?;
+            self.stderr_needs_erase = true;
+        }
+        Ok(())
+    }
+
+    /// erases the progress message, if one was printed.
+    pub fn finish_progress(&mut self) -> Result<()> {
+        self.stderr_check_erase()
+    }
+
     /// prints a high-priority message to stdout.
     #[track_caller]
     pub fn print<T: fmt::Display>(&mut self, message: T) -> Result<()> {
@@ -417,16 +445,60 @@ pub fn invalid_color(provided: Option<&str>) -> ! {
     MessageInfo::default().fatal_usage("--color <WHEN>", provided, Some(&possible), 1);
 }
 
+// an explicit `--color` or `CARGO_TERM_COLOR` has precedence over the
+// `NO_COLOR` and `CLICOLOR_FORCE` conventions, like cargo.
 fn get_color_choice(color: Option<&str>) -> Result<ColorChoice> {
+    let var = |name| env::var(name).ok().filter(|v: &String| !v.is_empty());
     Ok(match color {
         Some(arg) => arg.parse().unwrap_or_else(|_| invalid_color(color)),
         None => match env::var("CARGO_TERM_COLOR").ok().as_deref() {
             Some(arg) => arg.parse().unwrap_or_else(|_| invalid_color(color)),
+            None if var("NO_COLOR").is_some() => ColorChoice::Never,
+            None if var("CLICOLOR_FORCE").filter(|v| v != "0").is_some() => ColorChoice::Always,
             None => ColorChoice::Auto,
         },
     })
 }
 
+/// Remove any ANSI escape sequences, such as colors, from the output of a command.
+#[must_use]
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1B') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1B' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI sequences, such as SGR colors, end with a byte in `@` to `~`.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC sequences, such as hyperlinks, end with BEL or ST (`ESC \\`).
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1B' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // other sequences have a single character after the escape.
+            _ => (),
+        }
+    }
+
+    Cow::Owned(result)
+}
+
 fn get_verbosity(
     color_choice: ColorChoice,
     verbose: impl Into<u8>,
@@ -491,3 +563,29 @@ pub fn indent(message: &str, spaces: usize) -> String {
         .map(|s| format!("{:spaces$}{s}", ""))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_ansi_sequences() {
+        assert!(matches!(
+            strip_ansi("no escapes"),
+            Cow::Borrowed("no escapes")
+        ));
+        assert_eq!(
+            strip_ansi("\x1B[1m\x1B[31merror\x1B[0m: failed"),
+            "error: failed"
+        );
+        assert_eq!(
+            strip_ansi("#1 [internal] \x1B[2K\x1B[1Gdone"),
+            "#1 [internal] done"
+        );
+        assert_eq!(
+            strip_ansi("see \x1B]8;;https://example.com\x1B\\here\x1B]8;;\x07."),
+            "see here."
+        );
+        assert_eq!(strip_ansi("unterminated \x1B["), "unterminated ");
+    }
+}
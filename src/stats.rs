@@ -0,0 +1,193 @@
+//! Local statistics for the builds run in containers.
+//!
+//! When `CROSS_STATS` is set, `cross` records the duration and result of every
+//! containerized run in `.cross/stats.json` in the workspace root. The file is
+//! never uploaded: it's only read by `cross-util stats`, to show whether
+//! features such as persistent volumes or `sccache` make builds faster.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::bool_from_envvar;
+use crate::docker;
+use crate::errors::*;
+use crate::file;
+
+pub const STATS_ENV: &str = "CROSS_STATS";
+pub const STATS_DIR: &str = ".cross";
+pub const STATS_FILENAME: &str = "stats.json";
+/// The number of runs kept for each target, older runs are only counted.
+pub const MAX_RUNS: usize = 100;
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Stats {
+    #[serde(default)]
+    pub targets: BTreeMap<String, TargetStats>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct TargetStats {
+    pub builds: u64,
+    pub failures: u64,
+    /// The most recent runs, oldest first.
+    #[serde(default)]
+    pub runs: Vec<Run>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Run {
+    /// When the run finished, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub subcommand: Option<String>,
+    pub duration_secs: f64,
+    pub success: bool,
+}
+
+/// Returns if statistics should be recorded, which is opt-in.
+pub fn enabled() -> bool {
+    env::var(STATS_ENV)
+        .map(|value| bool_from_envvar(&value))
+        .unwrap_or_default()
+}
+
+/// The path of the statistics for the workspace, which are in the data
+/// directory if the workspace is read-only.
+pub fn path(workspace_root: &Path) -> PathBuf {
+    let dir = workspace_root.join(STATS_DIR);
+    if !dir.exists() && !file::is_writable(workspace_root) {
+        let data_path = file::cross_data_dir().and_then(|data| {
+            let hash = docker::path_hash(workspace_root, docker::PATH_HASH_SHORT)?;
+            Ok(data.join("stats").join(format!("{hash}.json")))
+        });
+        if let Ok(path) = data_path {
+            return path;
+        }
+    }
+    dir.join(STATS_FILENAME)
+}
+
+impl Stats {
+    /// Read the statistics, which are empty if the file doesn't exist.
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Stats::default());
+        }
+        let contents = file::read(path)?;
+        serde_json::from_str(&contents).wrap_err_with(|| format!("could not parse `{path:?}`"))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut file = file::write_file(path, true)?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .wrap_err_with(|| format!("could not write `{path:?}`"))?;
+        file.write_all(b"\n")?;
+
+        Ok(())
+    }
+
+    pub fn record(&mut self, target: &str, run: Run) {
+        let stats = self.targets.entry(target.to_owned()).or_default();
+        stats.builds += 1;
+        if !run.success {
+            stats.failures += 1;
+        }
+        stats.runs.push(run);
+        if stats.runs.len() > MAX_RUNS {
+            let extra = stats.runs.len() - MAX_RUNS;
+            stats.runs.drain(..extra);
+        }
+    }
+}
+
+impl TargetStats {
+    /// The mean duration of the successful runs among the recorded ones.
+    pub fn mean_duration(&self) -> Option<f64> {
+        mean(self.runs.iter())
+    }
+
+    /// The change in the mean duration of the `recent` latest successful
+    /// runs, compared to the ones before them, as a fraction.
+    pub fn trend(&self, recent: usize) -> Option<f64> {
+        let successful: Vec<_> = self.runs.iter().filter(|run| run.success).collect();
+        if successful.len() <= recent {
+            return None;
+        }
+        let (before, after) = successful.split_at(successful.len() - recent);
+        let before = mean(before.iter().copied())?;
+        let after = mean(after.iter().copied())?;
+        match before > 0.0 {
+            true => Some((after - before) / before),
+            false => None,
+        }
+    }
+}
+
+fn mean<'a>(runs: impl Iterator<Item = &'a Run>) -> Option<f64> {
+    let durations: Vec<f64> = runs
+        .filter(|run| run.success)
+        .map(|run| run.duration_secs)
+        .collect();
+    match durations.is_empty() {
+        true => None,
+        false => Some(durations.iter().sum::<f64>() / durations.len() as f64),
+    }
+}
+
+/// Record a run in the statistics for the workspace.
+pub fn record(workspace_root: &Path, target: &str, run: Run) -> Result<()> {
+    let path = path(workspace_root);
+    let mut stats = Stats::read(&path)?;
+    stats.record(target, run);
+    stats.write(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(duration_secs: f64, success: bool) -> Run {
+        Run {
+            timestamp: 1_654_077_600,
+            subcommand: Some("build".to_owned()),
+            duration_secs,
+            success,
+        }
+    }
+
+    #[test]
+    fn record_runs() {
+        let mut stats = Stats::default();
+        for i in 0..MAX_RUNS + 5 {
+            stats.record("aarch64-unknown-linux-gnu", run(i as f64, i % 10 != 0));
+        }
+        let target = &stats.targets["aarch64-unknown-linux-gnu"];
+        assert_eq!(target.builds, MAX_RUNS as u64 + 5);
+        assert_eq!(target.failures, 11);
+        assert_eq!(target.runs.len(), MAX_RUNS);
+        assert_eq!(target.runs[0].duration_secs, 5.0);
+    }
+
+    #[test]
+    fn durations() {
+        let target = TargetStats {
+            builds: 5,
+            failures: 1,
+            runs: vec![
+                run(100.0, true),
+                run(80.0, true),
+                run(1.0, false),
+                run(30.0, true),
+                run(10.0, true),
+            ],
+        };
+        assert_eq!(target.mean_duration(), Some(55.0));
+        assert_eq!(target.trend(2), Some(-0.7777777777777778));
+        assert_eq!(target.trend(4), None);
+        assert_eq!(TargetStats::default().mean_duration(), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! The environment `cross` sets in the container, so test suites can skip or
+//! adapt tests that don't work under emulation.
+//!
+//! The variables are set for every command run in the container, including
+//! when the crate is compiled, so they can also be read at compile time with
+//! `option_env!`. Outside of `cross`, none of them are set.
+//!
+//! ```rust,no_run
+//! # use cross::test_env;
+//! if test_env::is_emulated() {
+//!     // timing-sensitive tests are unreliable under qemu
+//!     return;
+//! }
+//! ```
+
+use std::env;
+
+use crate::docker::Architecture;
+use crate::Target;
+
+/// The target triple being built.
+pub const TARGET_ENV: &str = "CROSS_TARGET";
+/// How the binaries for the target are run, see [`RunnerKind`].
+pub const RUNNER_KIND_ENV: &str = "CROSS_RUNNER_KIND";
+/// `1` if the binaries are run in an emulator, otherwise `0`.
+pub const EMULATED_ENV: &str = "CROSS_EMULATED";
+
+/// How the binaries for the target are run in the container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunnerKind {
+    Native,
+    QemuUser,
+    QemuSystem,
+    Wine,
+}
+
+impl RunnerKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunnerKind::Native => "native",
+            RunnerKind::QemuUser => "qemu-user",
+            RunnerKind::QemuSystem => "qemu-system",
+            RunnerKind::Wine => "wine",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<RunnerKind> {
+        match s {
+            "native" => Some(RunnerKind::Native),
+            "qemu-user" => Some(RunnerKind::QemuUser),
+            "qemu-system" => Some(RunnerKind::QemuSystem),
+            "wine" => Some(RunnerKind::Wine),
+            _ => None,
+        }
+    }
+
+    /// Returns if the binaries are run in an emulator for another
+    /// architecture.
+    pub fn is_emulated(self) -> bool {
+        matches!(self, RunnerKind::QemuUser | RunnerKind::QemuSystem)
+    }
+
+    /// The runner used for the target by the runners in the images, from the
+    /// configured `runner` and the architecture the image runs on. Targets
+    /// without a runner, such as the BSDs, have none.
+    pub(crate) fn for_target(
+        target: &Target,
+        runner: Option<&str>,
+        host: &Architecture,
+    ) -> Option<RunnerKind> {
+        if target.is_windows() {
+            Some(RunnerKind::Wine)
+        } else if target.is_linux() || target.is_android() {
+            match runner.and_then(RunnerKind::from_name) {
+                Some(kind) => Some(kind),
+                None if !target.is_android() && is_native(target.triple(), host) => {
+                    Some(RunnerKind::Native)
+                }
+                None => Some(RunnerKind::QemuUser),
+            }
+        } else {
+            None
+        }
+    }
+
+    /// The runner of the current process, if it's run by `cross`.
+    pub fn from_env() -> Option<RunnerKind> {
+        env::var(RUNNER_KIND_ENV)
+            .ok()
+            .and_then(|kind| RunnerKind::from_name(&kind))
+    }
+}
+
+/// Like `is_native_binary` in the runners of the images.
+fn is_native(triple: &str, host: &Architecture) -> bool {
+    let arch = triple.split('-').next().unwrap_or_default();
+    match host {
+        Architecture::Amd64 => matches!(arch, "x86_64" | "i586" | "i686"),
+        Architecture::Arm64 => arch == "aarch64",
+        _ => false,
+    }
+}
+
+/// The target being built, if the current process is run by `cross`.
+pub fn target() -> Option<String> {
+    env::var(TARGET_ENV).ok()
+}
+
+/// Returns if the current process is run by `cross` in an emulator.
+pub fn is_emulated() -> bool {
+    matches!(env::var(EMULATED_ENV).as_deref(), Ok("1"))
+}
+
+/// The `-e` values to set the variables in the container.
+pub(crate) fn container_env(target: &Target, kind: Option<RunnerKind>) -> Vec<String> {
+    let mut env = vec![format!("{TARGET_ENV}={target}")];
+    if let Some(kind) = kind {
+        env.push(format!("{RUNNER_KIND_ENV}={}", kind.as_str()));
+        env.push(format!("{EMULATED_ENV}={}", kind.is_emulated() as u8));
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetList;
+
+    fn target(triple: &str) -> Target {
+        Target::from(
+            triple,
+            &TargetList {
+                triples: vec![triple.to_owned()],
+            },
+        )
+    }
+
+    #[test]
+    fn runner_kinds() {
+        let amd64 = Architecture::Amd64;
+        let kind = |triple, runner| RunnerKind::for_target(&target(triple), runner, &amd64);
+        assert_eq!(
+            kind("x86_64-unknown-linux-gnu", None),
+            Some(RunnerKind::Native)
+        );
+        assert_eq!(
+            kind("i686-unknown-linux-gnu", None),
+            Some(RunnerKind::Native)
+        );
+        assert_eq!(
+            kind("aarch64-unknown-linux-gnu", None),
+            Some(RunnerKind::QemuUser)
+        );
+        assert_eq!(
+            kind("aarch64-unknown-linux-gnu", Some("qemu-system")),
+            Some(RunnerKind::QemuSystem)
+        );
+        assert_eq!(
+            kind("x86_64-linux-android", None),
+            Some(RunnerKind::QemuUser)
+        );
+        assert_eq!(kind("x86_64-pc-windows-gnu", None), Some(RunnerKind::Wine));
+        assert_eq!(kind("x86_64-unknown-freebsd", None), None);
+        assert_eq!(
+            RunnerKind::for_target(
+                &target("aarch64-unknown-linux-gnu"),
+                None,
+                &Architecture::Arm64
+            ),
+            Some(RunnerKind::Native)
+        );
+
+        assert_eq!(
+            container_env(
+                &target("aarch64-unknown-linux-gnu"),
+                Some(RunnerKind::QemuUser)
+            ),
+            [
+                "CROSS_TARGET=aarch64-unknown-linux-gnu",
+                "CROSS_RUNNER_KIND=qemu-user",
+                "CROSS_EMULATED=1",
+            ]
+        );
+    }
+}
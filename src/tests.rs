@@ -136,6 +136,140 @@ release: {version}
     );
 }
 
+#[test]
+fn run_targets_second_fails() -> crate::Result<()> {
+    use crate::{cli::Args, extensions::exit_status, Target};
+
+    let args = Args {
+        targets: vec![
+            Target::new_built_in("x86_64-unknown-linux-gnu"),
+            Target::new_built_in("aarch64-unknown-linux-gnu"),
+        ],
+        ..Args::default()
+    };
+    let mut msg_info = crate::shell::Verbosity::Quiet.into();
+    let mut ran = vec![];
+    let status = crate::run_each_target(&args, None, &mut msg_info, |args, _, _| {
+        let target = args.target.expect("a target should be given");
+        let code = match target.triple() {
+            "aarch64-unknown-linux-gnu" => 101,
+            _ => 0,
+        };
+        ran.push(target);
+        Ok(Some(exit_status(code)))
+    })?;
+    assert_eq!(ran, args.targets);
+    assert_eq!(status.code(), Some(101));
+
+    Ok(())
+}
+
+#[test]
+fn run_targets_keep_going() -> crate::Result<()> {
+    use crate::{cli::Args, extensions::exit_status, Target};
+
+    let mut args = Args {
+        targets: vec![
+            Target::new_built_in("x86_64-unknown-linux-gnu"),
+            Target::new_built_in("aarch64-unknown-linux-gnu"),
+            Target::new_built_in("armv7-unknown-linux-gnueabihf"),
+        ],
+        ..Args::default()
+    };
+    let target_dir = tempfile::tempdir()?;
+    let mut msg_info = crate::shell::Verbosity::Quiet.into();
+    let mut run_all = |args: &Args, ran: &mut Vec<Target>| {
+        crate::run_each_target(
+            args,
+            Some(target_dir.path()),
+            &mut msg_info,
+            |args, _, _| {
+                let target = args.target.expect("a target should be given");
+                ran.push(target.clone());
+                match target.triple() {
+                    "x86_64-unknown-linux-gnu" => eyre::bail!("no image for `{target}`"),
+                    "aarch64-unknown-linux-gnu" => Ok(Some(exit_status(0))),
+                    _ => Ok(Some(exit_status(101))),
+                }
+            },
+        )
+    };
+
+    // the error of the first target stops the others, after the manifest is written
+    let mut ran = vec![];
+    let result = run_all(&args, &mut ran);
+    assert_eq!(ran, &args.targets[..1]);
+    assert!(result.is_err());
+    let manifest = crate::manifest::Manifest::read(
+        &target_dir.path().join(crate::manifest::MANIFEST_FILENAME),
+    )?;
+    assert_eq!(manifest.targets.len(), 1);
+    assert_eq!(manifest.targets[0].target, "x86_64-unknown-linux-gnu");
+    assert!(!manifest.targets[0].success);
+
+    // every target is run, and the first error is still returned
+    args.keep_going = true;
+    let mut ran = vec![];
+    let result = run_all(&args, &mut ran);
+    assert_eq!(ran, args.targets);
+    assert_eq!(
+        result.map_err(|e| e.to_string()),
+        Err("no image for `x86_64-unknown-linux-gnu`".to_owned())
+    );
+
+    // a failing status is returned over a later error
+    args.targets.reverse();
+    let mut ran = vec![];
+    let status = run_all(&args, &mut ran)?;
+    assert_eq!(ran, args.targets);
+    assert_eq!(status.code(), Some(101));
+
+    Ok(())
+}
+
+#[test]
+fn host_cargo_args() -> crate::Result<()> {
+    use crate::{cli::Args, Target};
+
+    let mut msg_info = crate::shell::Verbosity::Quiet.into();
+    let target_dir = std::env::temp_dir().join("target");
+    let args = Args {
+        cargo_args: vec![
+            "fmt".to_owned(),
+            "--target-dir".to_owned(),
+            "/target".to_owned(),
+        ],
+        rest_args: vec!["--".to_owned(), "--check".to_owned()],
+        channel: Some("nightly".to_owned()),
+        target: Some(Target::new_built_in("wasm32-unknown-unknown")),
+        target_dir: Some(target_dir.clone()),
+        strict: true,
+        no_copy_back: true,
+        ..Args::default()
+    };
+    assert_eq!(
+        crate::host_cargo_args(&args, &mut msg_info)?,
+        [
+            "+nightly",
+            "fmt",
+            "--target-dir",
+            target_dir.to_utf8()?,
+            "--target",
+            "wasm32-unknown-unknown",
+            "--",
+            "--check",
+        ]
+    );
+
+    let docs_rs = Args {
+        docs_rs: true,
+        ..args
+    };
+    assert!(crate::host_cargo_args(&docs_rs, &mut msg_info).is_err());
+
+    Ok(())
+}
+
 #[test]
 fn check_newlines() -> crate::Result<()> {
     for file in walk_dir(get_cargo_workspace(), &[".git", "target"], |_| true) {
@@ -0,0 +1,188 @@
+//! Checking for a newer release of `cross`.
+//!
+//! The check is opt-in, with `--version-check` or `CROSS_VERSION_CHECK`, and
+//! the latest release is only fetched from GitHub once a day. It never runs
+//! in offline mode, and it's disabled in CI unless `--version-check` is given.
+//! The release is fetched with `curl`, and the startup check is skipped
+//! quietly if it's not installed or the request fails.
+
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::config::bool_from_envvar;
+use crate::docker;
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::file;
+use crate::shell::MessageInfo;
+
+pub const VERSION_CHECK_ENV: &str = "CROSS_VERSION_CHECK";
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/cross-rs/cross/releases/latest";
+/// How long the latest release is cached for.
+const CACHE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct Cache {
+    /// When the latest release was fetched, in seconds since the Unix epoch.
+    checked: u64,
+    latest: String,
+}
+
+/// Returns if network access is disabled, like it is for cargo.
+pub fn is_offline(cargo_args: &[String]) -> bool {
+    has_offline_flag(cargo_args)
+        || env::var("CARGO_NET_OFFLINE")
+            .map(|value| bool_from_envvar(&value))
+            .unwrap_or_default()
+}
+
+// flags after `--` are passed to the binary, rather than cargo.
+fn has_offline_flag(cargo_args: &[String]) -> bool {
+    cargo_args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--offline" || arg == "--frozen")
+}
+
+/// Returns if the check should run on startup.
+pub fn is_enabled(flag: bool, offline: bool) -> bool {
+    if offline {
+        false
+    } else if flag {
+        true
+    } else {
+        env::var("CI").is_err()
+            && env::var(VERSION_CHECK_ENV)
+                .map(|value| bool_from_envvar(&value))
+                .unwrap_or_default()
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|d| d.cache_dir().join("cross-rs").join("latest-release.json"))
+}
+
+fn parse_release(json: &str) -> Result<Version> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).wrap_err("could not parse the latest release")?;
+    let tag = value["tag_name"]
+        .as_str()
+        .ok_or_else(|| eyre::eyre!("the latest release has no tag"))?;
+    Version::parse(tag.trim_start_matches('v'))
+        .wrap_err_with(|| format!("invalid version for release `{tag}`"))
+}
+
+fn fetch_latest(msg_info: &mut MessageInfo) -> Result<Version> {
+    if which::which("curl").is_err() {
+        return Err(eyre::eyre!("`curl` was not found"))
+            .with_suggestion(|| "install `curl` to check for a newer release of cross.");
+    }
+    let json = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", "5"])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .arg(LATEST_RELEASE_URL)
+        .run_and_get_stdout(msg_info)
+        .wrap_err("could not fetch the latest release of cross")?;
+    parse_release(&json)
+}
+
+/// Get the latest release, from the cache if it was fetched in the last day,
+/// unless `refresh` is set.
+pub fn latest_release(refresh: bool, msg_info: &mut MessageInfo) -> Result<Version> {
+    let now = docker::now_as_millis()? / 1000;
+    let path = cache_path();
+    if let (false, Some(path)) = (refresh, &path) {
+        let cache = file::read(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Cache>(&contents).ok());
+        if let Some(cache) = cache {
+            if now.saturating_sub(cache.checked) < CACHE_DURATION.as_secs() {
+                if let Ok(version) = Version::parse(&cache.latest) {
+                    return Ok(version);
+                }
+            }
+        }
+    }
+
+    let latest = fetch_latest(msg_info)?;
+    if let Some(path) = path {
+        let cache = Cache {
+            checked: now,
+            latest: latest.to_string(),
+        };
+        let mut file = file::write_file(&path, true)?;
+        file.write_all(serde_json::to_string(&cache)?.as_bytes())?;
+    }
+
+    Ok(latest)
+}
+
+/// Returns the latest release if it's newer than the running version.
+pub fn newer_release(refresh: bool, msg_info: &mut MessageInfo) -> Result<Option<Version>> {
+    let current = Version::parse(env!("CARGO_PKG_VERSION"))?;
+    let latest = latest_release(refresh, msg_info)?;
+    match is_newer(&current, &latest) {
+        true => Ok(Some(latest)),
+        false => Ok(None),
+    }
+}
+
+fn is_newer(current: &Version, latest: &Version) -> bool {
+    // development builds of a release, such as `0.2.5-dev`, are older than it
+    latest > current
+}
+
+pub fn upgrade_hint(latest: &Version) -> String {
+    format!(
+        "cross {latest} is available, you have {}.\n > Upgrade with `cargo install cross --locked`",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// Run the startup check, which never fails the build.
+pub fn startup(flag: bool, cargo_args: &[String], msg_info: &mut MessageInfo) -> Result<()> {
+    if !is_enabled(flag, is_offline(cargo_args)) {
+        return Ok(());
+    }
+    match newer_release(false, msg_info) {
+        Ok(Some(latest)) => msg_info.note(upgrade_hint(&latest)),
+        Ok(None) => Ok(()),
+        Err(err) => msg_info.debug(format_args!("unable to check for a newer release: {err}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_versions() -> Result<()> {
+        let latest = parse_release(r#"{"tag_name": "v0.2.5", "name": "v0.2.5"}"#)?;
+        assert_eq!(latest, Version::new(0, 2, 5));
+        assert!(parse_release(r#"{"message": "Not Found"}"#).is_err());
+
+        assert!(is_newer(&Version::parse("0.2.4")?, &latest));
+        assert!(is_newer(&Version::parse("0.2.5-dev")?, &latest));
+        assert!(!is_newer(&latest, &latest));
+        assert!(!is_newer(&Version::parse("0.3.0-dev")?, &latest));
+
+        Ok(())
+    }
+
+    #[test]
+    fn offline_args() {
+        let args = |args: &[&str]| args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        assert!(has_offline_flag(&args(&["build", "--offline"])));
+        assert!(has_offline_flag(&args(&["build", "--frozen"])));
+        assert!(!has_offline_flag(&args(&["run", "--", "--offline"])));
+        assert!(is_offline(&args(&["build", "--offline"])));
+        assert!(!is_enabled(true, true));
+    }
+}
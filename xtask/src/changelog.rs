@@ -2,15 +2,17 @@ use std::cmp;
 use std::collections::BTreeSet;
 use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 
 use crate::util::{project_dir, write_to_string};
 use chrono::{Datelike, Utc};
-use clap::Args;
+use clap::{Args, ValueEnum};
+use color_eyre::Section;
 use cross::shell::MessageInfo;
 use cross::ToUtf8;
 use eyre::Context;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Args, Debug)]
 pub struct BuildChangelog {
@@ -29,6 +31,15 @@ pub struct BuildChangelog {
     /// Whether we're doing a dry run or not.
     #[clap(long, env = "DRY_RUN")]
     dry_run: bool,
+    /// Merge the changes into the latest release, rather than a new one.
+    #[clap(long, conflicts_with = "release")]
+    amend: bool,
+    /// Wrap the entries longer than this width.
+    #[clap(long, env = "CHANGELOG_WRAP_WIDTH")]
+    wrap_width: Option<usize>,
+    /// The indentation of the continuation lines of wrapped entries.
+    #[clap(long, default_value_t = 2)]
+    continuation_indent: usize,
 }
 
 #[derive(Args, Debug)]
@@ -46,6 +57,41 @@ pub struct ValidateChangelog {
     pub color: Option<String>,
 }
 
+#[derive(Args, Debug)]
+pub struct IngestChangelog {
+    /// The file stem for the entry, e.g. the PR number `1024`.
+    id: String,
+    /// Read the input from stdin.
+    #[clap(long, conflicts_with = "input")]
+    stdin: bool,
+    /// Read the input from a file.
+    #[clap(long)]
+    input: Option<PathBuf>,
+    /// The format of the input.
+    #[clap(long, value_enum, default_value_t = IngestFormat::Markdown)]
+    format: IngestFormat,
+    /// Overwrite an existing changelog entry.
+    #[clap(long)]
+    force: bool,
+    /// Provide verbose diagnostic output.
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Do not print cross log messages.
+    #[clap(short, long)]
+    pub quiet: bool,
+    /// Whether messages should use color output.
+    #[clap(long)]
+    pub color: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IngestFormat {
+    /// A fenced `changelog` code block, such as in a PR description.
+    Markdown,
+    /// The changelog entry as JSON.
+    Json,
+}
+
 // the type for the identifier: if it's a PR, sort
 // by the number, otherwise, sort as 0. the numbers
 // should be sorted, and the `max(values) || 0` should
@@ -94,6 +140,19 @@ impl IdType {
 
         Ok(IdType::PullRequest(numbers))
     }
+
+    fn label(&self) -> String {
+        let numbers = self
+            .numbers()
+            .iter()
+            .map(|x| format!("#{x}"))
+            .collect::<Vec<String>>()
+            .join(",");
+        match self {
+            IdType::PullRequest(_) => numbers,
+            IdType::Issue(_) => format!("issue {numbers}"),
+        }
+    }
 }
 
 impl cmp::PartialOrd for IdType {
@@ -161,6 +220,8 @@ struct ChangelogContents {
     issues: Vec<u64>,
     #[serde(default)]
     breaking: bool,
+    #[serde(default)]
+    authors: Vec<String>,
     #[serde(rename = "type")]
     kind: ChangelogType,
 }
@@ -225,6 +286,7 @@ impl ChangelogEntry {
                 breaking,
                 description,
                 issues: vec![],
+                authors: vec![],
             },
         })
     }
@@ -265,6 +327,104 @@ impl fmt::Display for ChangelogEntry {
     }
 }
 
+fn normalize_description(description: &str) -> String {
+    description
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .trim_end_matches('.')
+        .to_lowercase()
+}
+
+impl ChangelogEntry {
+    // the issues of the entry, including its ID for changesets of an issue
+    fn all_issues(&self) -> BTreeSet<u64> {
+        let mut issues: BTreeSet<u64> = self.contents.issues.iter().copied().collect();
+        if let IdType::Issue(numbers) = &self.id {
+            issues.extend(numbers);
+        }
+        issues
+    }
+
+    // entries describing the same change, such as after a cherry-pick: either
+    // the descriptions only differ in whitespace, case or a trailing period,
+    // or they close the same issues.
+    fn is_duplicate(&self, other: &ChangelogEntry) -> bool {
+        let (x, y) = (&self.contents, &other.contents);
+        let issues = self.all_issues();
+        let same_issues = !issues.is_empty() && issues == other.all_issues();
+        x.kind == y.kind
+            && (same_issues
+                || normalize_description(&x.description) == normalize_description(&y.description))
+    }
+
+    // merge the identifiers of a duplicate entry into this one. an issue ID
+    // merged with a pull request becomes one of its issues.
+    fn absorb(&mut self, other: ChangelogEntry) {
+        let mut issues = vec![];
+        self.id = match (&self.id, other.id) {
+            (IdType::PullRequest(x), IdType::PullRequest(y)) => {
+                IdType::PullRequest(x.iter().copied().chain(y).collect())
+            }
+            (IdType::Issue(x), IdType::Issue(y)) => {
+                IdType::Issue(x.iter().copied().chain(y).collect())
+            }
+            (IdType::PullRequest(x), IdType::Issue(y)) => {
+                issues = y;
+                IdType::PullRequest(x.clone())
+            }
+            (IdType::Issue(x), pr) => {
+                issues = x.clone();
+                pr
+            }
+        };
+        match &mut self.id {
+            IdType::PullRequest(numbers) | IdType::Issue(numbers) => {
+                numbers.sort_unstable();
+                numbers.dedup();
+            }
+        }
+
+        let contents = &mut self.contents;
+        contents.issues.extend(issues);
+        contents.issues.extend(other.contents.issues);
+        contents.issues.sort_unstable();
+        contents.issues.dedup();
+        for author in other.contents.authors {
+            if !contents.authors.contains(&author) {
+                contents.authors.push(author);
+            }
+        }
+        contents.breaking |= other.contents.breaking;
+    }
+}
+
+// squash the entries describing the same change, returning what was squashed
+fn squash_entries(original: &mut Vec<ChangelogEntry>) -> Vec<String> {
+    // pull requests first, so their descriptions are kept over the issues'
+    original.sort();
+    original.sort_by_key(|entry| matches!(entry.id, IdType::Issue(_)));
+    let mut result: Vec<ChangelogEntry> = Vec::with_capacity(original.len());
+    let mut squashed = vec![];
+    for item in original.drain(..) {
+        match result.iter_mut().find(|entry| entry.is_duplicate(&item)) {
+            Some(entry) => {
+                squashed.push(format!(
+                    "{} into {}: {}",
+                    item.id.label(),
+                    entry.id.label(),
+                    entry.contents.description
+                ));
+                entry.absorb(item);
+            }
+            None => result.push(item),
+        }
+    }
+
+    *original = result;
+    squashed
+}
+
 // de-duplicate in place
 fn deduplicate_entries(original: &mut Vec<ChangelogEntry>) {
     let mut result = Vec::with_capacity(original.len());
@@ -304,6 +464,15 @@ impl Changes {
         deduplicate_entries(&mut self.internal);
     }
 
+    fn squash(&mut self) -> Vec<String> {
+        let mut squashed = squash_entries(&mut self.added);
+        squashed.append(&mut squash_entries(&mut self.changed));
+        squashed.append(&mut squash_entries(&mut self.fixed));
+        squashed.append(&mut squash_entries(&mut self.removed));
+        squashed.append(&mut squash_entries(&mut self.internal));
+        squashed
+    }
+
     fn merge(&mut self, other: &mut Self) {
         self.added.append(&mut other.added);
         self.changed.append(&mut other.changed);
@@ -323,6 +492,50 @@ impl Changes {
     }
 }
 
+// how the entries are wrapped in the changelog. the continuation lines must be
+// indented by at least 2 spaces to stay in the list item in markdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Wrapping {
+    width: Option<usize>,
+    indent: usize,
+}
+
+impl Default for Wrapping {
+    fn default() -> Self {
+        Self {
+            width: None,
+            indent: 2,
+        }
+    }
+}
+
+impl Wrapping {
+    fn wrap(&self, line: &str) -> String {
+        let width = match self.width {
+            Some(width) => width,
+            None => return line.to_owned(),
+        };
+        let mut output = String::new();
+        let mut line_width = 0;
+        for (index, word) in line.split_whitespace().enumerate() {
+            let word_width = word.chars().count();
+            if index == 0 {
+                line_width = word_width;
+            } else if line_width + 1 + word_width > width {
+                output.push('\n');
+                output.push_str(&" ".repeat(self.indent));
+                line_width = self.indent + word_width;
+            } else {
+                output.push(' ');
+                line_width += 1 + word_width;
+            }
+            output.push_str(word);
+        }
+
+        output
+    }
+}
+
 macro_rules! fmt_changelog_vec {
     ($self:ident, $fmt:ident, $field:ident, $header:literal) => {{
         if !$self.$field.is_empty() {
@@ -334,6 +547,23 @@ macro_rules! fmt_changelog_vec {
     }};
 }
 
+impl Changes {
+    fn format(&self, wrapping: Wrapping) -> String {
+        let mut output = String::new();
+        for line in self.to_string().split_inclusive('\n') {
+            match line.starts_with("- ") {
+                true => {
+                    output.push_str(&wrapping.wrap(line.trim_end()));
+                    output.push('\n');
+                }
+                false => output.push_str(line),
+            }
+        }
+
+        output
+    }
+}
+
 impl fmt::Display for Changes {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt_changelog_vec!(self, f, added, "Added");
@@ -403,29 +633,95 @@ fn read_changelog(root: &Path) -> cross::Result<(String, Changes, String)> {
         .ok_or(eyre::eyre!("could not find the next release section"))?;
     let (section, footer) = rest.split_at(last_index);
 
-    // the unreleased should have the format:
-    //  ## [Unreleased] - ReleaseDate
-    //
-    //  ### Added
-    //
-    //  - #905 - ...
+    if section[0].trim() != "## [Unreleased] - ReleaseDate" {
+        eyre::bail!("invalid unreleased section, got \"{}\"", section[0]);
+    }
+    let changes = parse_section(&section[1..])?;
+
+    Ok((header.join("\n"), changes, footer.join("\n")))
+}
+
+// parse the entries of a section, after its header, which have the format:
+//  ### Added
+//
+//  - #905 - ...
+//    continued ...
+fn parse_section(section: &[String]) -> cross::Result<Changes> {
     let mut kind = None;
-    let mut changes = Changes::default();
+    let mut entries = vec![];
+    // if the previous line is part of an entry, so the next can continue it
+    let mut in_entry = false;
     for line in section {
         let line = line.trim();
         if let Some(header) = line.strip_prefix("### ") {
             kind = Some(ChangelogType::from_header(header)?);
+            in_entry = false;
         } else if let Some(entry) = line.strip_prefix("- ") {
             match kind {
-                Some(kind) => changes.push(ChangelogEntry::parse(entry, kind)?),
+                Some(kind) => entries.push((kind, entry.to_owned())),
                 None => eyre::bail!("changelog entry \"{line}\" without header"),
             }
-        } else if !(line.is_empty() || line == "## [Unreleased] - ReleaseDate") {
+            in_entry = true;
+        } else if line.is_empty() {
+            in_entry = false;
+        } else if in_entry {
+            let (_, entry) = entries.last_mut().expect("must have an entry");
+            entry.push(' ');
+            entry.push_str(line);
+        } else {
             eyre::bail!("invalid changelog entry, got \"{line}\"");
         }
     }
 
-    Ok((header.join("\n"), changes, footer.join("\n")))
+    let mut changes = Changes::default();
+    for (kind, entry) in entries {
+        changes.push(ChangelogEntry::parse(&entry, kind)?);
+    }
+
+    Ok(changes)
+}
+
+/// Split the changelog into everything up to the latest release, its
+/// version and changes, and the older releases, which are never modified.
+fn read_latest_release(root: &Path) -> cross::Result<(String, String, Changes, String)> {
+    split_latest_release(&fs::read_to_string(root.join("CHANGELOG.md"))?)
+}
+
+fn split_latest_release(changelog: &str) -> cross::Result<(String, String, Changes, String)> {
+    let lines: Vec<String> = changelog.lines().map(ToOwned::to_owned).collect();
+
+    let is_section = |line: &String| line.trim().starts_with("## ");
+    let unreleased_index = lines
+        .iter()
+        .position(|x| x.trim().starts_with("## [Unreleased]"))
+        .ok_or(eyre::eyre!("could not find unreleased section"))?;
+    let release_index = unreleased_index
+        + 1
+        + lines[unreleased_index + 1..]
+            .iter()
+            .position(is_section)
+            .ok_or(eyre::eyre!("could not find the latest release section"))?;
+    let end_index = lines[release_index + 1..]
+        .iter()
+        .position(is_section)
+        .map(|index| release_index + 1 + index)
+        .unwrap_or(lines.len());
+
+    // the latest release has the format `## [v0.2.4] - 2022-07-10`
+    let release_header = lines[release_index].trim();
+    let version = release_header
+        .strip_prefix("## [v")
+        .and_then(|rest| rest.split_once(']'))
+        .map(|(version, _)| version.to_owned())
+        .ok_or_else(|| eyre::eyre!("invalid release section, got \"{release_header}\""))?;
+    let changes = parse_section(&lines[release_index + 1..end_index])?;
+
+    Ok((
+        lines[..release_index].join("\n"),
+        version,
+        changes,
+        lines[end_index..].join("\n"),
+    ))
 }
 
 fn delete_changes(root: &Path) -> cross::Result<()> {
@@ -451,20 +747,154 @@ pub fn get_current_date() -> String {
     format!("{}-{:0>2}-{}", date.year(), date.month(), date.day())
 }
 
-// used for internal testing
-fn build_changelog_from_dir(
+/// Release metadata, written alongside the changelog for downstream automation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ReleaseMetadata {
+    version: String,
+    date: String,
+    prerelease: bool,
+    breaking: bool,
+    changes: ReleaseChanges,
+    contributors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+struct ReleaseChanges {
+    added: Vec<ReleaseEntry>,
+    changed: Vec<ReleaseEntry>,
+    fixed: Vec<ReleaseEntry>,
+    removed: Vec<ReleaseEntry>,
+    internal: Vec<ReleaseEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ReleaseEntry {
+    description: String,
+    breaking: bool,
+    pull_requests: Vec<u64>,
+    issues: Vec<u64>,
+    authors: Vec<String>,
+}
+
+impl ReleaseEntry {
+    fn new(entry: &ChangelogEntry) -> Self {
+        let (pull_requests, mut issues) = match &entry.id {
+            IdType::PullRequest(prs) => (prs.clone(), vec![]),
+            IdType::Issue(issues) => (vec![], issues.clone()),
+        };
+        issues.extend(&entry.contents.issues);
+        issues.sort_unstable();
+        issues.dedup();
+
+        ReleaseEntry {
+            description: entry.contents.description.clone(),
+            breaking: entry.contents.breaking,
+            pull_requests,
+            issues,
+            authors: entry.contents.authors.clone(),
+        }
+    }
+}
+
+impl ReleaseMetadata {
+    fn new(release: &str, changes: &Changes) -> cross::Result<Self> {
+        let version = semver::Version::parse(release)?;
+        let entries = |v: &[ChangelogEntry]| v.iter().map(ReleaseEntry::new).collect::<Vec<_>>();
+        let all = || {
+            changes
+                .added
+                .iter()
+                .chain(&changes.changed)
+                .chain(&changes.fixed)
+                .chain(&changes.removed)
+                .chain(&changes.internal)
+        };
+        let contributors: BTreeSet<&String> = all().flat_map(|x| &x.contents.authors).collect();
+
+        Ok(ReleaseMetadata {
+            version: release.to_owned(),
+            date: get_current_date(),
+            prerelease: !version.pre.is_empty(),
+            breaking: all().any(|x| x.contents.breaking),
+            changes: ReleaseChanges {
+                added: entries(&changes.added),
+                changed: entries(&changes.changed),
+                fixed: entries(&changes.fixed),
+                removed: entries(&changes.removed),
+                internal: entries(&changes.internal),
+            },
+            contributors: contributors.into_iter().cloned().collect(),
+        })
+    }
+}
+
+// read the pending changes, squashing the duplicate changesets
+fn read_squashed_changes(changes_dir: &Path, msg_info: &mut MessageInfo) -> cross::Result<Changes> {
+    let mut changes = read_changes(changes_dir)?;
+    for squashed in changes.squash() {
+        msg_info.info(format_args!("Squashed duplicate changeset {squashed}"))?;
+    }
+
+    Ok(changes)
+}
+
+// merge the pending changes with the unreleased section of the changelog
+fn collect_changes(
     root: &Path,
     changes_dir: &Path,
-    release: Option<&str>,
-) -> cross::Result<String> {
-    use std::fmt::Write;
-
-    let mut new = read_changes(changes_dir)?;
+    msg_info: &mut MessageInfo,
+) -> cross::Result<(String, Changes, String)> {
+    let mut new = read_squashed_changes(changes_dir, msg_info)?;
     let (header, mut existing, footer) = read_changelog(root)?;
     new.merge(&mut existing);
     new.deduplicate();
     new.sort_descending();
 
+    Ok((header, new, footer))
+}
+
+// merge the pending changes into the latest release of the changelog
+fn collect_amended_changes(
+    root: &Path,
+    changes_dir: &Path,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<(String, String, Changes, String)> {
+    let mut new = read_squashed_changes(changes_dir, msg_info)?;
+    let (header, version, mut existing, footer) = read_latest_release(root)?;
+    new.merge(&mut existing);
+    new.deduplicate();
+    new.sort_descending();
+
+    Ok((header, version, new, footer))
+}
+
+fn format_amended_changelog(
+    header: String,
+    version: &str,
+    new: &Changes,
+    footer: &str,
+    wrapping: Wrapping,
+) -> String {
+    let mut output = header;
+    output.push_str(&format!("\n## [v{version}] - {}\n", get_current_date()));
+    output.push_str(&new.format(wrapping));
+    output.push('\n');
+    output.push_str(footer);
+
+    output
+}
+
+fn format_changelog(
+    header: String,
+    new: &Changes,
+    footer: &str,
+    release: Option<&str>,
+    wrapping: Wrapping,
+) -> cross::Result<String> {
+    use std::fmt::Write;
+
     let mut output = header;
     output.push_str("\n## [Unreleased] - ReleaseDate\n");
     if let Some(release) = release {
@@ -474,16 +904,102 @@ fn build_changelog_from_dir(
             writeln!(&mut output, "\n## [v{release}] - {date}")?;
         }
     }
-    output.push_str(&new.to_string());
+    output.push_str(&new.format(wrapping));
     output.push('\n');
-    output.push_str(&footer);
+    output.push_str(footer);
 
     Ok(output)
 }
 
+/// A lock held while building the changelog, so concurrent runs can't
+/// interleave writes to `CHANGELOG.md` or delete each other's changes.
+///
+/// The lock is a file named after the hash of the project path, which is
+/// created exclusively and removed when the lock is dropped.
+#[derive(Debug)]
+struct ChangelogLock {
+    path: PathBuf,
+}
+
+impl ChangelogLock {
+    fn acquire(root: &Path) -> cross::Result<Self> {
+        let hash = cross::docker::path_hash(root, cross::docker::PATH_HASH_SHORT)?;
+        Self::acquire_at(std::env::temp_dir().join(format!("cross-changelog-{hash}.lock")))
+    }
+
+    fn acquire_at(path: PathBuf) -> cross::Result<Self> {
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(&path).unwrap_or_default();
+                let owner = owner.trim();
+                // the process holding the lock was killed before removing it.
+                if is_stale_owner(owner) {
+                    fs::remove_file(&path)
+                        .wrap_err_with(|| format!("unable to remove the stale lock {path:?}"))?;
+                    return Self::acquire_at(path);
+                }
+                let suggestion = format!(
+                    "wait for the other run to finish, or remove the lock with `rm {}` if no other run is active",
+                    path.to_utf8()?
+                );
+                return Err(eyre::eyre!(
+                    "another process is building the changelog, the lock {path:?} is held{}",
+                    match owner {
+                        "" => String::new(),
+                        pid => format!(" by process {pid}"),
+                    }
+                ))
+                .suggestion(suggestion);
+            }
+            Err(e) => {
+                return Err(e).wrap_err_with(|| format!("unable to create the lock {path:?}"))
+            }
+        };
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { path })
+    }
+}
+
+// if the process that wrote the lock no longer exists. this can only be
+// checked on linux, elsewhere the lock is never considered stale.
+fn is_stale_owner(owner: &str) -> bool {
+    match owner.parse::<u32>() {
+        Ok(pid) if cfg!(target_os = "linux") => !Path::new("/proc").join(pid.to_string()).exists(),
+        _ => false,
+    }
+}
+
+impl Drop for ChangelogLock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+// used for internal testing
+#[cfg(test)]
+fn build_changelog_from_dir(
+    root: &Path,
+    changes_dir: &Path,
+    release: Option<&str>,
+) -> cross::Result<String> {
+    let (header, new, footer) = collect_changes(root, changes_dir, &mut MessageInfo::default())?;
+    format_changelog(header, &new, &footer, release, Wrapping::default())
+}
+
 pub fn build_changelog(
     BuildChangelog {
-        dry_run, release, ..
+        dry_run,
+        release,
+        amend,
+        wrap_width,
+        continuation_indent,
+        ..
     }: BuildChangelog,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<()> {
@@ -492,18 +1008,42 @@ pub fn build_changelog(
         "Running with dry-run set the {dry_run} and with release {release:?}"
     ))?;
 
+    let wrapping = Wrapping {
+        width: wrap_width,
+        indent: continuation_indent,
+    };
     let root = project_dir(msg_info)?;
+    let _lock = ChangelogLock::acquire(&root)?;
     let changes_dir = root.join(".changes");
-    let output = build_changelog_from_dir(&root, &changes_dir, release.as_deref())?;
+    let (output, metadata) = if amend {
+        let (header, version, new, footer) =
+            collect_amended_changes(&root, &changes_dir, msg_info)?;
+        msg_info.info(format_args!("Amending the release v{version}."))?;
+        let output = format_amended_changelog(header, &version, &new, &footer, wrapping);
+        (output, Some(ReleaseMetadata::new(&version, &new)?))
+    } else {
+        let (header, new, footer) = collect_changes(&root, &changes_dir, msg_info)?;
+        let output = format_changelog(header, &new, &footer, release.as_deref(), wrapping)?;
+        let metadata = release
+            .as_deref()
+            .map(|release| ReleaseMetadata::new(release, &new))
+            .transpose()?;
+        (output, metadata)
+    };
 
-    let filename = match !dry_run && release.is_some() {
+    let (filename, metadata_filename) = match !dry_run && metadata.is_some() {
         true => {
             delete_changes(&root)?;
-            "CHANGELOG.md"
+            ("CHANGELOG.md", "release.json")
         }
-        false => "CHANGELOG.md.draft",
+        false => ("CHANGELOG.md.draft", "release.json.draft"),
     };
     write_to_string(&root.join(filename), &output)?;
+    if let Some(metadata) = metadata {
+        let mut contents = serde_json::to_string_pretty(&metadata)?;
+        contents.push('\n');
+        write_to_string(&root.join(metadata_filename), &contents)?;
+    }
 
     Ok(())
 }
@@ -535,6 +1075,80 @@ pub fn validate_changelog(
     Ok(())
 }
 
+/// Extract the contents of the first fenced `changelog` code block.
+fn extract_fenced_block(markdown: &str) -> cross::Result<String> {
+    let mut lines = markdown.lines();
+    lines
+        .by_ref()
+        .find(|line| line.trim().strip_prefix("```").map(str::trim) == Some("changelog"))
+        .ok_or(eyre::eyre!(
+            "could not find a fenced `changelog` code block"
+        ))?;
+
+    let mut block = vec![];
+    for line in lines {
+        if line.trim() == "```" {
+            return Ok(block.join("\n"));
+        }
+        block.push(line);
+    }
+
+    eyre::bail!("unterminated `changelog` code block")
+}
+
+fn ingest_value(id: &str, input: &str, format: IngestFormat) -> cross::Result<serde_json::Value> {
+    let contents = match format {
+        IngestFormat::Markdown => extract_fenced_block(input)?,
+        IngestFormat::Json => input.to_owned(),
+    };
+    let value: serde_json::Value =
+        serde_json::from_str(&contents).wrap_err("unable to parse changelog JSON")?;
+    let _ = ChangelogEntry::from_value(IdType::parse_stem(id)?, value.clone())
+        .wrap_err("unable to extract changelog entry")?;
+
+    Ok(value)
+}
+
+pub fn ingest_changelog(
+    IngestChangelog {
+        id,
+        stdin,
+        input,
+        format,
+        force,
+        ..
+    }: IngestChangelog,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let contents = match (stdin, input) {
+        (true, _) => {
+            let mut contents = String::new();
+            io::stdin().read_to_string(&mut contents)?;
+            contents
+        }
+        (false, Some(path)) => {
+            fs::read_to_string(&path).wrap_err_with(|| eyre::eyre!("cannot read file {path:?}"))?
+        }
+        (false, None) => eyre::bail!("must provide one of `--stdin` or `--input`"),
+    };
+    let value = ingest_value(&id, &contents, format)?;
+
+    let root = project_dir(msg_info)?;
+    let path = root.join(".changes").join(format!("{id}.json"));
+    if path.exists() && !force {
+        eyre::bail!("changelog entry {path:?} already exists, use `--force` to overwrite");
+    }
+
+    let mut output = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    serde::Serialize::serialize(&value, &mut serializer)?;
+    write_to_string(&path, &String::from_utf8(output)?)?;
+    msg_info.info(format_args!("Wrote changelog entry to {path:?}."))?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -574,6 +1188,113 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_ingest_markdown() -> cross::Result<()> {
+        let body = r#"
+This PR fixes a bug.
+
+```changelog
+{
+    "type": "fixed",
+    "description": "fixed a bug.",
+    "issues": [437]
+}
+```
+"#;
+        let value = ingest_value("978", body, IngestFormat::Markdown)?;
+        assert_eq!(value["type"], "fixed");
+        assert_eq!(value["issues"][0], 437);
+
+        let value = ingest_value("978", CHANGES_ARRAY, IngestFormat::Json)?;
+        assert!(value.is_array());
+
+        assert!(ingest_value("978", "no changelog here.", IngestFormat::Markdown).is_err());
+        assert!(ingest_value("978", "```changelog\n{}\n```", IngestFormat::Markdown).is_err());
+        assert!(ingest_value("978", "```changelog\n[]", IngestFormat::Markdown).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn squash_duplicate_changes() -> cross::Result<()> {
+        let entry = |id: &str, description: &str, issues: Vec<u64>| -> cross::Result<_> {
+            Ok(ChangelogEntry::new(
+                IdType::parse_stem(id)?,
+                ChangelogContents {
+                    description: description.to_owned(),
+                    issues,
+                    breaking: false,
+                    authors: vec![],
+                    kind: ChangelogType::Fixed,
+                },
+            ))
+        };
+        let mut changes = Changes::default();
+        changes.push(entry("1020", "fixed the qemu runner.", vec![])?);
+        changes.push(entry("1010", "Fixed the  qemu runner", vec![])?);
+        changes.push(entry("issue1005", "fixed a hang.", vec![])?);
+        changes.push(entry(
+            "1030",
+            "fixed hangs when pulling images.",
+            vec![1005],
+        )?);
+        changes.push(entry("1040", "fixed a typo.", vec![])?);
+
+        let squashed = changes.squash();
+        assert_eq!(
+            squashed,
+            [
+                "#1020 into #1010: Fixed the  qemu runner",
+                "issue #1005 into #1030: fixed hangs when pulling images.",
+            ]
+        );
+        changes.sort_descending();
+        assert_eq!(
+            changes.to_string(),
+            "\n### Fixed\n\n\
+            - #1040 - fixed a typo.\n\
+            - #1030 - fixed hangs when pulling images.\n\
+            - #1010,1020 - Fixed the  qemu runner\n"
+        );
+        assert_eq!(changes.fixed[1].contents.issues, [1005]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wrapped_entries() -> cross::Result<()> {
+        let mut changes = Changes::default();
+        changes.push(ChangelogEntry::parse(
+            "#1050 - support building the images for new targets without the external scripts.",
+            ChangelogType::Added,
+        )?);
+        changes.push(ChangelogEntry::parse(
+            "#1040 - short entry.",
+            ChangelogType::Added,
+        )?);
+        let wrapping = Wrapping {
+            width: Some(40),
+            indent: 4,
+        };
+        let wrapped = changes.format(wrapping);
+        assert_eq!(
+            wrapped,
+            "\n### Added\n\n\
+            - #1050 - support building the images\n\
+            \x20   for new targets without the external\n\
+            \x20   scripts.\n\
+            - #1040 - short entry.\n"
+        );
+        assert_eq!(changes.format(Wrapping::default()), changes.to_string());
+
+        let lines = |s: &str| s.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
+        assert_eq!(parse_section(&lines(&wrapped))?, changes);
+        assert_eq!(parse_section(&lines(&changes.to_string()))?, changes);
+        assert!(parse_section(&lines("### Added\n\ncontinued")).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn changelog_type_sort() {
         assert!(ChangelogType::Added > ChangelogType::Changed);
@@ -593,6 +1314,7 @@ mod tests {
         let expected = ChangelogContents {
             description: s!("sample description for a PR adding one CHANGELOG entry."),
             issues: vec![437],
+            authors: vec![],
             breaking: false,
             kind: ChangelogType::Fixed,
         };
@@ -603,24 +1325,28 @@ mod tests {
             ChangelogContents {
                 description: s!("this is one added entry."),
                 issues: vec![630],
+                authors: vec![],
                 breaking: false,
                 kind: ChangelogType::Added,
             },
             ChangelogContents {
                 description: s!("this is another added entry."),
                 issues: vec![642],
+                authors: vec![],
                 breaking: false,
                 kind: ChangelogType::Added,
             },
             ChangelogContents {
                 description: s!("this is a fixed entry that has no attached issue."),
                 issues: vec![],
+                authors: vec![],
                 breaking: false,
                 kind: ChangelogType::Fixed,
             },
             ChangelogContents {
                 description: s!("this is a breaking change."),
                 issues: vec![679],
+                authors: vec![],
                 breaking: true,
                 kind: ChangelogType::Changed,
             },
@@ -637,6 +1363,7 @@ mod tests {
             ChangelogContents {
                 description: s!("this is one added entry."),
                 issues: vec![630],
+                authors: vec![],
                 breaking: false,
                 kind: ChangelogType::Added,
             },
@@ -673,6 +1400,7 @@ mod tests {
                     ChangelogContents {
                         description: s!("this has 2 PRs associated."),
                         issues: vec![441],
+                        authors: vec![],
                         breaking: false,
                         kind: ChangelogType::Added,
                     },
@@ -682,6 +1410,7 @@ mod tests {
                     ChangelogContents {
                         description: s!("this is one added entry."),
                         issues: vec![630],
+                        authors: vec![],
                         breaking: false,
                         kind: ChangelogType::Added,
                     },
@@ -691,6 +1420,7 @@ mod tests {
                     ChangelogContents {
                         description: s!("this is another added entry."),
                         issues: vec![642],
+                        authors: vec![],
                         breaking: false,
                         kind: ChangelogType::Added,
                     },
@@ -701,6 +1431,7 @@ mod tests {
                 ChangelogContents {
                     description: s!("this is a breaking change."),
                     issues: vec![679],
+                    authors: vec![],
                     breaking: true,
                     kind: ChangelogType::Changed,
                 },
@@ -711,6 +1442,7 @@ mod tests {
                     ChangelogContents {
                         description: s!("sample description for a PR adding one CHANGELOG entry."),
                         issues: vec![437],
+                        authors: vec![],
                         breaking: false,
                         kind: ChangelogType::Fixed,
                     },
@@ -720,6 +1452,7 @@ mod tests {
                     ChangelogContents {
                         description: s!("this is a fixed entry that has no attached issue."),
                         issues: vec![],
+                        authors: vec![],
                         breaking: false,
                         kind: ChangelogType::Fixed,
                     },
@@ -729,6 +1462,7 @@ mod tests {
                     ChangelogContents {
                         description: s!("no associated PR."),
                         issues: vec![440],
+                        authors: vec![],
                         breaking: false,
                         kind: ChangelogType::Fixed,
                     },
@@ -754,6 +1488,7 @@ mod tests {
             ChangelogContents {
                 description: s!("added qemu emulation to `i586-unknown-linux-gnu`, `i686-unknown-linux-musl`, and `i586-unknown-linux-gnu`, so they can run on an `x86` CPU, rather than an `x86_64` CPU."),
                 issues: vec![],
+                authors: vec![],
                 breaking: false,
                 kind: ChangelogType::Added,
             },
@@ -765,6 +1500,7 @@ mod tests {
             ChangelogContents {
                 description: s!("ensure cargo configuration environment variable flags are passed to the docker container."),
                 issues: vec![],
+                authors: vec![],
                 breaking: false,
                 kind: ChangelogType::Changed,
             },
@@ -776,6 +1512,7 @@ mod tests {
             ChangelogContents {
                 description: s!("fixed running dynamically-linked libraries for all musl targets except `x86_64-unknown-linux-musl`."),
                 issues: vec![],
+                authors: vec![],
                 breaking: false,
                 kind: ChangelogType::Fixed,
             },
@@ -787,6 +1524,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_changelog_lock() -> cross::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("cross-changelog-test-{}.lock", std::process::id()));
+        let lock = ChangelogLock::acquire_at(path.clone())?;
+        let err = ChangelogLock::acquire_at(path.clone()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("held by process {}", std::process::id())));
+        drop(lock);
+        assert!(!path.exists());
+        drop(ChangelogLock::acquire_at(path.clone())?);
+
+        // a lock left by a process that no longer exists is reclaimed
+        if cfg!(target_os = "linux") {
+            fs::write(&path, u32::MAX.to_string())?;
+            drop(ChangelogLock::acquire_at(path.clone())?);
+            assert!(!path.exists());
+        }
+
+        Ok(())
+    }
+
     fn build_changelog_test(release: Option<&str>) -> cross::Result<String> {
         let mut msg_info = MessageInfo::default();
         let root = project_dir(&mut msg_info)?;
@@ -865,6 +1625,103 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_build_changelog_amend() -> cross::Result<()> {
+        let changelog = "# Change Log
+
+<!-- next-header -->
+
+## [Unreleased] - ReleaseDate
+
+### Fixed
+
+- #1000 - an unreleased fix.
+
+## [v0.2.4] - 2022-07-10
+
+### Fixed
+
+- #951 - fixed the build of `powerpc64-unknown-linux-gnu`.
+
+## [v0.2.3] - 2022-07-09
+
+### Added
+
+- #890 - an older entry.
+";
+        let (header, version, mut existing, footer) = split_latest_release(changelog)?;
+        assert_eq!(version, "0.2.4");
+        assert_eq!(existing.fixed.len(), 1);
+        assert_eq!(
+            footer,
+            "## [v0.2.3] - 2022-07-09\n\n### Added\n\n- #890 - an older entry."
+        );
+
+        let mut msg_info = MessageInfo::default();
+        let root = project_dir(&mut msg_info)?;
+        let mut new = read_changes(&root.join(".changes").join("template"))?;
+        new.merge(&mut existing);
+        new.sort_descending();
+        let output = format_amended_changelog(header, &version, &new, &footer, Wrapping::default());
+        let lines: Vec<&str> = output.lines().collect();
+        let date = get_current_date();
+
+        assert_eq!(
+            &lines[4..14],
+            &[
+                "## [Unreleased] - ReleaseDate",
+                "",
+                "### Fixed",
+                "",
+                "- #1000 - an unreleased fix.",
+                "",
+                &format!("## [v0.2.4] - {date}"),
+                "",
+                "### Added",
+                "",
+            ]
+        );
+        assert!(lines.contains(&"- #951 - fixed the build of `powerpc64-unknown-linux-gnu`."));
+        assert!(output.ends_with(&footer));
+        assert!(split_latest_release("## [Unreleased] - ReleaseDate\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_release_metadata() -> cross::Result<()> {
+        let mut msg_info = MessageInfo::default();
+        let root = project_dir(&mut msg_info)?;
+        let changes_dir = root.join(".changes").join("template");
+        let (_, mut changes, _) = collect_changes(&changes_dir, &changes_dir, &mut msg_info)?;
+        changes.changed[0].contents.authors = vec!["octocat".to_owned()];
+        changes.fixed[0].contents.authors = vec!["Alexhuszagh".to_owned(), "octocat".to_owned()];
+
+        let metadata = ReleaseMetadata::new("0.2.4", &changes)?;
+        assert_eq!(metadata.version, "0.2.4");
+        assert_eq!(metadata.date, get_current_date());
+        assert!(!metadata.prerelease);
+        assert!(metadata.breaking);
+        assert_eq!(metadata.contributors, vec!["Alexhuszagh", "octocat"]);
+        assert_eq!(
+            metadata.changes.added[0],
+            ReleaseEntry {
+                description: "this has 2 PRs associated.".to_owned(),
+                breaking: false,
+                pull_requests: vec![979, 981],
+                issues: vec![441],
+                authors: vec![],
+            }
+        );
+        assert_eq!(metadata.changes.added.len(), changes.added.len());
+
+        let value = serde_json::to_value(&metadata)?;
+        assert_eq!(value["changes"]["added"][0]["pull-requests"][1], 981);
+        assert!(ReleaseMetadata::new("0.2.4-alpha", &changes)?.prerelease);
+
+        Ok(())
+    }
+
     static CHANGES_OBJECT: &str = r#"
     {
         "description": "sample description for a PR adding one CHANGELOG entry.",
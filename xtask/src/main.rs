@@ -18,7 +18,7 @@ use cross::shell::{MessageInfo, Verbosity};
 use util::{cargo_metadata, ImageTarget};
 
 use self::build_docker_image::BuildDockerImage;
-use self::changelog::{BuildChangelog, ValidateChangelog};
+use self::changelog::{BuildChangelog, IngestChangelog, ValidateChangelog};
 use self::crosstool::ConfigureCrosstool;
 use self::hooks::{Check, Test};
 use self::install_git_hooks::InstallGitHooks;
@@ -30,6 +30,9 @@ struct Cli {
     /// Toolchain name/version to use (such as stable or 1.59.0).
     #[clap(value_parser = is_toolchain)]
     toolchain: Option<String>,
+    /// Disable color output, the same as `--color never`.
+    #[clap(long, global = true)]
+    no_color: bool,
     #[clap(subcommand)]
     command: Commands,
 }
@@ -63,6 +66,9 @@ enum Commands {
     /// Validate changelog entries.
     #[clap(hide = true)]
     ValidateChangelog(ValidateChangelog),
+    /// Create a changelog entry from a PR description.
+    #[clap(hide = true)]
+    IngestChangelog(IngestChangelog),
     /// Code generation
     Codegen(Codegen),
 }
@@ -83,8 +89,12 @@ macro_rules! get_engine {
 }
 
 macro_rules! get_msg_info {
-    ($args:ident, $verbose:expr) => {{
-        MessageInfo::create($verbose, $args.quiet, $args.color.as_deref())
+    ($args:ident, $verbose:expr, $no_color:expr) => {{
+        let color = match $no_color {
+            true => Some("never"),
+            false => $args.color.as_deref(),
+        };
+        MessageInfo::create($verbose, $args.quiet, color)
     }};
 }
 
@@ -93,25 +103,25 @@ pub fn main() -> cross::Result<()> {
     let cli = Cli::parse();
     match cli.command {
         Commands::TargetInfo(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             let engine = get_engine!(args, msg_info)?;
             target_info::target_info(args, &engine, &mut msg_info)?;
         }
         Commands::BuildDockerImage(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             let engine = get_engine!(args, msg_info)?;
             build_docker_image::build_docker_image(args, &engine, &mut msg_info)?;
         }
         Commands::InstallGitHooks(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             install_git_hooks::install_git_hooks(&mut msg_info)?;
         }
         Commands::Check(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             hooks::check(args, cli.toolchain.as_deref(), &mut msg_info)?;
         }
         Commands::Test(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             hooks::test(args, cli.toolchain.as_deref(), &mut msg_info)?;
         }
         Commands::CiJob(args) => {
@@ -119,17 +129,21 @@ pub fn main() -> cross::Result<()> {
             ci::ci(args, metadata)?;
         }
         Commands::ConfigureCrosstool(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             crosstool::configure_crosstool(args, &mut msg_info)?;
         }
         Commands::BuildChangelog(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             changelog::build_changelog(args, &mut msg_info)?;
         }
         Commands::ValidateChangelog(args) => {
-            let mut msg_info = get_msg_info!(args, args.verbose)?;
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
             changelog::validate_changelog(args, &mut msg_info)?;
         }
+        Commands::IngestChangelog(args) => {
+            let mut msg_info = get_msg_info!(args, args.verbose, cli.no_color)?;
+            changelog::ingest_changelog(args, &mut msg_info)?;
+        }
         Commands::Codegen(args) => codegen::codegen(args)?,
     }
 
@@ -145,5 +159,5 @@ fn get_container_engine(
     } else {
         docker::get_container_engine()?
     };
-    docker::Engine::from_path(engine, None, None, msg_info)
+    docker::Engine::from_path(engine, None, None, None, msg_info)
 }